@@ -0,0 +1,482 @@
+//! Offloading TLS record protection to the Linux kernel (kTLS).
+//!
+//! Once the handshake is complete, [`KtlsStream::new()`] hands a rustls [`Connection`] over
+//! with [`dangerous_into_kernel_connection()`], and configures the kernel TLS ULP on its TCP
+//! socket with the connection's traffic secrets.  From then on the kernel encrypts and
+//! decrypts application data, so it may be sent with `sendfile` and `splice`, for example.
+//!
+//! The connection must be created with a config that has `enable_secret_extraction` set, and
+//! use the AES-GCM or ChaCha20-Poly1305 cipher suites.  The kernel must have been built with
+//! `CONFIG_TLS`, and have the `tls` module loaded.
+//!
+//! Post-handshake messages received after offloading are handled: TLS 1.3 session tickets
+//! are stored in the client's [`ClientSessionStore`], and key updates move the kernel on to
+//! the next traffic secrets.  Changing the keys of a socket that is already offloaded needs
+//! Linux 6.13 or later; with earlier kernels, a key update is an error.
+//!
+//! [`dangerous_into_kernel_connection()`]: rustls::ConnectionCommon::dangerous_into_kernel_connection
+//! [`ClientSessionStore`]: rustls::client::ClientSessionStore
+
+#![cfg(target_os = "linux")]
+#![warn(missing_docs, clippy::use_self)]
+
+use std::io::{self, Read};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::{mem, ptr};
+
+use rustls::client::ClientConnectionData;
+use rustls::kernel::KernelConnection;
+use rustls::server::ServerConnectionData;
+use rustls::Connection;
+use rustls::{
+    AlertDescription, ConnectionTrafficSecrets, ContentType, Error, HandshakeType, InvalidMessage,
+    PeerMisbehaved, ProtocolVersion,
+};
+use zeroize::Zeroizing;
+
+/// A TCP stream whose TLS records are protected by the kernel.
+///
+/// Reads and writes carry application data, like [`rustls::StreamOwned`].  Plaintext
+/// received by rustls before the connection was offloaded is returned by the first reads.
+///
+/// Reads handle the non-application data records that follow: see the
+/// [crate documentation](crate).  An alert other than `close_notify` is an error.
+pub struct KtlsStream<S> {
+    socket: S,
+    conn: Kernel,
+    received_plaintext: io::Cursor<Vec<u8>>,
+    has_received_close_notify: bool,
+    /// Partial handshake messages received after offloading.
+    handshake: Vec<u8>,
+    /// A partial alert received after offloading.
+    alert: Vec<u8>,
+}
+
+impl<S: AsRawFd + io::Write> KtlsStream<S> {
+    /// Offload record protection for `conn` to the kernel.
+    ///
+    /// `socket` is the TCP socket over which `conn` performed its handshake.  Any TLS data
+    /// that `conn` has yet to send is first written to `socket`, so `socket` should be in
+    /// blocking mode.
+    ///
+    /// This fails if `conn` is still handshaking, its secrets cannot be extracted, or
+    /// `conn` holds part of a record that was read from `socket` but not yet processed.
+    /// If configuring `socket` fails, it is left in an unspecified state and should be closed.
+    pub fn new(mut socket: S, conn: impl Into<Connection>) -> io::Result<Self> {
+        let mut conn = conn.into();
+        if conn.is_handshaking() {
+            return Err(invalid_input(Error::HandshakeNotComplete));
+        }
+
+        conn.process_new_packets()
+            .map_err(invalid_data)?;
+        while conn.wants_write() {
+            conn.write_tls(&mut socket)?;
+        }
+        socket.flush()?;
+
+        let mut received_plaintext = Vec::new();
+        let has_received_close_notify = match conn
+            .reader()
+            .read_to_end(&mut received_plaintext)
+        {
+            Ok(_) => true,
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => false,
+            Err(err) => return Err(err),
+        };
+
+        let (secrets, conn) = match conn {
+            Connection::Client(conn) => {
+                let (secrets, conn) = conn
+                    .dangerous_into_kernel_connection()
+                    .map_err(invalid_input)?;
+                (secrets, Kernel::Client(conn))
+            }
+            Connection::Server(conn) => {
+                let (secrets, conn) = conn
+                    .dangerous_into_kernel_connection()
+                    .map_err(invalid_input)?;
+                (secrets, Kernel::Server(conn))
+            }
+        };
+
+        let version = conn.protocol_version();
+        let tx = crypto_info(version, secrets.tx);
+        let rx = crypto_info(version, secrets.rx);
+
+        let fd = socket.as_raw_fd();
+        set_socket_option(fd, libc::SOL_TCP, libc::TCP_ULP, b"tls")?;
+        set_socket_option(fd, SOL_TLS, TLS_TX, &tx)?;
+        set_socket_option(fd, SOL_TLS, TLS_RX, &rx)?;
+
+        Ok(Self {
+            socket,
+            conn,
+            received_plaintext: io::Cursor::new(received_plaintext),
+            has_received_close_notify,
+            handshake: Vec::new(),
+            alert: Vec::new(),
+        })
+    }
+}
+
+impl<S: AsRawFd> KtlsStream<S> {
+    /// Queues a close_notify warning alert to be sent to the peer.
+    ///
+    /// This informs the peer that the connection is being closed.  Further writes fail.
+    pub fn send_close_notify(&mut self) -> io::Result<()> {
+        send_record(
+            self.socket.as_raw_fd(),
+            ContentType::Alert,
+            &[1, AlertDescription::CloseNotify.get_u8()],
+        )?;
+        Ok(())
+    }
+
+    /// Send a TLS 1.3 key update, and move on to the next traffic secret for sending.
+    ///
+    /// The peer is asked to update its keys in return.
+    pub fn send_key_update(&mut self) -> io::Result<()> {
+        send_key_update(
+            self.socket.as_raw_fd(),
+            &mut self.conn,
+            KEY_UPDATE_REQUESTED,
+        )
+    }
+
+    /// Process the contents of a non-application data record received after offloading.
+    fn process_control(&mut self, typ: ContentType, data: &[u8]) -> io::Result<()> {
+        match typ {
+            ContentType::Alert => {
+                self.alert.extend_from_slice(data);
+                if self.alert.len() < 2 {
+                    return Ok(());
+                }
+
+                match AlertDescription::from(self.alert[1]) {
+                    AlertDescription::CloseNotify => {
+                        self.has_received_close_notify = true;
+                        Ok(())
+                    }
+                    alert => Err(invalid_data(Error::AlertReceived(alert))),
+                }
+            }
+            ContentType::Handshake => {
+                self.handshake.extend_from_slice(data);
+                while self.handshake.len() >= 4 {
+                    let len = usize::from(self.handshake[1]) << 16
+                        | usize::from(self.handshake[2]) << 8
+                        | usize::from(self.handshake[3]);
+                    if self.handshake.len() < 4 + len {
+                        break;
+                    }
+
+                    let body = &self.handshake[4..4 + len];
+                    match (HandshakeType::from(self.handshake[0]), &mut self.conn) {
+                        (HandshakeType::NewSessionTicket, Kernel::Client(conn))
+                            if conn.protocol_version() == ProtocolVersion::TLSv1_3 =>
+                        {
+                            conn.handle_new_session_ticket(body)
+                                .map_err(invalid_data)?;
+                        }
+                        (HandshakeType::HelloRequest, Kernel::Client(conn))
+                            if conn.protocol_version() == ProtocolVersion::TLSv1_2 => {}
+                        (HandshakeType::KeyUpdate, conn) => {
+                            // The next record is protected with the new key, so the key
+                            // update must end this one.
+                            if self.handshake.len() != 4 + len {
+                                return Err(invalid_data(
+                                    PeerMisbehaved::KeyEpochWithPendingFragment.into(),
+                                ));
+                            }
+                            handle_key_update(self.socket.as_raw_fd(), conn, body)?;
+                        }
+                        (got_type, _) => {
+                            return Err(invalid_data(Error::InappropriateHandshakeMessage {
+                                expect_types: vec![
+                                    HandshakeType::NewSessionTicket,
+                                    HandshakeType::KeyUpdate,
+                                ],
+                                got_type,
+                            }));
+                        }
+                    }
+
+                    self.handshake.drain(..4 + len);
+                }
+                Ok(())
+            }
+            got_type => Err(invalid_data(Error::InappropriateMessage {
+                expect_types: vec![ContentType::ApplicationData],
+                got_type,
+            })),
+        }
+    }
+
+    /// Get a reference to the underlying socket.
+    pub fn get_ref(&self) -> &S {
+        &self.socket
+    }
+
+    /// Get a mutable reference to the underlying socket.
+    ///
+    /// Reading or writing through it bypasses the handling of non-application data records.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.socket
+    }
+}
+
+impl<S: AsRawFd> io::Read for KtlsStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if (self.received_plaintext.position() as usize) < self.received_plaintext.get_ref().len() {
+            return self.received_plaintext.read(buf);
+        }
+
+        while !buf.is_empty() && !self.has_received_close_notify {
+            let (len, typ) = recv_record(self.socket.as_raw_fd(), buf)?;
+            match typ {
+                ContentType::ApplicationData if len == 0 => {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+                ContentType::ApplicationData => return Ok(len),
+                typ => self.process_control(typ, &buf[..len])?,
+            }
+        }
+
+        Ok(0)
+    }
+}
+
+impl<S: io::Write> io::Write for KtlsStream<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.socket.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.socket.flush()
+    }
+}
+
+impl<S> core::fmt::Debug for KtlsStream<S> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("KtlsStream")
+            .field("conn", &self.conn)
+            .finish()
+    }
+}
+
+/// The rustls side of an offloaded connection.
+#[derive(Debug)]
+enum Kernel {
+    Client(KernelConnection<ClientConnectionData>),
+    Server(KernelConnection<ServerConnectionData>),
+}
+
+impl Kernel {
+    fn protocol_version(&self) -> ProtocolVersion {
+        match self {
+            Self::Client(conn) => conn.protocol_version(),
+            Self::Server(conn) => conn.protocol_version(),
+        }
+    }
+
+    fn update_tx_secret(&mut self) -> Result<(u64, ConnectionTrafficSecrets), Error> {
+        match self {
+            Self::Client(conn) => conn.update_tx_secret(),
+            Self::Server(conn) => conn.update_tx_secret(),
+        }
+    }
+
+    fn update_rx_secret(&mut self) -> Result<(u64, ConnectionTrafficSecrets), Error> {
+        match self {
+            Self::Client(conn) => conn.update_rx_secret(),
+            Self::Server(conn) => conn.update_rx_secret(),
+        }
+    }
+}
+
+/// Handle a key update from the peer, whose body is `body`.
+///
+/// Records received after it use the peer's next traffic secret.  If the peer asked us to
+/// update our keys too, send a key update in reply and then move on to our next secret.
+fn handle_key_update(fd: RawFd, conn: &mut Kernel, body: &[u8]) -> io::Result<()> {
+    let update_requested = match body {
+        [KEY_UPDATE_NOT_REQUESTED] => false,
+        [KEY_UPDATE_REQUESTED] => true,
+        _ => return Err(invalid_data(InvalidMessage::InvalidKeyUpdate.into())),
+    };
+
+    let rx = conn
+        .update_rx_secret()
+        .map_err(invalid_data)?;
+    set_socket_option(
+        fd,
+        SOL_TLS,
+        TLS_RX,
+        &crypto_info(conn.protocol_version(), rx),
+    )?;
+
+    match update_requested {
+        true => send_key_update(fd, conn, KEY_UPDATE_NOT_REQUESTED),
+        false => Ok(()),
+    }
+}
+
+/// Send a key update message with `request`, and move on to our next traffic secret.
+fn send_key_update(fd: RawFd, conn: &mut Kernel, request: u8) -> io::Result<()> {
+    send_record(
+        fd,
+        ContentType::Handshake,
+        &[HandshakeType::KeyUpdate.get_u8(), 0, 0, 1, request],
+    )?;
+
+    let tx = conn
+        .update_tx_secret()
+        .map_err(invalid_input)?;
+    set_socket_option(
+        fd,
+        SOL_TLS,
+        TLS_TX,
+        &crypto_info(conn.protocol_version(), tx),
+    )
+}
+
+/// Encode `secrets` for `version` as the kernel's `struct tls12_crypto_info_*`.
+///
+/// These begin with the `u16` version and cipher type in native byte order, followed by
+/// byte arrays, so have no padding.
+fn crypto_info(
+    version: ProtocolVersion,
+    (seq, secrets): (u64, ConnectionTrafficSecrets),
+) -> Zeroizing<Vec<u8>> {
+    let (cipher_type, key, iv, salt_len) = match secrets {
+        ConnectionTrafficSecrets::Aes128Gcm { key, iv } => (TLS_CIPHER_AES_GCM_128, key, iv, 4),
+        ConnectionTrafficSecrets::Aes256Gcm { key, iv } => (TLS_CIPHER_AES_GCM_256, key, iv, 4),
+        ConnectionTrafficSecrets::Chacha20Poly1305 { key, iv } => {
+            (TLS_CIPHER_CHACHA20_POLY1305, key, iv, 0)
+        }
+        _ => unreachable!("rustls only extracts AES-GCM and ChaCha20-Poly1305 secrets"),
+    };
+    let (salt, iv) = iv.as_ref().split_at(salt_len);
+
+    let mut info = Zeroizing::new(Vec::new());
+    info.extend_from_slice(&version.get_u16().to_ne_bytes());
+    info.extend_from_slice(&cipher_type.to_ne_bytes());
+    info.extend_from_slice(iv);
+    info.extend_from_slice(key.as_ref());
+    info.extend_from_slice(salt);
+    info.extend_from_slice(&seq.to_be_bytes());
+    info
+}
+
+fn set_socket_option(fd: RawFd, level: i32, name: i32, value: &[u8]) -> io::Result<()> {
+    // SAFETY: `value` is valid for reads of `value.len()` bytes.
+    let ret = unsafe {
+        libc::setsockopt(
+            fd,
+            level,
+            name,
+            value.as_ptr().cast(),
+            value.len() as libc::socklen_t,
+        )
+    };
+    match ret {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+/// Receive the contents of one or more records of the same type into `buf`.
+fn recv_record(fd: RawFd, buf: &mut [u8]) -> io::Result<(usize, ContentType)> {
+    let mut iov = libc::iovec {
+        iov_base: buf.as_mut_ptr().cast(),
+        iov_len: buf.len(),
+    };
+    let mut control = ControlMessage::default();
+    let mut msg = control.header(&mut iov);
+
+    // SAFETY: `msg` refers to `buf` and `control`, which outlive the call.
+    let len = unsafe { libc::recvmsg(fd, &mut msg, 0) };
+    if len < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // SAFETY: `msg` was filled in by `recvmsg`, and its control messages lie within `control`.
+    let typ = unsafe {
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        match cmsg.is_null() {
+            false if (*cmsg).cmsg_level == SOL_TLS && (*cmsg).cmsg_type == TLS_GET_RECORD_TYPE => {
+                ContentType::from(*libc::CMSG_DATA(cmsg))
+            }
+            _ => ContentType::ApplicationData,
+        }
+    };
+
+    Ok((len as usize, typ))
+}
+
+/// Send `data` in a record of type `typ`.
+fn send_record(fd: RawFd, typ: ContentType, data: &[u8]) -> io::Result<usize> {
+    let mut iov = libc::iovec {
+        // `sendmsg` does not write through `iov_base`.
+        iov_base: data.as_ptr() as *mut _,
+        iov_len: data.len(),
+    };
+    let mut control = ControlMessage::default();
+    let msg = control.header(&mut iov);
+
+    // SAFETY: `control` has room for a control message with one byte of data, and `msg`
+    // refers to `data` and `control`, which outlive the call.
+    let len = unsafe {
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        (*cmsg).cmsg_level = SOL_TLS;
+        (*cmsg).cmsg_type = TLS_SET_RECORD_TYPE;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(1) as _;
+        ptr::write(libc::CMSG_DATA(cmsg), typ.get_u8());
+        libc::sendmsg(fd, &msg, 0)
+    };
+
+    match len {
+        len if len < 0 => Err(io::Error::last_os_error()),
+        len => Ok(len as usize),
+    }
+}
+
+/// Suitably aligned space for a control message carrying a record type.
+#[derive(Default)]
+struct ControlMessage([u64; 4]);
+
+impl ControlMessage {
+    /// A message header for `iov`, with this as its control message buffer.
+    fn header(&mut self, iov: &mut libc::iovec) -> libc::msghdr {
+        // SAFETY: all-zero is a valid `msghdr`, and `CMSG_SPACE` only does arithmetic.
+        let (mut msg, space): (libc::msghdr, _) = unsafe { (mem::zeroed(), libc::CMSG_SPACE(1)) };
+        msg.msg_iov = iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = self.0.as_mut_ptr().cast();
+        msg.msg_controllen = space as _;
+        msg
+    }
+}
+
+fn invalid_input(err: Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, err)
+}
+
+fn invalid_data(err: Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+// `KeyUpdateRequest` values, from RFC 8446.
+const KEY_UPDATE_NOT_REQUESTED: u8 = 0;
+const KEY_UPDATE_REQUESTED: u8 = 1;
+
+// From `linux/tls.h`; not all of these are available from older versions of `libc`.
+const SOL_TLS: i32 = 282;
+const TLS_TX: i32 = 1;
+const TLS_RX: i32 = 2;
+const TLS_SET_RECORD_TYPE: i32 = 1;
+const TLS_GET_RECORD_TYPE: i32 = 2;
+const TLS_CIPHER_AES_GCM_128: u16 = 51;
+const TLS_CIPHER_AES_GCM_256: u16 = 52;
+const TLS_CIPHER_CHACHA20_POLY1305: u16 = 54;
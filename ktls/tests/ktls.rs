@@ -0,0 +1,275 @@
+#![cfg(target_os = "linux")]
+
+use std::io::{self, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::os::unix::io::AsRawFd;
+use std::sync::Arc;
+
+use rustls::client::{ClientSessionMemoryCache, ClientSessionStore, Resumption};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use rustls::{
+    ClientConfig, ClientConnection, ConnectionCommon, RootCertStore, ServerConfig,
+    ServerConnection, SideData, SupportedProtocolVersion,
+};
+use rustls_ktls::KtlsStream;
+
+#[test]
+fn offload_tls12() {
+    if !tls_ulp_available() {
+        return;
+    }
+    offload(&rustls::version::TLS12);
+}
+
+#[test]
+fn offload_tls13() {
+    if !tls_ulp_available() {
+        return;
+    }
+    offload(&rustls::version::TLS13);
+}
+
+#[test]
+fn offloaded_client_stores_tickets_and_receives_close_notify() {
+    if !tls_ulp_available() {
+        return;
+    }
+
+    let storage = Arc::new(ClientSessionMemoryCache::new(8));
+    let mut client_config = client_config(&rustls::version::TLS13);
+    client_config.resumption = Resumption::store(Arc::clone(&storage) as Arc<_>);
+    let (client, mut server) = handshake(client_config, server_config());
+
+    // The server's tickets are queued once it has the client's Finished, and not
+    // received by the client until after offload.
+    assert!(server.wants_write());
+
+    // Only the client is offloaded; the server keeps using rustls.
+    let (client_socket, mut server_socket) = socket_pair();
+    let mut client = KtlsStream::new(client_socket, client).unwrap();
+
+    server
+        .writer()
+        .write_all(b"after ticket")
+        .unwrap();
+    server.send_close_notify();
+    while server.wants_write() {
+        server
+            .write_tls(&mut server_socket)
+            .unwrap();
+    }
+
+    let mut received = Vec::new();
+    client
+        .read_to_end(&mut received)
+        .unwrap();
+    assert_eq!(received, b"after ticket");
+    assert!(storage
+        .take_tls13_ticket(&ServerName::try_from("localhost").unwrap())
+        .is_some());
+}
+
+#[test]
+fn offloaded_key_updates() {
+    if !tls_ulp_available() {
+        return;
+    }
+
+    let (client, server) = handshake(client_config(&rustls::version::TLS13), server_config());
+    let (client_socket, server_socket) = socket_pair();
+    let mut server = KtlsStream::new(server_socket, server).unwrap();
+    let mut client = KtlsStream::new(client_socket, client).unwrap();
+
+    // Changing the keys of an offloaded socket needs Linux 6.13 or later.
+    match client.send_key_update() {
+        Err(err) if err.raw_os_error() == Some(libc::EBUSY) => return,
+        result => result.unwrap(),
+    }
+
+    // The server takes the client's new keys, and updates its own in reply.
+    client.write_all(b"ping").unwrap();
+    let mut buf = [0u8; 4];
+    server.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"ping");
+
+    server.write_all(b"pong").unwrap();
+    client.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"pong");
+}
+
+#[test]
+fn offload_requires_completed_handshake() {
+    let client = ClientConnection::new(
+        Arc::new(client_config(&rustls::version::TLS13)),
+        ServerName::try_from("localhost").unwrap(),
+    )
+    .unwrap();
+
+    let (client_socket, _server_socket) = socket_pair();
+    let err = KtlsStream::new(client_socket, client).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn offload_requires_secret_extraction() {
+    let mut client_config = client_config(&rustls::version::TLS13);
+    client_config.enable_secret_extraction = false;
+    let (client, _server) = handshake(client_config, server_config());
+
+    let (client_socket, _server_socket) = socket_pair();
+    let err = KtlsStream::new(client_socket, client).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    assert_eq!(
+        err.to_string(),
+        "unexpected error: Secret extraction is disabled"
+    );
+}
+
+fn offload(version: &'static SupportedProtocolVersion) {
+    let (mut client, mut server) = handshake(client_config(version), server_config());
+
+    // Plaintext received by rustls before offload ...
+    server
+        .writer()
+        .write_all(b"buffered")
+        .unwrap();
+    transfer(&mut server, &mut client);
+    client.process_new_packets().unwrap();
+
+    // ... and TLS data queued by rustls but not yet sent.
+    server
+        .writer()
+        .write_all(b" queued")
+        .unwrap();
+
+    let (client_socket, server_socket) = socket_pair();
+    let mut server = KtlsStream::new(server_socket, server).unwrap();
+    let mut client = KtlsStream::new(client_socket, client).unwrap();
+
+    server.write_all(b" offloaded").unwrap();
+    let mut buf = [0u8; 64];
+    let mut received = Vec::new();
+    while received.len() < b"buffered queued offloaded".len() {
+        let len = client.read(&mut buf).unwrap();
+        received.extend_from_slice(&buf[..len]);
+    }
+    assert_eq!(received, b"buffered queued offloaded");
+
+    client.write_all(b"hello").unwrap();
+    client.send_close_notify().unwrap();
+    let mut received = Vec::new();
+    server
+        .read_to_end(&mut received)
+        .unwrap();
+    assert_eq!(received, b"hello");
+}
+
+/// Whether the kernel's `tls` ULP can be used; if not, the tests that need it pass
+/// without doing anything.
+fn tls_ulp_available() -> bool {
+    let (socket, _peer) = socket_pair();
+    let name = b"tls";
+    // SAFETY: `name` is valid for reads of `name.len()` bytes.
+    let ret = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::SOL_TCP,
+            libc::TCP_ULP,
+            name.as_ptr().cast(),
+            name.len() as libc::socklen_t,
+        )
+    };
+
+    if ret != 0 {
+        println!(
+            "skipping: the kernel `tls` ULP is not available: {}",
+            io::Error::last_os_error()
+        );
+    }
+    ret == 0
+}
+
+fn socket_pair() -> (TcpStream, TcpStream) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let (server, _) = listener.accept().unwrap();
+    (client, server)
+}
+
+/// Complete a handshake in memory, leaving any session tickets unsent.
+fn handshake(
+    client_config: ClientConfig,
+    server_config: ServerConfig,
+) -> (ClientConnection, ServerConnection) {
+    let mut client = ClientConnection::new(
+        Arc::new(client_config),
+        ServerName::try_from("localhost").unwrap(),
+    )
+    .unwrap();
+    let mut server = ServerConnection::new(Arc::new(server_config)).unwrap();
+
+    while client.is_handshaking() {
+        transfer(&mut client, &mut server);
+        server.process_new_packets().unwrap();
+        transfer(&mut server, &mut client);
+        client.process_new_packets().unwrap();
+    }
+    transfer(&mut client, &mut server);
+    server.process_new_packets().unwrap();
+    assert!(!server.is_handshaking());
+
+    (client, server)
+}
+
+fn transfer<L: SideData, R: SideData>(
+    left: &mut ConnectionCommon<L>,
+    right: &mut ConnectionCommon<R>,
+) {
+    let mut buf = Vec::new();
+    while left.wants_write() {
+        left.write_tls(&mut buf).unwrap();
+    }
+
+    let mut buf = &buf[..];
+    while !buf.is_empty() {
+        right.read_tls(&mut buf).unwrap();
+    }
+}
+
+fn client_config(version: &'static SupportedProtocolVersion) -> ClientConfig {
+    let mut roots = RootCertStore::empty();
+    roots
+        .add(certs("ca.cert").remove(0))
+        .unwrap();
+    let mut config = ClientConfig::builder_with_protocol_versions(&[version])
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    config.enable_secret_extraction = true;
+    config
+}
+
+fn server_config() -> ServerConfig {
+    let key = rustls_pemfile::pkcs8_private_keys(&mut BufReader::new(
+        &include_bytes!("../../test-ca/rsa/end.key")[..],
+    ))
+    .next()
+    .unwrap()
+    .unwrap();
+    let mut config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs("end.fullchain"), PrivateKeyDer::Pkcs8(key))
+        .unwrap();
+    config.enable_secret_extraction = true;
+    config
+}
+
+fn certs(name: &str) -> Vec<CertificateDer<'static>> {
+    let pem: &[u8] = match name {
+        "ca.cert" => include_bytes!("../../test-ca/rsa/ca.cert"),
+        "end.fullchain" => include_bytes!("../../test-ca/rsa/end.fullchain"),
+        _ => unreachable!(),
+    };
+    rustls_pemfile::certs(&mut BufReader::new(pem))
+        .map(|result| result.unwrap())
+        .collect()
+}
@@ -18,8 +18,13 @@ fn main() {
 
         let accepted = loop {
             acceptor.read_tls(&mut stream).unwrap();
-            if let Some(accepted) = acceptor.accept().unwrap() {
-                break accepted;
+            match acceptor.accept() {
+                Ok(Some(accepted)) => break accepted,
+                Ok(None) => continue,
+                Err((e, mut alert)) => {
+                    alert.write_all(&mut stream).unwrap();
+                    panic!("error accepting connection: {}", e);
+                }
             }
         };
 
@@ -43,7 +48,8 @@ fn main() {
                 conn.write_tls(&mut stream).unwrap();
                 conn.complete_io(&mut stream).unwrap();
             }
-            Err(e) => {
+            Err((e, mut alert)) => {
+                alert.write_all(&mut stream).unwrap();
                 eprintln!("{}", e);
             }
         }
@@ -10,7 +10,9 @@ use rustls::crypto::{CryptoProvider, SupportedKxGroup};
 use rustls::internal::msgs::codec::Codec;
 use rustls::internal::msgs::persist::ServerSessionValue;
 use rustls::server::danger::{ClientCertVerified, ClientCertVerifier};
-use rustls::server::{ClientHello, ServerConfig, ServerConnection, WebPkiClientVerifier};
+use rustls::server::{
+    ClientHello, ClientIdentity, ServerConfig, ServerConnection, WebPkiClientVerifier,
+};
 use rustls::{
     self, client, server, sign, version, AlertDescription, CertificateError, Connection,
     DigitallySignedStruct, DistinguishedName, Error, InvalidMessage, NamedGroup, PeerIncompatible,
@@ -24,7 +26,7 @@ use rustls::crypto::aws_lc_rs as provider;
 use rustls::crypto::ring as provider;
 
 use base64::prelude::{Engine, BASE64_STANDARD};
-use pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use pki_types::{CertificateDer, DnsName, PrivateKeyDer, ServerName, UnixTime};
 
 use std::fmt::{Debug, Formatter};
 use std::io::{self, BufReader, Read, Write};
@@ -231,14 +233,15 @@ impl ClientCertVerifier for DummyClientAuth {
         self.mandatory
     }
 
-    fn root_hint_subjects(&self) -> &[DistinguishedName] {
+    fn root_hint_subjects_for(&self, _server_name: Option<&DnsName<'_>>) -> &[DistinguishedName] {
         &[]
     }
 
-    fn verify_client_cert(
+    fn verify_client_cert_for(
         &self,
         _end_entity: &CertificateDer<'_>,
         _intermediates: &[CertificateDer<'_>],
+        _server_name: Option<&DnsName<'_>>,
         _now: UnixTime,
     ) -> Result<ClientCertVerified, Error> {
         Ok(ClientCertVerified::assertion())
@@ -267,6 +270,13 @@ impl ClientCertVerifier for DummyClientAuth {
     fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
         self.parent.supported_verify_schemes()
     }
+
+    fn client_identity(
+        &self,
+        end_entity: &CertificateDer<'_>,
+    ) -> Result<Option<ClientIdentity>, Error> {
+        self.parent.client_identity(end_entity)
+    }
 }
 
 #[derive(Debug)]
@@ -294,6 +304,7 @@ impl ServerCertVerifier for DummyServerAuth {
         _certs: &[CertificateDer<'_>],
         _hostname: &ServerName<'_>,
         _ocsp: &[u8],
+        _scts: &[&[u8]],
         _now: UnixTime,
     ) -> Result<ServerCertVerified, Error> {
         Ok(ServerCertVerified::assertion())
@@ -159,6 +159,8 @@ impl ConfigBuilder<ClientConfig, WantsClientCert> {
             key_log: Arc::new(NoKeyLog {}),
             enable_secret_extraction: false,
             enable_early_data: false,
+            enable_ocsp_stapling: true,
+            enable_signed_cert_timestamps: false,
         }
     }
 }
@@ -4,10 +4,12 @@ use crate::conn::{ConnectionCommon, ConnectionCore, UnbufferedConnectionCommon};
 use crate::crypto::{CryptoProvider, SupportedKxGroup};
 use crate::enums::{CipherSuite, ProtocolVersion, SignatureScheme};
 use crate::error::Error;
+use crate::kernel::KernelConnection;
 #[cfg(feature = "logging")]
 use crate::log::trace;
-use crate::msgs::enums::NamedGroup;
-use crate::msgs::handshake::ClientExtension;
+use crate::msgs::base::Payload;
+use crate::msgs::enums::{ExtensionType, NamedGroup};
+use crate::msgs::handshake::{ClientExtension, UnknownExtension};
 use crate::msgs::persist;
 use crate::sign;
 use crate::suites::{ExtractedSecrets, SupportedCipherSuite};
@@ -21,8 +23,9 @@ use crate::{verify, WantsVersions};
 use super::handy::{ClientSessionMemoryCache, NoClientSessionStorage};
 use super::hs;
 
-use pki_types::ServerName;
+use pki_types::{DnsName, ServerName};
 
+use alloc::format;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 use core::fmt;
@@ -97,6 +100,31 @@ pub trait ClientSessionStore: fmt::Debug + Send + Sync {
     ) -> Option<persist::Tls13ClientSessionValue>;
 }
 
+/// A trait for the ability to remember the public key of each server,
+/// for use by a trust-on-first-use verifier such as
+/// [`TofuVerifier`](crate::client::TofuVerifier).
+///
+/// Keys are DER-encoded `SubjectPublicKeyInfo`s.
+pub trait StoresKnownHosts: fmt::Debug + Send + Sync {
+    /// Find the key recorded for `server_name`.  Return it, or None
+    /// if there isn't one.
+    fn get(&self, server_name: &ServerName<'_>) -> Option<Vec<u8>>;
+
+    /// Record `spki` as the key for `server_name`, unless a key is already
+    /// recorded for it.
+    ///
+    /// Returns the key that was already recorded, in which case `spki` is
+    /// discarded, or None if `spki` is now the recorded key.  This must be
+    /// atomic: when several calls race for the same `server_name`, exactly
+    /// one of them records its key and the rest return that key.
+    fn put(&self, server_name: ServerName<'static>, spki: Vec<u8>) -> Option<Vec<u8>>;
+
+    /// Forget the key recorded for `server_name`, so that the next key seen
+    /// for it is trusted.  Returns the key that was forgotten, or None if
+    /// there wasn't one.
+    fn remove(&self, server_name: &ServerName<'_>) -> Option<Vec<u8>>;
+}
+
 /// A trait for the ability to choose a certificate chain and
 /// private key for the purposes of client authentication.
 pub trait ResolvesClientCert: fmt::Debug + Send + Sync {
@@ -146,6 +174,10 @@ pub trait ResolvesClientCert: fmt::Debug + Send + Sync {
 ///    ids or tickets, with a max of eight tickets per server.
 /// * [`ClientConfig::alpn_protocols`]: the default is empty -- no ALPN protocol is negotiated.
 /// * [`ClientConfig::key_log`]: key material is not logged.
+/// * [`ClientConfig::enable_ocsp_stapling`]: the default is true -- the server is asked to
+///   staple an OCSP response.
+/// * [`ClientConfig::enable_signed_cert_timestamps`]: the default is false -- the server is
+///   not asked for signed certificate timestamps.
 ///
 /// [`RootCertStore`]: crate::RootCertStore
 #[derive(Debug)]
@@ -202,6 +234,28 @@ pub struct ClientConfig {
     ///
     /// The default is false.
     pub enable_early_data: bool,
+
+    /// Whether to ask the server to staple an OCSP response for its
+    /// certificate, by sending the `status_request` extension.
+    ///
+    /// A stapled response is passed to the certificate verifier, and is
+    /// available afterwards from [`ClientConnection::ocsp_response`].
+    ///
+    /// The default is true.
+    pub enable_ocsp_stapling: bool,
+
+    /// Whether to ask the server for signed certificate timestamps (SCTs) for
+    /// its certificate, by sending the `signed_certificate_timestamp`
+    /// extension ([RFC 6962]).
+    ///
+    /// SCTs sent by the server are passed to the certificate verifier, which
+    /// can check them with [`CtVerifier`].
+    ///
+    /// The default is false.
+    ///
+    /// [RFC 6962]: https://datatracker.ietf.org/doc/html/rfc6962#section-3.3.1
+    /// [`CtVerifier`]: crate::webpki::CtVerifier
+    pub enable_signed_cert_timestamps: bool,
 }
 
 /// What mechanisms to support for resuming a TLS 1.2 session.
@@ -234,6 +288,8 @@ impl Clone for ClientConfig {
             key_log: Arc::clone(&self.key_log),
             enable_secret_extraction: self.enable_secret_extraction,
             enable_early_data: self.enable_early_data,
+            enable_ocsp_stapling: self.enable_ocsp_stapling,
+            enable_signed_cert_timestamps: self.enable_signed_cert_timestamps,
         }
     }
 }
@@ -526,6 +582,49 @@ impl<'a> io::Write for WriteEarlyData<'a> {
     }
 }
 
+/// Per-connection options, for [`ClientConnection::new_with`] and
+/// [`UnbufferedClientConnection::new_with`].
+///
+/// These override parts of the [`ClientConfig`] for a single connection,
+/// so that connections which differ only in these respects can share one
+/// `ClientConfig` (and therefore one session store).
+#[derive(Clone, Debug, Default)]
+pub struct ClientConnectionOptions {
+    /// Which ALPN protocols to include in our client hello, replacing
+    /// [`ClientConfig::alpn_protocols`].  If empty, no ALPN extension is sent.
+    ///
+    /// `None` means the config's protocols are used.
+    pub alpn_protocols: Option<Vec<Vec<u8>>>,
+
+    /// What to send in the Server Name Indication (SNI) extension.
+    pub server_name_indication: ServerNameIndication,
+
+    /// Additional extensions to include in our client hello, as pairs
+    /// of extension type and body.
+    ///
+    /// Extension types that rustls itself knows about are rejected
+    /// when the connection is created.
+    pub extra_extensions: Vec<(u16, Vec<u8>)>,
+}
+
+/// What to send in the Server Name Indication (SNI) extension.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ServerNameIndication {
+    /// Send the name used to verify the server, if it is a DNS name and
+    /// [`ClientConfig::enable_sni`] is set.
+    FromServerName,
+    /// Do not send the SNI extension.
+    Disabled,
+    /// Send this name, instead of the name used to verify the server.
+    Name(DnsName<'static>),
+}
+
+impl Default for ServerNameIndication {
+    fn default() -> Self {
+        Self::FromServerName
+    }
+}
+
 /// This represents a single TLS client connection.
 pub struct ClientConnection {
     inner: ConnectionCommon<ClientConnectionData>,
@@ -543,8 +642,22 @@ impl ClientConnection {
     /// we behave in the TLS protocol, `name` is the
     /// name of the server we want to talk to.
     pub fn new(config: Arc<ClientConfig>, name: ServerName<'static>) -> Result<Self, Error> {
+        Self::new_with(config, name, ClientConnectionOptions::default())
+    }
+
+    /// Make a new ClientConnection, overriding parts of `config` with
+    /// `options` for this connection only.
+    ///
+    /// `name` is still used to verify the server's certificate and to
+    /// find sessions to resume.
+    pub fn new_with(
+        config: Arc<ClientConfig>,
+        name: ServerName<'static>,
+        options: ClientConnectionOptions,
+    ) -> Result<Self, Error> {
         Ok(Self {
-            inner: ConnectionCore::for_client(config, name, Vec::new(), Protocol::Tcp)?.into(),
+            inner: ConnectionCore::for_client(config, name, options, Vec::new(), Protocol::Tcp)?
+                .into(),
         })
     }
 
@@ -589,12 +702,31 @@ impl ClientConnection {
         self.inner.core.is_early_data_accepted()
     }
 
+    /// Returns the OCSP response the server stapled to its certificate, if any.
+    ///
+    /// This is only available once the server's certificate has been verified. It is `None`
+    /// if [`ClientConfig::enable_ocsp_stapling`] is false, if the server did not staple a
+    /// response, or if the session was resumed (in which case no certificate is sent).
+    pub fn ocsp_response(&self) -> Option<&[u8]> {
+        self.inner.core.data.ocsp_response()
+    }
+
     /// Extract secrets, so they can be used when configuring kTLS, for example.
     /// Should be used with care as it exposes secret key material.
     pub fn dangerous_extract_secrets(self) -> Result<ExtractedSecrets, Error> {
         self.inner.dangerous_extract_secrets()
     }
 
+    /// Extract secrets, and keep what is needed to handle post-handshake messages.
+    ///
+    /// See [`ConnectionCommon::dangerous_into_kernel_connection()`].
+    pub fn dangerous_into_kernel_connection(
+        self,
+    ) -> Result<(ExtractedSecrets, KernelConnection<ClientConnectionData>), Error> {
+        self.inner
+            .dangerous_into_kernel_connection()
+    }
+
     fn write_early_data(&mut self, data: &[u8]) -> io::Result<usize> {
         self.inner
             .core
@@ -645,9 +777,36 @@ impl ConnectionCore<ClientConnectionData> {
     pub(crate) fn for_client(
         config: Arc<ClientConfig>,
         name: ServerName<'static>,
-        extra_exts: Vec<ClientExtension>,
+        options: ClientConnectionOptions,
+        mut extra_exts: Vec<ClientExtension>,
         proto: Protocol,
     ) -> Result<Self, Error> {
+        for (typ, body) in options.extra_extensions {
+            let typ = ExtensionType::from(typ);
+            if !matches!(typ, ExtensionType::Unknown(_)) {
+                return Err(Error::General(format!(
+                    "cannot send extra extension {:?}: it is handled by rustls",
+                    typ
+                )));
+            }
+            extra_exts.push(ClientExtension::Unknown(UnknownExtension {
+                typ,
+                payload: Payload::new(body),
+            }));
+        }
+
+        let sni = match options.server_name_indication {
+            ServerNameIndication::FromServerName => match &name {
+                ServerName::DnsName(dns) if config.enable_sni => Some(dns.to_owned()),
+                _ => None,
+            },
+            ServerNameIndication::Disabled => None,
+            ServerNameIndication::Name(dns) => Some(dns),
+        };
+        let alpn_protocols = options
+            .alpn_protocols
+            .unwrap_or_else(|| config.alpn_protocols.clone());
+
         let mut common_state = CommonState::new(Side::Client);
         common_state.set_max_fragment_size(config.max_fragment_size)?;
         common_state.protocol = proto;
@@ -661,7 +820,7 @@ impl ConnectionCore<ClientConnectionData> {
             sendable_plaintext: None,
         };
 
-        let state = hs::start_handshake(name, extra_exts, config, &mut cx)?;
+        let state = hs::start_handshake(name, sni, alpn_protocols, extra_exts, config, &mut cx)?;
         Ok(Self::new(state, data, common_state))
     }
 
@@ -681,10 +840,30 @@ impl UnbufferedClientConnection {
     /// Make a new ClientConnection. `config` controls how we behave in the TLS protocol, `name` is
     /// the name of the server we want to talk to.
     pub fn new(config: Arc<ClientConfig>, name: ServerName<'static>) -> Result<Self, Error> {
+        Self::new_with(config, name, ClientConnectionOptions::default())
+    }
+
+    /// Make a new UnbufferedClientConnection, overriding parts of `config`
+    /// with `options` for this connection only.
+    ///
+    /// See [`ClientConnection::new_with`].
+    pub fn new_with(
+        config: Arc<ClientConfig>,
+        name: ServerName<'static>,
+        options: ClientConnectionOptions,
+    ) -> Result<Self, Error> {
         Ok(Self {
-            inner: ConnectionCore::for_client(config, name, Vec::new(), Protocol::Tcp)?.into(),
+            inner: ConnectionCore::for_client(config, name, options, Vec::new(), Protocol::Tcp)?
+                .into(),
         })
     }
+
+    /// Returns the OCSP response the server stapled to its certificate, if any.
+    ///
+    /// See [`ClientConnection::ocsp_response`].
+    pub fn ocsp_response(&self) -> Option<&[u8]> {
+        self.inner.core.data.ocsp_response()
+    }
 }
 
 impl Deref for UnbufferedClientConnection {
@@ -786,6 +965,7 @@ impl StdError for EarlyDataError {}
 pub struct ClientConnectionData {
     pub(super) early_data: EarlyData,
     pub(super) resumption_ciphersuite: Option<SupportedCipherSuite>,
+    pub(super) ocsp_response: Vec<u8>,
 }
 
 impl ClientConnectionData {
@@ -793,6 +973,14 @@ impl ClientConnectionData {
         Self {
             early_data: EarlyData::new(),
             resumption_ciphersuite: None,
+            ocsp_response: Vec::new(),
+        }
+    }
+
+    fn ocsp_response(&self) -> Option<&[u8]> {
+        match self.ocsp_response.is_empty() {
+            true => None,
+            false => Some(&self.ocsp_response),
         }
     }
 }
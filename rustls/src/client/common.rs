@@ -3,7 +3,7 @@ use super::ResolvesClientCert;
 use crate::log::{debug, trace};
 use crate::msgs::enums::ExtensionType;
 use crate::msgs::handshake::ServerExtension;
-use crate::msgs::handshake::{CertificateChain, DistinguishedName};
+use crate::msgs::handshake::{CertificateChain, DistinguishedName, Sct};
 use crate::{sign, SignatureScheme};
 
 use alloc::boxed::Box;
@@ -14,25 +14,41 @@ use alloc::vec::Vec;
 pub(super) struct ServerCertDetails {
     pub(super) cert_chain: CertificateChain,
     pub(super) ocsp_response: Vec<u8>,
+    pub(super) sct_list: Vec<Sct>,
 }
 
 impl ServerCertDetails {
-    pub(super) fn new(cert_chain: CertificateChain, ocsp_response: Vec<u8>) -> Self {
+    pub(super) fn new(
+        cert_chain: CertificateChain,
+        ocsp_response: Vec<u8>,
+        sct_list: Vec<Sct>,
+    ) -> Self {
         Self {
             cert_chain,
             ocsp_response,
+            sct_list,
         }
     }
+
+    /// The encoded SCTs the server sent, to pass to the certificate verifier.
+    pub(super) fn scts(&self) -> Vec<&[u8]> {
+        self.sct_list
+            .iter()
+            .map(|sct| sct.0.as_slice())
+            .collect()
+    }
 }
 
 pub(super) struct ClientHelloDetails {
     pub(super) sent_extensions: Vec<ExtensionType>,
+    pub(super) alpn_protocols: Vec<Vec<u8>>,
 }
 
 impl ClientHelloDetails {
-    pub(super) fn new() -> Self {
+    pub(super) fn new(alpn_protocols: Vec<Vec<u8>>) -> Self {
         Self {
             sent_extensions: Vec::new(),
+            alpn_protocols,
         }
     }
 
@@ -2,6 +2,8 @@ use crate::client;
 use crate::enums::SignatureScheme;
 use crate::error::Error;
 use crate::limited_cache;
+#[cfg(feature = "logging")]
+use crate::log::warn;
 use crate::msgs::handshake::CertificateChain;
 use crate::msgs::persist;
 use crate::sign;
@@ -10,8 +12,17 @@ use crate::NamedGroup;
 use pki_types::ServerName;
 
 use alloc::collections::VecDeque;
+use alloc::format;
+use alloc::string::String;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 use core::fmt;
+use core::fmt::Write;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
 use std::sync::Mutex;
 
 /// An implementer of `ClientSessionStore` which does nothing.
@@ -172,6 +183,179 @@ impl fmt::Debug for ClientSessionMemoryCache {
     }
 }
 
+/// An implementer of `StoresKnownHosts` that stores everything
+/// in memory.
+#[derive(Debug, Default)]
+pub struct KnownHostsMemoryCache {
+    hosts: Mutex<HashMap<ServerName<'static>, Vec<u8>>>,
+}
+
+impl KnownHostsMemoryCache {
+    /// Make a new, empty KnownHostsMemoryCache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl client::StoresKnownHosts for KnownHostsMemoryCache {
+    fn get(&self, server_name: &ServerName<'_>) -> Option<Vec<u8>> {
+        self.hosts
+            .lock()
+            .unwrap()
+            .get(server_name)
+            .cloned()
+    }
+
+    fn put(&self, server_name: ServerName<'static>, spki: Vec<u8>) -> Option<Vec<u8>> {
+        match self
+            .hosts
+            .lock()
+            .unwrap()
+            .entry(server_name)
+        {
+            Entry::Occupied(known) => Some(known.get().clone()),
+            Entry::Vacant(entry) => {
+                entry.insert(spki);
+                None
+            }
+        }
+    }
+
+    fn remove(&self, server_name: &ServerName<'_>) -> Option<Vec<u8>> {
+        self.hosts
+            .lock()
+            .unwrap()
+            .remove(&server_name.to_owned())
+    }
+}
+
+/// An implementer of `StoresKnownHosts` that keeps keys in a file.
+///
+/// The file has one line per server, holding the server name and the
+/// hex-encoded key separated by a space.  Empty lines and lines starting
+/// with `#` are ignored.  The file is read once, when opened, and then
+/// rewritten whenever a key is stored or removed.  If it cannot be
+/// rewritten, the change is still kept in memory, and is written along with
+/// the next change.
+pub struct KnownHostsFile {
+    path: PathBuf,
+    hosts: Mutex<HashMap<ServerName<'static>, Vec<u8>>>,
+}
+
+impl KnownHostsFile {
+    /// Open the known hosts file at `path`.
+    ///
+    /// A missing file is treated as empty, and is created when the first
+    /// key is stored.
+    pub fn open(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err),
+        };
+
+        let mut hosts = HashMap::new();
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, spki) = parse_known_host(line).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, "invalid known hosts entry")
+            })?;
+            hosts.insert(name, spki);
+        }
+
+        Ok(Self {
+            path,
+            hosts: Mutex::new(hosts),
+        })
+    }
+
+    /// Write `hosts` to a temporary file, then move it into place so that
+    /// the file is never seen half-written.
+    fn write(&self, hosts: &HashMap<ServerName<'static>, Vec<u8>>) -> io::Result<()> {
+        let mut lines = hosts
+            .iter()
+            .map(|(name, spki)| {
+                let mut line = format!("{} ", name.to_str());
+                for b in spki {
+                    let _ = write!(line, "{:02x}", b);
+                }
+                line.push('\n');
+                line
+            })
+            .collect::<Vec<_>>();
+        lines.sort();
+
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        fs::write(&tmp, lines.concat())?;
+        fs::rename(&tmp, &self.path)
+    }
+
+    fn write_or_warn(&self, hosts: &HashMap<ServerName<'static>, Vec<u8>>) {
+        if let Err(_err) = self.write(hosts) {
+            warn!("Failed to write known hosts file {:?}: {}", self.path, _err);
+        }
+    }
+}
+
+fn parse_known_host(line: &str) -> Option<(ServerName<'static>, Vec<u8>)> {
+    let (name, hex) = line.split_once(' ')?;
+    let name = ServerName::try_from(String::from(name)).ok()?;
+
+    let hex = hex.trim().as_bytes();
+    if hex.is_empty() || hex.len() % 2 != 0 || !hex.iter().all(u8::is_ascii_hexdigit) {
+        return None;
+    }
+    let spki = hex
+        .chunks(2)
+        .map(|pair| u8::from_str_radix(core::str::from_utf8(pair).ok()?, 16).ok())
+        .collect::<Option<Vec<_>>>()?;
+    Some((name, spki))
+}
+
+impl client::StoresKnownHosts for KnownHostsFile {
+    fn get(&self, server_name: &ServerName<'_>) -> Option<Vec<u8>> {
+        self.hosts
+            .lock()
+            .unwrap()
+            .get(server_name)
+            .cloned()
+    }
+
+    fn put(&self, server_name: ServerName<'static>, spki: Vec<u8>) -> Option<Vec<u8>> {
+        // The lock is held while writing, so concurrent writes are not
+        // interleaved.
+        let mut hosts = self.hosts.lock().unwrap();
+        match hosts.entry(server_name) {
+            Entry::Occupied(known) => return Some(known.get().clone()),
+            Entry::Vacant(entry) => {
+                entry.insert(spki);
+            }
+        }
+        self.write_or_warn(&hosts);
+        None
+    }
+
+    fn remove(&self, server_name: &ServerName<'_>) -> Option<Vec<u8>> {
+        let mut hosts = self.hosts.lock().unwrap();
+        let removed = hosts.remove(&server_name.to_owned())?;
+        self.write_or_warn(&hosts);
+        Some(removed)
+    }
+}
+
+impl fmt::Debug for KnownHostsFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KnownHostsFile")
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
 #[derive(Debug)]
 pub(super) struct FailResolveClientCert {}
 
@@ -220,8 +404,11 @@ impl client::ResolvesClientCert for AlwaysResolvesClientCert {
 
 #[cfg(all(test, any(feature = "ring", feature = "aws_lc_rs")))]
 mod tests {
-    use super::NoClientSessionStorage;
-    use crate::client::ClientSessionStore;
+    use std::prelude::v1::*;
+    use std::{env, fs, process};
+
+    use super::{KnownHostsFile, NoClientSessionStorage};
+    use crate::client::{ClientSessionStore, StoresKnownHosts};
     use crate::msgs::enums::NamedGroup;
     use crate::msgs::handshake::CertificateChain;
     #[cfg(feature = "tls12")]
@@ -288,4 +475,48 @@ mod tests {
         );
         assert!(c.take_tls13_ticket(&name).is_none());
     }
+
+    #[test]
+    fn test_known_hosts_file_persists_keys() {
+        let path = env::temp_dir().join(format!("rustls-known-hosts-{}", process::id()));
+        let _ = fs::remove_file(&path);
+        let example = ServerName::try_from("example.com").unwrap();
+        let ip = ServerName::try_from("192.0.2.1").unwrap();
+
+        let file = KnownHostsFile::open(&path).unwrap();
+        assert_eq!(file.get(&example), None);
+        assert_eq!(file.put(example.clone(), vec![0x30, 0x01, 0xff]), None);
+        assert_eq!(file.put(ip.clone(), vec![0x30, 0x00]), None);
+        assert_eq!(
+            file.put(example.clone(), vec![0x30, 0x01, 0x00]),
+            Some(vec![0x30, 0x01, 0xff])
+        );
+        assert_eq!(file.remove(&example), Some(vec![0x30, 0x01, 0xff]));
+        assert_eq!(file.remove(&example), None);
+        assert_eq!(file.put(example.clone(), vec![0x30, 0x01, 0x00]), None);
+
+        let reopened = KnownHostsFile::open(&path).unwrap();
+        assert_eq!(reopened.get(&example), Some(vec![0x30, 0x01, 0x00]));
+        assert_eq!(reopened.get(&ip), Some(vec![0x30, 0x00]));
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "192.0.2.1 3000\nexample.com 300100\n"
+        );
+
+        fs::write(&path, "# comment\n\nexample.com 30\n").unwrap();
+        let reopened = KnownHostsFile::open(&path).unwrap();
+        assert_eq!(reopened.get(&example), Some(vec![0x30]));
+
+        for bad in [
+            "example.com",
+            "example.com 3",
+            "example.com +3",
+            "not a name 30",
+        ] {
+            fs::write(&path, bad).unwrap();
+            assert!(KnownHostsFile::open(&path).is_err());
+        }
+
+        fs::remove_file(&path).unwrap();
+    }
 }
@@ -30,7 +30,7 @@ use crate::client::client_conn::ClientConnectionData;
 use crate::client::common::ClientHelloDetails;
 use crate::client::{tls13, ClientConfig};
 
-use pki_types::{ServerName, UnixTime};
+use pki_types::{DnsName, ServerName, UnixTime};
 
 use alloc::borrow::ToOwned;
 use alloc::boxed::Box;
@@ -92,6 +92,8 @@ fn find_session(
 
 pub(super) fn start_handshake(
     server_name: ServerName<'static>,
+    sni: Option<DnsName<'static>>,
+    alpn_protocols: Vec<Vec<u8>>,
     extra_exts: Vec<ClientExtension>,
     config: Arc<ClientConfig>,
     cx: &mut ClientContext<'_>,
@@ -155,9 +157,10 @@ pub(super) fn start_handshake(
             #[cfg(feature = "tls12")]
             using_ems: false,
             sent_tls13_fake_ccs: false,
-            hello: ClientHelloDetails::new(),
+            hello: ClientHelloDetails::new(alpn_protocols),
             session_id,
             server_name,
+            sni,
         },
         cx,
     ))
@@ -186,6 +189,7 @@ struct ClientHelloInput {
     hello: ClientHelloDetails,
     session_id: SessionId,
     server_name: ServerName<'static>,
+    sni: Option<DnsName<'static>>,
 }
 
 fn emit_client_hello_for_retry(
@@ -230,10 +234,19 @@ fn emit_client_hello_for_retry(
                 .supported_verify_schemes(),
         ),
         ClientExtension::ExtendedMasterSecretRequest,
-        ClientExtension::CertificateStatusRequest(CertificateStatusRequest::build_ocsp()),
     ];
 
-    if let (ServerName::DnsName(dns), true) = (&input.server_name, config.enable_sni) {
+    if config.enable_ocsp_stapling {
+        exts.push(ClientExtension::CertificateStatusRequest(
+            CertificateStatusRequest::build_ocsp(),
+        ));
+    }
+
+    if config.enable_signed_cert_timestamps {
+        exts.push(ClientExtension::SignedCertificateTimestampRequest);
+    }
+
+    if let Some(dns) = &input.sni {
         // We only want to send the SNI extension if the server name contains a DNS name.
         exts.push(ClientExtension::make_sni(dns));
     }
@@ -255,9 +268,10 @@ fn emit_client_hello_for_retry(
         exts.push(ClientExtension::PresharedKeyModes(psk_modes));
     }
 
-    if !config.alpn_protocols.is_empty() {
+    if !input.hello.alpn_protocols.is_empty() {
         exts.push(ClientExtension::Protocols(Vec::from_slices(
-            &config
+            &input
+                .hello
                 .alpn_protocols
                 .iter()
                 .map(|proto| &proto[..])
@@ -440,16 +454,13 @@ fn prepare_resumption<'a>(
 
 pub(super) fn process_alpn_protocol(
     common: &mut CommonState,
-    config: &ClientConfig,
+    offered_protocols: &[Vec<u8>],
     proto: Option<&[u8]>,
 ) -> Result<(), Error> {
     common.alpn_protocol = proto.map(ToOwned::to_owned);
 
     if let Some(alpn_protocol) = &common.alpn_protocol {
-        if !config
-            .alpn_protocols
-            .contains(alpn_protocol)
-        {
+        if !offered_protocols.contains(alpn_protocol) {
             return Err(common.send_fatal_alert(
                 AlertDescription::IllegalParameter,
                 PeerMisbehaved::SelectedUnofferedApplicationProtocol,
@@ -463,7 +474,7 @@ pub(super) fn process_alpn_protocol(
     // mechanism) iff any ALPN protocols were configured. This defends against badly-behaved
     // servers which accept a connection that requires an application-layer protocol they do not
     // understand.
-    if common.is_quic() && common.alpn_protocol.is_none() && !config.alpn_protocols.is_empty() {
+    if common.is_quic() && common.alpn_protocol.is_none() && !offered_protocols.is_empty() {
         return Err(common.send_fatal_alert(
             AlertDescription::NoApplicationProtocol,
             Error::NoApplicationProtocol,
@@ -564,7 +575,11 @@ impl State<ClientConnectionData> for ExpectServerHello {
 
         // Extract ALPN protocol
         if !cx.common.is_tls13() {
-            process_alpn_protocol(cx.common, config, server_hello.get_alpn_protocol())?;
+            process_alpn_protocol(
+                cx.common,
+                &self.input.hello.alpn_protocols,
+                server_hello.get_alpn_protocol(),
+            )?;
         }
 
         // If ECPointFormats extension is supplied by the server, it must contain
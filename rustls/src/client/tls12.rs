@@ -5,6 +5,7 @@ use crate::enums::ProtocolVersion;
 use crate::enums::{AlertDescription, ContentType, HandshakeType};
 use crate::error::{Error, InvalidMessage, PeerMisbehaved};
 use crate::hash_hs::HandshakeHash;
+use crate::kernel::{Direction, KernelContext, KernelState};
 #[cfg(feature = "logging")]
 use crate::log::{debug, trace, warn};
 use crate::msgs::base::{Payload, PayloadU8};
@@ -12,12 +13,12 @@ use crate::msgs::ccs::ChangeCipherSpecPayload;
 use crate::msgs::codec::Codec;
 use crate::msgs::handshake::{
     CertificateChain, HandshakeMessagePayload, HandshakePayload, NewSessionTicketPayload,
-    ServerEcdhParams, SessionId,
+    NewSessionTicketPayloadTls13, Sct, ServerEcdhParams, SessionId,
 };
 use crate::msgs::message::{Message, MessagePayload};
 use crate::msgs::persist;
 use crate::sign::Signer;
-use crate::suites::{PartiallyExtractedSecrets, SupportedCipherSuite};
+use crate::suites::{ConnectionTrafficSecrets, PartiallyExtractedSecrets, SupportedCipherSuite};
 use crate::tls12::{self, ConnectionSecrets, Tls12CipherSuite};
 use crate::verify::{self, DigitallySignedStruct};
 
@@ -102,6 +103,12 @@ mod server_hello {
                 debug!("Server may staple OCSP response");
             }
 
+            // Save any sent SCTs for verification against the certificate.
+            let server_cert_sct_list = server_hello
+                .get_sct_list()
+                .map(<[Sct]>::to_vec)
+                .unwrap_or_default();
+
             // See if we're successfully resuming.
             if let Some(resuming) = self.resuming_session {
                 if resuming.session_id == server_hello.session_id {
@@ -175,6 +182,7 @@ mod server_hello {
                 suite,
                 may_send_cert_status,
                 must_issue_new_ticket,
+                server_cert_sct_list,
             }))
         }
     }
@@ -191,6 +199,7 @@ struct ExpectCertificate {
     pub(super) suite: &'static Tls12CipherSuite,
     may_send_cert_status: bool,
     must_issue_new_ticket: bool,
+    server_cert_sct_list: Vec<Sct>,
 }
 
 impl State<ClientConnectionData> for ExpectCertificate {
@@ -218,9 +227,11 @@ impl State<ClientConnectionData> for ExpectCertificate {
                 suite: self.suite,
                 server_cert_chain,
                 must_issue_new_ticket: self.must_issue_new_ticket,
+                server_cert_sct_list: self.server_cert_sct_list,
             }))
         } else {
-            let server_cert = ServerCertDetails::new(server_cert_chain, vec![]);
+            let server_cert =
+                ServerCertDetails::new(server_cert_chain, vec![], self.server_cert_sct_list);
 
             Ok(Box::new(ExpectServerKx {
                 config: self.config,
@@ -249,6 +260,7 @@ struct ExpectCertificateStatusOrServerKx {
     suite: &'static Tls12CipherSuite,
     server_cert_chain: CertificateChain,
     must_issue_new_ticket: bool,
+    server_cert_sct_list: Vec<Sct>,
 }
 
 impl State<ClientConnectionData> for ExpectCertificateStatusOrServerKx {
@@ -270,7 +282,11 @@ impl State<ClientConnectionData> for ExpectCertificateStatusOrServerKx {
                 using_ems: self.using_ems,
                 transcript: self.transcript,
                 suite: self.suite,
-                server_cert: ServerCertDetails::new(self.server_cert_chain, vec![]),
+                server_cert: ServerCertDetails::new(
+                    self.server_cert_chain,
+                    vec![],
+                    self.server_cert_sct_list,
+                ),
                 must_issue_new_ticket: self.must_issue_new_ticket,
             })
             .handle(cx, m),
@@ -292,6 +308,7 @@ impl State<ClientConnectionData> for ExpectCertificateStatusOrServerKx {
                 suite: self.suite,
                 server_cert_chain: self.server_cert_chain,
                 must_issue_new_ticket: self.must_issue_new_ticket,
+                server_cert_sct_list: self.server_cert_sct_list,
             })
             .handle(cx, m),
             payload => Err(inappropriate_handshake_message(
@@ -317,6 +334,7 @@ struct ExpectCertificateStatus {
     suite: &'static Tls12CipherSuite,
     server_cert_chain: CertificateChain,
     must_issue_new_ticket: bool,
+    server_cert_sct_list: Vec<Sct>,
 }
 
 impl State<ClientConnectionData> for ExpectCertificateStatus {
@@ -338,7 +356,11 @@ impl State<ClientConnectionData> for ExpectCertificateStatus {
             &server_cert_ocsp_response
         );
 
-        let server_cert = ServerCertDetails::new(self.server_cert_chain, server_cert_ocsp_response);
+        let server_cert = ServerCertDetails::new(
+            self.server_cert_chain,
+            server_cert_ocsp_response,
+            self.server_cert_sct_list,
+        );
 
         Ok(Box::new(ExpectServerKx {
             config: self.config,
@@ -706,20 +728,24 @@ impl State<ClientConnectionData> for ExpectServerDone {
             .cert_chain
             .split_first()
             .ok_or(Error::NoCertificatesPresented)?;
-        let cert_verified = st
+        let scts = st.server_cert.scts();
+        let now = UnixTime::now();
+        let (cert_verified, report) = st
             .config
             .verifier
-            .verify_server_cert(
+            .verify_server_cert_with_report(
                 end_entity,
                 intermediates,
                 &st.server_name,
                 &st.server_cert.ocsp_response,
-                UnixTime::now(),
-            )
-            .map_err(|err| {
-                cx.common
-                    .send_cert_verify_error_alert(err)
-            })?;
+                &scts,
+                now,
+            );
+        cx.common.peer_verification_report = report;
+        let cert_verified = cert_verified.map_err(|err| {
+            cx.common
+                .send_cert_verify_error_alert(err)
+        })?;
 
         // 3.
         // Build up the contents of the signed message.
@@ -751,6 +777,7 @@ impl State<ClientConnectionData> for ExpectServerDone {
                 })?
         };
         cx.common.peer_certificates = Some(st.server_cert.cert_chain);
+        cx.data.ocsp_response = st.server_cert.ocsp_response;
 
         // 4.
         if let Some(client_auth) = &st.client_auth {
@@ -1094,4 +1121,26 @@ impl State<ClientConnectionData> for ExpectTraffic {
         self.secrets
             .extract_secrets(Side::Client)
     }
+
+    fn into_kernel_state(self: Box<Self>) -> Result<Box<dyn KernelState + 'static>, Error> {
+        Ok(self)
+    }
+}
+
+impl KernelState for ExpectTraffic {
+    fn update_secrets(&mut self, _dir: Direction) -> Result<ConnectionTrafficSecrets, Error> {
+        Err(Error::General(
+            "TLS 1.2 connections do not support traffic secret updates".into(),
+        ))
+    }
+
+    fn handle_new_session_ticket(
+        &mut self,
+        _cx: &mut KernelContext<'_>,
+        _message: &NewSessionTicketPayloadTls13,
+    ) -> Result<(), Error> {
+        Err(Error::General(
+            "TLS 1.2 session tickets may not be sent once the handshake has completed".into(),
+        ))
+    }
 }
@@ -9,6 +9,7 @@ use crate::enums::{
 };
 use crate::error::{Error, InvalidMessage, PeerIncompatible, PeerMisbehaved};
 use crate::hash_hs::{HandshakeHash, HandshakeHashBuffer};
+use crate::kernel::{Direction, KernelContext, KernelState};
 #[cfg(feature = "logging")]
 use crate::log::{debug, trace, warn};
 use crate::msgs::base::{Payload, PayloadU8};
@@ -24,7 +25,7 @@ use crate::msgs::handshake::{PresharedKeyIdentity, PresharedKeyOffer};
 use crate::msgs::message::{Message, MessagePayload};
 use crate::msgs::persist;
 use crate::sign::{CertifiedKey, Signer};
-use crate::suites::PartiallyExtractedSecrets;
+use crate::suites::{ConnectionTrafficSecrets, PartiallyExtractedSecrets};
 use crate::tls13::construct_client_verify_message;
 use crate::tls13::construct_server_verify_message;
 use crate::tls13::key_schedule::{
@@ -62,6 +63,7 @@ static DISALLOWED_TLS13_EXTS: &[ExtensionType] = &[
     ExtensionType::SessionTicket,
     ExtensionType::RenegotiationInfo,
     ExtensionType::ExtendedMasterSecret,
+    ExtensionType::SCT,
 ];
 
 pub(super) fn handle_server_hello(
@@ -390,7 +392,11 @@ impl State<ClientConnectionData> for ExpectEncryptedExtensions {
         self.transcript.add_message(&m);
 
         validate_encrypted_extensions(cx.common, &self.hello, exts)?;
-        hs::process_alpn_protocol(cx.common, &self.config, exts.get_alpn_protocol())?;
+        hs::process_alpn_protocol(
+            cx.common,
+            &self.hello.alpn_protocols,
+            exts.get_alpn_protocol(),
+        )?;
 
         // QUIC transport parameters
         if cx.common.is_quic() {
@@ -622,7 +628,20 @@ impl State<ClientConnectionData> for ExpectCertificate {
             ));
         }
         let end_entity_ocsp = cert_chain.get_end_entity_ocsp();
-        let server_cert = ServerCertDetails::new(cert_chain.convert(), end_entity_ocsp);
+        let end_entity_sct_list = cert_chain.get_end_entity_sct_list();
+        if (!end_entity_ocsp.is_empty() && !self.config.enable_ocsp_stapling)
+            || (!end_entity_sct_list.is_empty()
+                && !self
+                    .config
+                    .enable_signed_cert_timestamps)
+        {
+            return Err(cx.common.send_fatal_alert(
+                AlertDescription::UnsupportedExtension,
+                PeerMisbehaved::UnsolicitedCertExtension,
+            ));
+        }
+        let server_cert =
+            ServerCertDetails::new(cert_chain.convert(), end_entity_ocsp, end_entity_sct_list);
 
         Ok(Box::new(ExpectCertificateVerify {
             config: self.config,
@@ -665,20 +684,24 @@ impl State<ClientConnectionData> for ExpectCertificateVerify {
             .cert_chain
             .split_first()
             .ok_or(Error::NoCertificatesPresented)?;
-        let cert_verified = self
+        let scts = self.server_cert.scts();
+        let now = UnixTime::now();
+        let (cert_verified, report) = self
             .config
             .verifier
-            .verify_server_cert(
+            .verify_server_cert_with_report(
                 end_entity,
                 intermediates,
                 &self.server_name,
                 &self.server_cert.ocsp_response,
-                UnixTime::now(),
-            )
-            .map_err(|err| {
-                cx.common
-                    .send_cert_verify_error_alert(err)
-            })?;
+                &scts,
+                now,
+            );
+        cx.common.peer_verification_report = report;
+        let cert_verified = cert_verified.map_err(|err| {
+            cx.common
+                .send_cert_verify_error_alert(err)
+        })?;
 
         // 2. Verify their signature on the handshake.
         let handshake_hash = self.transcript.get_current_hash();
@@ -696,6 +719,7 @@ impl State<ClientConnectionData> for ExpectCertificateVerify {
             })?;
 
         cx.common.peer_certificates = Some(self.server_cert.cert_chain);
+        cx.data.ocsp_response = self.server_cert.ocsp_response;
         self.transcript.add_message(&m);
 
         Ok(Box::new(ExpectFinished {
@@ -943,6 +967,19 @@ impl ExpectTraffic {
             ));
         }
 
+        let mut kcx = KernelContext {
+            peer_certificates: cx.common.peer_certificates.as_ref(),
+            protocol: cx.common.protocol,
+            quic: &cx.common.quic,
+        };
+        self.store_new_ticket_tls13(&mut kcx, nst)
+    }
+
+    fn store_new_ticket_tls13(
+        &mut self,
+        cx: &mut KernelContext<'_>,
+        nst: &NewSessionTicketPayloadTls13,
+    ) -> Result<(), Error> {
         let handshake_hash = self.transcript.get_current_hash();
         let secret = self
             .key_schedule
@@ -953,9 +990,8 @@ impl ExpectTraffic {
             self.suite,
             nst.ticket.0.clone(),
             secret.as_ref(),
-            cx.common
-                .peer_certificates
-                .clone()
+            cx.peer_certificates
+                .cloned()
                 .unwrap_or_default(),
             UnixTime::now(),
             nst.lifetime,
@@ -964,14 +1000,14 @@ impl ExpectTraffic {
                 .unwrap_or_default(),
         );
 
-        if cx.common.is_quic() {
+        if cx.is_quic() {
             if let Some(sz) = nst.get_max_early_data_size() {
                 if sz != 0 && sz != 0xffff_ffff {
                     return Err(PeerMisbehaved::InvalidMaxEarlyDataSize.into());
                 }
             }
 
-            if let Some(ref quic_params) = &cx.common.quic.params {
+            if let Some(ref quic_params) = &cx.quic.params {
                 value.set_quic_params(quic_params);
             }
         }
@@ -1056,6 +1092,32 @@ impl State<ClientConnectionData> for ExpectTraffic {
         self.key_schedule
             .extract_secrets(Side::Client)
     }
+
+    fn into_kernel_state(self: Box<Self>) -> Result<Box<dyn KernelState + 'static>, Error> {
+        Ok(self)
+    }
+}
+
+impl KernelState for ExpectTraffic {
+    fn update_secrets(&mut self, dir: Direction) -> Result<ConnectionTrafficSecrets, Error> {
+        self.key_schedule
+            .refresh_traffic_secret(match dir {
+                Direction::Transmit => Side::Client,
+                Direction::Receive => Side::Server,
+            })
+    }
+
+    fn handle_new_session_ticket(
+        &mut self,
+        cx: &mut KernelContext<'_>,
+        message: &NewSessionTicketPayloadTls13,
+    ) -> Result<(), Error> {
+        if message.has_duplicate_extension() {
+            return Err(PeerMisbehaved::DuplicateNewSessionTicketExtensions.into());
+        }
+
+        self.store_new_ticket_tls13(cx, message)
+    }
 }
 
 struct ExpectQuicTraffic(ExpectTraffic);
@@ -1,11 +1,12 @@
 use crate::enums::{AlertDescription, ContentType, HandshakeType, ProtocolVersion};
 use crate::error::{Error, InvalidMessage, PeerMisbehaved};
+use crate::kernel::KernelState;
 #[cfg(feature = "logging")]
 use crate::log::{debug, warn};
 use crate::msgs::alert::AlertMessagePayload;
 use crate::msgs::base::Payload;
 use crate::msgs::enums::{AlertLevel, KeyUpdateRequest};
-use crate::msgs::fragmenter::MessageFragmenter;
+use crate::msgs::fragmenter::{MessageFragmenter, PACKET_OVERHEAD};
 use crate::msgs::handshake::CertificateChain;
 use crate::msgs::message::MessagePayload;
 use crate::msgs::message::{BorrowedPlainMessage, Message, OpaqueMessage, PlainMessage};
@@ -17,9 +18,12 @@ use crate::suites::SupportedCipherSuite;
 use crate::tls12::ConnectionSecrets;
 use crate::unbuffered::{EncryptError, InsufficientSizeError};
 use crate::vecbuf::ChunkVecBuffer;
+use crate::webpki::VerificationReport;
 
 use alloc::boxed::Box;
 use alloc::vec::Vec;
+use core::{cmp, iter};
+use std::io;
 
 use pki_types::CertificateDer;
 
@@ -40,6 +44,7 @@ pub struct CommonState {
     pub(crate) has_seen_eof: bool,
     pub(crate) received_middlebox_ccs: u8,
     pub(crate) peer_certificates: Option<CertificateChain>,
+    pub(crate) peer_verification_report: Option<VerificationReport>,
     message_fragmenter: MessageFragmenter,
     pub(crate) received_plaintext: ChunkVecBuffer,
     pub(crate) sendable_tls: ChunkVecBuffer,
@@ -68,6 +73,7 @@ impl CommonState {
             has_seen_eof: false,
             received_middlebox_ccs: 0,
             peer_certificates: None,
+            peer_verification_report: None,
             message_fragmenter: MessageFragmenter::default(),
             received_plaintext: ChunkVecBuffer::new(Some(DEFAULT_RECEIVED_PLAINTEXT_LIMIT)),
             sendable_tls: ChunkVecBuffer::new(Some(DEFAULT_BUFFER_LIMIT)),
@@ -115,6 +121,19 @@ impl CommonState {
         self.peer_certificates.as_deref()
     }
 
+    /// Retrieves the report of how the peer's certificate chain was verified.
+    ///
+    /// This is available once the chain has been verified, whether or not verification
+    /// succeeded, if the certificate verifier makes reports: see
+    /// [`ServerCertVerifier::verify_server_cert_with_report`] and
+    /// [`ClientCertVerifier::verify_client_cert_with_report`].
+    ///
+    /// [`ServerCertVerifier::verify_server_cert_with_report`]: crate::client::danger::ServerCertVerifier::verify_server_cert_with_report
+    /// [`ClientCertVerifier::verify_client_cert_with_report`]: crate::server::danger::ClientCertVerifier::verify_client_cert_with_report
+    pub fn peer_verification_report(&self) -> Option<&VerificationReport> {
+        self.peer_verification_report.as_ref()
+    }
+
     /// Retrieves the protocol agreed with the peer via ALPN.
     ///
     /// A return value of `None` after handshake completion
@@ -237,6 +256,110 @@ impl CommonState {
         Ok(written)
     }
 
+    /// Like [`Self::write_plaintext`], but gathers the plaintext from several buffers.
+    ///
+    /// Every record but the last carries a full `max_fragment_size` worth of plaintext.
+    /// Records that lie within a single buffer are encrypted straight from it; those
+    /// straddling buffer boundaries are assembled first.
+    pub(crate) fn write_plaintext_vectored(
+        &mut self,
+        plaintext: &[io::IoSlice<'_>],
+        outgoing_tls: &mut [u8],
+    ) -> Result<usize, EncryptError> {
+        let total_len = plaintext
+            .iter()
+            .map(|slice| slice.len())
+            .sum::<usize>();
+        if total_len == 0 {
+            return Ok(0);
+        }
+
+        let max_frag = self
+            .message_fragmenter
+            .max_fragment_len();
+        let full_records = total_len / max_frag;
+        let last_len = total_len % max_frag;
+        let records = full_records + usize::from(last_len > 0);
+
+        let remaining_encryptions = self
+            .record_layer
+            .remaining_write_seq()
+            .ok_or(EncryptError::EncryptExhausted)?;
+
+        if records as u64 > remaining_encryptions.get() {
+            return Err(EncryptError::EncryptExhausted);
+        }
+
+        let record_len = |len: usize| PACKET_OVERHEAD + self.record_layer.encrypted_len(len);
+        let mut required_size = full_records * record_len(max_frag);
+        if last_len > 0 {
+            required_size += record_len(last_len);
+        }
+        if let Some(message) = &self.queued_key_update_message {
+            required_size += message.len();
+        }
+
+        if required_size > outgoing_tls.len() {
+            return Err(EncryptError::InsufficientSize(InsufficientSizeError {
+                required_size,
+            }));
+        }
+
+        let opt_msg = self.queued_key_update_message.take();
+        let mut written = self.write_fragments(outgoing_tls, opt_msg, iter::empty());
+
+        let mut slices = plaintext
+            .iter()
+            .map(|slice| &slice[..])
+            .filter(|slice| !slice.is_empty())
+            .peekable();
+        let mut assembled = Vec::new();
+        let mut current: &[u8] = &[];
+
+        loop {
+            if current.is_empty() {
+                current = match slices.next() {
+                    Some(slice) => slice,
+                    None => break,
+                };
+            }
+
+            if assembled.is_empty() && (current.len() >= max_frag || slices.peek().is_none()) {
+                let (payload, rest) = current.split_at(cmp::min(max_frag, current.len()));
+                written += self.write_app_data_record(&mut outgoing_tls[written..], payload);
+                current = rest;
+                continue;
+            }
+
+            let take = cmp::min(max_frag - assembled.len(), current.len());
+            assembled.extend_from_slice(&current[..take]);
+            current = &current[take..];
+
+            if assembled.len() == max_frag {
+                written += self.write_app_data_record(&mut outgoing_tls[written..], &assembled);
+                assembled.clear();
+            }
+        }
+
+        if !assembled.is_empty() {
+            written += self.write_app_data_record(&mut outgoing_tls[written..], &assembled);
+        }
+
+        Ok(written)
+    }
+
+    fn write_app_data_record(&mut self, outgoing_tls: &mut [u8], payload: &[u8]) -> usize {
+        self.write_fragments(
+            outgoing_tls,
+            None,
+            iter::once(BorrowedPlainMessage {
+                typ: ContentType::ApplicationData,
+                version: ProtocolVersion::TLSv1_2,
+                payload,
+            }),
+        )
+    }
+
     pub(crate) fn send_early_plaintext(&mut self, data: &[u8]) -> usize {
         debug_assert!(self.early_traffic);
         debug_assert!(self.record_layer.is_encrypting());
@@ -427,6 +550,11 @@ impl CommonState {
         }
     }
 
+    /// Queue decrypted application data for the application to read.
+    ///
+    /// `bytes` has already been copied out of the deframer buffer, which is
+    /// reused for later records, so [`Reader`](crate::Reader) cannot lend
+    /// records from that buffer in place.
     pub(crate) fn take_received_plaintext(&mut self, bytes: Payload) {
         self.received_plaintext.append(bytes.0);
     }
@@ -728,6 +856,10 @@ pub(crate) trait State<Data>: Send + Sync {
         Err(Error::HandshakeNotComplete)
     }
 
+    fn into_kernel_state(self: Box<Self>) -> Result<Box<dyn KernelState + 'static>, Error> {
+        Err(Error::HandshakeNotComplete)
+    }
+
     fn handle_decrypt_error(&self) {}
 }
 
@@ -1,6 +1,7 @@
 use crate::common_state::{CommonState, Context, IoState, State, DEFAULT_BUFFER_LIMIT};
 use crate::enums::{AlertDescription, ContentType};
 use crate::error::{Error, PeerMisbehaved};
+use crate::kernel::KernelConnection;
 #[cfg(feature = "logging")]
 use crate::log::trace;
 use crate::msgs::deframer::{Deframed, DeframerSliceBuffer, DeframerVecBuffer, MessageDeframer};
@@ -140,12 +141,41 @@ impl DerefMut for Connection {
 }
 
 /// A structure that implements [`std::io::Read`] for reading plaintext.
+///
+/// It also implements [`std::io::BufRead`]: [`BufRead::fill_buf`] lends out
+/// the received plaintext one decrypted record at a time, so it can be
+/// processed without being copied into a caller-supplied buffer.
+///
+/// This is not zero-copy: each record is still copied once, out of the buffer
+/// it was received and decrypted in, when it is processed by
+/// [`Connection::process_new_packets`].  For reads without that copy, use
+/// the unbuffered API's [`ReadTraffic`](crate::unbuffered::ReadTraffic).
+///
+/// [`BufRead::fill_buf`]: std::io::BufRead::fill_buf
 pub struct Reader<'a> {
     received_plaintext: &'a mut ChunkVecBuffer,
     peer_cleanly_closed: bool,
     has_seen_eof: bool,
 }
 
+impl<'a> Reader<'a> {
+    /// Check the state of the connection when no plaintext is available.
+    fn check_no_bytes_state(&self) -> io::Result<()> {
+        match (self.peer_cleanly_closed, self.has_seen_eof) {
+            // cleanly closed; don't care about TCP EOF: express this as Ok(0)
+            (true, _) => Ok(()),
+            // unclean closure
+            (false, true) => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                UNEXPECTED_EOF_MESSAGE,
+            )),
+            // connection still going, but needs more data: signal `WouldBlock` so that
+            // the caller knows this
+            (false, false) => Err(io::ErrorKind::WouldBlock.into()),
+        }
+    }
+}
+
 impl<'a> io::Read for Reader<'a> {
     /// Obtain plaintext data received from the peer over this TLS connection.
     ///
@@ -172,20 +202,7 @@ impl<'a> io::Read for Reader<'a> {
 
         if len == 0 && !buf.is_empty() {
             // No bytes available:
-            match (self.peer_cleanly_closed, self.has_seen_eof) {
-                // cleanly closed; don't care about TCP EOF: express this as Ok(0)
-                (true, _) => {}
-                // unclean closure
-                (false, true) => {
-                    return Err(io::Error::new(
-                        io::ErrorKind::UnexpectedEof,
-                        UNEXPECTED_EOF_MESSAGE,
-                    ))
-                }
-                // connection still going, but needs more data: signal `WouldBlock` so that
-                // the caller knows this
-                (false, false) => return Err(io::ErrorKind::WouldBlock.into()),
-            }
+            self.check_no_bytes_state()?;
         }
 
         Ok(len)
@@ -221,26 +238,45 @@ impl<'a> io::Read for Reader<'a> {
 
         if len == 0 && cursor.capacity() > 0 {
             // No bytes available:
-            match (self.peer_cleanly_closed, self.has_seen_eof) {
-                // cleanly closed; don't care about TCP EOF: express this as Ok(0)
-                (true, _) => {}
-                // unclean closure
-                (false, true) => {
-                    return Err(io::Error::new(
-                        io::ErrorKind::UnexpectedEof,
-                        UNEXPECTED_EOF_MESSAGE,
-                    ));
-                }
-                // connection still going, but need more data: signal `WouldBlock` so that
-                // the caller knows this
-                (false, false) => return Err(io::ErrorKind::WouldBlock.into()),
-            }
+            self.check_no_bytes_state()?;
         }
 
         Ok(())
     }
 }
 
+impl<'a> io::BufRead for Reader<'a> {
+    /// Obtain a reference to the plaintext of the next decrypted record
+    /// received from the peer, without copying it into a caller-supplied buffer.
+    ///
+    /// The returned slice covers (the unconsumed remainder of) a single record;
+    /// call [`consume`] with the number of bytes processed, and call this
+    /// function again to reach the following record.
+    ///
+    /// Once all pending data has been consumed, this returns an empty slice if
+    /// the peer closed the TLS session cleanly, and otherwise fails in the same
+    /// way as [`read`]: `ErrorKind::UnexpectedEof` after an unclean closure, or
+    /// `ErrorKind::WouldBlock` if more data is needed.
+    ///
+    /// [`consume`]: io::BufRead::consume
+    /// [`read`]: io::Read::read
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.received_plaintext.is_empty() {
+            self.check_no_bytes_state()?;
+            return Ok(&[]);
+        }
+
+        Ok(self
+            .received_plaintext
+            .chunk()
+            .unwrap_or_default())
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.received_plaintext.consume(amt);
+    }
+}
+
 /// Internal trait implemented by the [`ServerConnection`]/[`ClientConnection`]
 /// allowing them to be the subject of a [`Writer`].
 ///
@@ -474,15 +510,10 @@ impl<Data> ConnectionCommon<Data> {
         let mut deframer_buffer = self.deframer_buffer.borrow();
         let res = self
             .core
-            .deframe(None, &mut deframer_buffer);
+            .first_handshake_message(&mut deframer_buffer);
         let discard = deframer_buffer.pending_discard();
         self.deframer_buffer.discard(discard);
-
-        match res?.map(Message::try_from) {
-            Some(Ok(msg)) => Ok(Some(msg)),
-            Some(Err(err)) => Err(self.send_fatal_alert(AlertDescription::DecodeError, err)),
-            None => Ok(None),
-        }
+        res
     }
 
     pub(crate) fn replace_state(&mut self, new: Box<dyn State<Data>>) {
@@ -606,6 +637,55 @@ impl<Data> ConnectionCommon<Data> {
         })
     }
 
+    /// Extract secrets, and keep what is needed to handle post-handshake messages.
+    ///
+    /// This is for handing record protection over to something else, such as the kernel
+    /// with kTLS: see the [`kernel`](crate::kernel) module for what that then has to do.
+    /// Should be used with care as it exposes secret key material.
+    ///
+    /// This fails if the handshake is not complete, if secret extraction is disabled, or if
+    /// the connection holds TLS data that is yet to be processed or sent, or plaintext that
+    /// is yet to be read or sent.
+    pub fn dangerous_into_kernel_connection(
+        self,
+    ) -> Result<(ExtractedSecrets, KernelConnection<Data>), Error> {
+        if !self.enable_secret_extraction {
+            return Err(Error::General("Secret extraction is disabled".into()));
+        }
+
+        if self.deframer_buffer.has_pending()
+            || !self.sendable_tls.is_empty()
+            || !self.received_plaintext.is_empty()
+            || !self.sendable_plaintext.is_empty()
+        {
+            return Err(Error::General(
+                "cannot hand over a connection with data pending".into(),
+            ));
+        }
+
+        let common = self.core.common_state;
+        let st = self.core.state?;
+        let (negotiated_version, suite) = match (common.negotiated_version, common.suite) {
+            (Some(version), Some(suite)) => (version, suite),
+            _ => return Err(Error::HandshakeNotComplete),
+        };
+
+        let PartiallyExtractedSecrets { tx, rx } = st.extract_secrets()?;
+        let secrets = ExtractedSecrets {
+            tx: (common.record_layer.write_seq(), tx),
+            rx: (common.record_layer.read_seq(), rx),
+        };
+        let kernel = KernelConnection::new(
+            st.into_kernel_state()?,
+            common.peer_certificates,
+            common.protocol,
+            common.quic,
+            negotiated_version,
+            suite,
+        );
+        Ok((secrets, kernel))
+    }
+
     /// Sets a limit on the internal buffers used to buffer
     /// unsent plaintext (prior to completing the TLS handshake)
     /// and unsent TLS records.  This limit acts only on application
@@ -695,6 +775,16 @@ pub struct UnbufferedConnectionCommon<Data> {
     wants_write: bool,
 }
 
+impl<'a, Data> From<&'a mut UnbufferedConnectionCommon<Data>> for Context<'a, Data> {
+    fn from(conn: &'a mut UnbufferedConnectionCommon<Data>) -> Self {
+        Self {
+            common: &mut conn.core.common_state,
+            data: &mut conn.core.data,
+            sendable_plaintext: None,
+        }
+    }
+}
+
 impl<Data> From<ConnectionCore<Data>> for UnbufferedConnectionCommon<Data> {
     fn from(core: ConnectionCore<Data>) -> Self {
         Self {
@@ -753,6 +843,25 @@ impl<Data> ConnectionCore<Data> {
         Ok(self.common_state.current_io_state())
     }
 
+    /// Extract the first handshake message from `deframer_buffer`.
+    ///
+    /// See [`ConnectionCommon::first_handshake_message()`].
+    pub(crate) fn first_handshake_message(
+        &mut self,
+        deframer_buffer: &mut DeframerSliceBuffer,
+    ) -> Result<Option<Message>, Error> {
+        match self
+            .deframe(None, deframer_buffer)?
+            .map(Message::try_from)
+        {
+            Some(Ok(msg)) => Ok(Some(msg)),
+            Some(Err(err)) => Err(self
+                .common_state
+                .send_fatal_alert(AlertDescription::DecodeError, err)),
+            None => Ok(None),
+        }
+    }
+
     /// Pull a message out of the deframer and send any messages that need to be sent as a result.
     fn deframe(
         &mut self,
@@ -811,7 +920,7 @@ impl<Data> ConnectionCore<Data> {
         }
     }
 
-    fn process_msg(
+    pub(crate) fn process_msg(
         &mut self,
         msg: PlainMessage,
         state: Box<dyn State<Data>>,
@@ -4,10 +4,12 @@ use alloc::vec::Vec;
 use core::num::NonZeroUsize;
 use core::{fmt, mem};
 use std::error::Error as StdError;
+use std::io;
 
 use super::UnbufferedConnectionCommon;
 use crate::client::ClientConnectionData;
 use crate::msgs::deframer::DeframerSliceBuffer;
+use crate::msgs::fragmenter::PACKET_OVERHEAD;
 use crate::server::ServerConnectionData;
 use crate::Error;
 
@@ -403,6 +405,40 @@ impl<Data> WriteTraffic<'_, Data> {
             .write_plaintext(application_data, outgoing_tls)
     }
 
+    /// Encrypts the concatenation of `application_data` into the `outgoing_tls` buffer
+    ///
+    /// The data is packed into as few records as the maximum fragment size allows, so
+    /// callers need not join many small buffers themselves first.
+    ///
+    /// Returns the number of bytes that were written into `outgoing_tls`, or an error if
+    /// the provided buffer is too small. In the error case, `outgoing_tls` is not modified
+    pub fn encrypt_vectored(
+        &mut self,
+        application_data: &[io::IoSlice<'_>],
+        outgoing_tls: &mut [u8],
+    ) -> Result<usize, EncryptError> {
+        self.conn
+            .core
+            .common_state
+            .write_plaintext_vectored(application_data, outgoing_tls)
+    }
+
+    /// Like [`WriteTraffic::encrypt_vectored`], but returns the written TLS records
+    ///
+    /// The returned [`EncryptedRecords`] yields each record in `outgoing_tls` in turn,
+    /// which can for example be handed to `writev` as separate buffers. In the error
+    /// case, `outgoing_tls` is not modified
+    pub fn encrypt_vectored_records<'o>(
+        &mut self,
+        application_data: &[io::IoSlice<'_>],
+        outgoing_tls: &'o mut [u8],
+    ) -> Result<EncryptedRecords<'o>, EncryptError> {
+        let written = self.encrypt_vectored(application_data, outgoing_tls)?;
+        Ok(EncryptedRecords {
+            remaining: &outgoing_tls[..written],
+        })
+    }
+
     /// Encrypts a close_notify warning alert in `outgoing_tls`
     ///
     /// Returns the number of bytes that were written into `outgoing_tls`, or an error if
@@ -415,6 +451,46 @@ impl<Data> WriteTraffic<'_, Data> {
     }
 }
 
+/// TLS records written by [`WriteTraffic::encrypt_vectored_records`]
+///
+/// Iterating yields each encoded record, header included.
+pub struct EncryptedRecords<'o> {
+    remaining: &'o [u8],
+}
+
+impl<'o> EncryptedRecords<'o> {
+    /// The bytes of the records that have not been yielded yet
+    pub fn as_slice(&self) -> &'o [u8] {
+        self.remaining
+    }
+}
+
+impl<'o> Iterator for EncryptedRecords<'o> {
+    type Item = &'o [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining.is_empty() {
+            return None;
+        }
+
+        // the header ends with the big-endian payload length
+        let payload_len = u16::from_be_bytes([self.remaining[3], self.remaining[4]]);
+        let (record, rest) = self
+            .remaining
+            .split_at(PACKET_OVERHEAD + usize::from(payload_len));
+        self.remaining = rest;
+        Some(record)
+    }
+}
+
+impl fmt::Debug for EncryptedRecords<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EncryptedRecords")
+            .field("remaining_len", &self.remaining.len())
+            .finish()
+    }
+}
+
 /// A handshake record must be encoded
 pub struct EncodeTlsData<'c, Data> {
     conn: &'c mut UnbufferedConnectionCommon<Data>,
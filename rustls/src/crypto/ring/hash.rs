@@ -8,6 +8,9 @@ use alloc::boxed::Box;
 
 pub(crate) static SHA256: Hash = Hash(&digest::SHA256, HashAlgorithm::SHA256);
 pub(crate) static SHA384: Hash = Hash(&digest::SHA384, HashAlgorithm::SHA384);
+// Not the hash of any cipher suite, but used to match DANE records.
+#[allow(dead_code)]
+pub(crate) static SHA512: Hash = Hash(&digest::SHA512, HashAlgorithm::SHA512);
 
 pub(crate) struct Hash(&'static digest::Algorithm, HashAlgorithm);
 
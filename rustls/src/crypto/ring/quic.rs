@@ -1,10 +1,11 @@
 #![allow(clippy::duplicate_mod)]
 
-use crate::crypto::cipher::{AeadKey, Iv, Nonce};
+use crate::crypto::cipher::{AeadKey, Iv, Nonce, NONCE_LEN};
 use crate::error::Error;
 use crate::quic;
 
 use alloc::boxed::Box;
+use alloc::vec::Vec;
 
 use super::ring_like::aead;
 
@@ -22,51 +23,81 @@ impl HeaderProtectionKey {
         packet_number: &mut [u8],
         masked: bool,
     ) -> Result<(), Error> {
-        // This implements "Header Protection Application" almost verbatim.
-        // <https://datatracker.ietf.org/doc/html/rfc9001#section-5.4.1>
+        let mask = self.mask(sample, packet_number)?;
+        apply_mask(&mask, first, packet_number, masked);
+        Ok(())
+    }
+
+    fn xor_batch_in_place(
+        &self,
+        headers: &mut [quic::BatchHeader<'_>],
+        masked: bool,
+    ) -> Result<(), Error> {
+        // Compute all the masks before applying any, so that the packets from
+        // the first bad one onwards are left unmodified.
+        let mut masks = Vec::with_capacity(headers.len());
+        let mut result = Ok(());
+        for header in headers.iter() {
+            match self.mask(header.sample, header.packet_number) {
+                Ok(mask) => masks.push(mask),
+                Err(err) => {
+                    result = Err(err);
+                    break;
+                }
+            }
+        }
+
+        for (header, mask) in headers.iter_mut().zip(&masks) {
+            apply_mask(mask, header.first, header.packet_number, masked);
+        }
+        result
+    }
 
+    /// Compute the mask for `sample`, checking it can cover `packet_number`.
+    fn mask(&self, sample: &[u8], packet_number: &[u8]) -> Result<[u8; 5], Error> {
         let mask = self
             .0
             .new_mask(sample)
             .map_err(|_| Error::General("sample of invalid length".into()))?;
 
-        // The `unwrap()` will not panic because `new_mask` returns a
-        // non-empty result.
-        let (first_mask, pn_mask) = mask.split_first().unwrap();
-
         // It is OK for the `mask` to be longer than `packet_number`,
         // but a valid `packet_number` will never be longer than `mask`.
-        if packet_number.len() > pn_mask.len() {
+        if packet_number.len() > mask.len() - 1 {
             return Err(Error::General("packet number too long".into()));
         }
 
-        // Infallible from this point on. Before this point, `first` and
-        // `packet_number` are unchanged.
+        Ok(mask)
+    }
+}
 
-        const LONG_HEADER_FORM: u8 = 0x80;
-        let bits = match *first & LONG_HEADER_FORM == LONG_HEADER_FORM {
-            true => 0x0f,  // Long header: 4 bits masked
-            false => 0x1f, // Short header: 5 bits masked
-        };
+/// Apply a header protection `mask` to `first` and `packet_number`.
+fn apply_mask(mask: &[u8; 5], first: &mut u8, packet_number: &mut [u8], masked: bool) {
+    // This implements "Header Protection Application" almost verbatim.
+    // <https://datatracker.ietf.org/doc/html/rfc9001#section-5.4.1>
 
-        let first_plain = match masked {
-            // When unmasking, use the packet length bits after unmasking
-            true => *first ^ (first_mask & bits),
-            // When masking, use the packet length bits before masking
-            false => *first,
-        };
-        let pn_len = (first_plain & 0x03) as usize + 1;
+    let (first_mask, pn_mask) = mask.split_first().unwrap();
 
-        *first ^= first_mask & bits;
-        for (dst, m) in packet_number
-            .iter_mut()
-            .zip(pn_mask)
-            .take(pn_len)
-        {
-            *dst ^= m;
-        }
+    const LONG_HEADER_FORM: u8 = 0x80;
+    let bits = match *first & LONG_HEADER_FORM == LONG_HEADER_FORM {
+        true => 0x0f,  // Long header: 4 bits masked
+        false => 0x1f, // Short header: 5 bits masked
+    };
 
-        Ok(())
+    let first_plain = match masked {
+        // When unmasking, use the packet length bits after unmasking
+        true => *first ^ (first_mask & bits),
+        // When masking, use the packet length bits before masking
+        false => *first,
+    };
+    let pn_len = (first_plain & 0x03) as usize + 1;
+
+    *first ^= first_mask & bits;
+    for (dst, m) in packet_number
+        .iter_mut()
+        .zip(pn_mask)
+        .take(pn_len)
+    {
+        *dst ^= m;
     }
 }
 
@@ -89,6 +120,14 @@ impl quic::HeaderProtectionKey for HeaderProtectionKey {
         self.xor_in_place(sample, first, packet_number, true)
     }
 
+    fn encrypt_batch_in_place(&self, headers: &mut [quic::BatchHeader<'_>]) -> Result<(), Error> {
+        self.xor_batch_in_place(headers, false)
+    }
+
+    fn decrypt_batch_in_place(&self, headers: &mut [quic::BatchHeader<'_>]) -> Result<(), Error> {
+        self.xor_batch_in_place(headers, true)
+    }
+
     #[inline]
     fn sample_len(&self) -> usize {
         self.0.algorithm().sample_len()
@@ -153,6 +192,54 @@ impl quic::PacketKey for PacketKey {
         Ok(&payload[..plain_len])
     }
 
+    fn encrypt_batch_in_place(&self, packets: &mut [quic::BatchPacket<'_>]) -> Result<(), Error> {
+        let tag_len = self.key.algorithm().tag_len();
+        let mut nonces = NonceSequence::new(&self.iv);
+        for packet in packets {
+            let payload_len = match packet
+                .payload
+                .len()
+                .checked_sub(tag_len)
+            {
+                Some(len) => len,
+                None => return Err(Error::EncryptError),
+            };
+            let (payload, tag) = packet.payload.split_at_mut(payload_len);
+            tag.copy_from_slice(
+                self.key
+                    .seal_in_place_separate_tag(
+                        nonces.next(packet.packet_number),
+                        aead::Aad::from(packet.header),
+                        payload,
+                    )
+                    .map_err(|_| Error::EncryptError)?
+                    .as_ref(),
+            );
+        }
+        Ok(())
+    }
+
+    fn decrypt_batch_in_place(
+        &self,
+        packets: &mut [quic::BatchPacket<'_>],
+        results: &mut [Result<usize, Error>],
+    ) {
+        assert_eq!(packets.len(), results.len());
+        let tag_len = self.key.algorithm().tag_len();
+        let mut nonces = NonceSequence::new(&self.iv);
+        for (packet, result) in packets.iter_mut().zip(results) {
+            let payload_len = packet.payload.len();
+            *result = match self.key.open_in_place(
+                nonces.next(packet.packet_number),
+                aead::Aad::from(packet.header),
+                packet.payload,
+            ) {
+                Ok(_) => Ok(payload_len - tag_len),
+                Err(_) => Err(Error::DecryptError),
+            };
+        }
+    }
+
     /// Tag length for the underlying AEAD algorithm
     #[inline]
     fn tag_len(&self) -> usize {
@@ -160,6 +247,36 @@ impl quic::PacketKey for PacketKey {
     }
 }
 
+/// The nonces for a batch of packets.
+///
+/// Each nonce is derived from the previous one by applying the change in packet
+/// number, rather than from the IV.
+struct NonceSequence {
+    nonce: [u8; NONCE_LEN],
+    packet_number: u64,
+}
+
+impl NonceSequence {
+    fn new(iv: &Iv) -> Self {
+        Self {
+            nonce: Nonce::new(iv, 0).0,
+            packet_number: 0,
+        }
+    }
+
+    fn next(&mut self, packet_number: u64) -> aead::Nonce {
+        let change = (self.packet_number ^ packet_number).to_be_bytes();
+        for (nonce, change) in self.nonce[NONCE_LEN - 8..]
+            .iter_mut()
+            .zip(change)
+        {
+            *nonce ^= change;
+        }
+        self.packet_number = packet_number;
+        aead::Nonce::assume_unique_for_key(self.nonce)
+    }
+}
+
 pub(crate) struct KeyBuilder(
     pub(crate) &'static aead::Algorithm,
     pub(crate) &'static aead::quic::Algorithm,
@@ -181,8 +298,11 @@ impl crate::quic::Algorithm for KeyBuilder {
 
 #[cfg(test)]
 mod tests {
+    use alloc::vec::Vec;
+
     use crate::common_state::Side;
     use crate::crypto::tls13::OkmBlock;
+    use crate::error::Error;
     use crate::quic::*;
     use crate::test_provider::tls13::{
         TLS13_AES_128_GCM_SHA256_INTERNAL, TLS13_CHACHA20_POLY1305_SHA256_INTERNAL,
@@ -252,6 +372,141 @@ mod tests {
         );
     }
 
+    #[test]
+    fn batches_match_per_packet_protection() {
+        const PNS: [u64; 4] = [0, 7, 3, 0x1_0000_0001];
+        const HEADER: &[u8] = &[0x41, 0x00, 0x00, 0x00, 0x00];
+        const PN_LEN: usize = 4;
+
+        let secret = OkmBlock::new(&[0x5a; 32]);
+        let suite = TLS13_AES_128_GCM_SHA256_INTERNAL;
+        let builder = KeyBuilder::new(
+            &secret,
+            Version::V1,
+            suite.quic.unwrap(),
+            suite.hkdf_provider,
+        );
+        let packet = builder.packet_key();
+        let hpk = builder.header_protection_key();
+        let tag_len = packet.tag_len();
+
+        let plain = |pn: u64| {
+            let mut buf = HEADER.to_vec();
+            buf.extend(pn.to_be_bytes().iter().cycle().take(32));
+            buf.extend(core::iter::repeat(0).take(tag_len));
+            buf
+        };
+
+        // Protect each packet on its own.
+        let mut expected = PNS
+            .iter()
+            .map(|&pn| plain(pn))
+            .collect::<Vec<_>>();
+        for (buf, &pn) in expected.iter_mut().zip(&PNS) {
+            let len = buf.len() - tag_len;
+            let (header, payload) = buf[..len].split_at_mut(HEADER.len());
+            let tag = packet
+                .encrypt_in_place(pn, header, payload)
+                .unwrap();
+            buf[len..].copy_from_slice(tag.as_ref());
+
+            let (header, sample) = buf.split_at_mut(1 + PN_LEN);
+            let (first, rest) = header.split_at_mut(1);
+            hpk.encrypt_in_place(&sample[..hpk.sample_len()], &mut first[0], rest)
+                .unwrap();
+        }
+
+        // Protect them all as a batch.
+        let mut actual = PNS
+            .iter()
+            .map(|&pn| plain(pn))
+            .collect::<Vec<_>>();
+        let mut packets = actual
+            .iter_mut()
+            .zip(&PNS)
+            .map(|(buf, &pn)| {
+                let (header, payload) = buf.split_at_mut(HEADER.len());
+                BatchPacket {
+                    packet_number: pn,
+                    header,
+                    payload,
+                }
+            })
+            .collect::<Vec<_>>();
+        packet
+            .encrypt_batch_in_place(&mut packets)
+            .unwrap();
+        drop(packets);
+        let mut headers = header_batch(&mut actual, hpk.sample_len());
+        hpk.encrypt_batch_in_place(&mut headers)
+            .unwrap();
+        drop(headers);
+
+        assert_eq!(actual, expected);
+
+        // Unprotect them as a batch, with one packet corrupted.
+        let last = actual.len() - 1;
+        *actual[1].last_mut().unwrap() ^= 1;
+        let mut headers = header_batch(&mut actual, hpk.sample_len());
+        hpk.decrypt_batch_in_place(&mut headers)
+            .unwrap();
+        drop(headers);
+
+        let mut results = PNS
+            .iter()
+            .map(|_| Ok(0))
+            .collect::<Vec<_>>();
+        let mut packets = actual
+            .iter_mut()
+            .zip(&PNS)
+            .map(|(buf, &pn)| {
+                let (header, payload) = buf.split_at_mut(HEADER.len());
+                BatchPacket {
+                    packet_number: pn,
+                    header,
+                    payload,
+                }
+            })
+            .collect::<Vec<_>>();
+        packet.decrypt_batch_in_place(&mut packets, &mut results);
+        drop(packets);
+
+        assert_eq!(results[1], Err(Error::DecryptError));
+        for i in [0, 2, last] {
+            let len = results[i].clone().unwrap();
+            assert_eq!(
+                actual[i][..HEADER.len() + len],
+                plain(PNS[i])[..HEADER.len() + len]
+            );
+        }
+
+        // A bad sample stops header protection there, leaving it and later headers alone.
+        let mut bufs = expected.clone();
+        let mut headers = header_batch(&mut bufs, hpk.sample_len());
+        headers[2].sample = &[];
+        assert!(hpk
+            .decrypt_batch_in_place(&mut headers)
+            .is_err());
+        drop(headers);
+        assert_eq!(bufs[0][..HEADER.len()], plain(PNS[0])[..HEADER.len()]);
+        assert_eq!(bufs[1][..HEADER.len()], plain(PNS[1])[..HEADER.len()]);
+        assert_eq!(bufs[2..], expected[2..]);
+    }
+
+    fn header_batch(bufs: &mut [Vec<u8>], sample_len: usize) -> Vec<BatchHeader<'_>> {
+        bufs.iter_mut()
+            .map(|buf| {
+                let (header, sample) = buf.split_at_mut(5);
+                let (first, packet_number) = header.split_at_mut(1);
+                BatchHeader {
+                    sample: &sample[..sample_len],
+                    first: &mut first[0],
+                    packet_number,
+                }
+            })
+            .collect()
+    }
+
     #[test]
     fn key_update_test_vector() {
         fn equal_okm(x: &OkmBlock, y: &OkmBlock) -> bool {
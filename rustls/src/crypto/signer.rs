@@ -78,7 +78,7 @@ pub trait Signer: Debug + Send + Sync {
 }
 
 /// A packaged-together certificate chain, matching `SigningKey` and
-/// optional stapled OCSP response.
+/// optional stapled OCSP response and signed certificate timestamps.
 #[derive(Clone, Debug)]
 pub struct CertifiedKey {
     /// The certificate chain.
@@ -90,6 +90,15 @@ pub struct CertifiedKey {
     /// An optional OCSP response from the certificate issuer,
     /// attesting to its continued validity.
     pub ocsp: Option<Vec<u8>>,
+
+    /// An optional list of signed certificate timestamps (SCTs) for the
+    /// end-entity certificate, sent to clients which request them.
+    ///
+    /// This is the TLS encoding of a `SignedCertificateTimestampList`
+    /// (see [RFC 6962 section 3.3]), including its length prefix.
+    ///
+    /// [RFC 6962 section 3.3]: https://datatracker.ietf.org/doc/html/rfc6962#section-3.3
+    pub sct_list: Option<Vec<u8>>,
 }
 
 impl CertifiedKey {
@@ -102,6 +111,7 @@ impl CertifiedKey {
             cert,
             key,
             ocsp: None,
+            sct_list: None,
         }
     }
 
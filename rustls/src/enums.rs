@@ -564,6 +564,42 @@ enum_builder! {
     }
 }
 
+enum_builder! {
+    /// The certificate usage field of a TLSA record, from
+    /// [RFC 6698 section 2.1.1](https://datatracker.ietf.org/doc/html/rfc6698#section-2.1.1).
+    /// The `Unknown` item is used when processing unrecognised ordinals.
+    @U8
+    pub enum TlsaUsage {
+        PkixTa => 0,
+        PkixEe => 1,
+        DaneTa => 2,
+        DaneEe => 3
+    }
+}
+
+enum_builder! {
+    /// The selector field of a TLSA record, from
+    /// [RFC 6698 section 2.1.2](https://datatracker.ietf.org/doc/html/rfc6698#section-2.1.2).
+    /// The `Unknown` item is used when processing unrecognised ordinals.
+    @U8
+    pub enum TlsaSelector {
+        FullCertificate => 0,
+        SubjectPublicKeyInfo => 1
+    }
+}
+
+enum_builder! {
+    /// The matching type field of a TLSA record, from
+    /// [RFC 6698 section 2.1.3](https://datatracker.ietf.org/doc/html/rfc6698#section-2.1.3).
+    /// The `Unknown` item is used when processing unrecognised ordinals.
+    @U8
+    pub enum TlsaMatchingType {
+        Full => 0,
+        Sha256 => 1,
+        Sha512 => 2
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -578,5 +614,11 @@ mod tests {
             AlertDescription::CloseNotify,
             AlertDescription::NoApplicationProtocol,
         );
+        test_enum8::<TlsaUsage>(TlsaUsage::PkixTa, TlsaUsage::DaneEe);
+        test_enum8::<TlsaSelector>(
+            TlsaSelector::FullCertificate,
+            TlsaSelector::SubjectPublicKeyInfo,
+        );
+        test_enum8::<TlsaMatchingType>(TlsaMatchingType::Full, TlsaMatchingType::Sha512);
     }
 }
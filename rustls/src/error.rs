@@ -330,6 +330,17 @@ pub enum CertificateError {
     /// reasons.
     ApplicationVerificationFailure,
 
+    /// The certificate is not accompanied by enough valid signed certificate
+    /// timestamps (SCTs) to meet the certificate transparency policy.
+    InsufficientCertificateTransparency,
+
+    /// The certificate's public key is not the one previously seen for
+    /// this server, by a trust-on-first-use verifier.
+    PublicKeyChanged,
+
+    /// A stapled OCSP response is malformed, or does not cover the certificate.
+    InvalidOcspResponse,
+
     /// Any other error.
     ///
     /// This can be used by custom verifiers to expose the underlying error
@@ -352,12 +363,16 @@ impl PartialEq<Self> for CertificateError {
             (Expired, Expired) => true,
             (NotValidYet, NotValidYet) => true,
             (Revoked, Revoked) => true,
+            (UnknownRevocationStatus, UnknownRevocationStatus) => true,
             (UnhandledCriticalExtension, UnhandledCriticalExtension) => true,
             (UnknownIssuer, UnknownIssuer) => true,
             (BadSignature, BadSignature) => true,
             (NotValidForName, NotValidForName) => true,
             (InvalidPurpose, InvalidPurpose) => true,
             (ApplicationVerificationFailure, ApplicationVerificationFailure) => true,
+            (InsufficientCertificateTransparency, InsufficientCertificateTransparency) => true,
+            (PublicKeyChanged, PublicKeyChanged) => true,
+            (InvalidOcspResponse, InvalidOcspResponse) => true,
             _ => false,
         }
     }
@@ -370,7 +385,9 @@ impl From<CertificateError> for AlertDescription {
     fn from(e: CertificateError) -> Self {
         use CertificateError::*;
         match e {
-            BadEncoding | UnhandledCriticalExtension | NotValidForName => Self::BadCertificate,
+            BadEncoding | UnhandledCriticalExtension | NotValidForName | PublicKeyChanged => {
+                Self::BadCertificate
+            }
             // RFC 5246/RFC 8446
             // certificate_expired
             //  A certificate has expired or **is not currently valid**.
@@ -380,13 +397,14 @@ impl From<CertificateError> for AlertDescription {
             // the case where revocation status can not be determined, so we do the same here.
             UnknownIssuer | UnknownRevocationStatus => Self::UnknownCA,
             BadSignature => Self::DecryptError,
+            InvalidOcspResponse => Self::BadCertificateStatusResponse,
             InvalidPurpose => Self::UnsupportedCertificate,
             ApplicationVerificationFailure => Self::AccessDenied,
             // RFC 5246/RFC 8446
             // certificate_unknown
             //  Some other (unspecified) issue arose in processing the
             //  certificate, rendering it unacceptable.
-            Other(_) => Self::CertificateUnknown,
+            InsufficientCertificateTransparency | Other(_) => Self::CertificateUnknown,
         }
     }
 }
@@ -405,6 +423,9 @@ pub enum CertRevocationListError {
     /// The CRL had a bad, or unsupported signature from its issuer.
     BadSignature,
 
+    /// The CRL's `nextUpdate` time has passed.
+    Expired,
+
     /// The CRL contained an invalid CRL number.
     InvalidCrlNumber,
 
@@ -414,6 +435,10 @@ pub enum CertRevocationListError {
     /// The CRL issuer does not specify the cRLSign key usage.
     IssuerInvalidForCrl,
 
+    /// The CRL has no `nextUpdate` time, so it is not known when it stops
+    /// being current.
+    MissingNextUpdate,
+
     /// The CRL is invalid for some other reason.
     ///
     /// Enums holding this variant will never compare equal to each other.
@@ -448,9 +473,11 @@ impl PartialEq<Self> for CertRevocationListError {
         #[allow(clippy::match_like_matches_macro)]
         match (self, other) {
             (BadSignature, BadSignature) => true,
+            (Expired, Expired) => true,
             (InvalidCrlNumber, InvalidCrlNumber) => true,
             (InvalidRevokedCertSerialNumber, InvalidRevokedCertSerialNumber) => true,
             (IssuerInvalidForCrl, IssuerInvalidForCrl) => true,
+            (MissingNextUpdate, MissingNextUpdate) => true,
             (ParseError, ParseError) => true,
             (UnsupportedCrlVersion, UnsupportedCrlVersion) => true,
             (UnsupportedCriticalExtension, UnsupportedCriticalExtension) => true,
@@ -590,6 +617,7 @@ mod tests {
         assert_eq!(Revoked, Revoked);
         assert_eq!(UnhandledCriticalExtension, UnhandledCriticalExtension);
         assert_eq!(UnknownIssuer, UnknownIssuer);
+        assert_eq!(UnknownRevocationStatus, UnknownRevocationStatus);
         assert_eq!(BadSignature, BadSignature);
         assert_eq!(NotValidForName, NotValidForName);
         assert_eq!(InvalidPurpose, InvalidPurpose);
@@ -597,6 +625,12 @@ mod tests {
             ApplicationVerificationFailure,
             ApplicationVerificationFailure
         );
+        assert_eq!(
+            InsufficientCertificateTransparency,
+            InsufficientCertificateTransparency
+        );
+        assert_eq!(PublicKeyChanged, PublicKeyChanged);
+        assert_eq!(InvalidOcspResponse, InvalidOcspResponse);
         let other = Other(OtherError(alloc::sync::Arc::from(Box::from(""))));
         assert_ne!(other, other);
         assert_ne!(BadEncoding, Expired);
@@ -606,12 +640,14 @@ mod tests {
     fn crl_error_equality() {
         use super::CertRevocationListError::*;
         assert_eq!(BadSignature, BadSignature);
+        assert_eq!(Expired, Expired);
         assert_eq!(InvalidCrlNumber, InvalidCrlNumber);
         assert_eq!(
             InvalidRevokedCertSerialNumber,
             InvalidRevokedCertSerialNumber
         );
         assert_eq!(IssuerInvalidForCrl, IssuerInvalidForCrl);
+        assert_eq!(MissingNextUpdate, MissingNextUpdate);
         assert_eq!(ParseError, ParseError);
         assert_eq!(UnsupportedCriticalExtension, UnsupportedCriticalExtension);
         assert_eq!(UnsupportedCrlVersion, UnsupportedCrlVersion);
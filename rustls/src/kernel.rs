@@ -0,0 +1,158 @@
+//! Support for connections whose record layer is handled elsewhere.
+//!
+//! [`ConnectionCommon::dangerous_into_kernel_connection()`] hands over a completed
+//! connection: it returns the connection's traffic secrets, so that record protection can
+//! be done by something else -- such as the operating system kernel, with kTLS -- and a
+//! [`KernelConnection`] holding the rest of the connection's state.
+//!
+//! rustls then no longer sees any records, so whatever now reads them must pass some
+//! TLS 1.3 post-handshake messages back to the [`KernelConnection`]:
+//!
+//! - On receiving a `KeyUpdate` message, call [`KernelConnection::update_rx_secret()`] and
+//!   use the new secrets to decrypt records that follow it.  If the peer requested an update
+//!   in return, send a `KeyUpdate` message with `update_not_requested`, and then call
+//!   [`KernelConnection::update_tx_secret()`] and use those secrets for records that follow.
+//! - On the client side, pass `NewSessionTicket` messages to
+//!   [`KernelConnection::handle_new_session_ticket()`], so that they can be used for
+//!   resumption.
+//!
+//! [`ConnectionCommon::dangerous_into_kernel_connection()`]: crate::ConnectionCommon::dangerous_into_kernel_connection
+
+use alloc::boxed::Box;
+use core::marker::PhantomData;
+
+use crate::client::ClientConnectionData;
+use crate::common_state::Protocol;
+use crate::enums::ProtocolVersion;
+use crate::error::Error;
+use crate::msgs::codec::{Codec, Reader};
+use crate::msgs::handshake::{CertificateChain, NewSessionTicketPayloadTls13};
+use crate::quic::Quic;
+use crate::suites::{ConnectionTrafficSecrets, SupportedCipherSuite};
+
+/// The state of a connection whose record layer has been handed over.
+///
+/// See the [module documentation](self).
+pub struct KernelConnection<Data> {
+    state: Box<dyn KernelState>,
+    peer_certificates: Option<CertificateChain>,
+    protocol: Protocol,
+    quic: Quic,
+    negotiated_version: ProtocolVersion,
+    suite: SupportedCipherSuite,
+    _data: PhantomData<Data>,
+}
+
+impl<Data> KernelConnection<Data> {
+    pub(crate) fn new(
+        state: Box<dyn KernelState>,
+        peer_certificates: Option<CertificateChain>,
+        protocol: Protocol,
+        quic: Quic,
+        negotiated_version: ProtocolVersion,
+        suite: SupportedCipherSuite,
+    ) -> Self {
+        Self {
+            state,
+            peer_certificates,
+            protocol,
+            quic,
+            negotiated_version,
+            suite,
+            _data: PhantomData,
+        }
+    }
+
+    /// Retrieves the protocol version agreed with the peer.
+    pub fn protocol_version(&self) -> ProtocolVersion {
+        self.negotiated_version
+    }
+
+    /// Retrieves the cipher suite agreed with the peer.
+    pub fn negotiated_cipher_suite(&self) -> SupportedCipherSuite {
+        self.suite
+    }
+
+    /// Move on to the next traffic secret for sending, after sending a `KeyUpdate` message.
+    ///
+    /// Returns the sequence number and secrets to use for records sent after the
+    /// `KeyUpdate` message.  This fails for TLS 1.2 connections, which have no key updates.
+    pub fn update_tx_secret(&mut self) -> Result<(u64, ConnectionTrafficSecrets), Error> {
+        let secrets = self
+            .state
+            .update_secrets(Direction::Transmit)?;
+        Ok((0, secrets))
+    }
+
+    /// Move on to the next traffic secret for receiving, after receiving a `KeyUpdate` message.
+    ///
+    /// Returns the sequence number and secrets to use for records received after the
+    /// `KeyUpdate` message.  This fails for TLS 1.2 connections, which have no key updates.
+    pub fn update_rx_secret(&mut self) -> Result<(u64, ConnectionTrafficSecrets), Error> {
+        let secrets = self
+            .state
+            .update_secrets(Direction::Receive)?;
+        Ok((0, secrets))
+    }
+}
+
+impl KernelConnection<ClientConnectionData> {
+    /// Handle a TLS 1.3 `NewSessionTicket` message received from the server.
+    ///
+    /// `payload` is the body of the handshake message, without its type and length.  The
+    /// ticket is stored in the client's [`ClientSessionStore`](crate::client::ClientSessionStore).
+    pub fn handle_new_session_ticket(&mut self, payload: &[u8]) -> Result<(), Error> {
+        let mut reader = Reader::init(payload);
+        let nst = NewSessionTicketPayloadTls13::read(&mut reader)?;
+        reader.expect_empty("NewSessionTicketPayloadTls13")?;
+
+        let mut cx = KernelContext {
+            peer_certificates: self.peer_certificates.as_ref(),
+            protocol: self.protocol,
+            quic: &self.quic,
+        };
+        self.state
+            .handle_new_session_ticket(&mut cx, &nst)
+    }
+}
+
+impl<Data> core::fmt::Debug for KernelConnection<Data> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("KernelConnection")
+            .field("negotiated_version", &self.negotiated_version)
+            .field("suite", &self.suite)
+            .finish()
+    }
+}
+
+/// The part of a handshake state machine that outlives the record layer.
+pub(crate) trait KernelState: Send + Sync {
+    /// Move on to the next traffic secret for `dir`, returning its keys.
+    fn update_secrets(&mut self, dir: Direction) -> Result<ConnectionTrafficSecrets, Error>;
+
+    /// Handle a TLS 1.3 `NewSessionTicket` message.
+    fn handle_new_session_ticket(
+        &mut self,
+        cx: &mut KernelContext<'_>,
+        message: &NewSessionTicketPayloadTls13,
+    ) -> Result<(), Error>;
+}
+
+/// The connection state a [`KernelState`] may need.
+pub(crate) struct KernelContext<'a> {
+    pub(crate) peer_certificates: Option<&'a CertificateChain>,
+    pub(crate) protocol: Protocol,
+    pub(crate) quic: &'a Quic,
+}
+
+impl KernelContext<'_> {
+    pub(crate) fn is_quic(&self) -> bool {
+        self.protocol == Protocol::Quic
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Direction {
+    Transmit,
+    Receive,
+}
@@ -440,6 +440,9 @@ pub mod internal {
 /// [`unbuffered::UnbufferedConnectionCommon::process_tls_records`]. The state machine API is
 /// documented in [`unbuffered::ConnectionState`].
 ///
+/// Servers that need to inspect the `ClientHello` before choosing a configuration can use
+/// [`crate::server::UnbufferedAcceptor`].
+///
 /// # Examples
 ///
 /// [`unbuffered-client`] and [`unbuffered-server`] are examples that fully exercise the API in
@@ -449,7 +452,7 @@ pub mod internal {
 /// [`unbuffered-server`]: https://github.com/rustls/rustls/blob/main/examples/src/bin/unbuffererd-server.rs
 pub mod unbuffered {
     pub use crate::conn::unbuffered::{
-        AppDataRecord, ConnectionState, EncodeError, EncodeTlsData, EncryptError,
+        AppDataRecord, ConnectionState, EncodeError, EncodeTlsData, EncryptError, EncryptedRecords,
         InsufficientSizeError, ReadEarlyData, ReadTraffic, TransmitTlsData, UnbufferedStatus,
         WriteTraffic,
     };
@@ -486,7 +489,10 @@ pub use crate::tls12::Tls12CipherSuite;
 pub use crate::tls13::Tls13CipherSuite;
 pub use crate::verify::DigitallySignedStruct;
 pub use crate::versions::{SupportedProtocolVersion, ALL_VERSIONS, DEFAULT_VERSIONS};
-pub use crate::webpki::RootCertStore;
+pub use crate::webpki::{
+    FailedPath, RevocationCheck, RevocationMethod, RevocationStatus, RootCertStore,
+    VerificationReport,
+};
 
 /// Items for use in a client.
 pub mod client {
@@ -501,11 +507,11 @@ pub mod client {
 
     pub use builder::WantsClientCert;
     pub use client_conn::{
-        ClientConfig, ClientConnection, ClientConnectionData, ClientSessionStore, EarlyDataError,
-        ResolvesClientCert, Resumption, Tls12Resumption, UnbufferedClientConnection,
-        WriteEarlyData,
+        ClientConfig, ClientConnection, ClientConnectionData, ClientConnectionOptions,
+        ClientSessionStore, EarlyDataError, ResolvesClientCert, Resumption, ServerNameIndication,
+        StoresKnownHosts, Tls12Resumption, UnbufferedClientConnection, WriteEarlyData,
     };
-    pub use handy::ClientSessionMemoryCache;
+    pub use handy::{ClientSessionMemoryCache, KnownHostsFile, KnownHostsMemoryCache};
 
     /// Dangerous configuration that should be audited and used with extreme care.
     pub mod danger {
@@ -515,7 +521,9 @@ pub mod client {
     }
 
     pub use crate::webpki::{
-        verify_server_cert_signed_by_trust_anchor, verify_server_name, ServerCertVerifierBuilder,
+        verify_server_cert_signed_by_trust_anchor, verify_server_name, CrlInfo, CrlSource,
+        CrlStore, CrlUpdate, CtLog, CtPolicy, CtVerifier, DaneVerifier, ServerCertVerifierBuilder,
+        SpkiPinningVerifier, TlsaMatchingType, TlsaRecord, TlsaSelector, TlsaUsage, TofuVerifier,
         VerifierBuilderError, WebPkiServerVerifier,
     };
 
@@ -530,25 +538,31 @@ pub mod server {
     pub(crate) mod builder;
     mod common;
     pub(crate) mod handy;
-    mod hs;
-    mod server_conn;
+    pub(crate) mod hs;
+    pub(crate) mod server_conn;
     #[cfg(feature = "tls12")]
     mod tls12;
     mod tls13;
 
     pub use crate::verify::NoClientAuth;
     pub use crate::webpki::{
-        ClientCertVerifierBuilder, ParsedCertificate, VerifierBuilderError, WebPkiClientVerifier,
+        ClientCertVerifierBuilder, ClientIdentity, ClientIdentityPolicy, CrlInfo, CrlSource,
+        CrlStore, CrlUpdate, ParsedCertificate, TrustDomainClientVerifier, TrustDomainSelector,
+        TrustDomainVerifierBuilder, VerifierBuilderError, WebPkiClientVerifier,
     };
     pub use builder::WantsServerCert;
     pub use handy::ResolvesServerCertUsingSni;
-    pub use handy::{NoServerSessionStorage, ServerSessionMemoryCache};
+    pub use handy::{ClientHelloMemoryRegister, NoServerSessionStorage, ServerSessionMemoryCache};
     pub use server_conn::StoresServerSessions;
     pub use server_conn::{
-        Accepted, Acceptor, ReadEarlyData, ServerConfig, ServerConnection, ServerConnectionData,
+        Accepted, AcceptedAlert, Acceptor, ReadEarlyData, ServerConfig, ServerConnection,
+        ServerConnectionData, UnbufferedAcceptStatus, UnbufferedAccepted, UnbufferedAcceptor,
         UnbufferedServerConnection,
     };
-    pub use server_conn::{ClientHello, ProducesTickets, ResolvesServerCert};
+    pub use server_conn::{
+        AlpnSelection, ClientHello, HandlesResumption, ProducesTickets, RecordsClientHellos,
+        ResolvesServerCert, ResumptionDecision, SelectsAlpnProtocol, TicketContext,
+    };
 
     /// Dangerous configuration that should be audited and used with extreme care.
     pub mod danger {
@@ -583,5 +597,7 @@ pub mod quic;
 /// APIs for implementing TLS tickets
 pub mod ticketer;
 
+pub mod kernel;
+
 /// This is the rustls manual.
 pub mod manual;
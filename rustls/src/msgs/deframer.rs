@@ -422,6 +422,8 @@ impl FilledDeframerBuffer for DeframerVecBuffer {
 
 impl DeframerBuffer<true> for DeframerVecBuffer {
     fn copy(&mut self, src: &[u8], at: usize) {
+        // `at` is relative to the start of the buffer, not of its unfilled part.
+        let at = at - self.used;
         copy_into_buffer(self.unfilled(), src, at);
         self.advance(src.len());
     }
@@ -543,7 +545,7 @@ struct HandshakePayloadMeta {
 /// Returns `Err` if the advertised length is larger than what we want to accept
 /// (`MAX_HANDSHAKE_SIZE`), `Ok(None)` if the buffer is too small to contain a complete header,
 /// and `Ok(Some(len))` otherwise.
-fn payload_size(buf: &[u8]) -> Result<Option<usize>, Error> {
+pub(crate) fn payload_size(buf: &[u8]) -> Result<Option<usize>, Error> {
     if buf.len() < HEADER_SIZE {
         return Ok(None);
     }
@@ -770,6 +772,33 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_push_handshake_message_in_two_parts() {
+        // A Finished message with a 12-byte body.
+        let mut message = vec![0x14, 0x00, 0x00, 0x0c];
+        message.extend(0..12u8);
+        let (first, second) = message.split_at(6);
+
+        let mut d = BufferedDeframer::default();
+        let mut rl = RecordLayer::new();
+        d.inner
+            .push(ProtocolVersion::TLSv1_3, first, &mut d.buffer)
+            .unwrap();
+        assert!(d.pop(&mut rl, None).unwrap().is_none());
+
+        d.inner
+            .push(ProtocolVersion::TLSv1_3, second, &mut d.buffer)
+            .unwrap();
+        let m = d
+            .pop(&mut rl, None)
+            .unwrap()
+            .unwrap()
+            .message;
+        assert_eq!(m.typ, ContentType::Handshake);
+        assert_eq!(m.payload.0, message);
+        assert!(!d.has_pending());
+    }
+
     #[test]
     fn test_limited_buffer() {
         const PAYLOAD_LEN: usize = 16_384;
@@ -552,6 +552,7 @@ pub enum ClientExtension {
     Cookie(PayloadU16),
     ExtendedMasterSecretRequest,
     CertificateStatusRequest(CertificateStatusRequest),
+    SignedCertificateTimestampRequest,
     TransportParameters(Vec<u8>),
     TransportParametersDraft(Vec<u8>),
     EarlyData,
@@ -574,6 +575,7 @@ impl ClientExtension {
             Self::Cookie(_) => ExtensionType::Cookie,
             Self::ExtendedMasterSecretRequest => ExtensionType::ExtendedMasterSecret,
             Self::CertificateStatusRequest(_) => ExtensionType::StatusRequest,
+            Self::SignedCertificateTimestampRequest => ExtensionType::SCT,
             Self::TransportParameters(_) => ExtensionType::TransportParameters,
             Self::TransportParametersDraft(_) => ExtensionType::TransportParametersDraft,
             Self::EarlyData => ExtensionType::EarlyData,
@@ -594,6 +596,7 @@ impl Codec for ClientExtension {
             Self::ServerName(ref r) => r.encode(nested.buf),
             Self::SessionTicket(ClientSessionTicket::Request)
             | Self::ExtendedMasterSecretRequest
+            | Self::SignedCertificateTimestampRequest
             | Self::EarlyData => {}
             Self::SessionTicket(ClientSessionTicket::Offer(ref r)) => r.encode(nested.buf),
             Self::Protocols(ref r) => r.encode(nested.buf),
@@ -641,6 +644,7 @@ impl Codec for ClientExtension {
                 let csr = CertificateStatusRequest::read(&mut sub)?;
                 Self::CertificateStatusRequest(csr)
             }
+            ExtensionType::SCT if !sub.any_left() => Self::SignedCertificateTimestampRequest,
             ExtensionType::TransportParameters => Self::TransportParameters(sub.rest().to_vec()),
             ExtensionType::TransportParametersDraft => {
                 Self::TransportParametersDraft(sub.rest().to_vec())
@@ -698,6 +702,7 @@ pub enum ServerExtension {
     PresharedKey(u16),
     ExtendedMasterSecretAck,
     CertificateStatusAck,
+    SignedCertificateTimestamp(Vec<Sct>),
     SupportedVersions(ProtocolVersion),
     TransportParameters(Vec<u8>),
     TransportParametersDraft(Vec<u8>),
@@ -717,6 +722,7 @@ impl ServerExtension {
             Self::PresharedKey(_) => ExtensionType::PreSharedKey,
             Self::ExtendedMasterSecretAck => ExtensionType::ExtendedMasterSecret,
             Self::CertificateStatusAck => ExtensionType::StatusRequest,
+            Self::SignedCertificateTimestamp(_) => ExtensionType::SCT,
             Self::SupportedVersions(_) => ExtensionType::SupportedVersions,
             Self::TransportParameters(_) => ExtensionType::TransportParameters,
             Self::TransportParametersDraft(_) => ExtensionType::TransportParametersDraft,
@@ -742,6 +748,7 @@ impl Codec for ServerExtension {
             Self::Protocols(ref r) => r.encode(nested.buf),
             Self::KeyShare(ref r) => r.encode(nested.buf),
             Self::PresharedKey(r) => r.encode(nested.buf),
+            Self::SignedCertificateTimestamp(ref r) => r.encode(nested.buf),
             Self::SupportedVersions(ref r) => r.encode(nested.buf),
             Self::TransportParameters(ref r) | Self::TransportParametersDraft(ref r) => {
                 nested.buf.extend_from_slice(r);
@@ -765,6 +772,7 @@ impl Codec for ServerExtension {
             ExtensionType::KeyShare => Self::KeyShare(KeyShareEntry::read(&mut sub)?),
             ExtensionType::PreSharedKey => Self::PresharedKey(u16::read(&mut sub)?),
             ExtensionType::ExtendedMasterSecret => Self::ExtendedMasterSecretAck,
+            ExtensionType::SCT => Self::SignedCertificateTimestamp(Vec::read(&mut sub)?),
             ExtensionType::SupportedVersions => {
                 Self::SupportedVersions(ProtocolVersion::read(&mut sub)?)
             }
@@ -786,6 +794,10 @@ impl ServerExtension {
         Self::Protocols(Vec::from_slices(proto))
     }
 
+    pub(crate) fn make_sct(sct_list: Vec<Sct>) -> Self {
+        Self::SignedCertificateTimestamp(sct_list)
+    }
+
     #[cfg(feature = "tls12")]
     pub(crate) fn make_empty_renegotiation_info() -> Self {
         let empty = Vec::new();
@@ -915,13 +927,13 @@ impl ClientHelloPayload {
         }
     }
 
-    pub(crate) fn get_quic_params_extension(&self) -> Option<Vec<u8>> {
+    pub(crate) fn get_quic_params_extension(&self) -> Option<&[u8]> {
         let ext = self
             .find_extension(ExtensionType::TransportParameters)
             .or_else(|| self.find_extension(ExtensionType::TransportParametersDraft))?;
         match *ext {
             ClientExtension::TransportParameters(ref bytes)
-            | ClientExtension::TransportParametersDraft(ref bytes) => Some(bytes.to_vec()),
+            | ClientExtension::TransportParametersDraft(ref bytes) => Some(bytes),
             _ => None,
         }
     }
@@ -1252,6 +1264,15 @@ impl ServerHelloPayload {
             _ => None,
         }
     }
+
+    #[cfg(feature = "tls12")]
+    pub(crate) fn get_sct_list(&self) -> Option<&[Sct]> {
+        let ext = self.find_extension(ExtensionType::SCT)?;
+        match *ext {
+            ServerExtension::SignedCertificateTimestamp(ref sct) => Some(sct),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Clone, Default, Debug)]
@@ -1286,6 +1307,7 @@ impl TlsListElement for CertificateDer<'_> {
 #[derive(Debug)]
 pub(crate) enum CertificateExtension {
     CertificateStatus(CertificateStatus),
+    SignedCertificateTimestamp(Vec<Sct>),
     Unknown(UnknownExtension),
 }
 
@@ -1293,6 +1315,7 @@ impl CertificateExtension {
     pub(crate) fn get_type(&self) -> ExtensionType {
         match *self {
             Self::CertificateStatus(_) => ExtensionType::StatusRequest,
+            Self::SignedCertificateTimestamp(_) => ExtensionType::SCT,
             Self::Unknown(ref r) => r.typ,
         }
     }
@@ -1303,6 +1326,13 @@ impl CertificateExtension {
             _ => None,
         }
     }
+
+    pub(crate) fn get_sct_list(&self) -> Option<&[Sct]> {
+        match *self {
+            Self::SignedCertificateTimestamp(ref sct) => Some(sct),
+            _ => None,
+        }
+    }
 }
 
 impl Codec for CertificateExtension {
@@ -1312,6 +1342,7 @@ impl Codec for CertificateExtension {
         let nested = LengthPrefixedBuffer::new(ListLength::U16, bytes);
         match *self {
             Self::CertificateStatus(ref r) => r.encode(nested.buf),
+            Self::SignedCertificateTimestamp(ref r) => r.encode(nested.buf),
             Self::Unknown(ref r) => r.encode(nested.buf),
         }
     }
@@ -1326,6 +1357,7 @@ impl Codec for CertificateExtension {
                 let st = CertificateStatus::read(&mut sub)?;
                 Self::CertificateStatus(st)
             }
+            ExtensionType::SCT => Self::SignedCertificateTimestamp(Vec::read(&mut sub)?),
             _ => Self::Unknown(UnknownExtension::read(typ, &mut sub)),
         };
 
@@ -1382,9 +1414,9 @@ impl CertificateEntry {
     }
 
     pub(crate) fn has_unknown_extension(&self) -> bool {
-        self.exts
-            .iter()
-            .any(|ext| ext.get_type() != ExtensionType::StatusRequest)
+        self.exts.iter().any(|ext| {
+            ext.get_type() != ExtensionType::StatusRequest && ext.get_type() != ExtensionType::SCT
+        })
     }
 
     pub(crate) fn get_ocsp_response(&self) -> Option<&Vec<u8>> {
@@ -1393,6 +1425,13 @@ impl CertificateEntry {
             .find(|ext| ext.get_type() == ExtensionType::StatusRequest)
             .and_then(CertificateExtension::get_cert_status)
     }
+
+    pub(crate) fn get_sct_list(&self) -> Option<&[Sct]> {
+        self.exts
+            .iter()
+            .find(|ext| ext.get_type() == ExtensionType::SCT)
+            .and_then(CertificateExtension::get_sct_list)
+    }
 }
 
 impl TlsListElement for CertificateEntry {
@@ -1465,6 +1504,14 @@ impl CertificatePayloadTls13 {
             .unwrap_or_default()
     }
 
+    pub(crate) fn get_end_entity_sct_list(&self) -> Vec<Sct> {
+        self.entries
+            .first()
+            .and_then(CertificateEntry::get_sct_list)
+            .map(<[Sct]>::to_vec)
+            .unwrap_or_default()
+    }
+
     pub(crate) fn convert(self) -> CertificateChain {
         CertificateChain(
             self.entries
@@ -2062,6 +2109,16 @@ impl CertificateStatus {
     }
 }
 
+// -- RFC6962 signed certificate timestamps
+
+/// A single serialized `SignedCertificateTimestamp`.  A
+/// `SignedCertificateTimestampList` is a `Vec<Sct>`.
+pub(crate) type Sct = PayloadU16;
+
+impl TlsListElement for PayloadU16 {
+    const SIZE_LEN: ListLength = ListLength::U16;
+}
+
 #[derive(Debug)]
 pub enum HandshakePayload {
     HelloRequest,
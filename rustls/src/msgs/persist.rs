@@ -261,7 +261,9 @@ pub struct ServerSessionValue {
     pub(crate) application_data: PayloadU16,
     pub creation_time_sec: u64,
     pub(crate) age_obfuscation_offset: u32,
-    freshness: Option<bool>,
+    /// Difference between the client's and our view of the ticket age,
+    /// in milliseconds, once known.
+    freshness: Option<u32>,
 }
 
 impl Codec for ServerSessionValue {
@@ -394,12 +396,18 @@ impl ServerSessionValue {
             client_age_ms - server_age_ms
         };
 
-        self.freshness = Some(age_difference <= MAX_FRESHNESS_SKEW_MS);
+        self.freshness = Some(age_difference);
         self
     }
 
     pub(crate) fn is_fresh(&self) -> bool {
-        self.freshness.unwrap_or_default()
+        self.is_fresh_within(MAX_FRESHNESS_SKEW_MS)
+    }
+
+    /// Like `is_fresh`, but with a caller-chosen tolerance in place
+    /// of `MAX_FRESHNESS_SKEW_MS`.
+    pub(crate) fn is_fresh_within(&self, max_skew_ms: u32) -> bool {
+        matches!(self.freshness, Some(age_difference) if age_difference <= max_skew_ms)
     }
 }
 
@@ -1,14 +1,17 @@
 /// This module contains optional APIs for implementing QUIC TLS.
-use crate::client::{ClientConfig, ClientConnectionData};
-use crate::common_state::{CommonState, Protocol, Side, DEFAULT_BUFFER_LIMIT};
+use crate::client::{ClientConfig, ClientConnectionData, ClientConnectionOptions};
+use crate::common_state::{CommonState, Context, Protocol, Side, DEFAULT_BUFFER_LIMIT};
 use crate::conn::{ConnectionCore, SideData};
 use crate::crypto::cipher::{AeadKey, Iv};
 use crate::crypto::tls13::{Hkdf, HkdfExpander, OkmBlock};
-use crate::enums::{AlertDescription, ProtocolVersion};
+use crate::enums::{AlertDescription, ProtocolVersion, SignatureScheme};
 use crate::error::Error;
 use crate::msgs::deframer::DeframerVecBuffer;
 use crate::msgs::handshake::{ClientExtension, ServerExtension};
-use crate::server::{ServerConfig, ServerConnectionData};
+use crate::msgs::message::Message;
+use crate::server::hs;
+use crate::server::server_conn::{accept_with_config, accepted_client_hello};
+use crate::server::{ClientHello, ServerConfig, ServerConnectionData};
 use crate::tls13::key_schedule::{
     hkdf_expand_label, hkdf_expand_label_aead_key, hkdf_expand_label_block,
 };
@@ -25,6 +28,18 @@ use alloc::vec::Vec;
 use core::fmt::{self, Debug};
 use core::ops::{Deref, DerefMut};
 
+/// Unbuffered QUIC connection API
+///
+/// This is the QUIC counterpart of [`crate::unbuffered`]: handshake data is read from and
+/// written into caller-provided buffers, and new keys are reported as
+/// [`unbuffered::ConnectionState::KeyChange`] states, so a QUIC stack need not hold on to
+/// buffers owned by the connection.
+///
+/// The entry points into this API are [`unbuffered::UnbufferedClientConnection::new`],
+/// [`unbuffered::UnbufferedServerConnection::new`] and
+/// [`unbuffered::UnbufferedConnectionCommon::process_quic_handshake`].
+pub mod unbuffered;
+
 /// A QUIC client or server connection.
 #[derive(Debug)]
 pub enum Connection {
@@ -83,6 +98,24 @@ impl Connection {
         }
     }
 
+    /// The QUIC version whose key derivation labels are used for this connection.
+    pub fn quic_version(&self) -> Version {
+        match self {
+            Self::Client(conn) => conn.quic_version(),
+            Self::Server(conn) => conn.quic_version(),
+        }
+    }
+
+    /// Change the QUIC version used for this connection.
+    ///
+    /// See [`ConnectionCommon::set_quic_version()`] for more details.
+    pub fn set_quic_version(&mut self, version: Version) -> Result<(), Error> {
+        match self {
+            Self::Client(conn) => conn.set_quic_version(version),
+            Self::Server(conn) => conn.set_quic_version(version),
+        }
+    }
+
     /// Derives key material from the agreed connection secrets.
     ///
     /// This function fills in `output` with `output.len()` bytes of key
@@ -152,27 +185,8 @@ impl ClientConnection {
         name: ServerName<'static>,
         params: Vec<u8>,
     ) -> Result<Self, Error> {
-        if !config.supports_version(ProtocolVersion::TLSv1_3) {
-            return Err(Error::General(
-                "TLS 1.3 support is required for QUIC".into(),
-            ));
-        }
-
-        if !config.supports_protocol(Protocol::Quic) {
-            return Err(Error::General(
-                "at least one ciphersuite must support QUIC".into(),
-            ));
-        }
-
-        let ext = match quic_version {
-            Version::V1Draft => ClientExtension::TransportParametersDraft(params),
-            Version::V1 | Version::V2 => ClientExtension::TransportParameters(params),
-        };
-
-        let mut inner = ConnectionCore::for_client(config, name, vec![ext], Protocol::Quic)?;
-        inner.common_state.quic.version = quic_version;
         Ok(Self {
-            inner: inner.into(),
+            inner: client_core(config, quic_version, name, params)?.into(),
         })
     }
 
@@ -228,33 +242,28 @@ impl ServerConnection {
         quic_version: Version,
         params: Vec<u8>,
     ) -> Result<Self, Error> {
-        if !config.supports_version(ProtocolVersion::TLSv1_3) {
-            return Err(Error::General(
-                "TLS 1.3 support is required for QUIC".into(),
-            ));
-        }
-
-        if !config.supports_protocol(Protocol::Quic) {
-            return Err(Error::General(
-                "at least one ciphersuite must support QUIC".into(),
-            ));
-        }
-
-        if config.max_early_data_size != 0 && config.max_early_data_size != 0xffff_ffff {
-            return Err(Error::General(
-                "QUIC sessions must set a max early data of 0 or 2^32-1".into(),
-            ));
-        }
+        Ok(Self {
+            inner: server_core(config, quic_version, params)?.into(),
+        })
+    }
 
-        let ext = match quic_version {
-            Version::V1Draft => ServerExtension::TransportParametersDraft(params),
-            Version::V1 | Version::V2 => ServerExtension::TransportParameters(params),
-        };
+    /// Application-controlled portion of the resumption ticket supplied by the client, if any.
+    ///
+    /// See [`crate::ServerConnection::received_resumption_data()`].
+    pub fn received_resumption_data(&self) -> Option<&[u8]> {
+        self.inner
+            .core
+            .data
+            .received_resumption_data
+            .as_deref()
+    }
 
-        let mut core = ConnectionCore::for_server(config, vec![ext])?;
-        core.common_state.protocol = Protocol::Quic;
-        core.common_state.quic.version = quic_version;
-        Ok(Self { inner: core.into() })
+    /// Set the resumption data to embed in future resumption tickets supplied to the client.
+    ///
+    /// See [`crate::ServerConnection::set_resumption_data()`].
+    pub fn set_resumption_data(&mut self, data: &[u8]) {
+        assert!(data.len() < 2usize.pow(15));
+        self.inner.core.data.resumption_data = data.into();
     }
 
     /// Explicitly discard early data, notifying the client
@@ -313,6 +322,256 @@ impl From<ServerConnection> for Connection {
     }
 }
 
+/// Make the connection core shared by buffered and unbuffered QUIC client connections.
+fn client_core(
+    config: Arc<ClientConfig>,
+    quic_version: Version,
+    name: ServerName<'static>,
+    params: Vec<u8>,
+) -> Result<ConnectionCore<ClientConnectionData>, Error> {
+    if !config.supports_version(ProtocolVersion::TLSv1_3) {
+        return Err(Error::General(
+            "TLS 1.3 support is required for QUIC".into(),
+        ));
+    }
+
+    if !config.supports_protocol(Protocol::Quic) {
+        return Err(Error::General(
+            "at least one ciphersuite must support QUIC".into(),
+        ));
+    }
+
+    let ext = match quic_version {
+        Version::V1Draft => ClientExtension::TransportParametersDraft(params),
+        Version::V1 | Version::V2 => ClientExtension::TransportParameters(params),
+    };
+
+    let mut core = ConnectionCore::for_client(
+        config,
+        name,
+        ClientConnectionOptions::default(),
+        vec![ext],
+        Protocol::Quic,
+    )?;
+    core.common_state.quic.version = quic_version;
+    Ok(core)
+}
+
+/// Make the connection core shared by buffered and unbuffered QUIC server connections.
+fn server_core(
+    config: Arc<ServerConfig>,
+    quic_version: Version,
+    params: Vec<u8>,
+) -> Result<ConnectionCore<ServerConnectionData>, Error> {
+    check_server_config(&config)?;
+    let ext = transport_parameters_extension(quic_version, params.clone());
+
+    let mut core = ConnectionCore::for_server(config, vec![ext])?;
+    core.common_state.protocol = Protocol::Quic;
+    core.common_state.quic.version = quic_version;
+    core.common_state.quic.sent_params = Some(params);
+    Ok(core)
+}
+
+/// Check that `config` can be used for a QUIC server connection.
+fn check_server_config(config: &ServerConfig) -> Result<(), Error> {
+    if !config.supports_version(ProtocolVersion::TLSv1_3) {
+        return Err(Error::General(
+            "TLS 1.3 support is required for QUIC".into(),
+        ));
+    }
+
+    if !config.supports_protocol(Protocol::Quic) {
+        return Err(Error::General(
+            "at least one ciphersuite must support QUIC".into(),
+        ));
+    }
+
+    if config.max_early_data_size != 0 && config.max_early_data_size != 0xffff_ffff {
+        return Err(Error::General(
+            "QUIC sessions must set a max early data of 0 or 2^32-1".into(),
+        ));
+    }
+
+    Ok(())
+}
+
+fn transport_parameters_extension(quic_version: Version, params: Vec<u8>) -> ServerExtension {
+    match quic_version {
+        Version::V1Draft => ServerExtension::TransportParametersDraft(params),
+        Version::V1 | Version::V2 => ServerExtension::TransportParameters(params),
+    }
+}
+
+/// Handle a QUIC server-side connection before configuration is available.
+///
+/// This is the QUIC counterpart of [`server::Acceptor`]: it allows the caller to choose a
+/// [`ServerConfig`] and the transport parameters to send after inspecting the
+/// [`ClientHello`] of an incoming connection, for example based on its server name, ALPN
+/// protocols or transport parameters.
+///
+/// Feed the contents of the client's Initial CRYPTO frames to [`Acceptor::read_hs()`] until
+/// [`Acceptor::accept()`] yields an [`Accepted`], then call [`Accepted::into_connection()`]
+/// to continue the handshake with a [`ServerConnection`].
+///
+/// [`server::Acceptor`]: crate::server::Acceptor
+pub struct Acceptor {
+    inner: Option<ConnectionCommon<ServerConnectionData>>,
+}
+
+impl Acceptor {
+    /// Make a new QUIC `Acceptor` for a connection using `quic_version`.
+    pub fn new(quic_version: Version) -> Self {
+        let mut core = ConnectionCore::for_acceptor();
+        core.common_state.protocol = Protocol::Quic;
+        core.common_state.quic.version = quic_version;
+        Self {
+            inner: Some(core.into()),
+        }
+    }
+
+    /// Consume unencrypted TLS handshake data.
+    ///
+    /// Returns an error if this `Acceptor` has already yielded an [`Accepted`].
+    /// See [`ConnectionCommon::read_hs()`] for more details.
+    pub fn read_hs(&mut self, plaintext: &[u8]) -> Result<(), Error> {
+        match &mut self.inner {
+            Some(conn) => conn.core.message_deframer.push(
+                ProtocolVersion::TLSv1_3,
+                plaintext,
+                &mut conn.deframer_buffer,
+            ),
+            None => Err(Error::General(
+                "acceptor cannot read after successful acceptance".into(),
+            )),
+        }
+    }
+
+    /// Check if a `ClientHello` message has been received.
+    ///
+    /// Returns `Ok(None)` if the complete `ClientHello` has not yet been received.
+    /// Call [`Acceptor::read_hs()`] with more data and then call this function again.
+    ///
+    /// Returns `Ok(Some(accepted))` if the connection has been accepted. Call
+    /// `accepted.into_connection()` to continue. Do not call this function again.
+    ///
+    /// Returns `Err((err, alert))` if an error occurred. If an alert is returned, the
+    /// caller should close the connection with the corresponding QUIC error code.
+    /// Do not call this function again.
+    pub fn accept(&mut self) -> Result<Option<Accepted>, (Error, Option<AlertDescription>)> {
+        let mut connection = match self.inner.take() {
+            Some(conn) => conn,
+            None => {
+                return Err((
+                    Error::General("Acceptor polled after completion".into()),
+                    None,
+                ));
+            }
+        };
+
+        let mut buffer = connection.deframer_buffer.borrow();
+        let res = connection
+            .core
+            .first_handshake_message(&mut buffer);
+        let discard = buffer.pending_discard();
+        connection
+            .deframer_buffer
+            .discard(discard);
+
+        let message = match res {
+            Ok(Some(msg)) => msg,
+            Ok(None) => {
+                self.inner = Some(connection);
+                return Ok(None);
+            }
+            Err(err) => return Err((err, connection.alert())),
+        };
+
+        let sig_schemes =
+            match hs::process_client_hello(&message, false, &mut Context::from(&mut connection)) {
+                Ok((_, sig_schemes)) => sig_schemes,
+                Err(err) => return Err((err, connection.alert())),
+            };
+
+        Ok(Some(Accepted {
+            connection,
+            message,
+            sig_schemes,
+        }))
+    }
+}
+
+impl Debug for Acceptor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("quic::Acceptor")
+            .finish()
+    }
+}
+
+/// Represents a `ClientHello` message received through the QUIC [`Acceptor`].
+///
+/// Contains the state required to resume the connection through [`Accepted::into_connection()`].
+pub struct Accepted {
+    connection: ConnectionCommon<ServerConnectionData>,
+    message: Message,
+    sig_schemes: Vec<SignatureScheme>,
+}
+
+impl Accepted {
+    /// Get the [`ClientHello`] for this connection.
+    ///
+    /// The client's transport parameters are available from
+    /// [`ClientHello::quic_transport_parameters()`].
+    pub fn client_hello(&self) -> ClientHello<'_> {
+        accepted_client_hello(&self.connection.core.data, &self.sig_schemes, &self.message)
+    }
+
+    /// Convert the [`Accepted`] into a [`ServerConnection`].
+    ///
+    /// Takes the [`ServerConfig`] that should be used for the session and the TLS-encoded
+    /// transport parameters to send, as for [`ServerConnection::new()`].  Returns an error if
+    /// `config` is unsuitable for QUIC, or if configuration-dependent validation of the
+    /// received `ClientHello` message fails.
+    ///
+    /// On success, the handshake messages responding to the `ClientHello` are available
+    /// from [`ConnectionCommon::write_hs()`] on the returned connection.
+    pub fn into_connection(
+        mut self,
+        config: Arc<ServerConfig>,
+        params: Vec<u8>,
+    ) -> Result<ServerConnection, (Error, Option<AlertDescription>)> {
+        if let Err(err) = check_server_config(&config) {
+            return Err((err, None));
+        }
+
+        let quic = &mut self.connection.core.common_state.quic;
+        let ext = transport_parameters_extension(quic.version, params.clone());
+        quic.sent_params = Some(params);
+        let new = match accept_with_config(
+            config,
+            vec![ext],
+            self.sig_schemes,
+            &self.message,
+            &mut Context::from(&mut self.connection),
+        ) {
+            Ok(new) => new,
+            Err(err) => return Err((err, self.connection.alert())),
+        };
+
+        self.connection.core.state = Ok(new);
+        Ok(ServerConnection {
+            inner: self.connection,
+        })
+    }
+}
+
+impl Debug for Accepted {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("quic::Accepted")
+            .finish()
+    }
+}
+
 /// A shared interface for QUIC connections.
 pub struct ConnectionCommon<Data> {
     core: ConnectionCore<Data>,
@@ -339,21 +598,7 @@ impl<Data: SideData> ConnectionCommon<Data> {
 
     /// Compute the keys for encrypting/decrypting 0-RTT packets, if available
     pub fn zero_rtt_keys(&self) -> Option<DirectionalKeys> {
-        let suite = self
-            .core
-            .common_state
-            .suite
-            .and_then(|suite| suite.tls13())?;
-        Some(DirectionalKeys::new(
-            suite,
-            suite.quic?,
-            self.core
-                .common_state
-                .quic
-                .early_secret
-                .as_ref()?,
-            self.core.common_state.quic.version,
-        ))
+        zero_rtt_keys(&self.core.common_state)
     }
 
     /// Consume unencrypted TLS handshake data.
@@ -386,6 +631,34 @@ impl<Data: SideData> ConnectionCommon<Data> {
     pub fn alert(&self) -> Option<AlertDescription> {
         self.core.common_state.quic.alert
     }
+
+    /// The QUIC version whose key derivation labels are used for this connection.
+    pub fn quic_version(&self) -> Version {
+        self.core.common_state.quic.version
+    }
+
+    /// Change the QUIC version used for this connection.
+    ///
+    /// This supports compatible version negotiation ([RFC 9368]): a server may switch to
+    /// a version compatible with that of the client's first Initial packet, and the client
+    /// then switches to the server's version when it sees it in the server's Initial
+    /// packets.  Subsequent [`KeyChange`]s derive keys using the labels of `version`.
+    /// [`Self::zero_rtt_keys()`] keeps using the labels of the original version, since
+    /// 0-RTT packets are sent with it.
+    ///
+    /// This must be called before [`Self::write_hs()`] returns [`KeyChange::Handshake`],
+    /// but may be called after the handshake data from which those keys are derived has
+    /// been passed to [`Self::read_hs()`].  Returns an error if it is too late, or when
+    /// changing between [`Version::V1Draft`] and a final version, as these use different
+    /// transport parameters extensions.
+    ///
+    /// [RFC 9368]: https://www.rfc-editor.org/rfc/rfc9368.html
+    pub fn set_quic_version(&mut self, version: Version) -> Result<(), Error> {
+        self.core
+            .common_state
+            .quic
+            .set_version(version)
+    }
 }
 
 impl<Data> Deref for ConnectionCommon<Data> {
@@ -402,6 +675,16 @@ impl<Data> DerefMut for ConnectionCommon<Data> {
     }
 }
 
+impl<'a, Data> From<&'a mut ConnectionCommon<Data>> for Context<'a, Data> {
+    fn from(conn: &'a mut ConnectionCommon<Data>) -> Self {
+        Self {
+            common: &mut conn.core.common_state,
+            data: &mut conn.core.data,
+            sendable_plaintext: Some(&mut conn.sendable_plaintext),
+        }
+    }
+}
+
 impl<Data> From<ConnectionCore<Data>> for ConnectionCommon<Data> {
     fn from(core: ConnectionCore<Data>) -> Self {
         Self {
@@ -412,10 +695,31 @@ impl<Data> From<ConnectionCore<Data>> for ConnectionCommon<Data> {
     }
 }
 
+/// Compute the keys for encrypting/decrypting 0-RTT packets, if available
+///
+/// 0-RTT packets keep the original version after compatible version negotiation
+/// (RFC 9368 section 4), so their keys use its labels.
+fn zero_rtt_keys(common: &CommonState) -> Option<DirectionalKeys> {
+    let suite = common
+        .suite
+        .and_then(|suite| suite.tls13())?;
+    Some(DirectionalKeys::new(
+        suite,
+        suite.quic?,
+        common.quic.early_secret.as_ref()?,
+        common
+            .quic
+            .original_version
+            .unwrap_or(common.quic.version),
+    ))
+}
+
 #[derive(Default)]
 pub(crate) struct Quic {
     /// QUIC transport parameters received from the peer during the handshake
     pub(crate) params: Option<Vec<u8>>,
+    /// QUIC transport parameters sent to the peer, for servers
+    pub(crate) sent_params: Option<Vec<u8>>,
     pub(crate) alert: Option<AlertDescription>,
     pub(crate) hs_queue: VecDeque<(bool, Vec<u8>)>,
     pub(crate) early_secret: Option<OkmBlock>,
@@ -423,22 +727,61 @@ pub(crate) struct Quic {
     pub(crate) traffic_secrets: Option<Secrets>,
     /// Whether keys derived from traffic_secrets have been passed to the QUIC implementation
     pub(crate) returned_traffic_keys: bool,
+    /// Whether keys derived from hs_secrets have been passed to the QUIC implementation
+    pub(crate) returned_handshake_keys: bool,
     pub(crate) version: Version,
+    /// The version before the first call to `set_version`, if any
+    pub(crate) original_version: Option<Version>,
 }
 
 impl Quic {
+    pub(crate) fn set_version(&mut self, version: Version) -> Result<(), Error> {
+        if self.returned_handshake_keys {
+            return Err(Error::General(
+                "QUIC version cannot change after handshake keys are in use".into(),
+            ));
+        }
+
+        // The transport parameters extension differs, and has already been sent.
+        if (self.version == Version::V1Draft) != (version == Version::V1Draft) {
+            return Err(Error::General(
+                "QUIC version cannot change between draft and final versions".into(),
+            ));
+        }
+
+        self.original_version
+            .get_or_insert(self.version);
+        self.version = version;
+        for secrets in [&mut self.hs_secrets, &mut self.traffic_secrets]
+            .into_iter()
+            .flatten()
+        {
+            secrets.version = version;
+        }
+        Ok(())
+    }
+
     pub(crate) fn write_hs(&mut self, buf: &mut Vec<u8>) -> Option<KeyChange> {
         while let Some((_, msg)) = self.hs_queue.pop_front() {
             buf.extend_from_slice(&msg);
-            if let Some(&(true, _)) = self.hs_queue.front() {
-                if self.hs_secrets.is_some() {
-                    // Allow the caller to switch keys before proceeding.
-                    break;
-                }
+            if self.at_key_change() {
+                // Allow the caller to switch keys before proceeding.
+                break;
             }
         }
 
+        self.key_change()
+    }
+
+    /// Whether the next queued handshake message must wait for the handshake keys.
+    pub(crate) fn at_key_change(&self) -> bool {
+        matches!(self.hs_queue.front(), Some(&(true, _))) && self.hs_secrets.is_some()
+    }
+
+    /// Take the keys for the next encryption level, if they are ready to be used.
+    pub(crate) fn key_change(&mut self) -> Option<KeyChange> {
         if let Some(secrets) = self.hs_secrets.take() {
+            self.returned_handshake_keys = true;
             return Some(KeyChange::Handshake {
                 keys: Keys::new(&secrets),
             });
@@ -646,10 +989,56 @@ pub trait HeaderProtectionKey: Send + Sync {
         packet_number: &mut [u8],
     ) -> Result<(), Error>;
 
+    /// Adds QUIC Header Protection to each of a batch of packets.
+    ///
+    /// This is equivalent to calling [`Self::encrypt_in_place()`] for each element of
+    /// `headers` in order, but implementations may compute the masks for all the
+    /// samples at once.
+    ///
+    /// Stops at and returns the first error, leaving that packet and those after it
+    /// unmodified.
+    fn encrypt_batch_in_place(&self, headers: &mut [BatchHeader<'_>]) -> Result<(), Error> {
+        for header in headers {
+            self.encrypt_in_place(header.sample, header.first, header.packet_number)?;
+        }
+        Ok(())
+    }
+
+    /// Removes QUIC Header Protection from each of a batch of packets.
+    ///
+    /// This is equivalent to calling [`Self::decrypt_in_place()`] for each element of
+    /// `headers` in order, but implementations may compute the masks for all the
+    /// samples at once.
+    ///
+    /// Stops at and returns the first error, leaving that packet and those after it
+    /// unmodified.
+    fn decrypt_batch_in_place(&self, headers: &mut [BatchHeader<'_>]) -> Result<(), Error> {
+        for header in headers {
+            self.decrypt_in_place(header.sample, header.first, header.packet_number)?;
+        }
+        Ok(())
+    }
+
     /// Expected sample length for the key's algorithm
     fn sample_len(&self) -> usize;
 }
 
+/// The header of one packet in a batch passed to
+/// [`HeaderProtectionKey::encrypt_batch_in_place()`] or
+/// [`HeaderProtectionKey::decrypt_batch_in_place()`].
+///
+/// The fields have the same meaning as the arguments of
+/// [`HeaderProtectionKey::encrypt_in_place()`].
+#[derive(Debug)]
+pub struct BatchHeader<'a> {
+    /// The sample of encrypted payload.
+    pub sample: &'a [u8],
+    /// The first byte of the header.
+    pub first: &'a mut u8,
+    /// The Packet Number field.
+    pub packet_number: &'a mut [u8],
+}
+
 /// Keys to encrypt or decrypt the payload of a packet
 pub trait PacketKey: Send + Sync {
     /// Encrypt a QUIC packet
@@ -680,10 +1069,78 @@ pub trait PacketKey: Send + Sync {
         payload: &'a mut [u8],
     ) -> Result<&'a [u8], Error>;
 
+    /// Encrypt a batch of QUIC packets.
+    ///
+    /// The last [`Self::tag_len()`] bytes of each packet's `payload` are not encrypted:
+    /// the packet's authentication tag is written there instead.  This allows a batch
+    /// of packets to be encrypted where they lie in a contiguous send buffer.
+    ///
+    /// This is equivalent to calling [`Self::encrypt_in_place()`] for each packet in
+    /// order, but avoids a dynamic call per packet and allows implementations to
+    /// process several packets at once.
+    ///
+    /// Stops at and returns the first error, leaving that packet and those after it
+    /// unmodified.
+    fn encrypt_batch_in_place(&self, packets: &mut [BatchPacket<'_>]) -> Result<(), Error> {
+        let tag_len = self.tag_len();
+        for packet in packets {
+            let payload_len = match packet
+                .payload
+                .len()
+                .checked_sub(tag_len)
+            {
+                Some(len) => len,
+                None => return Err(Error::EncryptError),
+            };
+            let (payload, tag) = packet.payload.split_at_mut(payload_len);
+            tag.copy_from_slice(
+                self.encrypt_in_place(packet.packet_number, packet.header, payload)?
+                    .as_ref(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Decrypt a batch of QUIC packets.
+    ///
+    /// Each packet's `payload` includes its authentication tag, as for
+    /// [`Self::decrypt_in_place()`].  The outcome for `packets[i]` is written to
+    /// `results[i]`: the length of the decrypted payload, which is found at the start of
+    /// `payload`, or the error.  Unlike encryption, a packet failing to decrypt does not
+    /// stop the rest of the batch being processed.
+    ///
+    /// # Panics
+    ///
+    /// If `results` is not the same length as `packets`.
+    fn decrypt_batch_in_place(
+        &self,
+        packets: &mut [BatchPacket<'_>],
+        results: &mut [Result<usize, Error>],
+    ) {
+        assert_eq!(packets.len(), results.len());
+        for (packet, result) in packets.iter_mut().zip(results) {
+            *result = self
+                .decrypt_in_place(packet.packet_number, packet.header, packet.payload)
+                .map(|plain| plain.len());
+        }
+    }
+
     /// Tag length for the underlying AEAD algorithm
     fn tag_len(&self) -> usize;
 }
 
+/// One packet in a batch passed to [`PacketKey::encrypt_batch_in_place()`] or
+/// [`PacketKey::decrypt_batch_in_place()`].
+#[derive(Debug)]
+pub struct BatchPacket<'a> {
+    /// The packet number, used to derive the nonce.
+    pub packet_number: u64,
+    /// The packet header, used as the additional authenticated data.
+    pub header: &'a [u8],
+    /// The packet payload, followed by its authentication tag (or room for it).
+    pub payload: &'a mut [u8],
+}
+
 /// Packet protection keys for bidirectional 1-RTT communication
 pub struct PacketKeySet {
     /// Encrypts outgoing packets
@@ -829,7 +1286,7 @@ pub enum KeyChange {
 ///
 /// Governs version-specific behavior in the TLS layer
 #[non_exhaustive]
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Version {
     /// Draft versions 29, 30, 31 and 32
     V1Draft,
@@ -890,6 +1347,111 @@ impl Version {
             Self::V2 => b"quicv2 ku",
         }
     }
+
+    /// Compute the Retry Integrity Tag for a Retry packet.
+    ///
+    /// `quic` must be the QUIC algorithm of an AES-128-GCM cipher suite, such as
+    /// `TLS13_AES_128_GCM_SHA256.quic` from the provider in use.  `original_dcid` is
+    /// the Destination Connection ID of the client's first Initial packet, and
+    /// `retry_packet` is the Retry packet up to, but excluding, the tag.
+    ///
+    /// See [RFC 9001 section 5.8](https://www.rfc-editor.org/rfc/rfc9001#section-5.8)
+    /// and [RFC 9369 section 3.3.3](https://www.rfc-editor.org/rfc/rfc9369#section-3.3.3).
+    pub fn retry_integrity_tag(
+        self,
+        quic: &dyn Algorithm,
+        original_dcid: &[u8],
+        retry_packet: &[u8],
+    ) -> Result<Tag, Error> {
+        let pseudo_packet = retry_pseudo_packet(original_dcid, retry_packet)?;
+        self.retry_integrity_key(quic)?
+            .encrypt_in_place(0, &pseudo_packet, &mut [])
+    }
+
+    /// Verify the Retry Integrity Tag at the end of `retry_packet`.
+    ///
+    /// `quic` and `original_dcid` are as for [`Version::retry_integrity_tag()`].
+    /// `retry_packet` is the entire Retry packet, including the tag.
+    ///
+    /// Returns [`Error::DecryptError`] if the tag is not valid.
+    pub fn verify_retry_integrity_tag(
+        self,
+        quic: &dyn Algorithm,
+        original_dcid: &[u8],
+        retry_packet: &[u8],
+    ) -> Result<(), Error> {
+        let tag_start = retry_packet
+            .len()
+            .checked_sub(TAG_LEN)
+            .ok_or(Error::DecryptError)?;
+        let (retry_packet, tag) = retry_packet.split_at(tag_start);
+
+        let pseudo_packet = retry_pseudo_packet(original_dcid, retry_packet)?;
+        let mut tag = Tag::from(tag);
+        self.retry_integrity_key(quic)?
+            .decrypt_in_place(0, &pseudo_packet, &mut tag.0)
+            .map(|_| ())
+    }
+
+    fn retry_integrity_key(self, quic: &dyn Algorithm) -> Result<Box<dyn PacketKey>, Error> {
+        let (key, nonce) = match self {
+            // https://datatracker.ietf.org/doc/html/draft-ietf-quic-tls-32#section-5.8
+            Self::V1Draft => (
+                [
+                    0xcc, 0xce, 0x18, 0x7e, 0xd0, 0x9a, 0x09, 0xd0, 0x57, 0x28, 0x15, 0x5a, 0x6c,
+                    0xb9, 0x6b, 0xe1,
+                ],
+                [
+                    0xe5, 0x49, 0x30, 0xf9, 0x7f, 0x21, 0x36, 0xf0, 0x53, 0x0a, 0x8c, 0x1c,
+                ],
+            ),
+            // https://www.rfc-editor.org/rfc/rfc9001#section-5.8
+            Self::V1 => (
+                [
+                    0xbe, 0x0c, 0x69, 0x0b, 0x9f, 0x66, 0x57, 0x5a, 0x1d, 0x76, 0x6b, 0x54, 0xe3,
+                    0x68, 0xc8, 0x4e,
+                ],
+                [
+                    0x46, 0x15, 0x99, 0xd3, 0x5d, 0x63, 0x2b, 0xf2, 0x23, 0x98, 0x25, 0xbb,
+                ],
+            ),
+            // https://www.rfc-editor.org/rfc/rfc9369#section-3.3.3
+            Self::V2 => (
+                [
+                    0x8f, 0xb4, 0xb0, 0x1b, 0x56, 0xac, 0x48, 0xe2, 0x60, 0xfb, 0xcb, 0xce, 0xad,
+                    0x7c, 0xcc, 0x92,
+                ],
+                [
+                    0xd8, 0x69, 0x69, 0xbc, 0x2d, 0x7c, 0x6d, 0x99, 0x90, 0xef, 0xb0, 0x4a,
+                ],
+            ),
+        };
+
+        if quic.aead_key_len() != key.len() {
+            return Err(Error::General(
+                "Retry integrity tags require AES-128-GCM".into(),
+            ));
+        }
+
+        let mut buf = [0u8; AeadKey::MAX_LEN];
+        buf[..key.len()].copy_from_slice(&key);
+        Ok(quic.packet_key(AeadKey::from(buf).with_length(key.len()), Iv::from(nonce)))
+    }
+}
+
+/// The Retry Pseudo-Packet, which is authenticated by the Retry Integrity Tag.
+fn retry_pseudo_packet(original_dcid: &[u8], retry_packet: &[u8]) -> Result<Vec<u8>, Error> {
+    // https://www.rfc-editor.org/rfc/rfc9000#section-17.2
+    const MAX_CID_LEN: usize = 20;
+    if original_dcid.len() > MAX_CID_LEN {
+        return Err(Error::General("connection ID is too long".into()));
+    }
+
+    let mut pseudo_packet = Vec::with_capacity(1 + original_dcid.len() + retry_packet.len());
+    pseudo_packet.push(original_dcid.len() as u8);
+    pseudo_packet.extend_from_slice(original_dcid);
+    pseudo_packet.extend_from_slice(retry_packet);
+    Ok(pseudo_packet)
 }
 
 impl Default for Version {
@@ -0,0 +1,384 @@
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::ops::{Deref, DerefMut};
+use core::{fmt, mem};
+
+use pki_types::ServerName;
+
+use super::{client_core, server_core, zero_rtt_keys, DirectionalKeys, KeyChange, Version};
+use crate::client::{ClientConfig, ClientConnectionData};
+use crate::common_state::CommonState;
+use crate::conn::ConnectionCore;
+use crate::enums::{AlertDescription, ContentType, ProtocolVersion};
+use crate::error::Error;
+use crate::msgs::base::Payload;
+use crate::msgs::deframer::payload_size;
+use crate::msgs::message::PlainMessage;
+use crate::server::{ServerConfig, ServerConnectionData};
+use crate::unbuffered::{EncodeError, InsufficientSizeError};
+
+/// Unbuffered version of [`quic::ClientConnection`]
+///
+/// See the [`crate::quic::unbuffered`] module docs for more details
+///
+/// [`quic::ClientConnection`]: crate::quic::ClientConnection
+pub struct UnbufferedClientConnection {
+    inner: UnbufferedConnectionCommon<ClientConnectionData>,
+}
+
+impl UnbufferedClientConnection {
+    /// Make a new unbuffered QUIC client connection.
+    ///
+    /// See [`quic::ClientConnection::new()`](crate::quic::ClientConnection::new).
+    pub fn new(
+        config: Arc<ClientConfig>,
+        quic_version: Version,
+        name: ServerName<'static>,
+        params: Vec<u8>,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            inner: client_core(config, quic_version, name, params)?.into(),
+        })
+    }
+
+    /// Returns True if the server signalled it will process early data.
+    ///
+    /// See [`quic::ClientConnection::is_early_data_accepted()`].
+    ///
+    /// [`quic::ClientConnection::is_early_data_accepted()`]: crate::quic::ClientConnection::is_early_data_accepted
+    pub fn is_early_data_accepted(&self) -> bool {
+        self.inner.core.is_early_data_accepted()
+    }
+}
+
+impl Deref for UnbufferedClientConnection {
+    type Target = UnbufferedConnectionCommon<ClientConnectionData>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl DerefMut for UnbufferedClientConnection {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+}
+
+impl fmt::Debug for UnbufferedClientConnection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("quic::UnbufferedClientConnection")
+            .finish()
+    }
+}
+
+/// Unbuffered version of [`quic::ServerConnection`]
+///
+/// See the [`crate::quic::unbuffered`] module docs for more details
+///
+/// [`quic::ServerConnection`]: crate::quic::ServerConnection
+pub struct UnbufferedServerConnection {
+    inner: UnbufferedConnectionCommon<ServerConnectionData>,
+}
+
+impl UnbufferedServerConnection {
+    /// Make a new unbuffered QUIC server connection.
+    ///
+    /// See [`quic::ServerConnection::new()`](crate::quic::ServerConnection::new).
+    pub fn new(
+        config: Arc<ServerConfig>,
+        quic_version: Version,
+        params: Vec<u8>,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            inner: server_core(config, quic_version, params)?.into(),
+        })
+    }
+
+    /// Explicitly discard early data, notifying the client
+    ///
+    /// Must be called while `is_handshaking` is true.
+    pub fn reject_early_data(&mut self) {
+        self.inner.core.reject_early_data()
+    }
+
+    /// Retrieves the server name, if any, used to select the certificate and
+    /// private key.
+    ///
+    /// See [`quic::ServerConnection::server_name()`].
+    ///
+    /// [`quic::ServerConnection::server_name()`]: crate::quic::ServerConnection::server_name
+    pub fn server_name(&self) -> Option<&str> {
+        self.inner.core.get_sni_str()
+    }
+}
+
+impl Deref for UnbufferedServerConnection {
+    type Target = UnbufferedConnectionCommon<ServerConnectionData>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl DerefMut for UnbufferedServerConnection {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+}
+
+impl fmt::Debug for UnbufferedServerConnection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("quic::UnbufferedServerConnection")
+            .finish()
+    }
+}
+
+/// Interface shared by unbuffered QUIC client and server connections.
+pub struct UnbufferedConnectionCommon<Data> {
+    core: ConnectionCore<Data>,
+}
+
+impl<Data> UnbufferedConnectionCommon<Data> {
+    /// Processes the handshake data in the `incoming_hs` buffer until a new
+    /// [`UnbufferedStatus`] is reached.
+    ///
+    /// `incoming_hs` holds the contents of the CRYPTO frames received at a single encryption
+    /// level, in order.  Handshake data obtained from separate encryption levels should be
+    /// supplied in separate calls.
+    pub fn process_quic_handshake<'c>(
+        &'c mut self,
+        incoming_hs: &mut [u8],
+    ) -> UnbufferedStatus<'c, Data> {
+        let mut discard = 0;
+
+        let state = loop {
+            let quic = &mut self.core.common_state.quic;
+            if !quic.hs_queue.is_empty() && !quic.at_key_change() {
+                break ConnectionState::EncodeHandshakeData(EncodeHandshakeData {
+                    conn: self,
+                    encoded: false,
+                });
+            }
+
+            if let Some(key_change) = quic.key_change() {
+                break ConnectionState::KeyChange(key_change);
+            }
+
+            let incoming = &incoming_hs[discard..];
+            let len = match payload_size(incoming) {
+                Ok(Some(len)) if len <= incoming.len() => len,
+                Ok(_) if self.core.common_state.is_handshaking() => {
+                    break ConnectionState::BlockedHandshake;
+                }
+                Ok(_) => break ConnectionState::Connected,
+                Err(err) => {
+                    self.core.common_state.quic.alert = Some(AlertDescription::DecodeError);
+                    return UnbufferedStatus {
+                        discard,
+                        state: Err(err),
+                    };
+                }
+            };
+
+            let msg = PlainMessage {
+                typ: ContentType::Handshake,
+                version: ProtocolVersion::TLSv1_3,
+                payload: Payload::new(&incoming[..len]),
+            };
+            discard += len;
+            self.core.common_state.aligned_handshake = discard == incoming_hs.len();
+
+            let state = match mem::replace(&mut self.core.state, Err(Error::HandshakeNotComplete)) {
+                Ok(state) => state,
+                Err(e) => {
+                    self.core.state = Err(e.clone());
+                    return UnbufferedStatus {
+                        discard,
+                        state: Err(e),
+                    };
+                }
+            };
+
+            match self.core.process_msg(msg, state, None) {
+                Ok(new) => self.core.state = Ok(new),
+                Err(e) => {
+                    self.core.state = Err(e.clone());
+                    return UnbufferedStatus {
+                        discard,
+                        state: Err(e),
+                    };
+                }
+            }
+        };
+
+        UnbufferedStatus {
+            discard,
+            state: Ok(state),
+        }
+    }
+
+    /// Return the TLS-encoded transport parameters for the session's peer.
+    ///
+    /// See [`quic::ConnectionCommon::quic_transport_parameters()`].
+    ///
+    /// [`quic::ConnectionCommon::quic_transport_parameters()`]: crate::quic::ConnectionCommon::quic_transport_parameters
+    pub fn quic_transport_parameters(&self) -> Option<&[u8]> {
+        self.core
+            .common_state
+            .quic
+            .params
+            .as_deref()
+    }
+
+    /// Compute the keys for encrypting/decrypting 0-RTT packets, if available
+    pub fn zero_rtt_keys(&self) -> Option<DirectionalKeys> {
+        zero_rtt_keys(&self.core.common_state)
+    }
+
+    /// Emit the TLS description code of a fatal alert, if one has arisen.
+    ///
+    /// Check after [`Self::process_quic_handshake()`] returns an error.
+    pub fn alert(&self) -> Option<AlertDescription> {
+        self.core.common_state.quic.alert
+    }
+
+    /// The QUIC version whose key derivation labels are used for this connection.
+    pub fn quic_version(&self) -> Version {
+        self.core.common_state.quic.version
+    }
+
+    /// Change the QUIC version used for this connection.
+    ///
+    /// This must be called before [`ConnectionState::KeyChange`] yields
+    /// [`KeyChange::Handshake`].  See [`quic::ConnectionCommon::set_quic_version()`].
+    ///
+    /// [`quic::ConnectionCommon::set_quic_version()`]: crate::quic::ConnectionCommon::set_quic_version
+    pub fn set_quic_version(&mut self, version: Version) -> Result<(), Error> {
+        self.core
+            .common_state
+            .quic
+            .set_version(version)
+    }
+}
+
+impl<Data> Deref for UnbufferedConnectionCommon<Data> {
+    type Target = CommonState;
+
+    fn deref(&self) -> &Self::Target {
+        &self.core.common_state
+    }
+}
+
+impl<Data> DerefMut for UnbufferedConnectionCommon<Data> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.core.common_state
+    }
+}
+
+impl<Data> From<ConnectionCore<Data>> for UnbufferedConnectionCommon<Data> {
+    fn from(core: ConnectionCore<Data>) -> Self {
+        Self { core }
+    }
+}
+
+/// The current status of the `quic::UnbufferedConnection*`
+#[must_use]
+#[derive(Debug)]
+pub struct UnbufferedStatus<'c, Data> {
+    /// Number of bytes to discard
+    ///
+    /// After the `state` field of this object has been handled, `discard` bytes must be
+    /// removed from the *front* of the `incoming_hs` buffer that was passed to
+    /// the [`UnbufferedConnectionCommon::process_quic_handshake`] call that returned this object.
+    ///
+    /// This discard operation MUST happen *before*
+    /// [`UnbufferedConnectionCommon::process_quic_handshake`] is called again.
+    pub discard: usize,
+
+    /// The current state of the handshake process
+    ///
+    /// This value MUST be handled prior to calling
+    /// [`UnbufferedConnectionCommon::process_quic_handshake`] again. See the documentation on the
+    /// variants of [`ConnectionState`] for more details.
+    ///
+    /// On error, [`UnbufferedConnectionCommon::alert`] gives the alert to send to the peer.
+    pub state: Result<ConnectionState<'c, Data>, Error>,
+}
+
+/// The state of the QUIC [`UnbufferedConnectionCommon`] object
+#[allow(clippy::large_enum_variant)]
+#[non_exhaustive]
+pub enum ConnectionState<'c, Data> {
+    /// Handshake data must be encoded into a CRYPTO frame
+    ///
+    /// Call [`EncodeHandshakeData::encode`] and send the data at the current encryption level.
+    EncodeHandshakeData(EncodeHandshakeData<'c, Data>),
+
+    /// The keys for the next encryption level are available
+    ///
+    /// Handshake data encoded after this state must be sent at the new encryption level.
+    KeyChange(KeyChange),
+
+    /// Processing cannot continue until more handshake data is received from the peer
+    ///
+    /// Append the data to the `incoming_hs` buffer and call
+    /// [`UnbufferedConnectionCommon::process_quic_handshake`] again.
+    BlockedHandshake,
+
+    /// The handshake is complete and all handshake data has been written
+    ///
+    /// Post-handshake messages, such as session tickets, may still arrive; pass them to
+    /// [`UnbufferedConnectionCommon::process_quic_handshake`] when they do.
+    Connected,
+}
+
+impl<Data> fmt::Debug for ConnectionState<'_, Data> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::EncodeHandshakeData(..) => f
+                .debug_tuple("EncodeHandshakeData")
+                .finish(),
+            Self::KeyChange(..) => f.debug_tuple("KeyChange").finish(),
+            Self::BlockedHandshake => f
+                .debug_tuple("BlockedHandshake")
+                .finish(),
+            Self::Connected => f.debug_tuple("Connected").finish(),
+        }
+    }
+}
+
+/// A handshake message must be encoded
+pub struct EncodeHandshakeData<'c, Data> {
+    conn: &'c mut UnbufferedConnectionCommon<Data>,
+    encoded: bool,
+}
+
+impl<Data> EncodeHandshakeData<'_, Data> {
+    /// Encodes a handshake message into the `outgoing_hs` buffer
+    ///
+    /// Returns the number of bytes that were written into `outgoing_hs`, or an error if
+    /// the provided buffer is too small. In the error case, `outgoing_hs` is not modified
+    pub fn encode(&mut self, outgoing_hs: &mut [u8]) -> Result<usize, EncodeError> {
+        let hs_queue = &mut self
+            .conn
+            .core
+            .common_state
+            .quic
+            .hs_queue;
+        let required_size = match hs_queue.front() {
+            Some((_, msg)) if !self.encoded => msg.len(),
+            _ => return Err(EncodeError::AlreadyEncoded),
+        };
+
+        if required_size > outgoing_hs.len() {
+            return Err(InsufficientSizeError { required_size }.into());
+        }
+
+        if let Some((_, msg)) = hs_queue.pop_front() {
+            outgoing_hs[..required_size].copy_from_slice(&msg);
+        }
+        self.encoded = true;
+        Ok(required_size)
+    }
+}
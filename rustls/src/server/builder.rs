@@ -118,12 +118,15 @@ impl ConfigBuilder<ServerConfig, WantsServerCert> {
             session_storage: handy::ServerSessionMemoryCache::new(256),
             ticketer: Arc::new(handy::NeverProducesTickets {}),
             alpn_protocols: Vec::new(),
+            alpn_selector: None,
             versions: self.state.versions,
             key_log: Arc::new(NoKeyLog {}),
             enable_secret_extraction: false,
             max_early_data_size: 0,
+            early_data_anti_replay: None,
             send_half_rtt_data: false,
             send_tls13_tickets: 4,
+            resumption_handler: None,
         }
     }
 }
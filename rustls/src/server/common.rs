@@ -2,10 +2,11 @@ use crate::sign;
 
 use pki_types::CertificateDer;
 
-/// ActiveCertifiedKey wraps [`sign::CertifiedKey`] and tracks OSCP state in a single handshake.
+/// ActiveCertifiedKey wraps [`sign::CertifiedKey`] and tracks OSCP and SCT state in a single handshake.
 pub(super) struct ActiveCertifiedKey<'a> {
     key: &'a sign::CertifiedKey,
     ocsp: Option<&'a [u8]>,
+    sct_list: Option<&'a [u8]>,
 }
 
 impl<'a> ActiveCertifiedKey<'a> {
@@ -13,6 +14,7 @@ impl<'a> ActiveCertifiedKey<'a> {
         ActiveCertifiedKey {
             key,
             ocsp: key.ocsp.as_deref(),
+            sct_list: key.sct_list.as_deref(),
         }
     }
 
@@ -32,4 +34,9 @@ impl<'a> ActiveCertifiedKey<'a> {
     pub(super) fn get_ocsp(&self) -> Option<&[u8]> {
         self.ocsp
     }
+
+    #[inline]
+    pub(super) fn get_sct_list(&self) -> Option<&[u8]> {
+        self.sct_list
+    }
 }
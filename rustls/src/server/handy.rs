@@ -6,13 +6,15 @@ use crate::server::ClientHello;
 use crate::sign;
 use crate::webpki::{verify_server_name, ParsedCertificate};
 
-use pki_types::{DnsName, ServerName};
+use pki_types::{DnsName, ServerName, UnixTime};
 
 use alloc::string::{String, ToString};
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 use core::fmt::{Debug, Formatter};
-use std::collections::HashMap;
+use core::mem;
+use core::time::Duration;
+use std::collections::{HashMap, HashSet};
 use std::sync::Mutex;
 
 /// Something which never stores sessions.
@@ -85,6 +87,91 @@ impl Debug for ServerSessionMemoryCache {
     }
 }
 
+/// An implementer of `RecordsClientHellos` that records everything
+/// in memory.  It enforces a limit on the number of recorded ClientHellos
+/// to bound memory usage; once that is reached, early data is rejected
+/// until older records expire.
+pub struct ClientHelloMemoryRegister {
+    window: Duration,
+    limit: usize,
+    state: Mutex<StrikeRegisterState>,
+}
+
+/// ClientHellos are recorded in `current`, which is moved to `previous`
+/// after every two `window`s have elapsed.  A record therefore lasts for at
+/// least two windows, and at most four.
+///
+/// Two windows are needed because the ticket age skew accepted for early data
+/// may be up to `window` either way: a ClientHello recorded with a skew of
+/// `-window` may be replayed with an acceptable skew until two windows later.
+struct StrikeRegisterState {
+    current_since: u64,
+    current: HashSet<Vec<u8>>,
+    previous: HashSet<Vec<u8>>,
+}
+
+impl ClientHelloMemoryRegister {
+    /// Make a new ClientHelloMemoryRegister.  `window` is the tolerated
+    /// ticket age skew, with a granularity of one second; ClientHellos are
+    /// remembered for at least twice as long.  `limit` is the maximum number
+    /// of recorded ClientHellos.
+    pub fn new(window: Duration, limit: usize) -> Arc<Self> {
+        Arc::new(Self {
+            window,
+            limit,
+            state: Mutex::new(StrikeRegisterState {
+                current_since: 0,
+                current: HashSet::new(),
+                previous: HashSet::new(),
+            }),
+        })
+    }
+}
+
+impl server::RecordsClientHellos for ClientHelloMemoryRegister {
+    fn window(&self) -> Duration {
+        self.window
+    }
+
+    fn check_and_record(&self, client_hello_id: &[u8], now: UnixTime) -> bool {
+        let generation = 2 * self.window.as_secs().max(1);
+        let now = now.as_secs();
+        let mut state = self.state.lock().unwrap();
+
+        let elapsed = now.saturating_sub(state.current_since);
+        if elapsed >= generation {
+            state.previous = match elapsed >= 2 * generation {
+                true => HashSet::new(),
+                false => mem::take(&mut state.current),
+            };
+            state.current.clear();
+            state.current_since = now;
+        }
+
+        if state.current.contains(client_hello_id) || state.previous.contains(client_hello_id) {
+            return false;
+        }
+
+        if state.current.len() + state.previous.len() >= self.limit {
+            return false;
+        }
+
+        state
+            .current
+            .insert(client_hello_id.to_vec());
+        true
+    }
+}
+
+impl Debug for ClientHelloMemoryRegister {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ClientHelloMemoryRegister")
+            .field("window", &self.window)
+            .field("limit", &self.limit)
+            .finish()
+    }
+}
+
 /// Something which never produces tickets.
 #[derive(Debug)]
 pub(super) struct NeverProducesTickets {}
@@ -205,6 +292,7 @@ impl server::ResolvesServerCert for ResolvesServerCertUsingSni {
 mod tests {
     use super::*;
     use crate::server::ProducesTickets;
+    use crate::server::RecordsClientHellos;
     use crate::server::ResolvesServerCert;
     use crate::server::StoresServerSessions;
 
@@ -271,6 +359,51 @@ mod tests {
         assert!(count < 5);
     }
 
+    #[test]
+    fn test_clienthellomemoryregister_detects_replay() {
+        let r = ClientHelloMemoryRegister::new(Duration::from_secs(10), 4);
+        let now = UnixTime::since_unix_epoch(Duration::from_secs(1_000));
+        assert!(r.check_and_record(&[0x01], now));
+        assert!(!r.check_and_record(&[0x01], now));
+        assert!(r.check_and_record(&[0x02], now));
+    }
+
+    #[test]
+    fn test_clienthellomemoryregister_remembers_for_two_windows() {
+        let r = ClientHelloMemoryRegister::new(Duration::from_secs(10), 4);
+        let at = |secs| UnixTime::since_unix_epoch(Duration::from_secs(secs));
+        assert!(r.check_and_record(&[0x01], at(1_000)));
+        assert!(!r.check_and_record(&[0x01], at(1_010)));
+        assert!(!r.check_and_record(&[0x01], at(1_019)));
+        assert!(!r.check_and_record(&[0x01], at(1_020)));
+        assert!(!r.check_and_record(&[0x01], at(1_039)));
+        assert!(r.check_and_record(&[0x01], at(1_060)));
+    }
+
+    #[test]
+    fn test_clienthellomemoryregister_detects_replay_after_rotation() {
+        let r = ClientHelloMemoryRegister::new(Duration::from_secs(10), 8);
+        let at = |secs| UnixTime::since_unix_epoch(Duration::from_secs(secs));
+        assert!(r.check_and_record(&[0x01], at(1_000)));
+        // recorded late in its generation
+        assert!(r.check_and_record(&[0x02], at(1_019)));
+        // rotation
+        assert!(r.check_and_record(&[0x03], at(1_020)));
+        // a replay with the opposite skew is still within two windows
+        assert!(!r.check_and_record(&[0x02], at(1_038)));
+        // forgotten after the next rotation
+        assert!(r.check_and_record(&[0x02], at(1_040)));
+    }
+
+    #[test]
+    fn test_clienthellomemoryregister_rejects_when_full() {
+        let r = ClientHelloMemoryRegister::new(Duration::from_secs(10), 2);
+        let now = UnixTime::since_unix_epoch(Duration::from_secs(1_000));
+        assert!(r.check_and_record(&[0x01], now));
+        assert!(r.check_and_record(&[0x02], now));
+        assert!(!r.check_and_record(&[0x03], now));
+    }
+
     #[test]
     fn test_neverproducestickets_does_nothing() {
         let npt = NeverProducesTickets {};
@@ -284,7 +417,7 @@ mod tests {
     fn test_resolvesservercertusingsni_requires_sni() {
         let rscsni = ResolvesServerCertUsingSni::new();
         assert!(rscsni
-            .resolve(ClientHello::new(&None, &[], None, &[]))
+            .resolve(ClientHello::new(&None, &[], None, &[], None))
             .is_none());
     }
 
@@ -295,7 +428,7 @@ mod tests {
             .unwrap()
             .to_owned();
         assert!(rscsni
-            .resolve(ClientHello::new(&Some(name), &[], None, &[]))
+            .resolve(ClientHello::new(&Some(name), &[], None, &[], None))
             .is_none());
     }
 }
@@ -6,16 +6,18 @@ use crate::enums::{AlertDescription, HandshakeType, ProtocolVersion, SignatureSc
 use crate::error::{Error, PeerIncompatible, PeerMisbehaved};
 use crate::hash_hs::{HandshakeHash, HandshakeHashBuffer};
 #[cfg(feature = "logging")]
-use crate::log::{debug, trace};
+use crate::log::{debug, trace, warn};
+use crate::msgs::codec::Codec;
 use crate::msgs::enums::{Compression, ExtensionType};
 #[cfg(feature = "tls12")]
 use crate::msgs::handshake::SessionId;
-use crate::msgs::handshake::{ClientHelloPayload, Random, ServerExtension};
+use crate::msgs::handshake::{CertificateChain, ClientHelloPayload, Random, Sct, ServerExtension};
 use crate::msgs::handshake::{ConvertProtocolNameList, ConvertServerNameList, HandshakePayload};
 use crate::msgs::message::{Message, MessagePayload};
 use crate::msgs::persist;
-use crate::server::{ClientHello, ServerConfig};
+use crate::server::{AlpnSelection, ClientHello, ServerConfig};
 use crate::suites;
+use crate::webpki::ClientIdentity;
 use crate::SupportedCipherSuite;
 
 use super::server_conn::ServerConnectionData;
@@ -71,6 +73,7 @@ impl ExtensionProcessing {
         config: &ServerConfig,
         cx: &mut ServerContext<'_>,
         ocsp_response: &mut Option<&[u8]>,
+        sct_list: &mut Option<&[u8]>,
         hello: &ClientHelloPayload,
         resumedata: Option<&persist::ServerSessionValue>,
         extra_exts: Vec<ServerExtension>,
@@ -88,15 +91,48 @@ impl ExtensionProcessing {
                 return Err(PeerMisbehaved::OfferedEmptyApplicationProtocol.into());
             }
 
-            cx.common.alpn_protocol = our_protocols
-                .iter()
-                .find(|protocol| their_protocols.contains(&protocol.as_slice()))
-                .cloned();
+            let refuse = match &config.alpn_selector {
+                Some(selector) => {
+                    let server_name = cx
+                        .data
+                        .sni
+                        .as_ref()
+                        .map(|sni| sni.as_ref());
+                    match selector.select(server_name, &their_protocols) {
+                        AlpnSelection::Protocol(protocol) => {
+                            if !their_protocols.contains(&protocol.as_slice()) {
+                                return Err(cx.common.send_fatal_alert(
+                                    AlertDescription::InternalError,
+                                    Error::General(
+                                        "ALPN selector chose a protocol the client did not offer"
+                                            .into(),
+                                    ),
+                                ));
+                            }
+                            cx.common.alpn_protocol = Some(protocol);
+                            false
+                        }
+                        AlpnSelection::NoProtocol => {
+                            cx.common.alpn_protocol = None;
+                            false
+                        }
+                        AlpnSelection::Refuse => true,
+                    }
+                }
+                None => {
+                    cx.common.alpn_protocol = our_protocols
+                        .iter()
+                        .find(|protocol| their_protocols.contains(&protocol.as_slice()))
+                        .cloned();
+                    cx.common.alpn_protocol.is_none() && !our_protocols.is_empty()
+                }
+            };
+
             if let Some(ref selected_protocol) = cx.common.alpn_protocol {
                 debug!("Chosen ALPN protocol {:?}", selected_protocol);
                 self.exts
                     .push(ServerExtension::make_alpn(&[selected_protocol]));
-            } else if !our_protocols.is_empty() {
+            } else if refuse {
                 return Err(cx.common.send_fatal_alert(
                     AlertDescription::NoApplicationProtocol,
                     Error::NoApplicationProtocol,
@@ -109,11 +145,13 @@ impl ExtensionProcessing {
             // says: "The server MUST treat the inability to select a compatible application
             // protocol as a connection error of type 0x0178". We judge that ALPN was desired
             // (rather than some out-of-band protocol negotiation mechanism) iff any ALPN
-            // protocols were configured locally or offered by the client. This helps prevent
+            // protocols (or a selector) were configured locally or offered by the client. This helps prevent
             // successful establishment of connections between peers that can't understand
             // each other.
             if cx.common.alpn_protocol.is_none()
-                && (!our_protocols.is_empty() || maybe_their_protocols.is_some())
+                && (!our_protocols.is_empty()
+                    || config.alpn_selector.is_some()
+                    || maybe_their_protocols.is_some())
             {
                 return Err(cx.common.send_fatal_alert(
                     AlertDescription::NoApplicationProtocol,
@@ -122,7 +160,7 @@ impl ExtensionProcessing {
             }
 
             match hello.get_quic_params_extension() {
-                Some(params) => cx.common.quic.params = Some(params),
+                Some(params) => cx.common.quic.params = Some(params.to_vec()),
                 None => {
                     return Err(cx
                         .common
@@ -156,6 +194,25 @@ impl ExtensionProcessing {
             ocsp_response.take();
         }
 
+        // Likewise for the SCT list.  In TLS1.3 it is sent with the certificate.
+        if !for_resume
+            && hello
+                .find_extension(ExtensionType::SCT)
+                .is_some()
+        {
+            if !cx.common.is_tls13() {
+                if let Some(sct_list) = sct_list
+                    .take()
+                    .and_then(decode_sct_list)
+                {
+                    self.exts
+                        .push(ServerExtension::make_sct(sct_list));
+                }
+            }
+        } else {
+            sct_list.take();
+        }
+
         self.exts.extend(extra_exts);
 
         Ok(())
@@ -318,6 +375,7 @@ impl ExpectClientHello {
                 &sig_schemes,
                 client_hello.get_alpn_extension(),
                 &client_hello.cipher_suites,
+                client_hello.get_quic_params_extension(),
             );
 
             let certkey = self
@@ -441,7 +499,7 @@ impl State<ServerConnectionData> for ExpectClientHello {
 /// Note that this will modify `data.sni` even if config or certificate resolution fail.
 ///
 /// [`ResolvesServerCert`]: crate::server::ResolvesServerCert
-pub(super) fn process_client_hello<'a>(
+pub(crate) fn process_client_hello<'a>(
     m: &'a Message,
     done_retry: bool,
     cx: &mut ServerContext,
@@ -523,3 +581,44 @@ pub(crate) enum HandshakeHashOrBuffer {
     Buffer(HandshakeHashBuffer),
     Hash(HandshakeHash),
 }
+
+/// Decode a `SignedCertificateTimestampList` from [`sign::CertifiedKey::sct_list`].
+///
+/// [`sign::CertifiedKey::sct_list`]: crate::sign::CertifiedKey::sct_list
+pub(super) fn decode_sct_list(sct_list: &[u8]) -> Option<Vec<Sct>> {
+    match Vec::<Sct>::read_bytes(sct_list) {
+        Ok(sct_list) if !sct_list.is_empty() => Some(sct_list),
+        _ => {
+            warn!("Not sending invalid SCT list");
+            None
+        }
+    }
+}
+
+/// Determine the client's identity from its certificate chain, using the
+/// client certificate verifier.
+///
+/// When resuming, this checks a chain accepted on an earlier connection
+/// against the verifier's current policy: if it returns an error, the
+/// session must not be resumed.
+pub(super) fn client_identity(
+    config: &ServerConfig,
+    chain: Option<&CertificateChain>,
+) -> Result<Option<ClientIdentity>, Error> {
+    match chain.and_then(|chain| chain.first()) {
+        Some(end_entity) => config
+            .verifier
+            .client_identity(end_entity),
+        None => Ok(None),
+    }
+}
+
+/// Record the client's certificate chain and identity.
+pub(super) fn set_client_cert_chain(
+    cx: &mut ServerContext<'_>,
+    chain: Option<CertificateChain>,
+    identity: Option<ClientIdentity>,
+) {
+    cx.data.client_identity = identity;
+    cx.common.peer_certificates = chain;
+}
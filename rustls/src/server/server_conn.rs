@@ -1,12 +1,16 @@
 use crate::builder::ConfigBuilder;
 use crate::common_state::{CommonState, Context, Protocol, Side, State};
+use crate::conn::unbuffered::{EncodeError, InsufficientSizeError};
 use crate::conn::{ConnectionCommon, ConnectionCore, UnbufferedConnectionCommon};
 use crate::crypto::CryptoProvider;
-use crate::enums::{CipherSuite, ProtocolVersion, SignatureScheme};
+use crate::enums::{AlertDescription, CipherSuite, ProtocolVersion, SignatureScheme};
 use crate::error::Error;
+use crate::kernel::KernelConnection;
 #[cfg(feature = "logging")]
 use crate::log::trace;
 use crate::msgs::base::Payload;
+use crate::msgs::deframer::DeframerSliceBuffer;
+use crate::msgs::enums::AlertLevel;
 use crate::msgs::handshake::{ClientHelloPayload, ProtocolName, ServerExtension};
 use crate::msgs::message::Message;
 use crate::suites::ExtractedSecrets;
@@ -14,6 +18,7 @@ use crate::vecbuf::ChunkVecBuffer;
 use crate::verify;
 #[cfg(feature = "ring")]
 use crate::versions;
+use crate::webpki::ClientIdentity;
 use crate::KeyLog;
 #[cfg(feature = "ring")]
 use crate::WantsVerifier;
@@ -21,7 +26,7 @@ use crate::{sign, WantsVersions};
 
 use super::hs;
 
-use pki_types::DnsName;
+use pki_types::{DnsName, UnixTime};
 
 use alloc::boxed::Box;
 use alloc::sync::Arc;
@@ -30,6 +35,7 @@ use core::fmt;
 use core::fmt::{Debug, Formatter};
 use core::marker::PhantomData;
 use core::ops::{Deref, DerefMut};
+use core::time::Duration;
 use std::io;
 
 #[cfg(doc)]
@@ -106,6 +112,38 @@ pub trait ProducesTickets: Debug + Send + Sync {
     fn decrypt(&self, cipher: &[u8]) -> Option<Vec<u8>>;
 }
 
+/// A strike register: the ability to record ClientHellos that offer
+/// early data, so that replays of them can be detected.
+///
+/// This is the "ClientHello recording" anti-replay mechanism described in
+/// [RFC 8446 section 8.2].  Single-use tickets held in [`StoresServerSessions`]
+/// already prevent replay; this is what allows early data to be accepted
+/// safely with stateless tickets from a [`ProducesTickets`].
+///
+/// Servers in a fleet can share one implementation (backed by a shared
+/// cache) so that a ClientHello replayed to a different server is also
+/// detected.
+///
+/// [RFC 8446 section 8.2]: https://datatracker.ietf.org/doc/html/rfc8446#section-8.2
+pub trait RecordsClientHellos: Debug + Send + Sync {
+    /// The tolerated difference between the client's and the server's view
+    /// of the ticket age.
+    ///
+    /// Early data is only accepted if the client's view of the ticket age
+    /// is within this window of the server's view, in either direction. A
+    /// ClientHello can therefore be replayed with an acceptable ticket age
+    /// for up to twice this window after it is first seen, and must be
+    /// remembered for at least that long.
+    fn window(&self) -> Duration;
+
+    /// Record `client_hello_id`, which identifies a ClientHello.
+    ///
+    /// Return `true` if it was not already recorded within the window.
+    /// Return `false` if it was, or if it cannot be recorded for some
+    /// reason: the early data is then rejected, but the handshake continues.
+    fn check_and_record(&self, client_hello_id: &[u8], now: UnixTime) -> bool;
+}
+
 /// How to choose a certificate chain and signing key for use
 /// in server authentication.
 ///
@@ -123,12 +161,133 @@ pub trait ResolvesServerCert: Debug + Send + Sync {
     fn resolve(&self, client_hello: ClientHello) -> Option<Arc<sign::CertifiedKey>>;
 }
 
+/// How to choose an ALPN protocol for a connection, given the
+/// protocols offered by the client and the server name it indicated.
+///
+/// This is an alternative to [`ServerConfig::alpn_protocols`] for servers
+/// whose choice of protocol varies per connection, for example by tenant.
+pub trait SelectsAlpnProtocol: Debug + Send + Sync {
+    /// Choose an ALPN protocol.
+    ///
+    /// `server_name` is the SNI hostname sent by the client, if any.
+    /// `offered` is the client's list of protocols, most preferred first;
+    /// this is never empty, as this is only called if the client offered ALPN.
+    fn select(&self, server_name: Option<&str>, offered: &[&[u8]]) -> AlpnSelection;
+}
+
+/// The outcome of [`SelectsAlpnProtocol::select`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AlpnSelection {
+    /// Use this protocol.  It must be one of those offered by the client,
+    /// otherwise the handshake fails with an `internal_error` alert.
+    Protocol(Vec<u8>),
+    /// Continue the handshake without negotiating a protocol.
+    NoProtocol,
+    /// Abort the handshake with a `no_application_protocol` alert.
+    Refuse,
+}
+
+/// How to embed application state in TLS1.3 session tickets, and decide
+/// whether to resume the sessions they carry.
+///
+/// This builds on [`ServerConnection::set_resumption_data`] and
+/// [`ServerConnection::received_resumption_data`] for servers that need to
+/// attach state as tickets are issued, or to check remembered state before
+/// the resumed handshake proceeds.  For example, a QUIC server can remember
+/// the transport parameters it sent, and reject 0-RTT if they have since
+/// changed incompatibly (RFC 9000 section 7.4.1).
+///
+/// This is only used for TLS1.3 connections, both QUIC and TCP.
+pub trait HandlesResumption: Debug + Send + Sync {
+    /// Choose the application data to embed in a ticket about to be issued.
+    ///
+    /// The default set by [`ServerConnection::set_resumption_data`] is available
+    /// from [`TicketContext::resumption_data`].  The returned data must be less
+    /// than 2^15 bytes, otherwise no ticket is issued.
+    fn issue(&self, context: &TicketContext<'_>) -> Vec<u8>;
+
+    /// Decide whether to resume the session of a ticket offered by a client.
+    ///
+    /// `data` is the application data embedded in the ticket when it was issued.
+    /// This is called after the ticket has been validated, but before the
+    /// handshake commits to resuming it.
+    fn resume(&self, client_hello: &ClientHello<'_>, data: &[u8]) -> ResumptionDecision;
+}
+
+/// The outcome of [`HandlesResumption::resume`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResumptionDecision {
+    /// Resume the session, accepting early data if it is otherwise acceptable.
+    Accept,
+    /// Resume the session, but reject any early data.
+    RejectEarlyData,
+    /// Do not resume the session.  If the client offered no other acceptable
+    /// ticket, a full handshake is performed.
+    RejectResumption,
+}
+
+/// Information about a connection issuing a ticket, given to [`HandlesResumption::issue`].
+#[derive(Debug)]
+pub struct TicketContext<'a> {
+    server_name: Option<&'a str>,
+    alpn_protocol: Option<&'a [u8]>,
+    quic_transport_parameters: Option<&'a [u8]>,
+    resumption_data: &'a [u8],
+}
+
+impl<'a> TicketContext<'a> {
+    pub(super) fn new(
+        server_name: Option<&'a str>,
+        alpn_protocol: Option<&'a [u8]>,
+        quic_transport_parameters: Option<&'a [u8]>,
+        resumption_data: &'a [u8],
+    ) -> Self {
+        Self {
+            server_name,
+            alpn_protocol,
+            quic_transport_parameters,
+            resumption_data,
+        }
+    }
+
+    /// The server name indicated by the client, if any.
+    pub fn server_name(&self) -> Option<&'a str> {
+        self.server_name
+    }
+
+    /// The negotiated ALPN protocol, if any.
+    pub fn alpn_protocol(&self) -> Option<&'a [u8]> {
+        self.alpn_protocol
+    }
+
+    /// The TLS-encoded QUIC transport parameters sent by the server, if this is a
+    /// QUIC connection.
+    ///
+    /// These are the parameters given to [`quic::ServerConnection::new`] or
+    /// [`quic::Accepted::into_connection`].  A server that remembers them in the ticket can
+    /// check that they have not since changed incompatibly before accepting early data
+    /// ([RFC 9000 section 7.4.1]).
+    ///
+    /// [`quic::ServerConnection::new`]: crate::quic::ServerConnection::new
+    /// [`quic::Accepted::into_connection`]: crate::quic::Accepted::into_connection
+    /// [RFC 9000 section 7.4.1]: https://datatracker.ietf.org/doc/html/rfc9000#section-7.4.1
+    pub fn quic_transport_parameters(&self) -> Option<&'a [u8]> {
+        self.quic_transport_parameters
+    }
+
+    /// The data set by [`ServerConnection::set_resumption_data`], or empty if it was not called.
+    pub fn resumption_data(&self) -> &'a [u8] {
+        self.resumption_data
+    }
+}
+
 /// A struct representing the received Client Hello
 pub struct ClientHello<'a> {
     server_name: &'a Option<DnsName<'a>>,
     signature_schemes: &'a [SignatureScheme],
     alpn: Option<&'a Vec<ProtocolName>>,
     cipher_suites: &'a [CipherSuite],
+    quic_transport_parameters: Option<&'a [u8]>,
 }
 
 impl<'a> ClientHello<'a> {
@@ -138,6 +297,7 @@ impl<'a> ClientHello<'a> {
         signature_schemes: &'a [SignatureScheme],
         alpn: Option<&'a Vec<ProtocolName>>,
         cipher_suites: &'a [CipherSuite],
+        quic_transport_parameters: Option<&'a [u8]>,
     ) -> Self {
         trace!("sni {:?}", server_name);
         trace!("sig schemes {:?}", signature_schemes);
@@ -149,6 +309,7 @@ impl<'a> ClientHello<'a> {
             signature_schemes,
             alpn,
             cipher_suites,
+            quic_transport_parameters,
         }
     }
 
@@ -197,6 +358,14 @@ impl<'a> ClientHello<'a> {
     pub fn cipher_suites(&self) -> &[CipherSuite] {
         self.cipher_suites
     }
+
+    /// Get the TLS-encoded QUIC transport parameters submitted by the client.
+    ///
+    /// Returns `None` if the client did not include a QUIC transport parameters extension,
+    /// which is always the case outside of QUIC.
+    pub fn quic_transport_parameters(&self) -> Option<&'a [u8]> {
+        self.quic_transport_parameters
+    }
 }
 
 /// Common configuration for a set of server sessions.
@@ -213,8 +382,13 @@ impl<'a> ClientHello<'a> {
 /// * [`ServerConfig::max_fragment_size`]: the default is `None` (meaning 16kB).
 /// * [`ServerConfig::session_storage`]: the default stores 256 sessions in memory.
 /// * [`ServerConfig::alpn_protocols`]: the default is empty -- no ALPN protocol is negotiated.
+/// * [`ServerConfig::alpn_selector`]: the default is `None` -- `alpn_protocols` is used.
 /// * [`ServerConfig::key_log`]: key material is not logged.
 /// * [`ServerConfig::send_tls13_tickets`]: 4 tickets are sent.
+/// * [`ServerConfig::early_data_anti_replay`]: the default is `None` -- early data is only
+///   accepted with stateful resumption.
+/// * [`ServerConfig::resumption_handler`]: the default is `None` -- tickets carry the
+///   connection's resumption data, and are resumed if valid.
 ///
 /// [`RootCertStore`]: crate::RootCertStore
 #[derive(Debug)]
@@ -253,8 +427,16 @@ pub struct ServerConfig {
 
     /// Protocol names we support, most preferred first.
     /// If empty we don't do ALPN at all.
+    ///
+    /// This is ignored if [`ServerConfig::alpn_selector`] is set.
     pub alpn_protocols: Vec<Vec<u8>>,
 
+    /// How to choose an ALPN protocol per connection.
+    ///
+    /// If set, this is consulted instead of [`ServerConfig::alpn_protocols`]
+    /// whenever the client offers ALPN.
+    pub alpn_selector: Option<Arc<dyn SelectsAlpnProtocol>>,
+
     /// Supported protocol versions, in no particular order.
     /// The default is all supported versions.
     pub(super) versions: crate::versions::EnabledVersions,
@@ -283,6 +465,18 @@ pub struct ServerConfig {
     /// expansion in the latter case.
     pub max_early_data_size: u32,
 
+    /// How to detect replayed early data.
+    ///
+    /// If this is `None`, early data is only accepted when resuming from
+    /// [`ServerConfig::session_storage`], because the single use of each
+    /// session prevents replay; it is never accepted with tickets from
+    /// [`ServerConfig::ticketer`].
+    ///
+    /// If set, every ClientHello whose early data would be accepted is also
+    /// checked against this register, and early data is accepted with
+    /// either kind of resumption.
+    pub early_data_anti_replay: Option<Arc<dyn RecordsClientHellos>>,
+
     /// Whether the server should send "0.5RTT" data.  This means the server
     /// sends data after its first flight of handshake messages, without
     /// waiting for the client to complete the handshake.
@@ -315,6 +509,13 @@ pub struct ServerConfig {
     /// If this is 0, no tickets are sent and clients will not be able to
     /// do any resumption.
     pub send_tls13_tickets: usize,
+
+    /// How to attach application state to TLS1.3 tickets, and decide whether
+    /// to resume them.
+    ///
+    /// If this is `None`, tickets carry the data set by
+    /// [`ServerConnection::set_resumption_data`], and any valid ticket is resumed.
+    pub resumption_handler: Option<Arc<dyn HandlesResumption>>,
 }
 
 // Avoid a `Clone` bound on `C`.
@@ -328,13 +529,16 @@ impl Clone for ServerConfig {
             ticketer: Arc::clone(&self.ticketer),
             cert_resolver: Arc::clone(&self.cert_resolver),
             alpn_protocols: self.alpn_protocols.clone(),
+            alpn_selector: self.alpn_selector.clone(),
             versions: self.versions,
             verifier: Arc::clone(&self.verifier),
             key_log: Arc::clone(&self.key_log),
             enable_secret_extraction: self.enable_secret_extraction,
             max_early_data_size: self.max_early_data_size,
+            early_data_anti_replay: self.early_data_anti_replay.clone(),
             send_half_rtt_data: self.send_half_rtt_data,
             send_tls13_tickets: self.send_tls13_tickets,
+            resumption_handler: self.resumption_handler.clone(),
         }
     }
 }
@@ -422,7 +626,7 @@ impl<'a> ReadEarlyData<'a> {
     }
 }
 
-impl<'a> std::io::Read for ReadEarlyData<'a> {
+impl<'a> io::Read for ReadEarlyData<'a> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         self.early_data.read(buf)
     }
@@ -472,6 +676,22 @@ impl ServerConnection {
         self.inner.core.get_sni_str()
     }
 
+    /// Returns the identity of the client, as determined by
+    /// [`ClientCertVerifier::client_identity`](crate::server::danger::ClientCertVerifier::client_identity)
+    /// from its certificate.
+    ///
+    /// This is `None` until the client's certificate has been verified, and if the client did
+    /// not authenticate or the verifier does not determine identities.  For a
+    /// [`WebPkiClientVerifier`](crate::server::WebPkiClientVerifier), see
+    /// [`ClientIdentityPolicy`](crate::server::ClientIdentityPolicy).
+    pub fn client_identity(&self) -> Option<&ClientIdentity> {
+        self.inner
+            .core
+            .data
+            .client_identity
+            .as_ref()
+    }
+
     /// Application-controlled portion of the resumption ticket supplied by the client, if any.
     ///
     /// Recovered from the prior session's `set_resumption_data`. Integrity is guaranteed by rustls.
@@ -532,6 +752,16 @@ impl ServerConnection {
     pub fn dangerous_extract_secrets(self) -> Result<ExtractedSecrets, Error> {
         self.inner.dangerous_extract_secrets()
     }
+
+    /// Extract secrets, and keep what is needed to handle post-handshake messages.
+    ///
+    /// See [`ConnectionCommon::dangerous_into_kernel_connection()`].
+    pub fn dangerous_into_kernel_connection(
+        self,
+    ) -> Result<(ExtractedSecrets, KernelConnection<ServerConnectionData>), Error> {
+        self.inner
+            .dangerous_into_kernel_connection()
+    }
 }
 
 impl Debug for ServerConnection {
@@ -633,17 +863,27 @@ impl UnbufferedConnectionCommon<ServerConnectionData> {
 ///     let mut acceptor = Acceptor::default();
 ///     let accepted = loop {
 ///         acceptor.read_tls(&mut stream).unwrap();
-///         if let Some(accepted) = acceptor.accept().unwrap() {
-///             break accepted;
+///
+///         match acceptor.accept() {
+///             Ok(Some(accepted)) => break accepted,
+///             Ok(None) => continue,
+///             Err((e, mut alert)) => {
+///                 alert.write_all(&mut stream).unwrap();
+///                 panic!("error accepting connection: {}", e);
+///             }
 ///         }
 ///     };
 ///
 ///     // For some user-defined choose_server_config:
 ///     let config = choose_server_config(accepted.client_hello());
-///     let conn = accepted
-///         .into_connection(config)
-///         .unwrap();
-
+///     let conn = match accepted.into_connection(config) {
+///         Ok(conn) => conn,
+///         Err((e, mut alert)) => {
+///             alert.write_all(&mut stream).unwrap();
+///             panic!("error completing accepting connection: {}", e);
+///         }
+///     };
+///
 ///     // Proceed with handling the ServerConnection.
 /// }
 /// # }
@@ -657,14 +897,7 @@ impl Default for Acceptor {
     /// Return an empty Acceptor, ready to receive bytes from a new client connection.
     fn default() -> Self {
         Self {
-            inner: Some(
-                ConnectionCore::new(
-                    Box::new(Accepting),
-                    ServerConnectionData::default(),
-                    CommonState::new(Side::Server),
-                )
-                .into(),
-            ),
+            inner: Some(ConnectionCore::for_acceptor().into()),
         }
     }
 }
@@ -694,25 +927,34 @@ impl Acceptor {
     /// Returns `Ok(Some(accepted))` if the connection has been accepted. Call
     /// `accepted.into_connection()` to continue. Do not call this function again.
     ///
-    /// Returns `Err(err)` if an error occurred. Do not call this function again.
-    pub fn accept(&mut self) -> Result<Option<Accepted>, Error> {
+    /// Returns `Err((err, alert))` if an error occurred. If an alert is returned, the
+    /// caller should call [`AcceptedAlert::write()`] to send the alert to the client.
+    /// Do not call this function again.
+    pub fn accept(&mut self) -> Result<Option<Accepted>, (Error, AcceptedAlert)> {
         let mut connection = match self.inner.take() {
             Some(conn) => conn,
             None => {
-                return Err(Error::General("Acceptor polled after completion".into()));
+                return Err((
+                    Error::General("Acceptor polled after completion".into()),
+                    AcceptedAlert::empty(),
+                ));
             }
         };
 
-        let message = match connection.first_handshake_message()? {
-            Some(msg) => msg,
-            None => {
+        let message = match connection.first_handshake_message() {
+            Ok(Some(msg)) => msg,
+            Ok(None) => {
                 self.inner = Some(connection);
                 return Ok(None);
             }
+            Err(err) => return Err((err, AcceptedAlert::from(connection))),
         };
 
-        let (_, sig_schemes) =
-            hs::process_client_hello(&message, false, &mut Context::from(&mut connection))?;
+        let sig_schemes =
+            match hs::process_client_hello(&message, false, &mut Context::from(&mut connection)) {
+                Ok((_, sig_schemes)) => sig_schemes,
+                Err(err) => return Err((err, AcceptedAlert::from(connection))),
+            };
 
         Ok(Some(Accepted {
             connection,
@@ -734,13 +976,7 @@ pub struct Accepted {
 impl Accepted {
     /// Get the [`ClientHello`] for this connection.
     pub fn client_hello(&self) -> ClientHello<'_> {
-        let payload = Self::client_hello_payload(&self.message);
-        ClientHello::new(
-            &self.connection.core.data.sni,
-            &self.sig_schemes,
-            payload.get_alpn_extension(),
-            &payload.cipher_suites,
-        )
+        accepted_client_hello(&self.connection.core.data, &self.sig_schemes, &self.message)
     }
 
     /// Convert the [`Accepted`] into a [`ServerConnection`].
@@ -748,37 +984,335 @@ impl Accepted {
     /// Takes the state returned from [`Acceptor::accept()`] as well as the [`ServerConfig`] and
     /// [`sign::CertifiedKey`] that should be used for the session. Returns an error if
     /// configuration-dependent validation of the received `ClientHello` message fails.
-    pub fn into_connection(mut self, config: Arc<ServerConfig>) -> Result<ServerConnection, Error> {
+    ///
+    /// If an error is returned, the caller should call [`AcceptedAlert::write()`] to send
+    /// any alert describing the failure to the client.
+    pub fn into_connection(
+        mut self,
+        config: Arc<ServerConfig>,
+    ) -> Result<ServerConnection, (Error, AcceptedAlert)> {
+        let new = match accept_with_config(
+            config,
+            Vec::new(),
+            self.sig_schemes,
+            &self.message,
+            &mut Context::from(&mut self.connection),
+        ) {
+            Ok(new) => new,
+            Err(err) => return Err((err, AcceptedAlert::from(self.connection))),
+        };
+
+        self.connection.replace_state(new);
+        Ok(ServerConnection {
+            inner: self.connection,
+        })
+    }
+
+    /// Reject the connection by producing a fatal alert for the client.
+    ///
+    /// This is useful when the application decides not to proceed after inspecting
+    /// the [`ClientHello`], for example because of an unknown server name
+    /// ([`AlertDescription::UnrecognisedName`]) or an unacceptable ALPN offer
+    /// ([`AlertDescription::NoApplicationProtocol`]).  The caller should write the
+    /// returned [`AcceptedAlert`] to the client before closing the underlying connection.
+    pub fn into_alert(mut self, description: AlertDescription) -> AcceptedAlert {
         self.connection
-            .set_max_fragment_size(config.max_fragment_size)?;
+            .send_msg(Message::build_alert(AlertLevel::Fatal, description), false);
+        AcceptedAlert::from(self.connection)
+    }
+}
+
+/// Handle an unbuffered server-side connection before configuration is available.
+///
+/// This is the [`crate::unbuffered`] counterpart of [`Acceptor`]: rather than reading from
+/// an `io::Read`, it consumes TLS data from a caller-owned `incoming_tls` buffer.  Once the
+/// [`ClientHello`] has been received, the caller can choose a [`ServerConfig`] and obtain an
+/// [`UnbufferedServerConnection`] through [`UnbufferedAccepted::into_connection()`].
+///
+/// Create an `UnbufferedAcceptor` with [`UnbufferedAcceptor::default()`].
+pub struct UnbufferedAcceptor {
+    inner: Option<UnbufferedConnectionCommon<ServerConnectionData>>,
+}
+
+impl Default for UnbufferedAcceptor {
+    /// Return an empty `UnbufferedAcceptor`, ready to receive bytes from a new client connection.
+    fn default() -> Self {
+        Self {
+            inner: Some(ConnectionCore::for_acceptor().into()),
+        }
+    }
+}
+
+impl UnbufferedAcceptor {
+    /// Check if a `ClientHello` message has been received in `incoming_tls`.
+    ///
+    /// The returned [`UnbufferedAcceptStatus::discard`] bytes must be removed from the front
+    /// of `incoming_tls` before this function is called again, or before the `incoming_tls`
+    /// buffer is passed to the resulting [`UnbufferedServerConnection`].
+    ///
+    /// The [`UnbufferedAcceptStatus::state`] is `Ok(None)` if the complete `ClientHello` has not
+    /// yet been received.  Append more data to `incoming_tls` and call this function again.
+    ///
+    /// It is `Ok(Some(accepted))` if the connection has been accepted.  Call
+    /// `accepted.into_connection()` to continue.  Do not call this function again.
+    ///
+    /// It is `Err((err, alert))` if an error occurred.  The caller should call
+    /// [`AcceptedAlert::encode()`] to obtain any alert to send to the client.
+    /// Do not call this function again.
+    pub fn accept(&mut self, incoming_tls: &mut [u8]) -> UnbufferedAcceptStatus {
+        let mut connection = match self.inner.take() {
+            Some(conn) => conn,
+            None => {
+                return UnbufferedAcceptStatus {
+                    discard: 0,
+                    state: Err((
+                        Error::General("Acceptor polled after completion".into()),
+                        AcceptedAlert::empty(),
+                    )),
+                };
+            }
+        };
 
-        self.connection.enable_secret_extraction = config.enable_secret_extraction;
+        let mut buffer = DeframerSliceBuffer::new(incoming_tls);
+        let res = connection
+            .core
+            .first_handshake_message(&mut buffer);
+        let discard = buffer.pending_discard();
+
+        let message = match res {
+            Ok(Some(msg)) => msg,
+            Ok(None) => {
+                self.inner = Some(connection);
+                return UnbufferedAcceptStatus {
+                    discard,
+                    state: Ok(None),
+                };
+            }
+            Err(err) => {
+                return UnbufferedAcceptStatus {
+                    discard,
+                    state: Err((err, AcceptedAlert::from(connection))),
+                };
+            }
+        };
 
-        let state = hs::ExpectClientHello::new(config, Vec::new());
-        let mut cx = hs::ServerContext::from(&mut self.connection);
+        let sig_schemes =
+            match hs::process_client_hello(&message, false, &mut Context::from(&mut connection)) {
+                Ok((_, sig_schemes)) => sig_schemes,
+                Err(err) => {
+                    return UnbufferedAcceptStatus {
+                        discard,
+                        state: Err((err, AcceptedAlert::from(connection))),
+                    };
+                }
+            };
+
+        UnbufferedAcceptStatus {
+            discard,
+            state: Ok(Some(UnbufferedAccepted {
+                connection,
+                message,
+                sig_schemes,
+            })),
+        }
+    }
+}
 
-        let new = state.with_certified_key(
+/// The result of [`UnbufferedAcceptor::accept()`].
+#[must_use]
+#[derive(Debug)]
+pub struct UnbufferedAcceptStatus {
+    /// Number of bytes to discard
+    ///
+    /// After the `state` field of this object has been handled, `discard` bytes must be
+    /// removed from the *front* of the `incoming_tls` buffer that was passed to
+    /// the [`UnbufferedAcceptor::accept()`] call that returned this object.
+    pub discard: usize,
+
+    /// Whether a `ClientHello` has been accepted
+    ///
+    /// See [`UnbufferedAcceptor::accept()`] for the meaning of each variant.
+    pub state: Result<Option<UnbufferedAccepted>, (Error, AcceptedAlert)>,
+}
+
+/// Represents a `ClientHello` message received through the [`UnbufferedAcceptor`].
+///
+/// Contains the state required to resume the connection through
+/// [`UnbufferedAccepted::into_connection()`].
+pub struct UnbufferedAccepted {
+    connection: UnbufferedConnectionCommon<ServerConnectionData>,
+    message: Message,
+    sig_schemes: Vec<SignatureScheme>,
+}
+
+impl UnbufferedAccepted {
+    /// Get the [`ClientHello`] for this connection.
+    pub fn client_hello(&self) -> ClientHello<'_> {
+        accepted_client_hello(&self.connection.core.data, &self.sig_schemes, &self.message)
+    }
+
+    /// Convert the [`UnbufferedAccepted`] into an [`UnbufferedServerConnection`].
+    ///
+    /// Takes the state returned from [`UnbufferedAcceptor::accept()`] as well as the
+    /// [`ServerConfig`] that should be used for the session. Returns an error if
+    /// configuration-dependent validation of the received `ClientHello` message fails.
+    ///
+    /// On success, the handshake messages responding to the `ClientHello` are made
+    /// available through the first [`UnbufferedConnectionCommon::process_tls_records()`]
+    /// call on the returned connection.  If an error is returned, the caller should call
+    /// [`AcceptedAlert::encode()`] to obtain any alert describing the failure.
+    pub fn into_connection(
+        mut self,
+        config: Arc<ServerConfig>,
+    ) -> Result<UnbufferedServerConnection, (Error, AcceptedAlert)> {
+        let new = match accept_with_config(
+            config,
+            Vec::new(),
             self.sig_schemes,
-            Self::client_hello_payload(&self.message),
             &self.message,
-            &mut cx,
-        )?;
+            &mut Context::from(&mut self.connection),
+        ) {
+            Ok(new) => new,
+            Err(err) => return Err((err, AcceptedAlert::from(self.connection))),
+        };
 
-        self.connection.replace_state(new);
-        Ok(ServerConnection {
+        self.connection.core.state = Ok(new);
+        Ok(UnbufferedServerConnection {
             inner: self.connection,
         })
     }
 
-    fn client_hello_payload(message: &Message) -> &ClientHelloPayload {
-        match &message.payload {
-            crate::msgs::message::MessagePayload::Handshake { parsed, .. } => match &parsed.payload
-            {
-                crate::msgs::handshake::HandshakePayload::ClientHello(ch) => ch,
-                _ => unreachable!(),
-            },
+    /// Reject the connection by producing a fatal alert for the client.
+    ///
+    /// See [`Accepted::into_alert()`].
+    pub fn into_alert(mut self, description: AlertDescription) -> AcceptedAlert {
+        self.connection
+            .core
+            .common_state
+            .send_msg(Message::build_alert(AlertLevel::Fatal, description), false);
+        AcceptedAlert::from(self.connection)
+    }
+}
+
+impl Debug for UnbufferedAccepted {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UnbufferedAccepted")
+            .finish()
+    }
+}
+
+pub(crate) fn accepted_client_hello<'a>(
+    data: &'a ServerConnectionData,
+    sig_schemes: &'a [SignatureScheme],
+    message: &'a Message,
+) -> ClientHello<'a> {
+    let payload = client_hello_payload(message);
+    ClientHello::new(
+        &data.sni,
+        sig_schemes,
+        payload.get_alpn_extension(),
+        &payload.cipher_suites,
+        payload.get_quic_params_extension(),
+    )
+}
+
+/// Continue the handshake for a `ClientHello` received by an acceptor, now that
+/// `config` is known.
+///
+/// `extra_exts` are sent to the client in addition to those derived from `config`.
+pub(crate) fn accept_with_config(
+    config: Arc<ServerConfig>,
+    extra_exts: Vec<ServerExtension>,
+    sig_schemes: Vec<SignatureScheme>,
+    message: &Message,
+    cx: &mut hs::ServerContext<'_>,
+) -> Result<Box<dyn State<ServerConnectionData>>, Error> {
+    cx.common
+        .set_max_fragment_size(config.max_fragment_size)?;
+    cx.common.enable_secret_extraction = config.enable_secret_extraction;
+
+    hs::ExpectClientHello::new(config, extra_exts).with_certified_key(
+        sig_schemes,
+        client_hello_payload(message),
+        message,
+        cx,
+    )
+}
+
+fn client_hello_payload(message: &Message) -> &ClientHelloPayload {
+    match &message.payload {
+        crate::msgs::message::MessagePayload::Handshake { parsed, .. } => match &parsed.payload {
+            crate::msgs::handshake::HandshakePayload::ClientHello(ch) => ch,
             _ => unreachable!(),
+        },
+        _ => unreachable!(),
+    }
+}
+
+/// Represents a TLS alert resulting from accepting a client.
+///
+/// This is returned when [`Acceptor::accept()`] or [`Accepted::into_connection()`]
+/// fails, or by [`Accepted::into_alert()`], and likewise by their [`UnbufferedAcceptor`]
+/// counterparts.  It may be empty if there is no alert to send.
+pub struct AcceptedAlert(ChunkVecBuffer);
+
+impl AcceptedAlert {
+    pub(super) fn empty() -> Self {
+        Self(ChunkVecBuffer::new(None))
+    }
+
+    /// Send the alert to the client.
+    ///
+    /// To account for short writes this function should be called repeatedly until it
+    /// returns `Ok(0)` or an error.
+    pub fn write(&mut self, wr: &mut dyn io::Write) -> Result<usize, io::Error> {
+        self.0.write_to(wr)
+    }
+
+    /// Send the alert to the client.
+    ///
+    /// This function will invoke the writer until the buffer has been completely written.
+    pub fn write_all(&mut self, wr: &mut dyn io::Write) -> Result<(), io::Error> {
+        while self.write(wr)? != 0 {}
+        Ok(())
+    }
+
+    /// Encodes the alert into the `outgoing_tls` buffer, for transmission to the client.
+    ///
+    /// Returns the number of bytes that were written into `outgoing_tls` (zero if there
+    /// is no alert to send), or an error if the provided buffer is too small. In the
+    /// error case, `outgoing_tls` is not modified.
+    pub fn encode(&mut self, outgoing_tls: &mut [u8]) -> Result<usize, EncodeError> {
+        let required_size = self.0.len();
+        if required_size > outgoing_tls.len() {
+            return Err(InsufficientSizeError { required_size }.into());
         }
+
+        let mut written = 0;
+        while let Some(chunk) = self.0.pop() {
+            outgoing_tls[written..written + chunk.len()].copy_from_slice(&chunk);
+            written += chunk.len();
+        }
+
+        Ok(written)
+    }
+}
+
+impl From<ConnectionCommon<ServerConnectionData>> for AcceptedAlert {
+    fn from(conn: ConnectionCommon<ServerConnectionData>) -> Self {
+        Self(conn.core.common_state.sendable_tls)
+    }
+}
+
+impl From<UnbufferedConnectionCommon<ServerConnectionData>> for AcceptedAlert {
+    fn from(conn: UnbufferedConnectionCommon<ServerConnectionData>) -> Self {
+        Self(conn.core.common_state.sendable_tls)
+    }
+}
+
+impl Debug for AcceptedAlert {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AcceptedAlert").finish()
     }
 }
 
@@ -882,6 +1416,16 @@ impl ConnectionCore<ServerConnectionData> {
         ))
     }
 
+    /// Make a connection for an acceptor, which waits for the `ClientHello`
+    /// before a [`ServerConfig`] is chosen.
+    pub(crate) fn for_acceptor() -> Self {
+        Self::new(
+            Box::new(Accepting),
+            ServerConnectionData::default(),
+            CommonState::new(Side::Server),
+        )
+    }
+
     pub(crate) fn reject_early_data(&mut self) {
         assert!(
             self.common_state.is_handshaking(),
@@ -899,8 +1443,9 @@ impl ConnectionCore<ServerConnectionData> {
 #[derive(Default, Debug)]
 pub struct ServerConnectionData {
     pub(super) sni: Option<DnsName<'static>>,
-    pub(super) received_resumption_data: Option<Vec<u8>>,
-    pub(super) resumption_data: Vec<u8>,
+    pub(super) client_identity: Option<ClientIdentity>,
+    pub(crate) received_resumption_data: Option<Vec<u8>>,
+    pub(crate) resumption_data: Vec<u8>,
     pub(super) early_data: EarlyDataState,
 }
 
@@ -6,6 +6,7 @@ use crate::enums::ProtocolVersion;
 use crate::enums::{AlertDescription, ContentType, HandshakeType};
 use crate::error::{Error, PeerIncompatible, PeerMisbehaved};
 use crate::hash_hs::HandshakeHash;
+use crate::kernel::{Direction, KernelContext, KernelState};
 #[cfg(feature = "logging")]
 use crate::log::{debug, trace};
 use crate::msgs::base::Payload;
@@ -14,12 +15,13 @@ use crate::msgs::codec::Codec;
 use crate::msgs::handshake::{
     CertificateChain, ClientEcdhParams, HandshakeMessagePayload, HandshakePayload,
 };
-use crate::msgs::handshake::{NewSessionTicketPayload, SessionId};
+use crate::msgs::handshake::{NewSessionTicketPayload, NewSessionTicketPayloadTls13, SessionId};
 use crate::msgs::message::{Message, MessagePayload};
 use crate::msgs::persist;
-use crate::suites::PartiallyExtractedSecrets;
+use crate::suites::{ConnectionTrafficSecrets, PartiallyExtractedSecrets};
 use crate::tls12::{self, ConnectionSecrets, Tls12CipherSuite};
 use crate::verify;
+use crate::webpki::ClientIdentity;
 
 use super::common::ActiveCertifiedKey;
 use super::hs::{self, ServerContext};
@@ -159,10 +161,25 @@ mod client_hello {
                 .and_then(|x| persist::ServerSessionValue::read_bytes(&x).ok())
                 .filter(|resumedata| {
                     hs::can_resume(self.suite.into(), &cx.data.sni, self.using_ems, resumedata)
+                })
+                .and_then(|resumedata| {
+                    match hs::client_identity(&self.config, resumedata.client_cert_chain.as_ref()) {
+                        Ok(identity) => Some((resumedata, identity)),
+                        Err(_) => {
+                            debug!("Client certificate verifier rejected resumed client");
+                            None
+                        }
+                    }
                 });
 
-            if let Some(data) = resume_data {
-                return self.start_resumption(cx, client_hello, &client_hello.session_id, data);
+            if let Some((data, identity)) = resume_data {
+                return self.start_resumption(
+                    cx,
+                    client_hello,
+                    &client_hello.session_id,
+                    data,
+                    identity,
+                );
             }
 
             // Now we have chosen a ciphersuite, we can make kx decisions.
@@ -205,6 +222,7 @@ mod client_hello {
             debug_assert_eq!(ecpoint, ECPointFormat::Uncompressed);
 
             let mut ocsp_response = server_key.get_ocsp();
+            let mut sct_list = server_key.get_sct_list();
 
             // If we're not offered a ticket or a potential session ID, allocate a session ID.
             if !self.config.session_storage.can_cache() {
@@ -221,6 +239,7 @@ mod client_hello {
                 self.suite,
                 self.using_ems,
                 &mut ocsp_response,
+                &mut sct_list,
                 client_hello,
                 None,
                 &self.randoms,
@@ -273,6 +292,7 @@ mod client_hello {
             client_hello: &ClientHelloPayload,
             id: &SessionId,
             resumedata: persist::ServerSessionValue,
+            identity: Option<ClientIdentity>,
         ) -> hs::NextStateOrError {
             debug!("Resuming connection");
 
@@ -292,6 +312,7 @@ mod client_hello {
                 self.suite,
                 self.using_ems,
                 &mut None,
+                &mut None,
                 client_hello,
                 Some(&resumedata),
                 &self.randoms,
@@ -310,7 +331,7 @@ mod client_hello {
             );
             cx.common
                 .start_encryption_tls12(&secrets, Side::Server);
-            cx.common.peer_certificates = resumedata.client_cert_chain;
+            hs::set_client_cert_chain(cx, resumedata.client_cert_chain, identity);
 
             if self.send_ticket {
                 emit_ticket(
@@ -347,13 +368,22 @@ mod client_hello {
         suite: &'static Tls12CipherSuite,
         using_ems: bool,
         ocsp_response: &mut Option<&[u8]>,
+        sct_list: &mut Option<&[u8]>,
         hello: &ClientHelloPayload,
         resumedata: Option<&persist::ServerSessionValue>,
         randoms: &ConnectionRandoms,
         extra_exts: Vec<ServerExtension>,
     ) -> Result<bool, Error> {
         let mut ep = hs::ExtensionProcessing::new();
-        ep.process_common(config, cx, ocsp_response, hello, resumedata, extra_exts)?;
+        ep.process_common(
+            config,
+            cx,
+            ocsp_response,
+            sct_list,
+            hello,
+            resumedata,
+            extra_exts,
+        )?;
         ep.process_tls12(config, hello, using_ems);
 
         let sh = Message {
@@ -466,7 +496,7 @@ mod client_hello {
 
         let names = config
             .verifier
-            .root_hint_subjects()
+            .root_hint_subjects_for(cx.data.sni.as_ref())
             .to_vec();
 
         let cr = CertificateRequestPayload {
@@ -548,13 +578,21 @@ impl State<ServerConnectionData> for ExpectCertificate {
                 None
             }
             Some((end_entity, intermediates)) => {
-                self.config
+                let now = UnixTime::now();
+                let (verified, report) = self
+                    .config
                     .verifier
-                    .verify_client_cert(end_entity, intermediates, UnixTime::now())
-                    .map_err(|err| {
-                        cx.common
-                            .send_cert_verify_error_alert(err)
-                    })?;
+                    .verify_client_cert_with_report(
+                        end_entity,
+                        intermediates,
+                        cx.data.sni.as_ref(),
+                        now,
+                    );
+                cx.common.peer_verification_report = report;
+                verified.map_err(|err| {
+                    cx.common
+                        .send_cert_verify_error_alert(err)
+                })?;
 
                 Some(cert_chain)
             }
@@ -690,8 +728,17 @@ impl State<ServerConnectionData> for ExpectCertificateVerify {
                 .send_cert_verify_error_alert(e));
         }
 
+        let identity = match hs::client_identity(&self.config, Some(&self.client_cert)) {
+            Ok(identity) => identity,
+            Err(e) => {
+                return Err(cx
+                    .common
+                    .send_cert_verify_error_alert(e))
+            }
+        };
+
         trace!("client CertificateVerify OK");
-        cx.common.peer_certificates = Some(self.client_cert);
+        hs::set_client_cert_chain(cx, Some(self.client_cert), identity);
 
         self.transcript.add_message(&m);
         Ok(Box::new(ExpectCcs {
@@ -956,4 +1003,26 @@ impl State<ServerConnectionData> for ExpectTraffic {
         self.secrets
             .extract_secrets(Side::Server)
     }
+
+    fn into_kernel_state(self: Box<Self>) -> Result<Box<dyn KernelState + 'static>, Error> {
+        Ok(self)
+    }
+}
+
+impl KernelState for ExpectTraffic {
+    fn update_secrets(&mut self, _dir: Direction) -> Result<ConnectionTrafficSecrets, Error> {
+        Err(Error::General(
+            "TLS 1.2 connections do not support traffic secret updates".into(),
+        ))
+    }
+
+    fn handle_new_session_ticket(
+        &mut self,
+        _cx: &mut KernelContext<'_>,
+        _message: &NewSessionTicketPayloadTls13,
+    ) -> Result<(), Error> {
+        Err(Error::General(
+            "TLS 1.2 session tickets may not be sent once the handshake has completed".into(),
+        ))
+    }
 }
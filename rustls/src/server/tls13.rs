@@ -7,6 +7,7 @@ use crate::enums::ProtocolVersion;
 use crate::enums::{AlertDescription, ContentType, HandshakeType};
 use crate::error::{Error, PeerIncompatible, PeerMisbehaved};
 use crate::hash_hs::HandshakeHash;
+use crate::kernel::{Direction, KernelContext, KernelState};
 #[cfg(feature = "logging")]
 use crate::log::{debug, trace, warn};
 use crate::msgs::codec::Codec;
@@ -19,7 +20,7 @@ use crate::msgs::message::{Message, MessagePayload};
 use crate::msgs::persist;
 use crate::rand;
 use crate::server::ServerConfig;
-use crate::suites::PartiallyExtractedSecrets;
+use crate::suites::{ConnectionTrafficSecrets, PartiallyExtractedSecrets};
 use crate::tls13::construct_client_verify_message;
 use crate::tls13::construct_server_verify_message;
 use crate::tls13::key_schedule::{KeyScheduleTraffic, KeyScheduleTrafficWithClientFinishedPending};
@@ -27,7 +28,7 @@ use crate::tls13::Tls13CipherSuite;
 use crate::verify;
 
 use super::hs::{self, HandshakeHashOrBuffer, ServerContext};
-use super::server_conn::ServerConnectionData;
+use super::server_conn::{ResumptionDecision, ServerConnectionData, TicketContext};
 
 use alloc::borrow::ToOwned;
 use alloc::boxed::Box;
@@ -62,6 +63,7 @@ mod client_hello {
     use crate::msgs::handshake::ServerHelloPayload;
     use crate::msgs::handshake::SessionId;
     use crate::server::common::ActiveCertifiedKey;
+    use crate::server::ClientHello;
     use crate::sign;
     use crate::tls13::key_schedule::{
         KeyScheduleEarly, KeyScheduleHandshake, KeySchedulePreHandshake,
@@ -277,6 +279,7 @@ mod client_hello {
 
             let mut chosen_psk_index = None;
             let mut resumedata = None;
+            let mut resumed_identity = None;
 
             if let Some(psk_offer) = client_hello.get_psk() {
                 if !client_hello.check_psk_ext_is_last() {
@@ -336,8 +339,41 @@ mod client_hello {
                         ));
                     }
 
+                    let identity = match hs::client_identity(
+                        &self.config,
+                        resume.client_cert_chain.as_ref(),
+                    ) {
+                        Ok(identity) => identity,
+                        Err(_) => {
+                            debug!("Client certificate verifier rejected resumed client");
+                            continue;
+                        }
+                    };
+
+                    if let Some(handler) = &self.config.resumption_handler {
+                        let hello = ClientHello::new(
+                            &cx.data.sni,
+                            &sigschemes_ext,
+                            client_hello.get_alpn_extension(),
+                            &client_hello.cipher_suites,
+                            client_hello.get_quic_params_extension(),
+                        );
+                        match handler.resume(&hello, &resume.application_data.0) {
+                            ResumptionDecision::Accept => {}
+                            ResumptionDecision::RejectEarlyData => {
+                                debug!("Resumption handler rejected early data");
+                                cx.data.early_data.reject();
+                            }
+                            ResumptionDecision::RejectResumption => {
+                                debug!("Resumption handler rejected resumption");
+                                continue;
+                            }
+                        }
+                    }
+
                     chosen_psk_index = Some(i);
                     resumedata = Some(resume);
+                    resumed_identity = identity;
                     break;
                 }
             }
@@ -353,7 +389,7 @@ mod client_hello {
 
             if let Some(ref resume) = resumedata {
                 cx.data.received_resumption_data = Some(resume.application_data.0.clone());
-                cx.common.peer_certificates = resume.client_cert_chain.clone();
+                hs::set_client_cert_chain(cx, resume.client_cert_chain.clone(), resumed_identity);
             }
 
             let full_handshake = resumedata.is_none();
@@ -376,13 +412,16 @@ mod client_hello {
             }
 
             let mut ocsp_response = server_key.get_ocsp();
+            let mut sct_list = server_key.get_sct_list();
             let doing_early_data = emit_encrypted_extensions(
                 &mut self.transcript,
                 self.suite,
                 cx,
                 &mut ocsp_response,
+                &mut sct_list,
                 client_hello,
                 resumedata.as_ref(),
+                chosen_psk_index,
                 self.extra_exts,
                 &self.config,
             )?;
@@ -395,6 +434,7 @@ mod client_hello {
                     cx.common,
                     server_key.get_cert(),
                     ocsp_response,
+                    sct_list,
                 );
                 emit_certificate_verify_tls13(
                     &mut self.transcript,
@@ -607,6 +647,7 @@ mod client_hello {
         cx: &mut ServerContext<'_>,
         client_hello: &ClientHelloPayload,
         resumedata: Option<&persist::ServerSessionValue>,
+        chosen_psk_index: Option<usize>,
         suite: &'static Tls13CipherSuite,
         config: &ServerConfig,
     ) -> EarlyDataDecision {
@@ -625,8 +666,10 @@ mod client_hello {
         };
 
         /* Non-zero max_early_data_size controls whether early_data is allowed at all.
-         * We also require stateful resumption. */
-        let early_data_configured = config.max_early_data_size > 0 && !config.ticketer.enabled();
+         * We also require stateful resumption, unless replays are detected by recording
+         * ClientHellos (RFC8446, 8.2). */
+        let early_data_configured = config.max_early_data_size > 0
+            && (!config.ticketer.enabled() || config.early_data_anti_replay.is_some());
 
         /* "For PSKs provisioned via NewSessionTicket, a server MUST validate
          *  that the ticket age for the selected PSK identity (computed by
@@ -644,13 +687,25 @@ mod client_hello {
          *  - The selected ALPN [RFC7301] protocol, if any"
          *
          * (RFC8446, 4.2.10) */
+        let fresh = match &config.early_data_anti_replay {
+            Some(register) => {
+                let window_ms = register.window().as_millis();
+                resume.is_fresh_within(u32::try_from(window_ms).unwrap_or(u32::MAX))
+            }
+            None => resume.is_fresh(),
+        };
+
         let early_data_possible = early_data_requested
-            && resume.is_fresh()
+            && fresh
             && Some(resume.version) == cx.common.negotiated_version
             && resume.cipher_suite == suite.common.suite
             && resume.alpn.as_ref().map(|x| &x.0) == cx.common.alpn_protocol.as_ref();
 
-        if early_data_configured && early_data_possible && !cx.data.early_data.was_rejected() {
+        if early_data_configured
+            && early_data_possible
+            && !cx.data.early_data.was_rejected()
+            && !is_replay(client_hello, chosen_psk_index, config)
+        {
             EarlyDataDecision::Accepted
         } else {
             if cx.common.is_quic() {
@@ -662,20 +717,56 @@ mod client_hello {
         }
     }
 
+    /// Check the ClientHello against the strike register, if any.
+    ///
+    /// "the recommended unique identifier [for a ClientHello] is the PSK binder"
+    /// (RFC8446, 8.2)
+    fn is_replay(
+        client_hello: &ClientHelloPayload,
+        chosen_psk_index: Option<usize>,
+        config: &ServerConfig,
+    ) -> bool {
+        let register = match &config.early_data_anti_replay {
+            Some(register) => register,
+            None => return false,
+        };
+
+        let binder = client_hello
+            .get_psk()
+            .zip(chosen_psk_index)
+            .and_then(|(psk_offer, i)| psk_offer.binders.get(i));
+
+        match binder {
+            Some(binder) => !register.check_and_record(binder.as_ref(), UnixTime::now()),
+            None => true,
+        }
+    }
+
     fn emit_encrypted_extensions(
         transcript: &mut HandshakeHash,
         suite: &'static Tls13CipherSuite,
         cx: &mut ServerContext<'_>,
         ocsp_response: &mut Option<&[u8]>,
+        sct_list: &mut Option<&[u8]>,
         hello: &ClientHelloPayload,
         resumedata: Option<&persist::ServerSessionValue>,
+        chosen_psk_index: Option<usize>,
         extra_exts: Vec<ServerExtension>,
         config: &ServerConfig,
     ) -> Result<EarlyDataDecision, Error> {
         let mut ep = hs::ExtensionProcessing::new();
-        ep.process_common(config, cx, ocsp_response, hello, resumedata, extra_exts)?;
+        ep.process_common(
+            config,
+            cx,
+            ocsp_response,
+            sct_list,
+            hello,
+            resumedata,
+            extra_exts,
+        )?;
 
-        let early_data = decide_if_early_data_allowed(cx, hello, resumedata, suite, config);
+        let early_data =
+            decide_if_early_data_allowed(cx, hello, resumedata, chosen_psk_index, suite, config);
         if early_data == EarlyDataDecision::Accepted {
             ep.exts.push(ServerExtension::EarlyData);
         }
@@ -718,7 +809,7 @@ mod client_hello {
             .push(CertReqExtension::AuthorityNames(
                 config
                     .verifier
-                    .root_hint_subjects()
+                    .root_hint_subjects_for(cx.data.sni.as_ref())
                     .to_vec(),
             ));
 
@@ -741,6 +832,7 @@ mod client_hello {
         common: &mut CommonState,
         cert_chain: &[CertificateDer<'static>],
         ocsp_response: Option<&[u8]>,
+        sct_list: Option<&[u8]>,
     ) {
         let mut cert_entries = vec![];
         for cert in cert_chain {
@@ -761,6 +853,13 @@ mod client_hello {
                     .exts
                     .push(CertificateExtension::CertificateStatus(cst));
             }
+
+            // Likewise, SCTs are only sent for the leaf certificate.
+            if let Some(sct_list) = sct_list.and_then(hs::decode_sct_list) {
+                end_entity_cert
+                    .exts
+                    .push(CertificateExtension::SignedCertificateTimestamp(sct_list));
+            }
         }
 
         let cert_body = CertificatePayloadTls13::new(cert_entries);
@@ -922,13 +1021,16 @@ impl State<ServerConnectionData> for ExpectCertificate {
             Some(chain) => chain,
         };
 
-        self.config
+        let now = UnixTime::now();
+        let (verified, report) = self
+            .config
             .verifier
-            .verify_client_cert(end_entity, intermediates, UnixTime::now())
-            .map_err(|err| {
-                cx.common
-                    .send_cert_verify_error_alert(err)
-            })?;
+            .verify_client_cert_with_report(end_entity, intermediates, cx.data.sni.as_ref(), now);
+        cx.common.peer_verification_report = report;
+        verified.map_err(|err| {
+            cx.common
+                .send_cert_verify_error_alert(err)
+        })?;
 
         Ok(Box::new(ExpectCertificateVerify {
             config: self.config,
@@ -974,8 +1076,17 @@ impl State<ServerConnectionData> for ExpectCertificateVerify {
                 .send_cert_verify_error_alert(e));
         }
 
+        let identity = match hs::client_identity(&self.config, Some(&self.client_cert)) {
+            Ok(identity) => identity,
+            Err(e) => {
+                return Err(cx
+                    .common
+                    .send_cert_verify_error_alert(e))
+            }
+        };
+
         trace!("client CertificateVerify OK");
-        cx.common.peer_certificates = Some(self.client_cert);
+        hs::set_client_cert_chain(cx, Some(self.client_cert), identity);
 
         self.transcript.add_message(&m);
         Ok(Box::new(ExpectFinished {
@@ -1050,6 +1161,7 @@ fn get_server_session_value(
     key_schedule: &KeyScheduleTraffic,
     cx: &ServerContext<'_>,
     nonce: &[u8],
+    application_data: Vec<u8>,
     time_now: UnixTime,
     age_obfuscation_offset: u32,
 ) -> persist::ServerSessionValue {
@@ -1066,7 +1178,7 @@ fn get_server_session_value(
         secret.as_ref(),
         cx.common.peer_certificates.clone(),
         cx.common.alpn_protocol.clone(),
-        cx.data.resumption_data.clone(),
+        application_data,
         time_now,
         age_obfuscation_offset,
     )
@@ -1091,12 +1203,31 @@ impl ExpectFinished {
         let secure_random = config.provider.secure_random;
         let nonce = rand::random_vec(secure_random, 32)?;
         let age_add = rand::random_u32(secure_random)?;
+
+        let application_data = match &config.resumption_handler {
+            Some(handler) => {
+                let data = handler.issue(&TicketContext::new(
+                    cx.data.get_sni_str(),
+                    cx.common.alpn_protocol.as_deref(),
+                    cx.common.quic.sent_params.as_deref(),
+                    &cx.data.resumption_data,
+                ));
+                if data.len() >= 2usize.pow(15) {
+                    warn!("resumption data too large; not issuing ticket");
+                    return Ok(());
+                }
+                data
+            }
+            None => cx.data.resumption_data.clone(),
+        };
+
         let plain = get_server_session_value(
             transcript,
             suite,
             key_schedule,
             cx,
             &nonce,
+            application_data,
             UnixTime::now(),
             age_add,
         )
@@ -1125,7 +1256,7 @@ impl ExpectFinished {
         let mut payload = NewSessionTicketPayloadTls13::new(lifetime, age_add, nonce, ticket);
 
         if config.max_early_data_size > 0 {
-            if !stateless {
+            if !stateless || config.early_data_anti_replay.is_some() {
                 payload
                     .exts
                     .push(NewSessionTicketExtension::EarlyData(
@@ -1133,8 +1264,9 @@ impl ExpectFinished {
                     ));
             } else {
                 // We implement RFC8446 section 8.1: by enforcing that 0-RTT is
-                // only possible if using stateful resumption
-                warn!("early_data with stateless resumption is not allowed");
+                // only possible if using stateful resumption, or section 8.2
+                // if ClientHellos are recorded.
+                warn!("early_data with stateless resumption is not allowed without anti-replay");
             }
         }
 
@@ -1277,6 +1409,30 @@ impl State<ServerConnectionData> for ExpectTraffic {
         self.key_schedule
             .extract_secrets(Side::Server)
     }
+
+    fn into_kernel_state(self: Box<Self>) -> Result<Box<dyn KernelState + 'static>, Error> {
+        Ok(self)
+    }
+}
+
+impl KernelState for ExpectTraffic {
+    fn update_secrets(&mut self, dir: Direction) -> Result<ConnectionTrafficSecrets, Error> {
+        self.key_schedule
+            .refresh_traffic_secret(match dir {
+                Direction::Transmit => Side::Server,
+                Direction::Receive => Side::Client,
+            })
+    }
+
+    fn handle_new_session_ticket(
+        &mut self,
+        _cx: &mut KernelContext<'_>,
+        _message: &NewSessionTicketPayloadTls13,
+    ) -> Result<(), Error> {
+        Err(Error::General(
+            "TLS 1.3 session tickets are not received by servers".into(),
+        ))
+    }
 }
 
 struct ExpectQuicTraffic {
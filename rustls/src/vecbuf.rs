@@ -10,6 +10,8 @@ use std::io::Read;
 /// more complexity when reading out.
 pub(crate) struct ChunkVecBuffer {
     chunks: VecDeque<Vec<u8>>,
+    /// How many bytes of the first chunk have already been read out.
+    consumed: usize,
     limit: Option<usize>,
 }
 
@@ -17,6 +19,7 @@ impl ChunkVecBuffer {
     pub(crate) fn new(limit: Option<usize>) -> Self {
         Self {
             chunks: VecDeque::new(),
+            consumed: 0,
             limit,
         }
     }
@@ -49,7 +52,7 @@ impl ChunkVecBuffer {
         for ch in &self.chunks {
             len += ch.len();
         }
-        len
+        len - self.consumed
     }
 
     /// For a proposed append of `len` bytes, how many
@@ -86,7 +89,17 @@ impl ChunkVecBuffer {
     /// Take one of the chunks from this object.  This
     /// function panics if the object `is_empty`.
     pub(crate) fn pop(&mut self) -> Option<Vec<u8>> {
-        self.chunks.pop_front()
+        let mut chunk = self.chunks.pop_front()?;
+        chunk.drain(..self.consumed);
+        self.consumed = 0;
+        Some(chunk)
+    }
+
+    /// The unread part of the first chunk, if any.
+    pub(crate) fn chunk(&self) -> Option<&[u8]> {
+        self.chunks
+            .front()
+            .map(|chunk| &chunk[self.consumed..])
     }
 
     /// Read data out of this object, writing it into `buf`
@@ -94,10 +107,12 @@ impl ChunkVecBuffer {
     pub(crate) fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         let mut offs = 0;
 
-        while offs < buf.len() && !self.is_empty() {
-            let used = self.chunks[0]
-                .as_slice()
-                .read(&mut buf[offs..])?;
+        while let Some(mut chunk) = self.chunk() {
+            if offs == buf.len() {
+                break;
+            }
+
+            let used = chunk.read(&mut buf[offs..])?;
 
             self.consume(used);
             offs += used;
@@ -109,8 +124,11 @@ impl ChunkVecBuffer {
     #[cfg(read_buf)]
     /// Read data out of this object, writing it into `cursor`.
     pub(crate) fn read_buf(&mut self, mut cursor: core::io::BorrowedCursor<'_>) -> io::Result<()> {
-        while !self.is_empty() && cursor.capacity() > 0 {
-            let chunk = self.chunks[0].as_slice();
+        while let Some(chunk) = self.chunk() {
+            if cursor.capacity() == 0 {
+                break;
+            }
+
             let used = core::cmp::min(chunk.len(), cursor.capacity());
             cursor.append(&chunk[..used]);
             self.consume(used);
@@ -119,15 +137,18 @@ impl ChunkVecBuffer {
         Ok(())
     }
 
-    fn consume(&mut self, mut used: usize) {
-        while let Some(mut buf) = self.chunks.pop_front() {
-            if used < buf.len() {
-                buf.drain(..used);
-                self.chunks.push_front(buf);
+    /// Discard `used` bytes from the front of this object.
+    pub(crate) fn consume(&mut self, mut used: usize) {
+        while let Some(buf) = self.chunks.front() {
+            let remaining = buf.len() - self.consumed;
+            if used < remaining {
+                self.consumed += used;
                 break;
-            } else {
-                used -= buf.len();
             }
+
+            used -= remaining;
+            self.chunks.pop_front();
+            self.consumed = 0;
         }
     }
 
@@ -138,8 +159,13 @@ impl ChunkVecBuffer {
         }
 
         let mut bufs = [io::IoSlice::new(&[]); 64];
-        for (iov, chunk) in bufs.iter_mut().zip(self.chunks.iter()) {
-            *iov = io::IoSlice::new(chunk);
+        for (i, (iov, chunk)) in bufs
+            .iter_mut()
+            .zip(self.chunks.iter())
+            .enumerate()
+        {
+            let start = if i == 0 { self.consumed } else { 0 };
+            *iov = io::IoSlice::new(&chunk[start..]);
         }
         let len = cmp::min(bufs.len(), self.chunks.len());
         let used = wr.write_vectored(&bufs[..len])?;
@@ -165,6 +191,24 @@ mod tests {
         assert_eq!(buf.to_vec(), b"helloworldhe".to_vec());
     }
 
+    #[test]
+    fn partial_consume() {
+        let mut cvb = ChunkVecBuffer::new(None);
+        cvb.append(b"hello".to_vec());
+        cvb.append(b"world".to_vec());
+
+        cvb.consume(2);
+        assert_eq!(cvb.len(), 8);
+        assert_eq!(cvb.chunk(), Some(&b"llo"[..]));
+
+        let mut buf = [0u8; 4];
+        assert_eq!(cvb.read(&mut buf).unwrap(), 4);
+        assert_eq!(&buf, b"llow");
+        assert_eq!(cvb.pop(), Some(b"orld".to_vec()));
+        assert_eq!(cvb.chunk(), None);
+        assert!(cvb.is_empty());
+    }
+
     #[cfg(read_buf)]
     #[test]
     fn read_buf() {
@@ -1,13 +1,14 @@
 use alloc::vec::Vec;
 use core::fmt::Debug;
 
-use pki_types::{CertificateDer, ServerName, UnixTime};
+use pki_types::{CertificateDer, DnsName, ServerName, UnixTime};
 
 use crate::enums::SignatureScheme;
 use crate::error::{Error, InvalidMessage};
 use crate::msgs::base::PayloadU16;
 use crate::msgs::codec::{Codec, Reader};
 use crate::msgs::handshake::DistinguishedName;
+use crate::webpki::{ClientIdentity, VerificationReport};
 
 // Marker types.  These are used to bind the fact some verification
 // (certificate chain or handshake signature) has taken place into
@@ -74,20 +75,65 @@ pub trait ServerCertVerifier: Debug + Send + Sync {
     /// were sent as part of the server's [Certificate] message. It is in the
     /// same order that the server sent them and may be empty.
     ///
+    /// `ocsp_response` is the OCSP response stapled by the server, or empty.
+    ///
+    /// `scts` contains the encoded `SignedCertificateTimestamp`s the server sent in the
+    /// `signed_certificate_timestamp` extension ([RFC 6962]), and may be empty.  The server
+    /// only sends these if [`ClientConfig::enable_signed_cert_timestamps`] is set.  A verifier
+    /// that wraps another must pass them on.
+    ///
+    /// rustls calls this through [`ServerCertVerifier::verify_server_cert_with_report`].
+    ///
     /// Note that none of the certificates have been parsed yet, so it is the responsibility of
     /// the implementor to handle invalid data. It is recommended that the implementor returns
     /// [`Error::InvalidCertificate(CertificateError::BadEncoding)`] when these cases are encountered.
     ///
     /// [Certificate]: https://datatracker.ietf.org/doc/html/rfc8446#section-4.4.2
+    /// [RFC 6962]: https://datatracker.ietf.org/doc/html/rfc6962#section-3.3.1
+    /// [`ClientConfig::enable_signed_cert_timestamps`]: crate::ClientConfig::enable_signed_cert_timestamps
     fn verify_server_cert(
         &self,
         end_entity: &CertificateDer<'_>,
         intermediates: &[CertificateDer<'_>],
         server_name: &ServerName<'_>,
         ocsp_response: &[u8],
+        scts: &[&[u8]],
         now: UnixTime,
     ) -> Result<ServerCertVerified, Error>;
 
+    /// Verify the end-entity certificate `end_entity` as
+    /// [`ServerCertVerifier::verify_server_cert`] does, and report how it was verified.
+    ///
+    /// This is what rustls calls during a handshake.  The report is made available from
+    /// [`CommonState::peer_verification_report`](crate::CommonState::peer_verification_report),
+    /// whether or not verification succeeded.
+    ///
+    /// The default implementation calls [`ServerCertVerifier::verify_server_cert`] and makes
+    /// no report.  A verifier that wraps another should call this method of the wrapped
+    /// verifier, so its report is not lost.
+    fn verify_server_cert_with_report(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        scts: &[&[u8]],
+        now: UnixTime,
+    ) -> (
+        Result<ServerCertVerified, Error>,
+        Option<VerificationReport>,
+    ) {
+        let result = self.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            scts,
+            now,
+        );
+        (result, None)
+    }
+
     /// Verify a signature allegedly by the given server certificate.
     ///
     /// `message` is not hashed, and needs hashing during the verification.
@@ -183,7 +229,20 @@ pub trait ClientCertVerifier: Debug + Send + Sync {
     /// [RFC 5280 A.1]: https://www.rfc-editor.org/rfc/rfc5280#appendix-A.1
     /// [`CertificateRequest`]: https://datatracker.ietf.org/doc/html/rfc8446#section-4.3.2
     /// [`certificate_authorities`]: https://datatracker.ietf.org/doc/html/rfc8446#section-4.2.4
-    fn root_hint_subjects(&self) -> &[DistinguishedName];
+    ///
+    /// The default implementation returns the hints for a client that did not send SNI, from
+    /// [`ClientCertVerifier::root_hint_subjects_for`].
+    fn root_hint_subjects(&self) -> &[DistinguishedName] {
+        self.root_hint_subjects_for(None)
+    }
+
+    /// Returns the [`DistinguishedName`] subjects to hint to a client that asked for
+    /// `server_name` using SNI, or that did not send SNI if it is `None`.
+    ///
+    /// This is what rustls calls during a handshake, and allows the hints to depend on which
+    /// of several trust domains the client is connecting to.  See
+    /// [`ClientCertVerifier::root_hint_subjects`] for what the hints are for.
+    fn root_hint_subjects_for(&self, server_name: Option<&DnsName<'_>>) -> &[DistinguishedName];
 
     /// Verify the end-entity certificate `end_entity` is valid, acceptable,
     /// and chains to at least one of the trust anchors trusted by
@@ -199,13 +258,55 @@ pub trait ClientCertVerifier: Debug + Send + Sync {
     ///
     /// [InvalidCertificate]: Error#variant.InvalidCertificate
     /// [BadEncoding]: crate::CertificateError#variant.BadEncoding
+    ///
+    /// The default implementation verifies the certificate for a client that did not send SNI,
+    /// with [`ClientCertVerifier::verify_client_cert_for`].
     fn verify_client_cert(
         &self,
         end_entity: &CertificateDer<'_>,
         intermediates: &[CertificateDer<'_>],
         now: UnixTime,
+    ) -> Result<ClientCertVerified, Error> {
+        self.verify_client_cert_for(end_entity, intermediates, None, now)
+    }
+
+    /// Verify the end-entity certificate `end_entity` as [`ClientCertVerifier::verify_client_cert`]
+    /// describes, for a client that asked for `server_name` using SNI, or that did not send SNI
+    /// if it is `None`.
+    ///
+    /// rustls calls this through [`ClientCertVerifier::verify_client_cert_with_report`].
+    fn verify_client_cert_for(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: Option<&DnsName<'_>>,
+        now: UnixTime,
     ) -> Result<ClientCertVerified, Error>;
 
+    /// Verify the end-entity certificate `end_entity` as
+    /// [`ClientCertVerifier::verify_client_cert_for`] does, and report how it was verified.
+    ///
+    /// This is what rustls calls during a handshake.  The report is made available from
+    /// [`CommonState::peer_verification_report`](crate::CommonState::peer_verification_report),
+    /// whether or not verification succeeded.
+    ///
+    /// The default implementation calls [`ClientCertVerifier::verify_client_cert_for`] and makes
+    /// no report.  A verifier that wraps another should call this method of the wrapped
+    /// verifier, so its report is not lost.
+    fn verify_client_cert_with_report(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: Option<&DnsName<'_>>,
+        now: UnixTime,
+    ) -> (
+        Result<ClientCertVerified, Error>,
+        Option<VerificationReport>,
+    ) {
+        let result = self.verify_client_cert_for(end_entity, intermediates, server_name, now);
+        (result, None)
+    }
+
     /// Verify a signature allegedly by the given client certificate.
     ///
     /// `message` is not hashed, and needs hashing during the verification.
@@ -249,6 +350,24 @@ pub trait ClientCertVerifier: Debug + Send + Sync {
     ///
     /// This should be in priority order, with the most preferred first.
     fn supported_verify_schemes(&self) -> Vec<SignatureScheme>;
+
+    /// Return the identity of the client with the end-entity certificate
+    /// `end_entity`.
+    ///
+    /// This is called once the certificate has been accepted by
+    /// [`ClientCertVerifier::verify_client_cert`], and when resuming a session
+    /// in which it was.  The result is available from
+    /// [`ServerConnection::client_identity`](crate::server::ServerConnection::client_identity).
+    ///
+    /// Return an error if `end_entity` is not acceptable to the verifier's
+    /// current policy.  A session in which it was accepted is then not
+    /// resumed, and a full handshake is done instead.
+    ///
+    /// Return `Ok(None)` if the verifier has no identity policy.
+    fn client_identity(
+        &self,
+        end_entity: &CertificateDer<'_>,
+    ) -> Result<Option<ClientIdentity>, Error>;
 }
 
 /// Turns off client authentication. In contrast to using
@@ -263,14 +382,15 @@ impl ClientCertVerifier for NoClientAuth {
         false
     }
 
-    fn root_hint_subjects(&self) -> &[DistinguishedName] {
+    fn root_hint_subjects_for(&self, _server_name: Option<&DnsName<'_>>) -> &[DistinguishedName] {
         unimplemented!();
     }
 
-    fn verify_client_cert(
+    fn verify_client_cert_for(
         &self,
         _end_entity: &CertificateDer<'_>,
         _intermediates: &[CertificateDer<'_>],
+        _server_name: Option<&DnsName<'_>>,
         _now: UnixTime,
     ) -> Result<ClientCertVerified, Error> {
         unimplemented!();
@@ -297,6 +417,13 @@ impl ClientCertVerifier for NoClientAuth {
     fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
         unimplemented!();
     }
+
+    fn client_identity(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+    ) -> Result<Option<ClientIdentity>, Error> {
+        unimplemented!();
+    }
 }
 
 /// This type combines a [`SignatureScheme`] and a signature payload produced with that scheme.
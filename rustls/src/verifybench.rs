@@ -226,6 +226,7 @@ impl Context {
                     intermediates,
                     &server_name,
                     OCSP_RESPONSE,
+                    &[],
                     self.now,
                 )
                 .unwrap();
@@ -1,18 +1,21 @@
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 
-use pki_types::{CertificateDer, CertificateRevocationListDer, UnixTime};
+use pki_types::{CertificateDer, CertificateRevocationListDer, DnsName, UnixTime};
 use webpki::{CertRevocationList, RevocationCheckDepth, UnknownStatusPolicy};
 
-use super::{pki_error, VerifierBuilderError};
+use super::VerifierBuilderError;
 use crate::crypto::{CryptoProvider, WebPkiSupportedAlgorithms};
 use crate::verify::{
     ClientCertVerified, ClientCertVerifier, DigitallySignedStruct, HandshakeSignatureValid,
     NoClientAuth,
 };
+use crate::webpki::crl_store::CrlStore;
+use crate::webpki::identity::{ClientIdentity, ClientIdentityPolicy};
 use crate::webpki::parse_crls;
+use crate::webpki::report::{ChainVerification, VerificationReport};
 use crate::webpki::verify::{verify_tls12_signature, verify_tls13_signature, ParsedCertificate};
-use crate::{DistinguishedName, Error, RootCertStore, SignatureScheme};
+use crate::{CertificateError, DistinguishedName, Error, RootCertStore, SignatureScheme};
 
 #[cfg(doc)]
 use crate::crypto;
@@ -29,9 +32,12 @@ pub struct ClientCertVerifierBuilder {
     roots: Arc<RootCertStore>,
     root_hint_subjects: Vec<DistinguishedName>,
     crls: Vec<CertificateRevocationListDer<'static>>,
+    crl_store: Option<CrlStore>,
+    identity_policy: Option<ClientIdentityPolicy>,
     revocation_check_depth: RevocationCheckDepth,
     unknown_revocation_policy: UnknownStatusPolicy,
     anon_policy: AnonymousClientPolicy,
+    reports: bool,
     supported_algs: WebPkiSupportedAlgorithms,
 }
 
@@ -44,9 +50,12 @@ impl ClientCertVerifierBuilder {
             root_hint_subjects: roots.subjects(),
             roots,
             crls: Vec::new(),
+            crl_store: None,
+            identity_policy: None,
             anon_policy: AnonymousClientPolicy::Deny,
             revocation_check_depth: RevocationCheckDepth::Chain,
             unknown_revocation_policy: UnknownStatusPolicy::Deny,
+            reports: false,
             supported_algs,
         }
     }
@@ -103,6 +112,28 @@ impl ClientCertVerifierBuilder {
         self
     }
 
+    /// Verify the revocation state of presented client certificates against the CRLs in `store`,
+    /// in addition to any provided with [`with_crls`][Self::with_crls].
+    ///
+    /// The CRLs in `store` can be replaced after the verifier is built; each verification uses
+    /// the CRLs current at that time. While `store` holds no current CRLs, the revocation status
+    /// of every certificate is unknown: see
+    /// [`allow_unknown_revocation_status`][Self::allow_unknown_revocation_status].
+    pub fn with_crl_store(mut self, store: CrlStore) -> Self {
+        self.crl_store = Some(store);
+        self
+    }
+
+    /// Only accept clients whose certificates meet `policy`, once their certificate chain has
+    /// been verified.
+    ///
+    /// The client's identity under `policy` is then available from
+    /// [`ServerConnection::client_identity`](crate::server::ServerConnection::client_identity).
+    pub fn with_identity_policy(mut self, policy: ClientIdentityPolicy) -> Self {
+        self.identity_policy = Some(policy);
+        self
+    }
+
     /// Only check the end entity certificate revocation status when using CRLs.
     ///
     /// If CRLs are provided using [`with_crls`][Self::with_crls] only check the end entity
@@ -139,6 +170,17 @@ impl ClientCertVerifierBuilder {
         self
     }
 
+    /// Make a [`VerificationReport`] for each client certificate chain verified during a
+    /// handshake, available from
+    /// [`CommonState::peer_verification_report`](crate::CommonState::peer_verification_report).
+    ///
+    /// Making a report copies the verified chain, and examines candidate paths when verification
+    /// fails, so is slower.  [`WebPkiClientVerifier::verify_with_report`] makes a report regardless of this setting.
+    pub fn with_verification_reports(mut self) -> Self {
+        self.reports = true;
+        self
+    }
+
     /// Build a client certificate verifier. The built verifier will be used for the server to offer
     /// client certificate authentication, to control how offered client certificates are validated,
     /// and to determine what to do with anonymous clients that do not respond to the client
@@ -160,15 +202,20 @@ impl ClientCertVerifierBuilder {
             return Err(VerifierBuilderError::NoRootAnchors);
         }
 
-        Ok(Arc::new(WebPkiClientVerifier::new(
-            self.roots,
-            self.root_hint_subjects,
-            parse_crls(self.crls)?,
-            self.revocation_check_depth,
-            self.unknown_revocation_policy,
-            self.anon_policy,
-            self.supported_algs,
-        )))
+        Ok(Arc::new(
+            WebPkiClientVerifier::new(
+                self.roots,
+                self.root_hint_subjects,
+                parse_crls(self.crls)?,
+                self.revocation_check_depth,
+                self.unknown_revocation_policy,
+                self.anon_policy,
+                self.supported_algs,
+            )
+            .with_crl_store(self.crl_store)
+            .with_identity_policy(self.identity_policy)
+            .with_reports(self.reports),
+        ))
     }
 }
 
@@ -235,9 +282,12 @@ pub struct WebPkiClientVerifier {
     roots: Arc<RootCertStore>,
     root_hint_subjects: Vec<DistinguishedName>,
     crls: Vec<CertRevocationList<'static>>,
+    crl_store: Option<CrlStore>,
+    identity_policy: Option<ClientIdentityPolicy>,
     revocation_check_depth: RevocationCheckDepth,
     unknown_revocation_policy: UnknownStatusPolicy,
     anonymous_policy: AnonymousClientPolicy,
+    reports: bool,
     supported_algs: WebPkiSupportedAlgorithms,
 }
 
@@ -311,41 +361,71 @@ impl WebPkiClientVerifier {
             roots,
             root_hint_subjects,
             crls,
+            crl_store: None,
+            identity_policy: None,
             revocation_check_depth,
             unknown_revocation_policy,
             anonymous_policy,
+            reports: false,
             supported_algs,
         }
     }
-}
 
-impl ClientCertVerifier for WebPkiClientVerifier {
-    fn offer_client_auth(&self) -> bool {
-        true
+    fn with_crl_store(mut self, store: Option<CrlStore>) -> Self {
+        self.crl_store = store;
+        self
     }
 
-    fn client_auth_mandatory(&self) -> bool {
-        match self.anonymous_policy {
-            AnonymousClientPolicy::Allow => false,
-            AnonymousClientPolicy::Deny => true,
-        }
+    pub(crate) fn with_identity_policy(mut self, policy: Option<ClientIdentityPolicy>) -> Self {
+        self.identity_policy = policy;
+        self
     }
 
-    fn root_hint_subjects(&self) -> &[DistinguishedName] {
-        &self.root_hint_subjects
+    fn with_reports(mut self, reports: bool) -> Self {
+        self.reports = reports;
+        self
     }
 
-    fn verify_client_cert(
+    /// Verify a client certificate chain as [`ClientCertVerifier::verify_client_cert`] does,
+    /// and report how it was verified.
+    ///
+    /// The outcome of verification is in [`VerificationReport::result`].
+    pub fn verify_with_report(
         &self,
         end_entity: &CertificateDer<'_>,
         intermediates: &[CertificateDer<'_>],
         now: UnixTime,
-    ) -> Result<ClientCertVerified, Error> {
+    ) -> VerificationReport {
+        let mut report = VerificationReport::new();
+        report.result = self.verify(end_entity, intermediates, now, Some(&mut report));
+        report
+    }
+
+    fn verify(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        now: UnixTime,
+        report: Option<&mut VerificationReport>,
+    ) -> Result<(), Error> {
         let cert = ParsedCertificate::try_from(end_entity)?;
 
-        let crl_refs = self.crls.iter().collect::<Vec<_>>();
+        let crl_set = self
+            .crl_store
+            .as_ref()
+            .map(CrlStore::snapshot);
+        let crl_refs = self
+            .crls
+            .iter()
+            .chain(
+                crl_set
+                    .iter()
+                    .flat_map(|set| set.current(now)),
+            )
+            .collect::<Vec<_>>();
+        let no_current_crls = self.crl_store.is_some() && crl_refs.is_empty();
 
-        let revocation = if self.crls.is_empty() {
+        let revocation = if crl_refs.is_empty() {
             None
         } else {
             Some(
@@ -359,18 +439,76 @@ impl ClientCertVerifier for WebPkiClientVerifier {
             )
         };
 
-        cert.0
-            .verify_for_usage(
-                self.supported_algs.all,
-                &self.roots.roots,
-                intermediates,
-                now,
-                webpki::KeyUsage::client_auth(),
-                revocation,
-                None,
-            )
-            .map_err(pki_error)
-            .map(|_| ClientCertVerified::assertion())
+        ChainVerification {
+            roots: &self.roots,
+            crls: &crl_refs,
+            revocation,
+            revocation_check_depth: self.revocation_check_depth,
+            supported_algs: self.supported_algs.all,
+            usage: webpki::KeyUsage::client_auth(),
+        }
+        .verify(end_entity, &cert, intermediates, now, report)?;
+
+        if no_current_crls && self.unknown_revocation_policy == UnknownStatusPolicy::Deny {
+            return Err(CertificateError::UnknownRevocationStatus.into());
+        }
+
+        if let Some(policy) = &self.identity_policy {
+            policy.verify(end_entity)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ClientCertVerifier for WebPkiClientVerifier {
+    fn offer_client_auth(&self) -> bool {
+        true
+    }
+
+    fn client_auth_mandatory(&self) -> bool {
+        match self.anonymous_policy {
+            AnonymousClientPolicy::Allow => false,
+            AnonymousClientPolicy::Deny => true,
+        }
+    }
+
+    fn root_hint_subjects_for(&self, _server_name: Option<&DnsName<'_>>) -> &[DistinguishedName] {
+        &self.root_hint_subjects
+    }
+
+    fn verify_client_cert_for(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        _server_name: Option<&DnsName<'_>>,
+        now: UnixTime,
+    ) -> Result<ClientCertVerified, Error> {
+        self.verify(end_entity, intermediates, now, None)?;
+        Ok(ClientCertVerified::assertion())
+    }
+
+    fn verify_client_cert_with_report(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: Option<&DnsName<'_>>,
+        now: UnixTime,
+    ) -> (
+        Result<ClientCertVerified, Error>,
+        Option<VerificationReport>,
+    ) {
+        if !self.reports {
+            let result = self.verify_client_cert_for(end_entity, intermediates, server_name, now);
+            return (result, None);
+        }
+
+        let report = self.verify_with_report(end_entity, intermediates, now);
+        let result = report
+            .result
+            .clone()
+            .map(|()| ClientCertVerified::assertion());
+        (result, Some(report))
     }
 
     fn verify_tls12_signature(
@@ -394,6 +532,16 @@ impl ClientCertVerifier for WebPkiClientVerifier {
     fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
         self.supported_algs.supported_schemes()
     }
+
+    fn client_identity(
+        &self,
+        end_entity: &CertificateDer<'_>,
+    ) -> Result<Option<ClientIdentity>, Error> {
+        match &self.identity_policy {
+            Some(policy) => policy.verify(end_entity),
+            None => Ok(None),
+        }
+    }
 }
 
 /// Controls how the [WebPkiClientVerifier] handles anonymous clients.
@@ -408,10 +556,10 @@ pub(crate) enum AnonymousClientPolicy {
 #[cfg(all(test, feature = "ring"))]
 mod tests {
     use super::WebPkiClientVerifier;
-    use crate::server::VerifierBuilderError;
-    use crate::RootCertStore;
+    use crate::server::{CrlStore, VerifierBuilderError};
+    use crate::{CertificateError, RootCertStore};
 
-    use pki_types::{CertificateDer, CertificateRevocationListDer};
+    use pki_types::{CertificateDer, CertificateRevocationListDer, UnixTime};
 
     use std::sync::Arc;
 
@@ -434,6 +582,12 @@ mod tests {
         ])
     }
 
+    fn load_certs(pem: &[u8]) -> Vec<CertificateDer<'static>> {
+        rustls_pemfile::certs(&mut &pem[..])
+            .map(|cert| cert.unwrap())
+            .collect()
+    }
+
     fn load_roots(roots_der: &[&[u8]]) -> Arc<RootCertStore> {
         let mut roots = RootCertStore::empty();
         roots_der.iter().for_each(|der| {
@@ -570,6 +724,45 @@ mod tests {
         builder.build().unwrap();
     }
 
+    #[test]
+    fn test_client_verifier_with_crl_store() {
+        let store = CrlStore::new();
+        let verifier = WebPkiClientVerifier::builder(test_roots())
+            .with_crl_store(store.clone())
+            .only_check_end_entity_revocation()
+            .build()
+            .unwrap();
+
+        let client = load_certs(include_bytes!("../../../test-ca/rsa/client.cert")).remove(0);
+        let intermediates = load_certs(include_bytes!("../../../test-ca/rsa/inter.cert"));
+        // between thisUpdate and nextUpdate of the test CRLs
+        let now = UnixTime::since_unix_epoch(core::time::Duration::from_secs(1_698_100_000));
+        let verify = || {
+            verifier
+                .verify_client_cert(&client, &intermediates, now)
+                .map(|_| ())
+        };
+
+        assert_eq!(
+            verify(),
+            Err(CertificateError::UnknownRevocationStatus.into())
+        );
+
+        store.replace(
+            load_crls(&[include_bytes!(
+                "../../../test-ca/rsa/client.revoked.crl.pem"
+            )]),
+            now,
+        );
+        assert_eq!(verify(), Err(CertificateError::Revoked.into()));
+
+        store.replace(
+            load_crls(&[include_bytes!("../../../test-ca/rsa/end.revoked.crl.pem")]),
+            now,
+        );
+        assert_eq!(verify(), Ok(()));
+    }
+
     #[test]
     fn test_builder_no_roots() {
         // Trying to create a client verifier builder with no trust anchors should fail at build time
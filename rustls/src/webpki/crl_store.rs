@@ -0,0 +1,479 @@
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use pki_types::pem::PemObject;
+use pki_types::{CertificateRevocationListDer, UnixTime};
+use webpki::{CertRevocationList, OwnedCertRevocationList};
+
+use super::crl_error;
+use crate::error::CertRevocationListError;
+use crate::x509::read_time;
+use crate::DistinguishedName;
+
+#[cfg(doc)]
+use crate::client::ServerCertVerifierBuilder;
+#[cfg(doc)]
+use crate::server::ClientCertVerifierBuilder;
+
+/// A shared set of certificate revocation lists (CRLs) that can be changed
+/// while in use.
+///
+/// Give a `CrlStore` to [`ServerCertVerifierBuilder::with_crl_store`] or
+/// [`ClientCertVerifierBuilder::with_crl_store`], and keep a clone of it to
+/// update the CRLs later: for example, when a periodic refresh fetches new
+/// ones.  Clones refer to the same set, and each update is atomic, so every
+/// certificate verification sees either the old set or the new one.
+///
+/// CRLs whose `nextUpdate` time has passed are not accepted into the store,
+/// and CRLs in the store are not used once their `nextUpdate` time passes.
+/// CRLs without a `nextUpdate` time (which RFC 5280 requires issuers to
+/// include) are rejected with [`CertRevocationListError::MissingNextUpdate`],
+/// rather than being kept forever.
+/// A verifier with a `CrlStore` treats the revocation status of every
+/// certificate as unknown while the store has no current CRLs.
+#[derive(Clone, Debug, Default)]
+pub struct CrlStore {
+    current: Arc<RwLock<Arc<CrlSet>>>,
+}
+
+impl CrlStore {
+    /// Make an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace all the CRLs in the store with `crls`.
+    ///
+    /// CRLs that cannot be parsed, have no `nextUpdate` time, or have expired
+    /// at `now`, are rejected.  The store then contains exactly the CRLs that
+    /// were applied, even if some were rejected.
+    pub fn replace(
+        &self,
+        crls: impl IntoIterator<Item = CertificateRevocationListDer<'static>>,
+        now: UnixTime,
+    ) -> CrlUpdate {
+        let candidates = crls
+            .into_iter()
+            .enumerate()
+            .map(|(i, der)| (CrlSource::Index(i), Ok(der)));
+        self.update(false, candidates, now)
+    }
+
+    /// Add `crls` to the store.
+    ///
+    /// Each CRL that is applied replaces any CRL already in the store with the
+    /// same issuer and issuing distribution point.  CRLs that cannot be parsed,
+    /// have no `nextUpdate` time, or have expired at `now`, are rejected.
+    pub fn add(
+        &self,
+        crls: impl IntoIterator<Item = CertificateRevocationListDer<'static>>,
+        now: UnixTime,
+    ) -> CrlUpdate {
+        let candidates = crls
+            .into_iter()
+            .enumerate()
+            .map(|(i, der)| (CrlSource::Index(i), Ok(der)));
+        self.update(true, candidates, now)
+    }
+
+    /// Replace all the CRLs in the store with those in the files in `dir`.
+    ///
+    /// Each file may contain one DER-encoded CRL, or any number of
+    /// PEM-encoded `X509 CRL` sections.  Subdirectories are ignored.  Files
+    /// are read in name order; CRLs are then applied as by [`Self::replace`].
+    ///
+    /// The store is not changed if `dir` or a file in it cannot be read.
+    pub fn replace_from_dir(&self, dir: impl AsRef<Path>, now: UnixTime) -> io::Result<CrlUpdate> {
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                paths.push(entry.path());
+            }
+        }
+        paths.sort();
+
+        let mut candidates = Vec::new();
+        for path in paths {
+            let contents = fs::read(&path)?;
+            if !contents
+                .windows(PEM_BEGIN.len())
+                .any(|w| w == PEM_BEGIN)
+            {
+                candidates.push((CrlSource::File(path), Ok(contents.into())));
+                continue;
+            }
+
+            for crl in CertificateRevocationListDer::pem_slice_iter(&contents) {
+                let crl = crl.map_err(|_| CertRevocationListError::ParseError);
+                candidates.push((CrlSource::File(path.clone()), crl));
+            }
+        }
+
+        Ok(self.update(false, candidates, now))
+    }
+
+    /// The number of CRLs in the store, including any that have expired since
+    /// they were applied.
+    pub fn len(&self) -> usize {
+        self.snapshot().0.len()
+    }
+
+    /// Returns true if the store contains no CRLs.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The current set of CRLs, which does not change while it is held.
+    pub(crate) fn snapshot(&self) -> Arc<CrlSet> {
+        match self.current.read() {
+            Ok(current) => Arc::clone(&current),
+            Err(poisoned) => Arc::clone(&poisoned.into_inner()),
+        }
+    }
+
+    fn update(
+        &self,
+        keep_existing: bool,
+        candidates: impl IntoIterator<
+            Item = (
+                CrlSource,
+                Result<CertificateRevocationListDer<'static>, CertRevocationListError>,
+            ),
+        >,
+        now: UnixTime,
+    ) -> CrlUpdate {
+        let mut current = match self.current.write() {
+            Ok(current) => current,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let mut set = match keep_existing {
+            true => current.as_ref().clone(),
+            false => CrlSet::default(),
+        };
+
+        let mut report = CrlUpdate::default();
+        for (source, der) in candidates {
+            let mut info = CrlInfo {
+                source,
+                issuer: None,
+                next_update: None,
+            };
+
+            let parsed = der.and_then(|der| {
+                // Checked before parsing, since webpki cannot parse a CRL
+                // without `nextUpdate` and would report it as malformed.
+                let next_update = next_update(&der)
+                    .ok_or(CertRevocationListError::ParseError)?
+                    .ok_or(CertRevocationListError::MissingNextUpdate)?;
+                let crl = OwnedCertRevocationList::from_der(der.as_ref()).map_err(crl_error)?;
+                Ok((CertRevocationList::from(crl), next_update))
+            });
+
+            match parsed {
+                Ok((crl, next_update)) => {
+                    info.issuer = Some(DistinguishedName::in_sequence(crl.issuer()));
+                    info.next_update = Some(next_update);
+                    match next_update > now {
+                        true => {
+                            set.insert(StoredCrl { crl, next_update });
+                            report.applied.push(info);
+                        }
+                        false => report
+                            .rejected
+                            .push((info, CertRevocationListError::Expired)),
+                    }
+                }
+                Err(err) => report.rejected.push((info, err)),
+            }
+        }
+
+        *current = Arc::new(set);
+        report
+    }
+}
+
+/// What happened to each CRL given to a [`CrlStore`] update.
+#[derive(Clone, Debug, Default)]
+pub struct CrlUpdate {
+    /// The CRLs that were applied to the store, in the order given.
+    pub applied: Vec<CrlInfo>,
+    /// The CRLs that were not applied, in the order given, and why.
+    pub rejected: Vec<(CrlInfo, CertRevocationListError)>,
+}
+
+/// Describes a CRL given to a [`CrlStore`].
+#[derive(Clone, Debug)]
+pub struct CrlInfo {
+    /// Where the CRL came from.
+    pub source: CrlSource,
+    /// The issuer of the CRL, if it could be parsed.
+    pub issuer: Option<DistinguishedName>,
+    /// The `nextUpdate` time of the CRL, if it could be parsed and has one.
+    pub next_update: Option<UnixTime>,
+}
+
+/// Where a CRL given to a [`CrlStore`] came from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CrlSource {
+    /// The CRL at this position among those passed to [`CrlStore::replace`]
+    /// or [`CrlStore::add`].
+    Index(usize),
+    /// The CRL was read from this file by [`CrlStore::replace_from_dir`].
+    File(PathBuf),
+}
+
+/// An immutable set of CRLs, shared by a [`CrlStore`] with the verifications
+/// in progress.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct CrlSet(Vec<Arc<StoredCrl>>);
+
+impl CrlSet {
+    /// The CRLs in the set that are current at `now`.
+    pub(crate) fn current(
+        &self,
+        now: UnixTime,
+    ) -> impl Iterator<Item = &CertRevocationList<'static>> {
+        self.0
+            .iter()
+            .filter(move |stored| stored.next_update > now)
+            .map(|stored| &stored.crl)
+    }
+
+    fn insert(&mut self, crl: StoredCrl) {
+        self.0.retain(|existing| {
+            existing.crl.issuer() != crl.crl.issuer()
+                || existing
+                    .crl
+                    .issuing_distribution_point()
+                    != crl.crl.issuing_distribution_point()
+        });
+        self.0.push(Arc::new(crl));
+    }
+}
+
+#[derive(Debug)]
+struct StoredCrl {
+    crl: CertRevocationList<'static>,
+    next_update: UnixTime,
+}
+
+/// Read the optional `nextUpdate` time of a CRL.
+///
+/// Returns `None` if the CRL is malformed, and `Some(None)` if it has no
+/// `nextUpdate`.
+fn next_update(der: &[u8]) -> Option<Option<UnixTime>> {
+    yasna::parse_der(der, |reader| {
+        reader.read_sequence(|reader| {
+            let next_update = reader.next().read_sequence(|reader| {
+                reader.read_optional(|reader| reader.read_u8())?;
+                // signature, issuer, thisUpdate
+                reader.next().read_der()?;
+                reader.next().read_der()?;
+                read_time(reader.next())?;
+                let next_update = reader.read_optional(read_time)?;
+                // revokedCertificates, crlExtensions
+                while reader
+                    .read_optional(|reader| reader.read_der())?
+                    .is_some()
+                {}
+                Ok(next_update)
+            })?;
+            // signatureAlgorithm, signatureValue
+            reader.next().read_der()?;
+            reader.next().read_der()?;
+            Ok(next_update)
+        })
+    })
+    .ok()
+}
+
+const PEM_BEGIN: &[u8] = b"-----BEGIN";
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+    use std::prelude::v1::*;
+    use std::{env, fs, process};
+
+    use pki_types::{CertificateRevocationListDer, UnixTime};
+
+    use super::{CrlSource, CrlStore};
+    use crate::error::CertRevocationListError;
+    use crate::x509::wrap_in_sequence;
+
+    const END_CRL: &[u8] = include_bytes!("../../../test-ca/rsa/end.revoked.crl.pem");
+    const CLIENT_CRL: &[u8] = include_bytes!("../../../test-ca/rsa/client.revoked.crl.pem");
+    const INTER_CRL: &[u8] = include_bytes!("../../../test-ca/rsa/inter.revoked.crl.pem");
+
+    fn crl(pem: &[u8]) -> CertificateRevocationListDer<'static> {
+        rustls_pemfile::crls(&mut &pem[..])
+            .next()
+            .unwrap()
+            .unwrap()
+    }
+
+    /// Between thisUpdate and nextUpdate of the test CRLs.
+    fn now() -> UnixTime {
+        UnixTime::since_unix_epoch(Duration::from_secs(1_698_100_000))
+    }
+
+    /// After nextUpdate of the test CRLs.
+    fn later() -> UnixTime {
+        UnixTime::since_unix_epoch(Duration::from_secs(1_698_700_000))
+    }
+
+    #[test]
+    fn replace_reports_applied_and_rejected() {
+        let store = CrlStore::new();
+        assert!(store.is_empty());
+
+        let update = store.replace(
+            vec![crl(END_CRL), CertificateRevocationListDer::from(vec![0xff])],
+            now(),
+        );
+        assert_eq!(update.applied.len(), 1);
+        assert_eq!(update.applied[0].source, CrlSource::Index(0));
+        assert!(update.applied[0].issuer.is_some());
+        assert_eq!(update.rejected.len(), 1);
+        assert_eq!(update.rejected[0].0.source, CrlSource::Index(1));
+        assert_eq!(update.rejected[0].1, CertRevocationListError::ParseError);
+        assert_eq!(store.len(), 1);
+
+        let update = store.replace(vec![crl(INTER_CRL)], now());
+        assert_eq!(update.applied.len(), 1);
+        assert_eq!(store.len(), 1);
+    }
+
+    #[test]
+    fn expired_crls_are_rejected() {
+        let store = CrlStore::new();
+        let update = store.replace(vec![crl(END_CRL)], later());
+        assert!(update.applied.is_empty());
+        assert_eq!(update.rejected[0].1, CertRevocationListError::Expired);
+        assert!(update.rejected[0]
+            .0
+            .next_update
+            .is_some());
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn crls_without_next_update_are_rejected() {
+        // END_CRL with the nextUpdate field of its TBSCertList removed.
+        let original = crl(END_CRL);
+        let (tbs, times, rest) = yasna::parse_der(original.as_ref(), |reader| {
+            reader.read_sequence(|reader| {
+                let (tbs, times) = reader.next().read_sequence(|reader| {
+                    let mut tbs = Vec::new();
+                    let mut times = 0;
+                    while let Some(tag) = reader.read_optional(|reader| reader.lookahead_tag())? {
+                        let field = reader.next().read_der()?;
+                        if tag == yasna::tags::TAG_UTCTIME
+                            || tag == yasna::tags::TAG_GENERALIZEDTIME
+                        {
+                            times += 1;
+                            if times == 2 {
+                                continue;
+                            }
+                        }
+                        tbs.extend_from_slice(&field);
+                    }
+                    Ok((tbs, times))
+                })?;
+                let mut rest = reader.next().read_der()?;
+                rest.extend_from_slice(&reader.next().read_der()?);
+                Ok((tbs, times, rest))
+            })
+        })
+        .unwrap();
+        assert_eq!(times, 2);
+        let mut der = wrap_in_sequence(&tbs);
+        der.extend_from_slice(&rest);
+        let der = CertificateRevocationListDer::from(wrap_in_sequence(&der));
+
+        let store = CrlStore::new();
+        let update = store.replace(vec![der], now());
+        assert!(update.applied.is_empty());
+        assert_eq!(
+            update.rejected[0].1,
+            CertRevocationListError::MissingNextUpdate
+        );
+        assert!(update.rejected[0].0.issuer.is_none());
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn expired_crls_are_not_used() {
+        let store = CrlStore::new();
+        store.replace(vec![crl(END_CRL)], now());
+        assert_eq!(store.snapshot().current(now()).count(), 1);
+        assert_eq!(
+            store
+                .snapshot()
+                .current(later())
+                .count(),
+            0
+        );
+        assert_eq!(store.len(), 1);
+    }
+
+    #[test]
+    fn add_replaces_crls_from_the_same_issuer() {
+        let store = CrlStore::new();
+        let clone = store.clone();
+        store.add(vec![crl(END_CRL)], now());
+        store.add(vec![crl(INTER_CRL)], now());
+        assert_eq!(clone.len(), 2);
+
+        // issued by the same intermediate as END_CRL
+        let update = clone.add(vec![crl(CLIENT_CRL)], now());
+        assert_eq!(update.applied.len(), 1);
+        assert_eq!(store.len(), 2);
+    }
+
+    #[test]
+    fn replace_from_dir() {
+        let dir = env::temp_dir().join(format!("rustls-crl-store-{}", process::id()));
+        fs::create_dir_all(dir.join("subdir")).unwrap();
+        fs::write(dir.join("a.pem"), [END_CRL, INTER_CRL].concat()).unwrap();
+        fs::write(dir.join("b.der"), crl(CLIENT_CRL).as_ref()).unwrap();
+        fs::write(dir.join("c.txt"), b"not a crl").unwrap();
+        fs::write(dir.join("subdir").join("d.der"), b"ignored").unwrap();
+
+        let store = CrlStore::new();
+        let update = store
+            .replace_from_dir(&dir, now())
+            .unwrap();
+        let sources = update
+            .applied
+            .iter()
+            .map(|info| info.source.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            sources,
+            vec![
+                CrlSource::File(dir.join("a.pem")),
+                CrlSource::File(dir.join("a.pem")),
+                CrlSource::File(dir.join("b.der")),
+            ]
+        );
+        assert_eq!(update.rejected.len(), 1);
+        assert_eq!(
+            update.rejected[0].0.source,
+            CrlSource::File(dir.join("c.txt"))
+        );
+        // b.der replaced the CRL from the same issuer in a.pem
+        assert_eq!(store.len(), 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(store
+            .replace_from_dir(&dir, now())
+            .is_err());
+        assert_eq!(store.len(), 2);
+    }
+}
@@ -0,0 +1,1011 @@
+//! Verification of signed certificate timestamps ([RFC 6962]).
+//!
+//! [RFC 6962]: https://datatracker.ietf.org/doc/html/rfc6962
+
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+
+use pki_types::{CertificateDer, ServerName, SubjectPublicKeyInfoDer, UnixTime};
+
+use crate::crypto::hash::{Hash, HashAlgorithm};
+use crate::crypto::{CryptoProvider, WebPkiSupportedAlgorithms};
+use crate::enums::SignatureScheme;
+use crate::verify::{
+    DigitallySignedStruct, HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
+};
+use crate::webpki::ocsp::{self, CertIdHashes};
+use crate::webpki::verify::{find_issuer, ParsedCertificate};
+use crate::webpki::VerificationReport;
+use crate::x509::{asn1_wrap, wrap_in_sequence, TbsCertificate};
+use crate::{CertificateError, Error};
+
+/// A certificate transparency log, whose signed certificate timestamps
+/// a [`CtVerifier`] accepts.
+#[derive(Clone, Debug)]
+pub struct CtLog {
+    /// The log ID: the SHA-256 hash of `key`.
+    pub id: [u8; 32],
+    /// The log's public key, as a DER-encoded `SubjectPublicKeyInfo`.
+    pub key: Vec<u8>,
+    /// The organisation operating the log.
+    ///
+    /// [`CtPolicy::min_distinct_operators`] counts distinct values of this.
+    pub operator: String,
+    /// SCTs with earlier timestamps are not accepted from this log.
+    pub valid_from: Option<UnixTime>,
+    /// SCTs with this timestamp or later are not accepted from this log.
+    ///
+    /// Use this for logs that have been retired.
+    pub valid_until: Option<UnixTime>,
+}
+
+impl CtLog {
+    /// Does this log accept an SCT with `timestamp` (in milliseconds since the
+    /// Unix epoch)?
+    fn covers(&self, timestamp: u64) -> bool {
+        let after_start = match self.valid_from {
+            Some(from) => timestamp >= from.as_secs().saturating_mul(1000),
+            None => true,
+        };
+        let before_end = match self.valid_until {
+            Some(until) => timestamp < until.as_secs().saturating_mul(1000),
+            None => true,
+        };
+        after_start && before_end
+    }
+}
+
+/// How many signed certificate timestamps a [`CtVerifier`] requires.
+///
+/// Each log is counted at most once, however many valid SCTs it issued
+/// for the certificate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CtPolicy {
+    /// The minimum number of distinct logs with a valid SCT.
+    pub min_scts: usize,
+    /// The minimum number of distinct log operators with a valid SCT.
+    pub min_distinct_operators: usize,
+}
+
+impl Default for CtPolicy {
+    fn default() -> Self {
+        Self {
+            min_scts: 2,
+            min_distinct_operators: 2,
+        }
+    }
+}
+
+/// A [`ServerCertVerifier`] that enforces a certificate transparency policy,
+/// on top of another verifier.
+///
+/// The wrapped verifier is called first, and must accept the certificate.
+/// Then the signed certificate timestamps (SCTs) from all three sources
+/// defined in [RFC 6962 section 3.3] are checked against the configured
+/// [`CtLog`]s:
+///
+/// - SCTs embedded in the end-entity certificate.  The issuer of the
+///   end-entity certificate must be among the intermediates sent by the
+///   server for these to be checked.
+/// - SCTs in the stapled OCSP response.  These are only used if the response
+///   is signed by the issuer of the end-entity certificate (or a responder it
+///   delegated to), which must be among the intermediates sent by the server,
+///   and gives the certificate the status good.
+/// - SCTs sent in the TLS extension.  The server only sends these if
+///   [`ClientConfig::enable_signed_cert_timestamps`] is set.
+///
+/// SCTs that are malformed, from unknown logs, have timestamps in the future
+/// or outside their log's validity period, or whose signatures do not verify
+/// are ignored.  If the remaining SCTs do not meet the [`CtPolicy`], the
+/// certificate is rejected with
+/// [`CertificateError::InsufficientCertificateTransparency`].
+///
+/// [RFC 6962 section 3.3]: https://datatracker.ietf.org/doc/html/rfc6962#section-3.3
+/// [`ClientConfig::enable_signed_cert_timestamps`]: crate::ClientConfig::enable_signed_cert_timestamps
+pub struct CtVerifier {
+    inner: Arc<dyn ServerCertVerifier>,
+    logs: Vec<CtLog>,
+    policy: CtPolicy,
+    supported: WebPkiSupportedAlgorithms,
+    sha256: Option<&'static dyn Hash>,
+    ocsp_hashes: CertIdHashes,
+}
+
+impl CtVerifier {
+    /// Make a verifier that accepts what `inner` accepts, if `policy` is met by
+    /// SCTs from `logs`.
+    ///
+    /// The cryptography used comes from the process-default [`CryptoProvider`]
+    /// for the `ring` feature.
+    #[cfg(feature = "ring")]
+    pub fn new(
+        inner: Arc<dyn ServerCertVerifier>,
+        logs: impl IntoIterator<Item = CtLog>,
+        policy: CtPolicy,
+    ) -> Self {
+        Self::new_with_provider(
+            inner,
+            logs,
+            policy,
+            crate::crypto::ring::default_provider().into(),
+        )
+    }
+
+    /// Make a verifier that accepts what `inner` accepts, if `policy` is met by
+    /// SCTs from `logs`.
+    ///
+    /// The cryptography used comes from the specified [`CryptoProvider`].  Its
+    /// cipher suites must include one using SHA-256, or embedded SCTs cannot
+    /// be checked.
+    pub fn new_with_provider(
+        inner: Arc<dyn ServerCertVerifier>,
+        logs: impl IntoIterator<Item = CtLog>,
+        policy: CtPolicy,
+        provider: Arc<CryptoProvider>,
+    ) -> Self {
+        let hashes = || {
+            provider
+                .cipher_suites
+                .iter()
+                .map(|suite| suite.hash_provider())
+        };
+        Self {
+            inner,
+            logs: logs.into_iter().collect(),
+            policy,
+            supported: provider.signature_verification_algorithms,
+            sha256: hashes().find(|hash| hash.algorithm() == HashAlgorithm::SHA256),
+            ocsp_hashes: CertIdHashes::new(hashes()),
+        }
+    }
+
+    /// Check the SCTs for `end_entity` meet the policy at `now`.
+    ///
+    /// `scts` are those the server sent in the TLS extension.
+    fn check_scts(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        ocsp_response: &[u8],
+        scts: &[&[u8]],
+        now: UnixTime,
+    ) -> Result<(), Error> {
+        let mut logs = Vec::new();
+        let mut add = |sct: &[u8], entry: &LogEntry<'_>| {
+            if let Some(log) = self.check_sct(sct, entry, now) {
+                if !logs
+                    .iter()
+                    .any(|seen: &&CtLog| seen.id == log.id)
+                {
+                    logs.push(log);
+                }
+            }
+        };
+
+        let x509_entry = LogEntry::X509(end_entity.as_ref());
+        for sct in scts {
+            add(sct, &x509_entry);
+        }
+
+        let cert = ParsedCertificate::try_from(end_entity)?;
+        let tbs = TbsCertificate::of(end_entity)
+            .ok_or(Error::InvalidCertificate(CertificateError::BadEncoding))?;
+
+        if let Some(list) = ocsp::good_single_extension(
+            ocsp_response,
+            end_entity,
+            intermediates,
+            self.supported.all,
+            &self.ocsp_hashes,
+            OID_OCSP_SCT_LIST,
+            now,
+        ) {
+            for sct in sct_list(&list) {
+                add(&sct, &x509_entry);
+            }
+        }
+
+        if let Some(list) = tbs.extension(OID_EMBEDDED_SCT_LIST) {
+            if let Some(entry) = self.precert_entry(&cert, &tbs, intermediates, now) {
+                for sct in sct_list(list) {
+                    add(&sct, &entry);
+                }
+            }
+        }
+
+        let mut operators = Vec::new();
+        for log in &logs {
+            if !operators.contains(&&log.operator) {
+                operators.push(&log.operator);
+            }
+        }
+
+        match logs.len() >= self.policy.min_scts
+            && operators.len() >= self.policy.min_distinct_operators
+        {
+            true => Ok(()),
+            false => Err(Error::InvalidCertificate(
+                CertificateError::InsufficientCertificateTransparency,
+            )),
+        }
+    }
+
+    /// Check one encoded `SignedCertificateTimestamp` over `entry`, returning
+    /// the log that issued it if it is valid at `now`.
+    fn check_sct(&self, sct: &[u8], entry: &LogEntry<'_>, now: UnixTime) -> Option<&CtLog> {
+        let sct = Sct::parse(sct)?;
+        let log = self
+            .logs
+            .iter()
+            .find(|log| log.id == sct.log_id)?;
+
+        if sct.timestamp > now.as_secs().saturating_mul(1000) || !log.covers(sct.timestamp) {
+            return None;
+        }
+
+        let algs = self
+            .supported
+            .mapping
+            .iter()
+            .find(|(scheme, _)| *scheme == sct.scheme)
+            .map(|(_, algs)| *algs)?;
+
+        let key = SubjectPublicKeyInfoDer::from(log.key.as_slice());
+        let key = webpki::RawPublicKeyEntity::try_from(&key).ok()?;
+
+        let mut message = Vec::new();
+        message.push(SCT_VERSION_V1);
+        message.push(SIGNATURE_TYPE_CERTIFICATE_TIMESTAMP);
+        message.extend_from_slice(&sct.timestamp.to_be_bytes());
+        entry.encode(&mut message);
+        message.extend_from_slice(&(sct.extensions.len() as u16).to_be_bytes());
+        message.extend_from_slice(sct.extensions);
+
+        match algs.iter().any(|alg| {
+            key.verify_signature(*alg, &message, sct.signature)
+                .is_ok()
+        }) {
+            true => Some(log),
+            false => None,
+        }
+    }
+
+    /// Reconstruct the precertificate log entry for `cert`, whose issuer must
+    /// be among `intermediates`.
+    fn precert_entry(
+        &self,
+        cert: &ParsedCertificate<'_>,
+        tbs: &TbsCertificate<'_>,
+        intermediates: &[CertificateDer<'_>],
+        now: UnixTime,
+    ) -> Option<LogEntry<'static>> {
+        let sha256 = self.sha256?;
+        let issuer = find_issuer(cert, intermediates, &[], now, self.supported.all)?;
+
+        let mut issuer_key_hash = [0u8; 32];
+        issuer_key_hash.copy_from_slice(
+            sha256
+                .hash(&wrap_in_sequence(&issuer.subject_public_key_info))
+                .as_ref(),
+        );
+        Some(LogEntry::Precert {
+            issuer_key_hash,
+            tbs: tbs_without_extension(tbs, OID_EMBEDDED_SCT_LIST),
+        })
+    }
+}
+
+impl ServerCertVerifier for CtVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        scts: &[&[u8]],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, Error> {
+        let verified = self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            scts,
+            now,
+        )?;
+        self.check_scts(end_entity, intermediates, ocsp_response, scts, now)?;
+        Ok(verified)
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        self.inner
+            .verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        self.inner
+            .verify_tls13_signature(message, cert, dss)
+    }
+
+    fn verify_server_cert_with_report(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        scts: &[&[u8]],
+        now: UnixTime,
+    ) -> (
+        Result<ServerCertVerified, Error>,
+        Option<VerificationReport>,
+    ) {
+        let (result, mut report) = self
+            .inner
+            .verify_server_cert_with_report(
+                end_entity,
+                intermediates,
+                server_name,
+                ocsp_response,
+                scts,
+                now,
+            );
+        let result = result.and_then(|verified| {
+            self.check_scts(end_entity, intermediates, ocsp_response, scts, now)?;
+            Ok(verified)
+        });
+        if let Some(report) = &mut report {
+            report.result = result
+                .as_ref()
+                .map(|_| ())
+                .map_err(Error::clone);
+        }
+        (result, report)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+impl fmt::Debug for CtVerifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CtVerifier")
+            .field("inner", &self.inner)
+            .field("logs", &self.logs)
+            .field("policy", &self.policy)
+            .finish()
+    }
+}
+
+/// The `signed_entry` an SCT's signature covers.
+enum LogEntry<'a> {
+    /// The end-entity certificate, for SCTs delivered in the TLS extension
+    /// or an OCSP response.
+    X509(&'a [u8]),
+    /// The precertificate, for SCTs embedded in the certificate.
+    Precert {
+        issuer_key_hash: [u8; 32],
+        tbs: Vec<u8>,
+    },
+}
+
+impl LogEntry<'_> {
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        match self {
+            Self::X509(cert) => {
+                bytes.extend_from_slice(&0u16.to_be_bytes());
+                encode_u24_prefixed(cert, bytes);
+            }
+            Self::Precert {
+                issuer_key_hash,
+                tbs,
+            } => {
+                bytes.extend_from_slice(&1u16.to_be_bytes());
+                bytes.extend_from_slice(issuer_key_hash);
+                encode_u24_prefixed(tbs, bytes);
+            }
+        }
+    }
+}
+
+fn encode_u24_prefixed(data: &[u8], bytes: &mut Vec<u8>) {
+    bytes.extend_from_slice(&(data.len() as u32).to_be_bytes()[1..]);
+    bytes.extend_from_slice(data);
+}
+
+/// The parts of a version 1 `SignedCertificateTimestamp` we need.
+struct Sct<'a> {
+    log_id: &'a [u8],
+    timestamp: u64,
+    extensions: &'a [u8],
+    scheme: SignatureScheme,
+    signature: &'a [u8],
+}
+
+impl<'a> Sct<'a> {
+    fn parse(mut bytes: &'a [u8]) -> Option<Self> {
+        let input = &mut bytes;
+        if take(input, 1)? != [SCT_VERSION_V1] {
+            return None;
+        }
+        let log_id = take(input, 32)?;
+        let mut timestamp = [0u8; 8];
+        timestamp.copy_from_slice(take(input, 8)?);
+        let extensions = take_u16_prefixed(input)?;
+        let scheme = take(input, 2)?;
+        let signature = take_u16_prefixed(input)?;
+        if !input.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            log_id,
+            timestamp: u64::from_be_bytes(timestamp),
+            extensions,
+            scheme: SignatureScheme::from(u16::from_be_bytes([scheme[0], scheme[1]])),
+            signature,
+        })
+    }
+}
+
+/// Split the SCTs out of the `extnValue` contents of an SCT list extension,
+/// which is a DER OCTET STRING containing a TLS `SignedCertificateTimestampList`.
+///
+/// A malformed list yields the SCTs before the error.
+fn sct_list(value: &[u8]) -> Vec<Vec<u8>> {
+    let value = yasna::parse_der(value, |reader| reader.read_bytes()).unwrap_or_default();
+    let mut list = &value[..];
+    let mut list = take_u16_prefixed(&mut list).unwrap_or_default();
+    let mut scts = Vec::new();
+    while let Some(sct) = take_u16_prefixed(&mut list) {
+        scts.push(sct.to_vec());
+    }
+    scts
+}
+
+/// Re-encode the `TBSCertificate` `tbs` without the extension `oid`.
+///
+/// The `extensions` field is omitted if no others remain.
+fn tbs_without_extension(tbs: &TbsCertificate<'_>, oid: &[u64]) -> Vec<u8> {
+    let mut out = tbs.fields.concat();
+    let kept = tbs
+        .extensions
+        .iter()
+        .filter(|extension| extension.id.components().as_slice() != oid)
+        .flat_map(|extension| extension.der.iter().copied())
+        .collect::<Vec<_>>();
+    if !kept.is_empty() {
+        out.extend_from_slice(&asn1_wrap(EXTENSIONS_TAG, &wrap_in_sequence(&kept)));
+    }
+    wrap_in_sequence(&out)
+}
+
+fn take<'a>(input: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+    if input.len() < len {
+        return None;
+    }
+    let (head, tail) = input.split_at(len);
+    *input = tail;
+    Some(head)
+}
+
+fn take_u16_prefixed<'a>(input: &mut &'a [u8]) -> Option<&'a [u8]> {
+    let len = take(input, 2)?;
+    take(input, usize::from(u16::from_be_bytes([len[0], len[1]])))
+}
+
+const SCT_VERSION_V1: u8 = 0;
+const SIGNATURE_TYPE_CERTIFICATE_TIMESTAMP: u8 = 0;
+
+/// The `[3]` tag of the `extensions` field of a `TBSCertificate`.
+const EXTENSIONS_TAG: u8 = 0xa3;
+
+const OID_EMBEDDED_SCT_LIST: &[u64] = &[1, 3, 6, 1, 4, 1, 11129, 2, 4, 2];
+const OID_OCSP_SCT_LIST: &[u64] = &[1, 3, 6, 1, 4, 1, 11129, 2, 4, 5];
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+    use std::prelude::v1::*;
+
+    use pki_types::{CertificateDer, PrivateKeyDer, UnixTime};
+
+    use super::*;
+    use crate::crypto::hash::HashAlgorithm;
+    use crate::test_provider;
+
+    const INTEGER: u8 = 0x02;
+    const BIT_STRING: u8 = 0x03;
+    const OCTET_STRING: u8 = 0x04;
+    const OID: u8 = 0x06;
+    const ENUMERATED: u8 = 0x0a;
+    const GENERALIZED_TIME: u8 = 0x18;
+
+    fn context_constructed(number: u8) -> u8 {
+        0xa0 | number
+    }
+
+    /// An inner verifier that accepts any certificate.
+    #[derive(Debug)]
+    struct AcceptAnything;
+
+    impl ServerCertVerifier for AcceptAnything {
+        fn verify_server_cert(
+            &self,
+            _end_entity: &CertificateDer<'_>,
+            _intermediates: &[CertificateDer<'_>],
+            _server_name: &ServerName<'_>,
+            _ocsp_response: &[u8],
+            _scts: &[&[u8]],
+            _now: UnixTime,
+        ) -> Result<ServerCertVerified, Error> {
+            Ok(ServerCertVerified::assertion())
+        }
+
+        fn verify_tls12_signature(
+            &self,
+            _message: &[u8],
+            _cert: &CertificateDer<'_>,
+            _dss: &DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, Error> {
+            Ok(HandshakeSignatureValid::assertion())
+        }
+
+        fn verify_tls13_signature(
+            &self,
+            _message: &[u8],
+            _cert: &CertificateDer<'_>,
+            _dss: &DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, Error> {
+            Ok(HandshakeSignatureValid::assertion())
+        }
+
+        fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+            Vec::new()
+        }
+    }
+
+    /// A log, with the key it signs with.
+    struct TestLog {
+        log: CtLog,
+        key: PrivateKeyDer<'static>,
+    }
+
+    impl TestLog {
+        fn new(cert: &[u8], key: &[u8], operator: &str) -> Self {
+            let cert = load_cert(cert);
+            let spki = webpki::EndEntityCert::try_from(&cert)
+                .unwrap()
+                .subject_public_key_info()
+                .to_vec();
+            let mut id = [0u8; 32];
+            id.copy_from_slice(sha256().hash(&spki).as_ref());
+            Self {
+                log: CtLog {
+                    id,
+                    key: spki,
+                    operator: operator.into(),
+                    valid_from: None,
+                    valid_until: None,
+                },
+                key: rustls_pemfile::private_key(&mut &key[..])
+                    .unwrap()
+                    .unwrap(),
+            }
+        }
+
+        fn sct(&self, timestamp: u64, entry: &LogEntry<'_>) -> Vec<u8> {
+            let mut message = vec![SCT_VERSION_V1, SIGNATURE_TYPE_CERTIFICATE_TIMESTAMP];
+            message.extend_from_slice(&timestamp.to_be_bytes());
+            entry.encode(&mut message);
+            message.extend_from_slice(&[0, 0]);
+            let signature = sign(&self.key, SignatureScheme::ECDSA_NISTP256_SHA256, &message);
+
+            let mut sct = vec![SCT_VERSION_V1];
+            sct.extend_from_slice(&self.log.id);
+            sct.extend_from_slice(&timestamp.to_be_bytes());
+            sct.extend_from_slice(&[0, 0]);
+            sct.extend_from_slice(
+                &SignatureScheme::ECDSA_NISTP256_SHA256
+                    .get_u16()
+                    .to_be_bytes(),
+            );
+            sct.extend_from_slice(&(signature.len() as u16).to_be_bytes());
+            sct.extend_from_slice(&signature);
+            sct
+        }
+    }
+
+    fn google() -> TestLog {
+        TestLog::new(
+            include_bytes!("../../../test-ca/ecdsa/end.cert"),
+            include_bytes!("../../../test-ca/ecdsa/end.key"),
+            "Google",
+        )
+    }
+
+    fn cloudflare() -> TestLog {
+        TestLog::new(
+            include_bytes!("../../../test-ca/ecdsa/inter.cert"),
+            include_bytes!("../../../test-ca/ecdsa/inter.key"),
+            "Cloudflare",
+        )
+    }
+
+    fn load_cert(pem: &[u8]) -> CertificateDer<'static> {
+        rustls_pemfile::certs(&mut &pem[..])
+            .next()
+            .unwrap()
+            .unwrap()
+    }
+
+    fn sha256() -> &'static dyn Hash {
+        test_provider::default_provider()
+            .cipher_suites
+            .iter()
+            .map(|suite| suite.hash_provider())
+            .find(|hash| hash.algorithm() == HashAlgorithm::SHA256)
+            .unwrap()
+    }
+
+    fn sign(key: &PrivateKeyDer<'static>, scheme: SignatureScheme, message: &[u8]) -> Vec<u8> {
+        test_provider::default_provider()
+            .key_provider
+            .load_private_key(key.clone_key())
+            .unwrap()
+            .choose_scheme(&[scheme])
+            .unwrap()
+            .sign(message)
+            .unwrap()
+    }
+
+    fn end_entity() -> CertificateDer<'static> {
+        load_cert(include_bytes!("../../../test-ca/rsa/end.cert"))
+    }
+
+    fn intermediate() -> CertificateDer<'static> {
+        load_cert(include_bytes!("../../../test-ca/rsa/inter.cert"))
+    }
+
+    fn verifier(logs: &[&TestLog], policy: CtPolicy) -> CtVerifier {
+        CtVerifier::new_with_provider(
+            Arc::new(AcceptAnything),
+            logs.iter().map(|log| log.log.clone()),
+            policy,
+            test_provider::default_provider().into(),
+        )
+    }
+
+    fn verify(
+        verifier: &CtVerifier,
+        end_entity: &CertificateDer<'_>,
+        ocsp_response: &[u8],
+        scts: &[Vec<u8>],
+    ) -> Result<(), Error> {
+        let scts = scts
+            .iter()
+            .map(|sct| sct.as_slice())
+            .collect::<Vec<_>>();
+        verifier.check_scts(end_entity, &[intermediate()], ocsp_response, &scts, now())
+    }
+
+    fn now() -> UnixTime {
+        UnixTime::since_unix_epoch(Duration::from_secs(1_700_000_000))
+    }
+
+    const TIMESTAMP: u64 = 1_690_000_000_000;
+
+    /// Encode an SCT list extension value.
+    fn sct_list_extension(scts: &[Vec<u8>]) -> Vec<u8> {
+        let mut entries = Vec::new();
+        for sct in scts {
+            entries.extend_from_slice(&(sct.len() as u16).to_be_bytes());
+            entries.extend_from_slice(sct);
+        }
+        let mut list = (entries.len() as u16)
+            .to_be_bytes()
+            .to_vec();
+        list.extend_from_slice(&entries);
+        asn1_wrap(OCTET_STRING, &list)
+    }
+
+    fn extension(oid: &[u64], value: &[u8]) -> Vec<u8> {
+        yasna::construct_der(|writer| {
+            writer.write_sequence(|writer| {
+                writer
+                    .next()
+                    .write_oid(&yasna::models::ObjectIdentifier::from_slice(oid));
+                writer.next().write_bytes(value);
+            })
+        })
+    }
+
+    #[test]
+    fn tls_extension_scts_from_two_operators_are_sufficient() {
+        let (google, cloudflare) = (google(), cloudflare());
+        let cert = end_entity();
+        let entry = LogEntry::X509(cert.as_ref());
+        let scts = [
+            google.sct(TIMESTAMP, &entry),
+            cloudflare.sct(TIMESTAMP, &entry),
+        ];
+        let verifier = verifier(&[&google, &cloudflare], CtPolicy::default());
+        assert!(verify(&verifier, &cert, &[], &scts).is_ok());
+    }
+
+    #[test]
+    fn no_scts_are_insufficient() {
+        let verifier = verifier(&[&google(), &cloudflare()], CtPolicy::default());
+        assert_eq!(
+            verify(&verifier, &end_entity(), &[], &[]).unwrap_err(),
+            Error::InvalidCertificate(CertificateError::InsufficientCertificateTransparency)
+        );
+    }
+
+    #[test]
+    fn repeated_scts_from_one_log_count_once() {
+        let google = google();
+        let cert = end_entity();
+        let entry = LogEntry::X509(cert.as_ref());
+        let scts = [
+            google.sct(TIMESTAMP, &entry),
+            google.sct(TIMESTAMP + 1, &entry),
+        ];
+        let policy = CtPolicy {
+            min_scts: 2,
+            min_distinct_operators: 1,
+        };
+        assert!(verify(&verifier(&[&google], policy), &cert, &[], &scts).is_err());
+    }
+
+    #[test]
+    fn distinct_operators_are_required() {
+        let (google, mut other) = (google(), cloudflare());
+        other.log.operator = google.log.operator.clone();
+        let cert = end_entity();
+        let entry = LogEntry::X509(cert.as_ref());
+        let scts = [google.sct(TIMESTAMP, &entry), other.sct(TIMESTAMP, &entry)];
+
+        let verifier_two = verifier(&[&google, &other], CtPolicy::default());
+        assert!(verify(&verifier_two, &cert, &[], &scts).is_err());
+
+        let policy = CtPolicy {
+            min_scts: 2,
+            min_distinct_operators: 1,
+        };
+        assert!(verify(&verifier(&[&google, &other], policy), &cert, &[], &scts).is_ok());
+    }
+
+    #[test]
+    fn invalid_scts_are_ignored() {
+        let (google, cloudflare) = (google(), cloudflare());
+        let cert = end_entity();
+        let entry = LogEntry::X509(cert.as_ref());
+        let policy = CtPolicy {
+            min_scts: 1,
+            min_distinct_operators: 1,
+        };
+        let verifier = verifier(&[&google], policy);
+
+        assert!(verify(&verifier, &cert, &[], &[google.sct(TIMESTAMP, &entry)]).is_ok());
+
+        // unknown log
+        assert!(verify(&verifier, &cert, &[], &[cloudflare.sct(TIMESTAMP, &entry)]).is_err());
+
+        // bad signature
+        let mut sct = google.sct(TIMESTAMP, &entry);
+        *sct.last_mut().unwrap() ^= 1;
+        assert!(verify(&verifier, &cert, &[], &[sct]).is_err());
+
+        // signed over another certificate
+        let other = intermediate();
+        let sct = google.sct(TIMESTAMP, &LogEntry::X509(other.as_ref()));
+        assert!(verify(&verifier, &cert, &[], &[sct]).is_err());
+
+        // timestamp in the future
+        let future = now().as_secs() * 1000 + 1;
+        assert!(verify(&verifier, &cert, &[], &[google.sct(future, &entry)]).is_err());
+
+        // malformed
+        assert!(verify(&verifier, &cert, &[], &[vec![0; 3]]).is_err());
+    }
+
+    #[test]
+    fn log_validity_period_is_enforced() {
+        let mut google = google();
+        let cert = end_entity();
+        let entry = LogEntry::X509(cert.as_ref());
+        let scts = [google.sct(TIMESTAMP, &entry)];
+        let policy = CtPolicy {
+            min_scts: 1,
+            min_distinct_operators: 1,
+        };
+
+        google.log.valid_until = Some(UnixTime::since_unix_epoch(Duration::from_secs(
+            TIMESTAMP / 1000,
+        )));
+        assert!(verify(&verifier(&[&google], policy), &cert, &[], &scts).is_err());
+
+        google.log.valid_until = None;
+        google.log.valid_from = Some(UnixTime::since_unix_epoch(Duration::from_secs(
+            TIMESTAMP / 1000 + 1,
+        )));
+        assert!(verify(&verifier(&[&google], policy), &cert, &[], &scts).is_err());
+
+        google.log.valid_from = Some(UnixTime::since_unix_epoch(Duration::from_secs(
+            TIMESTAMP / 1000,
+        )));
+        assert!(verify(&verifier(&[&google], policy), &cert, &[], &scts).is_ok());
+    }
+
+    #[test]
+    fn embedded_scts_are_checked() {
+        let (google, cloudflare) = (google(), cloudflare());
+        let original = end_entity();
+        let (original_tbs, signature_algorithm) = yasna::parse_der(&original, |reader| {
+            reader.read_sequence(|reader| {
+                let tbs = reader.next().read_der()?;
+                let signature_algorithm = reader.next().read_der()?;
+                reader.next().read_der()?;
+                Ok((tbs, signature_algorithm))
+            })
+        })
+        .unwrap();
+        let issuer_cert = intermediate();
+        let issuer_spki = webpki::EndEntityCert::try_from(&issuer_cert)
+            .unwrap()
+            .subject_public_key_info();
+        let mut issuer_key_hash = [0u8; 32];
+        issuer_key_hash.copy_from_slice(sha256().hash(&issuer_spki).as_ref());
+
+        // the precertificate TBS is the final one without the SCT extension
+        let precert = LogEntry::Precert {
+            issuer_key_hash,
+            tbs: original_tbs,
+        };
+        let scts = [
+            google.sct(TIMESTAMP, &precert),
+            cloudflare.sct(TIMESTAMP, &precert),
+        ];
+
+        let fields = TbsCertificate::of(&original).unwrap();
+        let mut extensions = fields
+            .extensions
+            .iter()
+            .flat_map(|extension| extension.der.iter().copied())
+            .collect::<Vec<_>>();
+        extensions.extend_from_slice(&extension(
+            OID_EMBEDDED_SCT_LIST,
+            &sct_list_extension(&scts),
+        ));
+        let mut tbs = fields.fields.concat();
+        tbs.extend_from_slice(&asn1_wrap(EXTENSIONS_TAG, &wrap_in_sequence(&extensions)));
+        let tbs = wrap_in_sequence(&tbs);
+
+        let issuer_key =
+            rustls_pemfile::private_key(&mut &include_bytes!("../../../test-ca/rsa/inter.key")[..])
+                .unwrap()
+                .unwrap();
+        let signature = sign(&issuer_key, SignatureScheme::RSA_PKCS1_SHA256, &tbs);
+        let mut cert = tbs;
+        cert.extend_from_slice(&signature_algorithm);
+        cert.extend_from_slice(&asn1_wrap(BIT_STRING, &[&[0u8][..], &signature].concat()));
+        let cert = CertificateDer::from(wrap_in_sequence(&cert));
+
+        let verifier = verifier(&[&google, &cloudflare], CtPolicy::default());
+        assert!(verify(&verifier, &cert, &[], &[]).is_ok());
+
+        // without the issuer, embedded SCTs cannot be checked
+        assert!(verifier
+            .check_scts(&cert, &[], &[], &[], now())
+            .is_err());
+    }
+
+    #[test]
+    fn ocsp_scts_are_checked() {
+        let (google, cloudflare) = (google(), cloudflare());
+        let cert = end_entity();
+        let serial = webpki::EndEntityCert::try_from(&cert)
+            .unwrap()
+            .serial()
+            .to_vec();
+        let entry = LogEntry::X509(cert.as_ref());
+        let scts = [
+            google.sct(TIMESTAMP, &entry),
+            cloudflare.sct(TIMESTAMP, &entry),
+        ];
+
+        let issuer_key =
+            rustls_pemfile::private_key(&mut &include_bytes!("../../../test-ca/rsa/inter.key")[..])
+                .unwrap()
+                .unwrap();
+        let response = |serial: &[u8], status: &[u8], sign_with: &PrivateKeyDer<'static>| {
+            let mut cert_id = wrap_in_sequence(&asn1_wrap(OID, &[0x2b, 0x0e, 0x03, 0x02, 0x1a]));
+            cert_id.extend_from_slice(&asn1_wrap(OCTET_STRING, &[0; 20]));
+            cert_id.extend_from_slice(&asn1_wrap(OCTET_STRING, &[0; 20]));
+            cert_id.extend_from_slice(&asn1_wrap(INTEGER, serial));
+
+            let mut single = wrap_in_sequence(&cert_id);
+            single.extend_from_slice(status);
+            single.extend_from_slice(&asn1_wrap(GENERALIZED_TIME, b"20230701000000Z"));
+            single.extend_from_slice(&asn1_wrap(
+                context_constructed(0),
+                &asn1_wrap(GENERALIZED_TIME, b"20240101000000Z"),
+            ));
+            single.extend_from_slice(&asn1_wrap(
+                context_constructed(1),
+                &wrap_in_sequence(&extension(OID_OCSP_SCT_LIST, &sct_list_extension(&scts))),
+            ));
+
+            let mut data = asn1_wrap(context_constructed(2), &asn1_wrap(OCTET_STRING, &[0; 20]));
+            data.extend_from_slice(&asn1_wrap(GENERALIZED_TIME, b"20230701000000Z"));
+            data.extend_from_slice(&wrap_in_sequence(&wrap_in_sequence(&single)));
+            let data = wrap_in_sequence(&data);
+
+            // sha256WithRSAEncryption
+            let mut signature_algorithm =
+                asn1_wrap(OID, &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0b]);
+            signature_algorithm.extend_from_slice(&[0x05, 0x00]);
+            let signature = sign(sign_with, SignatureScheme::RSA_PKCS1_SHA256, &data);
+
+            let mut basic = data;
+            basic.extend_from_slice(&wrap_in_sequence(&signature_algorithm));
+            basic.extend_from_slice(&asn1_wrap(BIT_STRING, &[&[0u8][..], &signature].concat()));
+
+            let mut bytes = asn1_wrap(OID, &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x30, 0x01, 0x01]);
+            bytes.extend_from_slice(&asn1_wrap(OCTET_STRING, &wrap_in_sequence(&basic)));
+
+            let mut response = asn1_wrap(ENUMERATED, &[0]);
+            response.extend_from_slice(&asn1_wrap(
+                context_constructed(0),
+                &wrap_in_sequence(&bytes),
+            ));
+            wrap_in_sequence(&response)
+        };
+        const GOOD: &[u8] = &[0x80, 0x00];
+        const UNKNOWN: &[u8] = &[0x82, 0x00];
+
+        let verifier = verifier(&[&google, &cloudflare], CtPolicy::default());
+        assert!(verify(&verifier, &cert, &response(&serial, GOOD, &issuer_key), &[]).is_ok());
+
+        // a response for another certificate does not count
+        assert!(verify(&verifier, &cert, &response(&[1], GOOD, &issuer_key), &[]).is_err());
+
+        // nor does one without the status good
+        assert!(verify(
+            &verifier,
+            &cert,
+            &response(&serial, UNKNOWN, &issuer_key),
+            &[]
+        )
+        .is_err());
+
+        // nor one not signed by the issuer
+        let other_key =
+            rustls_pemfile::private_key(&mut &include_bytes!("../../../test-ca/rsa/end.key")[..])
+                .unwrap()
+                .unwrap();
+        assert!(verify(&verifier, &cert, &response(&serial, GOOD, &other_key), &[]).is_err());
+
+        // nor one whose signed data was altered
+        let mut tampered = response(&serial, GOOD, &issuer_key);
+        let at = tampered
+            .windows(15)
+            .position(|w| w == b"20230701000000Z")
+            .unwrap();
+        tampered[at + 3] ^= 1;
+        assert!(verify(&verifier, &cert, &tampered, &[]).is_err());
+    }
+}
@@ -0,0 +1,704 @@
+//! Verification of server certificates using TLSA records ([RFC 6698], [RFC 7671]).
+//!
+//! [RFC 6698]: https://datatracker.ietf.org/doc/html/rfc6698
+//! [RFC 7671]: https://datatracker.ietf.org/doc/html/rfc7671
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+
+use pki_types::{CertificateDer, ServerName, UnixTime};
+
+use super::{
+    verify_server_cert_signed_by_trust_anchor, verify_server_name, verify_tls12_signature,
+    verify_tls13_signature, ParsedCertificate,
+};
+use crate::crypto::hash::{Hash, HashAlgorithm};
+use crate::crypto::{CryptoProvider, WebPkiSupportedAlgorithms};
+use crate::enums::SignatureScheme;
+pub use crate::enums::{TlsaMatchingType, TlsaSelector, TlsaUsage};
+use crate::error::InvalidMessage;
+use crate::msgs::codec::{Codec, Reader};
+use crate::verify::{
+    DigitallySignedStruct, HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
+};
+use crate::{CertificateError, Error, RootCertStore};
+
+/// A TLSA record, as obtained (and authenticated with DNSSEC) by the
+/// application.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TlsaRecord {
+    /// How the record is used to verify the server.
+    pub usage: TlsaUsage,
+    /// Which part of a certificate is matched.
+    pub selector: TlsaSelector,
+    /// How the selected data is compared with `data`.
+    pub matching_type: TlsaMatchingType,
+    /// The certificate association data.
+    pub data: Vec<u8>,
+}
+
+impl TlsaRecord {
+    /// Parse a TLSA record from its DNS wire format RDATA.
+    pub fn from_rdata(rdata: &[u8]) -> Result<Self, InvalidMessage> {
+        let mut r = Reader::init(rdata);
+        Ok(Self {
+            usage: TlsaUsage::read(&mut r)?,
+            selector: TlsaSelector::read(&mut r)?,
+            matching_type: TlsaMatchingType::read(&mut r)?,
+            data: r.rest().to_vec(),
+        })
+    }
+}
+
+/// A [`ServerCertVerifier`] that verifies servers using TLSA records.
+///
+/// A server is accepted if its certificate chain matches any usable TLSA
+/// record configured for its name:
+///
+/// - `DaneEe` records match the end-entity certificate.  Its names and
+///   validity period are not checked.
+/// - `DaneTa` records match a trust anchor, which must be one of the
+///   intermediates that issued the end-entity certificate, or the record
+///   data itself (for a record with the full certificate).  The end-entity
+///   certificate is then verified against that trust anchor, including its
+///   names and validity period.
+/// - `PkixEe` and `PkixTa` records are like `DaneEe` and `DaneTa`, but the
+///   chain must also be accepted by the verifier given to
+///   [`DaneVerifier::with_pkix`].  `PkixTa` records only match certificates
+///   the server sends.  Without such a verifier, these records are unusable.
+///
+/// Records with unknown parameters are unusable.  If a server name has no
+/// usable records, it is verified by the verifier given to
+/// [`DaneVerifier::with_pkix`], or otherwise rejected with
+/// [`CertificateError::UnknownIssuer`].  A chain that matches no record is
+/// rejected with [`CertificateError::ApplicationVerificationFailure`].
+///
+/// The records are inputs: no DNS lookups are made.
+pub struct DaneVerifier {
+    pkix: Option<Arc<dyn ServerCertVerifier>>,
+    records: Vec<(ServerName<'static>, Vec<TlsaRecord>)>,
+    supported: WebPkiSupportedAlgorithms,
+    sha256: Option<&'static dyn Hash>,
+    sha512: Option<&'static dyn Hash>,
+}
+
+impl DaneVerifier {
+    /// Make a verifier with no records yet.
+    ///
+    /// The cryptography used comes from the process-default [`CryptoProvider`]
+    /// for the `ring` feature.
+    #[cfg(feature = "ring")]
+    pub fn new() -> Self {
+        Self::new_with_provider(crate::crypto::ring::default_provider().into())
+            .with_sha512(&crate::crypto::ring::hash::SHA512)
+    }
+
+    /// Make a verifier with no records yet.
+    ///
+    /// The cryptography used comes from the specified [`CryptoProvider`].
+    /// Records using SHA-256 are only usable if its cipher suites include one
+    /// using SHA-256, and likewise for SHA-512; see also
+    /// [`DaneVerifier::with_sha512`].
+    pub fn new_with_provider(provider: Arc<CryptoProvider>) -> Self {
+        let hash = |algorithm| {
+            provider
+                .cipher_suites
+                .iter()
+                .map(|suite| suite.hash_provider())
+                .find(|hash| hash.algorithm() == algorithm)
+        };
+        Self {
+            pkix: None,
+            records: Vec::new(),
+            sha256: hash(HashAlgorithm::SHA256),
+            sha512: hash(HashAlgorithm::SHA512),
+            supported: provider.signature_verification_algorithms,
+        }
+    }
+
+    /// Use `sha512` to match records with the SHA-512 matching type.
+    pub fn with_sha512(mut self, sha512: &'static dyn Hash) -> Self {
+        self.sha512 = Some(sha512);
+        self
+    }
+
+    /// Use `pkix` to verify chains for `PkixTa` and `PkixEe` records, and
+    /// for servers without usable records.
+    ///
+    /// This is typically a [`WebPkiServerVerifier`](super::WebPkiServerVerifier).
+    pub fn with_pkix(mut self, pkix: Arc<dyn ServerCertVerifier>) -> Self {
+        self.pkix = Some(pkix);
+        self
+    }
+
+    /// Add `records` for `server_name`.
+    ///
+    /// Calling this more than once for the same server name adds to its
+    /// records.
+    pub fn with_records(
+        mut self,
+        server_name: ServerName<'static>,
+        records: impl IntoIterator<Item = TlsaRecord>,
+    ) -> Self {
+        match self
+            .records
+            .iter_mut()
+            .find(|(name, _)| *name == server_name)
+        {
+            Some((_, existing)) => existing.extend(records),
+            None => self
+                .records
+                .push((server_name, records.into_iter().collect())),
+        }
+        self
+    }
+
+    fn is_usable(&self, record: &TlsaRecord) -> bool {
+        let usage = match record.usage {
+            TlsaUsage::DaneTa | TlsaUsage::DaneEe => true,
+            TlsaUsage::PkixTa | TlsaUsage::PkixEe => self.pkix.is_some(),
+            TlsaUsage::Unknown(_) => false,
+        };
+        let selector = !matches!(record.selector, TlsaSelector::Unknown(_));
+        let matching_type = match record.matching_type {
+            TlsaMatchingType::Full => true,
+            TlsaMatchingType::Sha256 => self.sha256.is_some(),
+            TlsaMatchingType::Sha512 => self.sha512.is_some(),
+            TlsaMatchingType::Unknown(_) => false,
+        };
+        usage && selector && matching_type
+    }
+
+    fn matches(&self, record: &TlsaRecord, cert: &CertificateDer<'_>, spki: &[u8]) -> bool {
+        let selected = match record.selector {
+            TlsaSelector::FullCertificate => cert.as_ref(),
+            TlsaSelector::SubjectPublicKeyInfo => spki,
+            TlsaSelector::Unknown(_) => return false,
+        };
+        let hash = match record.matching_type {
+            TlsaMatchingType::Full => return selected == record.data,
+            TlsaMatchingType::Sha256 => self.sha256,
+            TlsaMatchingType::Sha512 => self.sha512,
+            TlsaMatchingType::Unknown(_) => None,
+        };
+        match hash {
+            Some(hash) => hash.hash(selected).as_ref() == record.data,
+            None => false,
+        }
+    }
+
+    /// Verify `end_entity` against `anchor`, as for a `DaneTa` record.
+    fn verify_with_anchor(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        anchor: &CertificateDer<'_>,
+        server_name: &ServerName<'_>,
+        now: UnixTime,
+    ) -> Result<(), Error> {
+        let mut roots = RootCertStore::empty();
+        roots.add(anchor.clone())?;
+        let cert = ParsedCertificate::try_from(end_entity)?;
+        verify_server_cert_signed_by_trust_anchor(
+            &cert,
+            &roots,
+            intermediates,
+            now,
+            self.supported.all,
+        )?;
+        verify_server_name(&cert, server_name)
+    }
+}
+
+impl ServerCertVerifier for DaneVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        scts: &[&[u8]],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, Error> {
+        let records = self
+            .records
+            .iter()
+            .filter(|(name, _)| name == server_name)
+            .flat_map(|(_, records)| records)
+            .filter(|record| self.is_usable(record))
+            .collect::<Vec<_>>();
+
+        let pkix = || match &self.pkix {
+            Some(pkix) => pkix.verify_server_cert(
+                end_entity,
+                intermediates,
+                server_name,
+                ocsp_response,
+                scts,
+                now,
+            ),
+            None => Err(CertificateError::UnknownIssuer.into()),
+        };
+
+        if records.is_empty() {
+            return pkix();
+        }
+
+        let end_entity_spki = ParsedCertificate::try_from(end_entity)?
+            .0
+            .subject_public_key_info();
+
+        // The intermediates, with their public keys.  Those matching a
+        // `DaneTa` or `PkixTa` record must also be on the path from the
+        // end-entity certificate, which is checked by verifying the
+        // end-entity certificate with that intermediate as the trust anchor.
+        let intermediate_spkis = intermediates
+            .iter()
+            .filter_map(|der| {
+                let cert = webpki::EndEntityCert::try_from(der).ok()?;
+                Some((der, cert.subject_public_key_info()))
+            })
+            .collect::<Vec<_>>();
+
+        let mut error = Error::from(CertificateError::ApplicationVerificationFailure);
+        for record in records {
+            match record.usage {
+                TlsaUsage::DaneEe => {
+                    if self.matches(record, end_entity, &end_entity_spki) {
+                        return Ok(ServerCertVerified::assertion());
+                    }
+                }
+                TlsaUsage::PkixEe => {
+                    if self.matches(record, end_entity, &end_entity_spki) {
+                        match pkix() {
+                            Ok(verified) => return Ok(verified),
+                            Err(err) => error = err,
+                        }
+                    }
+                }
+                TlsaUsage::DaneTa => {
+                    let in_record = match (record.selector, record.matching_type) {
+                        (TlsaSelector::FullCertificate, TlsaMatchingType::Full) => {
+                            Some(CertificateDer::from(&record.data[..]))
+                        }
+                        _ => None,
+                    };
+                    let anchors = intermediate_spkis
+                        .iter()
+                        .filter(|(der, spki)| self.matches(record, der, spki))
+                        .map(|(der, _)| ((*der).clone(), true))
+                        .chain(in_record.map(|der| (der, false)));
+                    for (anchor, presented) in anchors {
+                        match self.verify_with_anchor(
+                            end_entity,
+                            intermediates,
+                            &anchor,
+                            server_name,
+                            now,
+                        ) {
+                            Ok(()) => return Ok(ServerCertVerified::assertion()),
+                            // A presented certificate that did not issue the end-entity
+                            // certificate does not match.
+                            Err(Error::InvalidCertificate(CertificateError::UnknownIssuer))
+                                if presented => {}
+                            Err(err) => error = err,
+                        }
+                    }
+                }
+                TlsaUsage::PkixTa => {
+                    let on_path = intermediate_spkis
+                        .iter()
+                        .filter(|(der, spki)| self.matches(record, der, spki))
+                        .any(|(der, _)| {
+                            self.verify_with_anchor(
+                                end_entity,
+                                intermediates,
+                                der,
+                                server_name,
+                                now,
+                            )
+                            .is_ok()
+                        });
+                    if on_path {
+                        match pkix() {
+                            Ok(verified) => return Ok(verified),
+                            Err(err) => error = err,
+                        }
+                    }
+                }
+                TlsaUsage::Unknown(_) => {}
+            }
+        }
+
+        Err(error)
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        verify_tls12_signature(message, cert, dss, &self.supported)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        verify_tls13_signature(message, cert, dss, &self.supported)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.supported.supported_schemes()
+    }
+}
+
+impl fmt::Debug for DaneVerifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DaneVerifier")
+            .field("pkix", &self.pkix)
+            .field("records", &self.records)
+            .finish()
+    }
+}
+
+#[cfg(all(test, any(feature = "ring", feature = "aws_lc_rs")))]
+mod tests {
+    use std::prelude::v1::*;
+    use std::sync::Arc;
+
+    use pki_types::{CertificateDer, ServerName, UnixTime};
+
+    use super::{DaneVerifier, TlsaMatchingType, TlsaRecord, TlsaSelector, TlsaUsage};
+    use crate::crypto::hash::Hash;
+    use crate::verify::ServerCertVerifier;
+    use crate::webpki::WebPkiServerVerifier;
+    use crate::{test_provider, CertificateError, Error, InvalidMessage, RootCertStore};
+
+    struct Chain {
+        end_entity: CertificateDer<'static>,
+        intermediate: CertificateDer<'static>,
+        ca: CertificateDer<'static>,
+    }
+
+    fn chain(pem: &[u8]) -> Chain {
+        let mut certs = rustls_pemfile::certs(&mut &pem[..])
+            .map(|cert| cert.unwrap())
+            .collect::<Vec<_>>()
+            .into_iter();
+        Chain {
+            end_entity: certs.next().unwrap(),
+            intermediate: certs.next().unwrap(),
+            ca: certs.next().unwrap(),
+        }
+    }
+
+    fn rsa() -> Chain {
+        chain(include_bytes!("../../../test-ca/rsa/end.fullchain"))
+    }
+
+    fn ecdsa() -> Chain {
+        chain(include_bytes!("../../../test-ca/ecdsa/end.fullchain"))
+    }
+
+    fn record(
+        usage: TlsaUsage,
+        selector: TlsaSelector,
+        matching_type: TlsaMatchingType,
+        cert: &CertificateDer<'_>,
+    ) -> TlsaRecord {
+        let spki = webpki::EndEntityCert::try_from(cert)
+            .unwrap()
+            .subject_public_key_info();
+        let selected = match selector {
+            TlsaSelector::FullCertificate => cert.as_ref(),
+            _ => spki.as_ref(),
+        };
+        let data = match matching_type {
+            TlsaMatchingType::Full => selected.to_vec(),
+            TlsaMatchingType::Sha256 => test_provider::hash::SHA256
+                .hash(selected)
+                .as_ref()
+                .to_vec(),
+            _ => test_provider::hash::SHA512
+                .hash(selected)
+                .as_ref()
+                .to_vec(),
+        };
+        TlsaRecord {
+            usage,
+            selector,
+            matching_type,
+            data,
+        }
+    }
+
+    fn dane_verifier() -> DaneVerifier {
+        DaneVerifier::new_with_provider(test_provider::default_provider().into())
+            .with_sha512(&test_provider::hash::SHA512)
+    }
+
+    fn pkix_verifier() -> Arc<dyn ServerCertVerifier> {
+        let mut roots = RootCertStore::empty();
+        roots.add(rsa().ca).unwrap();
+        WebPkiServerVerifier::builder_with_provider(
+            roots.into(),
+            test_provider::default_provider().into(),
+        )
+        .build()
+        .unwrap()
+    }
+
+    fn testserver() -> ServerName<'static> {
+        ServerName::try_from("testserver.com").unwrap()
+    }
+
+    fn verify(
+        verifier: &DaneVerifier,
+        chain: &Chain,
+        intermediates: &[CertificateDer<'static>],
+        server_name: &str,
+    ) -> Result<(), Error> {
+        verifier
+            .verify_server_cert(
+                &chain.end_entity,
+                intermediates,
+                &ServerName::try_from(server_name).unwrap(),
+                &[],
+                &[],
+                UnixTime::now(),
+            )
+            .map(|_| ())
+    }
+
+    fn mismatch() -> Result<(), Error> {
+        Err(CertificateError::ApplicationVerificationFailure.into())
+    }
+
+    #[test]
+    fn parse_rdata() {
+        assert_eq!(
+            TlsaRecord::from_rdata(&[3, 1, 1, 0xab, 0xcd]),
+            Ok(TlsaRecord {
+                usage: TlsaUsage::DaneEe,
+                selector: TlsaSelector::SubjectPublicKeyInfo,
+                matching_type: TlsaMatchingType::Sha256,
+                data: vec![0xab, 0xcd],
+            })
+        );
+        assert_eq!(
+            TlsaRecord::from_rdata(&[4, 2, 3]).map(|r| (r.usage, r.selector, r.matching_type)),
+            Ok((
+                TlsaUsage::Unknown(4),
+                TlsaSelector::Unknown(2),
+                TlsaMatchingType::Unknown(3)
+            ))
+        );
+        assert_eq!(
+            TlsaRecord::from_rdata(&[3, 1]),
+            Err(InvalidMessage::MissingData("TlsaMatchingType"))
+        );
+    }
+
+    #[test]
+    fn dane_ee_matches_end_entity() {
+        let rsa = rsa();
+        for (selector, matching_type) in [
+            (TlsaSelector::FullCertificate, TlsaMatchingType::Full),
+            (TlsaSelector::FullCertificate, TlsaMatchingType::Sha256),
+            (TlsaSelector::SubjectPublicKeyInfo, TlsaMatchingType::Sha256),
+            (TlsaSelector::SubjectPublicKeyInfo, TlsaMatchingType::Sha512),
+        ] {
+            let verifier = dane_verifier().with_records(
+                testserver(),
+                [record(
+                    TlsaUsage::DaneEe,
+                    selector,
+                    matching_type,
+                    &rsa.end_entity,
+                )],
+            );
+            // names and issuers are not checked for DANE-EE
+            assert_eq!(verify(&verifier, &rsa, &[], "testserver.com"), Ok(()));
+            assert_eq!(
+                verify(&verifier, &ecdsa(), &[], "testserver.com"),
+                mismatch()
+            );
+            assert_eq!(
+                verify(&verifier, &rsa, &[], "other.com"),
+                Err(CertificateError::UnknownIssuer.into())
+            );
+        }
+    }
+
+    #[test]
+    fn dane_ta_matches_issuer() {
+        let rsa = rsa();
+        let verifier = dane_verifier().with_records(
+            testserver(),
+            [record(
+                TlsaUsage::DaneTa,
+                TlsaSelector::SubjectPublicKeyInfo,
+                TlsaMatchingType::Sha256,
+                &rsa.intermediate,
+            )],
+        );
+        assert_eq!(
+            verify(
+                &verifier,
+                &rsa,
+                core::slice::from_ref(&rsa.intermediate),
+                "testserver.com"
+            ),
+            Ok(())
+        );
+
+        // the trust anchor must be sent, and is checked against the name
+        assert_eq!(verify(&verifier, &rsa, &[], "testserver.com"), mismatch());
+        let verifier = verifier.with_records(
+            ServerName::try_from("wrong.com").unwrap(),
+            [record(
+                TlsaUsage::DaneTa,
+                TlsaSelector::SubjectPublicKeyInfo,
+                TlsaMatchingType::Sha256,
+                &rsa.intermediate,
+            )],
+        );
+        assert_eq!(
+            verify(
+                &verifier,
+                &rsa,
+                core::slice::from_ref(&rsa.intermediate),
+                "wrong.com"
+            ),
+            Err(CertificateError::NotValidForName.into())
+        );
+
+        // unrelated intermediates do not match
+        let ecdsa = ecdsa();
+        let verifier = dane_verifier().with_records(
+            testserver(),
+            [record(
+                TlsaUsage::DaneTa,
+                TlsaSelector::FullCertificate,
+                TlsaMatchingType::Sha256,
+                &ecdsa.intermediate,
+            )],
+        );
+        assert_eq!(
+            verify(
+                &verifier,
+                &rsa,
+                &[rsa.intermediate.clone(), ecdsa.intermediate],
+                "testserver.com"
+            ),
+            mismatch()
+        );
+    }
+
+    #[test]
+    fn dane_ta_full_certificate_in_record() {
+        let rsa = rsa();
+        let verifier = dane_verifier().with_records(
+            testserver(),
+            [record(
+                TlsaUsage::DaneTa,
+                TlsaSelector::FullCertificate,
+                TlsaMatchingType::Full,
+                &rsa.ca,
+            )],
+        );
+        assert_eq!(
+            verify(
+                &verifier,
+                &rsa,
+                core::slice::from_ref(&rsa.intermediate),
+                "testserver.com"
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn pkix_usages_need_pkix_verifier() {
+        let rsa = rsa();
+        let records = [
+            record(
+                TlsaUsage::PkixEe,
+                TlsaSelector::SubjectPublicKeyInfo,
+                TlsaMatchingType::Sha256,
+                &rsa.end_entity,
+            ),
+            record(
+                TlsaUsage::PkixTa,
+                TlsaSelector::SubjectPublicKeyInfo,
+                TlsaMatchingType::Sha256,
+                &rsa.intermediate,
+            ),
+        ];
+
+        for record in records {
+            // unusable without a PKIX verifier
+            let verifier = dane_verifier().with_records(testserver(), [record.clone()]);
+            assert_eq!(
+                verify(
+                    &verifier,
+                    &rsa,
+                    core::slice::from_ref(&rsa.intermediate),
+                    "testserver.com"
+                ),
+                Err(CertificateError::UnknownIssuer.into())
+            );
+
+            let verifier = verifier.with_pkix(pkix_verifier());
+            assert_eq!(
+                verify(
+                    &verifier,
+                    &rsa,
+                    core::slice::from_ref(&rsa.intermediate),
+                    "testserver.com"
+                ),
+                Ok(())
+            );
+            assert_eq!(
+                verify(&verifier, &rsa, &[], "testserver.com"),
+                match record.usage {
+                    TlsaUsage::PkixEe => Err(CertificateError::UnknownIssuer.into()),
+                    _ => mismatch(),
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn falls_back_to_pkix_without_usable_records() {
+        let rsa = rsa();
+        let mut unusable = record(
+            TlsaUsage::DaneEe,
+            TlsaSelector::FullCertificate,
+            TlsaMatchingType::Full,
+            &ecdsa().end_entity,
+        );
+        unusable.matching_type = TlsaMatchingType::Unknown(3);
+        let verifier = dane_verifier()
+            .with_records(testserver(), [unusable])
+            .with_pkix(pkix_verifier());
+        assert_eq!(
+            verify(
+                &verifier,
+                &rsa,
+                core::slice::from_ref(&rsa.intermediate),
+                "testserver.com"
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            verify(&verifier, &rsa, &[], "testserver.com"),
+            Err(CertificateError::UnknownIssuer.into())
+        );
+    }
+}
@@ -0,0 +1,414 @@
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use pki_types::CertificateDer;
+
+use super::ParsedCertificate;
+use crate::x509::TbsCertificate;
+use crate::{CertificateError, Error};
+use yasna::tags::TAG_OID;
+use yasna::{ASN1Error, ASN1ErrorKind, TagClass};
+
+/// The identity of a client, taken from a subject alternative name of its
+/// certificate.
+///
+/// See [`ClientIdentityPolicy`].
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ClientIdentity {
+    /// A [SPIFFE ID], from a `spiffe://` URI.
+    ///
+    /// [SPIFFE ID]: https://github.com/spiffe/spiffe/blob/main/standards/SPIFFE-ID.md
+    Spiffe {
+        /// The trust domain, such as `example.org`.
+        trust_domain: String,
+        /// The path, such as `/ns/default/sa/web`.  This is empty for the
+        /// ID of the trust domain itself.
+        path: String,
+    },
+    /// A DNS name.
+    DnsName(String),
+    /// An email address.
+    Email(String),
+}
+
+impl ClientIdentity {
+    /// Parse a SPIFFE ID, such as `spiffe://example.org/ns/default/sa/web`.
+    fn parse_spiffe_id(uri: &str) -> Option<Self> {
+        const MAX_LEN: usize = 2048;
+
+        if uri.len() > MAX_LEN {
+            return None;
+        }
+        let rest = uri.strip_prefix("spiffe://")?;
+        let (trust_domain, path) = match rest.find('/') {
+            Some(i) => rest.split_at(i),
+            None => (rest, ""),
+        };
+
+        let trust_domain_valid = !trust_domain.is_empty()
+            && trust_domain
+                .bytes()
+                .all(|b| matches!(b, b'a'..=b'z' | b'0'..=b'9' | b'.' | b'-' | b'_'));
+        let path_valid = path.is_empty()
+            || path.split('/').skip(1).all(|segment| {
+                !segment.is_empty()
+                    && segment != "."
+                    && segment != ".."
+                    && segment
+                        .bytes()
+                        .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'.' | b'-' | b'_'))
+            });
+        if !trust_domain_valid || !path_valid {
+            return None;
+        }
+
+        Some(Self::Spiffe {
+            trust_domain: trust_domain.to_string(),
+            path: path.to_string(),
+        })
+    }
+}
+
+/// Which clients a [`WebPkiClientVerifier`](super::WebPkiClientVerifier)
+/// accepts, based on the subject alternative names and extended key usages
+/// of their certificates.
+///
+/// This is checked after the client's certificate chain has been verified.
+/// If any identities are allowed, the certificate must have a subject
+/// alternative name matching one of them, or it is rejected with
+/// [`CertificateError::NotValidForName`].  The matching name is then the
+/// client's [`ClientIdentity`], available from
+/// [`ServerConnection::client_identity`](crate::server::ServerConnection::client_identity).
+/// If no identities are allowed, the client's identity is its first SPIFFE ID,
+/// DNS name or email address (in that order of preference), if any.
+///
+/// SPIFFE IDs are only taken from certificates with exactly one URI subject
+/// alternative name, as required for an [X.509-SVID].
+///
+/// [X.509-SVID]: https://github.com/spiffe/spiffe/blob/main/standards/X509-SVID.md
+#[derive(Clone, Debug, Default)]
+pub struct ClientIdentityPolicy {
+    spiffe_trust_domains: Vec<String>,
+    spiffe_ids: Vec<String>,
+    dns_names: Vec<String>,
+    emails: Vec<String>,
+    required_ekus: Vec<Vec<u8>>,
+}
+
+impl ClientIdentityPolicy {
+    /// Make a policy that allows any identity, and requires no extended key
+    /// usages beyond client authentication.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allow any SPIFFE ID in `trust_domain`, such as `example.org`.
+    pub fn allow_spiffe_trust_domain(mut self, trust_domain: impl Into<String>) -> Self {
+        self.spiffe_trust_domains
+            .push(trust_domain.into());
+        self
+    }
+
+    /// Allow the SPIFFE ID `id`, such as `spiffe://example.org/ns/default/sa/web`.
+    pub fn allow_spiffe_id(mut self, id: impl Into<String>) -> Self {
+        self.spiffe_ids.push(id.into());
+        self
+    }
+
+    /// Allow the DNS name `name`.  DNS names are compared case-insensitively,
+    /// and wildcards are not expanded.
+    pub fn allow_dns_name(mut self, name: impl Into<String>) -> Self {
+        self.dns_names.push(name.into());
+        self
+    }
+
+    /// Allow the email address `email`.
+    pub fn allow_email(mut self, email: impl Into<String>) -> Self {
+        self.emails.push(email.into());
+        self
+    }
+
+    /// Require the extended key usage `oid`, given as the DER encoding of
+    /// the object identifier, without its tag and length.
+    ///
+    /// The client authentication extended key usage is always required.
+    pub fn require_extended_key_usage(mut self, oid: impl Into<Vec<u8>>) -> Self {
+        self.required_ekus.push(oid.into());
+        self
+    }
+
+    fn allows_any(&self) -> bool {
+        self.spiffe_trust_domains.is_empty()
+            && self.spiffe_ids.is_empty()
+            && self.dns_names.is_empty()
+            && self.emails.is_empty()
+    }
+
+    fn allows(&self, identity: &ClientIdentity, name: &str) -> bool {
+        match identity {
+            ClientIdentity::Spiffe { trust_domain, .. } => {
+                self.spiffe_trust_domains
+                    .iter()
+                    .any(|allowed| allowed == trust_domain)
+                    || self
+                        .spiffe_ids
+                        .iter()
+                        .any(|allowed| allowed == name)
+            }
+            ClientIdentity::DnsName(name) => self
+                .dns_names
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(name)),
+            ClientIdentity::Email(email) => self
+                .emails
+                .iter()
+                .any(|allowed| allowed == email),
+        }
+    }
+
+    /// Check `end_entity` against this policy, returning the client's
+    /// identity.
+    pub(crate) fn verify(
+        &self,
+        end_entity: &CertificateDer<'_>,
+    ) -> Result<Option<ClientIdentity>, Error> {
+        let parsed = ParsedCertificate::try_from(end_entity)?;
+        // webpki only exposes a certificate's DNS names, so its URIs, email
+        // addresses and extended key usages are read here.
+        let cert = TbsCertificate::of(end_entity).ok_or(CertificateError::BadEncoding)?;
+
+        if !self.required_ekus.is_empty() {
+            let ekus = match cert.extension(OID_EXT_KEY_USAGE) {
+                Some(ekus) => extended_key_usages(ekus).ok_or(CertificateError::BadEncoding)?,
+                None => Vec::new(),
+            };
+            if !self
+                .required_ekus
+                .iter()
+                .all(|required| ekus.contains(required))
+            {
+                return Err(CertificateError::InvalidPurpose.into());
+            }
+        }
+
+        let names = match cert.extension(OID_SUBJECT_ALT_NAME) {
+            Some(names) => subject_alt_names(names).ok_or(CertificateError::BadEncoding)?,
+            None => Vec::new(),
+        };
+
+        // Candidate identities in order of preference, with the name they
+        // were taken from.
+        let mut candidates = Vec::new();
+        let mut uris = names
+            .iter()
+            .filter(|(tag, _)| *tag == GENERAL_NAME_URI);
+        if let (Some((_, uri)), None) = (uris.next(), uris.next()) {
+            if let Some(id) = ClientIdentity::parse_spiffe_id(uri) {
+                candidates.push((id, uri.as_str()));
+            }
+        }
+        for name in parsed.0.valid_dns_names() {
+            candidates.push((ClientIdentity::DnsName(name.to_string()), name));
+        }
+        for (tag, name) in &names {
+            if *tag == GENERAL_NAME_EMAIL {
+                candidates.push((ClientIdentity::Email(name.clone()), name.as_str()));
+            }
+        }
+
+        let mut candidates = candidates.into_iter();
+        if self.allows_any() {
+            return Ok(candidates
+                .next()
+                .map(|(identity, _)| identity));
+        }
+
+        match candidates.find(|(identity, name)| self.allows(identity, name)) {
+            Some((identity, _)) => Ok(Some(identity)),
+            None => Err(CertificateError::NotValidForName.into()),
+        }
+    }
+}
+
+/// Parse the contents of a `subjectAltName` extension, returning the tag and
+/// value of each `rfc822Name` and `uniformResourceIdentifier`.
+fn subject_alt_names(extension: &[u8]) -> Option<Vec<(u64, String)>> {
+    yasna::parse_der(extension, |reader| {
+        reader.collect_sequence_of(|reader| {
+            let tag = reader.lookahead_tag()?;
+            let number = match tag.tag_class {
+                TagClass::ContextSpecific => tag.tag_number,
+                _ => return Err(ASN1Error::new(ASN1ErrorKind::Invalid)),
+            };
+            if number != GENERAL_NAME_EMAIL && number != GENERAL_NAME_URI {
+                reader.read_der()?;
+                return Ok(None);
+            }
+            // These are both IA5Strings.
+            let value = reader.read_tagged_implicit(tag, |reader| reader.read_bytes())?;
+            match String::from_utf8(value) {
+                Ok(value) if value.is_ascii() => Ok(Some((number, value))),
+                _ => Err(ASN1Error::new(ASN1ErrorKind::Invalid)),
+            }
+        })
+    })
+    .ok()
+    .map(|names| names.into_iter().flatten().collect())
+}
+
+/// Parse the contents of an `extKeyUsage` extension, returning the DER
+/// encoding (without tag and length) of each object identifier.
+fn extended_key_usages(extension: &[u8]) -> Option<Vec<Vec<u8>>> {
+    yasna::parse_der(extension, |reader| {
+        reader.collect_sequence_of(|reader| {
+            reader.read_tagged_implicit(TAG_OID, |reader| reader.read_bytes())
+        })
+    })
+    .ok()
+}
+
+/// The context-specific tag numbers of `GeneralName` choices.
+const GENERAL_NAME_EMAIL: u64 = 1;
+const GENERAL_NAME_URI: u64 = 6;
+
+const OID_SUBJECT_ALT_NAME: &[u64] = &[2, 5, 29, 17];
+const OID_EXT_KEY_USAGE: &[u64] = &[2, 5, 29, 37];
+
+#[cfg(test)]
+mod tests {
+    use std::prelude::v1::*;
+
+    use pki_types::CertificateDer;
+
+    use super::{ClientIdentity, ClientIdentityPolicy};
+    use crate::CertificateError;
+
+    fn spiffe_cert() -> CertificateDer<'static> {
+        rustls_pemfile::certs(&mut &include_bytes!("../../../test-ca/rsa/client-spiffe.cert")[..])
+            .next()
+            .unwrap()
+            .unwrap()
+    }
+
+    fn plain_cert() -> CertificateDer<'static> {
+        rustls_pemfile::certs(&mut &include_bytes!("../../../test-ca/rsa/client.cert")[..])
+            .next()
+            .unwrap()
+            .unwrap()
+    }
+
+    fn web() -> ClientIdentity {
+        ClientIdentity::Spiffe {
+            trust_domain: "example.org".into(),
+            path: "/ns/default/sa/web".into(),
+        }
+    }
+
+    #[test]
+    fn parse_spiffe_ids() {
+        assert_eq!(
+            ClientIdentity::parse_spiffe_id("spiffe://example.org/ns/default/sa/web"),
+            Some(web())
+        );
+        assert_eq!(
+            ClientIdentity::parse_spiffe_id("spiffe://example.org"),
+            Some(ClientIdentity::Spiffe {
+                trust_domain: "example.org".into(),
+                path: "".into(),
+            })
+        );
+        for invalid in [
+            "https://example.org/web",
+            "spiffe://",
+            "spiffe:///web",
+            "spiffe://Example.org/web",
+            "spiffe://user@example.org/web",
+            "spiffe://example.org:443/web",
+            "spiffe://example.org/",
+            "spiffe://example.org//web",
+            "spiffe://example.org/web/",
+            "spiffe://example.org/../web",
+            "spiffe://example.org/web?query",
+            "spiffe://example.org/web#fragment",
+        ] {
+            assert_eq!(
+                ClientIdentity::parse_spiffe_id(invalid),
+                None,
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn default_policy_prefers_spiffe_id() {
+        let policy = ClientIdentityPolicy::new();
+        assert_eq!(policy.verify(&spiffe_cert()), Ok(Some(web())));
+        assert_eq!(policy.verify(&plain_cert()), Ok(None));
+    }
+
+    #[test]
+    fn allowed_identities() {
+        for policy in [
+            ClientIdentityPolicy::new().allow_spiffe_trust_domain("example.org"),
+            ClientIdentityPolicy::new().allow_spiffe_id("spiffe://example.org/ns/default/sa/web"),
+        ] {
+            assert_eq!(policy.verify(&spiffe_cert()), Ok(Some(web())));
+        }
+
+        assert_eq!(
+            ClientIdentityPolicy::new()
+                .allow_spiffe_trust_domain("other.org")
+                .allow_dns_name("WEB.example.org")
+                .verify(&spiffe_cert()),
+            Ok(Some(ClientIdentity::DnsName("web.example.org".into())))
+        );
+        assert_eq!(
+            ClientIdentityPolicy::new()
+                .allow_email("web@example.org")
+                .verify(&spiffe_cert()),
+            Ok(Some(ClientIdentity::Email("web@example.org".into())))
+        );
+
+        for policy in [
+            ClientIdentityPolicy::new().allow_spiffe_trust_domain("example.com"),
+            ClientIdentityPolicy::new().allow_spiffe_id("spiffe://example.org/ns/default/sa/db"),
+            ClientIdentityPolicy::new().allow_dns_name("*.example.org"),
+            ClientIdentityPolicy::new().allow_email("WEB@example.org"),
+        ] {
+            assert_eq!(
+                policy.verify(&spiffe_cert()),
+                Err(CertificateError::NotValidForName.into())
+            );
+        }
+        assert_eq!(
+            ClientIdentityPolicy::new()
+                .allow_spiffe_trust_domain("example.org")
+                .verify(&plain_cert()),
+            Err(CertificateError::NotValidForName.into())
+        );
+    }
+
+    #[test]
+    fn required_extended_key_usages() {
+        // 1.3.6.1.5.5.7.3.2 (id-kp-clientAuth)
+        let client_auth = [0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x02];
+        // 1.3.6.1.5.5.7.3.4 (id-kp-emailProtection)
+        let email_protection = [0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x04];
+
+        assert_eq!(
+            ClientIdentityPolicy::new()
+                .require_extended_key_usage(&client_auth[..])
+                .verify(&spiffe_cert()),
+            Ok(Some(web()))
+        );
+        assert_eq!(
+            ClientIdentityPolicy::new()
+                .require_extended_key_usage(&client_auth[..])
+                .require_extended_key_usage(&email_protection[..])
+                .verify(&spiffe_cert()),
+            Err(CertificateError::InvalidPurpose.into())
+        );
+    }
+}
@@ -10,13 +10,34 @@ use crate::error::{CertRevocationListError, CertificateError, Error, OtherError}
 
 mod anchors;
 mod client_verifier;
+mod crl_store;
+mod ct;
+mod dane;
+mod identity;
+mod ocsp;
+mod pinning;
+mod report;
 mod server_verifier;
+mod tofu;
+mod trust_domain;
 mod verify;
 
 pub use anchors::RootCertStore;
 
 pub use client_verifier::{ClientCertVerifierBuilder, WebPkiClientVerifier};
+pub use crl_store::{CrlInfo, CrlSource, CrlStore, CrlUpdate};
+pub use ct::{CtLog, CtPolicy, CtVerifier};
+pub use dane::{DaneVerifier, TlsaMatchingType, TlsaRecord, TlsaSelector, TlsaUsage};
+pub use identity::{ClientIdentity, ClientIdentityPolicy};
+pub use pinning::SpkiPinningVerifier;
+pub use report::{
+    FailedPath, RevocationCheck, RevocationMethod, RevocationStatus, VerificationReport,
+};
 pub use server_verifier::{ServerCertVerifierBuilder, WebPkiServerVerifier};
+pub use tofu::TofuVerifier;
+pub use trust_domain::{
+    TrustDomainClientVerifier, TrustDomainSelector, TrustDomainVerifierBuilder,
+};
 
 pub use verify::{verify_tls12_signature, verify_tls13_signature, WebPkiSupportedAlgorithms};
 
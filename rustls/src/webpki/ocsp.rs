@@ -0,0 +1,675 @@
+//! Validation of stapled OCSP responses ([RFC 6960]).
+//!
+//! [RFC 6960]: https://datatracker.ietf.org/doc/html/rfc6960
+
+use alloc::vec::Vec;
+use core::{fmt, slice};
+
+use pki_types::{
+    CertificateDer, SignatureVerificationAlgorithm, SubjectPublicKeyInfoDer, TrustAnchor, UnixTime,
+};
+use yasna::models::ObjectIdentifier;
+use yasna::tags::{TAG_GENERALIZEDTIME, TAG_INTEGER};
+use yasna::{ASN1Error, ASN1ErrorKind, ASN1Result, BERReader, Tag};
+
+use crate::crypto::hash::{Hash, HashAlgorithm};
+use crate::webpki::verify::{find_issuer, ParsedCertificate};
+use crate::webpki::RootCertStore;
+use crate::x509::{read_time, wrap_in_sequence, Extension, TbsCertificate};
+
+/// The revocation status given by a valid OCSP response.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum OcspStatus {
+    Good,
+    Revoked,
+    Unknown,
+}
+
+/// Why an OCSP response could not be used.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum OcspError {
+    /// The response (or a certificate it depends on) is malformed.
+    Malformed,
+    /// The responder reported an error rather than a status.
+    Unsuccessful,
+    /// We could not find the issuer of the end-entity certificate.
+    UnknownIssuer,
+    /// The response was not signed by the issuer or a responder it delegated to.
+    BadSignature,
+    /// The response does not cover the end-entity certificate.
+    WrongCertificate,
+    /// The response is not yet valid, or has expired.
+    Stale,
+}
+
+/// Hash functions available for matching the `CertID` in a response.
+#[derive(Clone, Default)]
+pub(crate) struct CertIdHashes(Vec<&'static dyn Hash>);
+
+impl CertIdHashes {
+    pub(crate) fn new(hashes: impl IntoIterator<Item = &'static dyn Hash>) -> Self {
+        let mut unique = Vec::<&'static dyn Hash>::new();
+        for hash in hashes {
+            if !unique
+                .iter()
+                .any(|h| h.algorithm() == hash.algorithm())
+            {
+                unique.push(hash);
+            }
+        }
+        Self(unique)
+    }
+
+    fn find(&self, algorithm: HashAlgorithm) -> Option<&'static dyn Hash> {
+        self.0
+            .iter()
+            .find(|h| h.algorithm() == algorithm)
+            .copied()
+    }
+}
+
+impl fmt::Debug for CertIdHashes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.0.iter().map(|h| h.algorithm()))
+            .finish()
+    }
+}
+
+/// Return true if `end_entity` carries the TLS Feature extension
+/// requesting `status_request` ([RFC 7633]), a.k.a. "must-staple".
+///
+/// [RFC 7633]: https://datatracker.ietf.org/doc/html/rfc7633
+pub(crate) fn requires_stapled_response(end_entity: &CertificateDer<'_>) -> bool {
+    const STATUS_REQUEST: u64 = 5;
+
+    let tbs = match TbsCertificate::of(end_entity) {
+        Some(tbs) => tbs,
+        None => return false,
+    };
+    let features = match tbs.extension(OID_TLS_FEATURE) {
+        Some(features) => features,
+        None => return false,
+    };
+
+    yasna::parse_der(features, |reader| {
+        reader.collect_sequence_of(|reader| reader.read_u64())
+    })
+    .map(|features| features.contains(&STATUS_REQUEST))
+    .unwrap_or(false)
+}
+
+/// Validate the OCSP `response` for `end_entity`, and return the status it gives.
+///
+/// The response must be signed by the issuer of `end_entity` (found among
+/// `intermediates` and `roots`), or by a responder certificate included in the
+/// response which that issuer has authorised for OCSP signing.  It must be
+/// current at `now`, and its `CertID` must identify `end_entity`.
+///
+/// The `CertID` hashes of the issuer's name and key are checked if `hashes`
+/// contains the hash function used.  Otherwise (typically for SHA-1, which
+/// providers do not offer) only the serial number is matched; this is sound
+/// because the response is bound to the issuer by its signature.
+pub(crate) fn check_response(
+    response: &[u8],
+    end_entity: &CertificateDer<'_>,
+    intermediates: &[CertificateDer<'_>],
+    roots: &RootCertStore,
+    supported_algs: &[&dyn SignatureVerificationAlgorithm],
+    hashes: &CertIdHashes,
+    now: UnixTime,
+) -> Result<OcspStatus, OcspError> {
+    verified_single_response(
+        response,
+        end_entity,
+        intermediates,
+        roots,
+        supported_algs,
+        hashes,
+        now,
+    )
+    .map(|single| single.status)
+}
+
+/// Return the contents of the `extnValue` of the `singleExtensions` entry with
+/// object identifier `oid`, in the OCSP `response` for `end_entity`.
+///
+/// The response is validated as by [`check_response`], except that the issuer
+/// of `end_entity` must be among `intermediates`.  `None` is returned unless it
+/// is valid and gives the status good.
+pub(crate) fn good_single_extension(
+    response: &[u8],
+    end_entity: &CertificateDer<'_>,
+    intermediates: &[CertificateDer<'_>],
+    supported_algs: &[&dyn SignatureVerificationAlgorithm],
+    hashes: &CertIdHashes,
+    oid: &[u64],
+    now: UnixTime,
+) -> Option<Vec<u8>> {
+    let single = verified_single_response(
+        response,
+        end_entity,
+        intermediates,
+        &RootCertStore::empty(),
+        supported_algs,
+        hashes,
+        now,
+    )
+    .ok()?;
+    match single.status {
+        OcspStatus::Good => Extension::find(&single.extensions, oid).map(<[u8]>::to_vec),
+        OcspStatus::Revoked | OcspStatus::Unknown => None,
+    }
+}
+
+fn verified_single_response(
+    response: &[u8],
+    end_entity: &CertificateDer<'_>,
+    intermediates: &[CertificateDer<'_>],
+    roots: &RootCertStore,
+    supported_algs: &[&dyn SignatureVerificationAlgorithm],
+    hashes: &CertIdHashes,
+    now: UnixTime,
+) -> Result<SingleResponse, OcspError> {
+    let cert = ParsedCertificate::try_from(end_entity).map_err(|_| OcspError::Malformed)?;
+    let issuer = find_issuer(&cert, intermediates, &roots.roots, now, supported_algs)
+        .and_then(Issuer::new)
+        .ok_or(OcspError::UnknownIssuer)?;
+    let basic = BasicResponse::parse(response)?;
+    basic.verify_signer(&issuer, supported_algs, now)?;
+
+    let single = basic
+        .responses
+        .into_iter()
+        .find(|single| single.is_for(&cert, &issuer, hashes))
+        .ok_or(OcspError::WrongCertificate)?;
+
+    let fresh = match single.next_update {
+        Some(next_update) => single.this_update <= now && now <= next_update,
+        None => {
+            single.this_update <= now
+                && now.as_secs() - single.this_update.as_secs() <= MAX_RESPONSE_AGE_SECS
+        }
+    };
+    match fresh {
+        true => Ok(single),
+        false => Err(OcspError::Stale),
+    }
+}
+
+/// The issuer of the end-entity certificate.
+struct Issuer {
+    anchor: TrustAnchor<'static>,
+    /// The issuer's public key, as a DER-encoded `SubjectPublicKeyInfo`.
+    spki: SubjectPublicKeyInfoDer<'static>,
+    /// The `subjectPublicKey` bits of `spki`.
+    key: Vec<u8>,
+}
+
+impl Issuer {
+    fn new(anchor: TrustAnchor<'static>) -> Option<Self> {
+        let spki = wrap_in_sequence(&anchor.subject_public_key_info);
+        let key = yasna::parse_der(&spki, |reader| {
+            reader.read_sequence(|reader| {
+                reader.next().read_der()?;
+                read_bit_string(reader.next())
+            })
+        })
+        .ok()?;
+
+        Some(Self {
+            anchor,
+            spki: spki.into(),
+            key,
+        })
+    }
+}
+
+/// The parts of a `BasicOCSPResponse` we need.
+struct BasicResponse {
+    /// The entire encoding of `tbsResponseData`, which is the signed data.
+    tbs: Vec<u8>,
+    /// The entire encoding of the `signatureAlgorithm`.
+    signature_algorithm: Vec<u8>,
+    signature: Vec<u8>,
+    certs: Vec<CertificateDer<'static>>,
+    responses: Vec<SingleResponse>,
+}
+
+impl BasicResponse {
+    /// Parse an `OCSPResponse`, which must be successful and contain a
+    /// `BasicOCSPResponse`.
+    fn parse(response: &[u8]) -> Result<Self, OcspError> {
+        let basic = yasna::parse_der(response, |reader| {
+            reader.read_sequence(|reader| {
+                if reader.next().read_enum()? != 0 {
+                    reader.read_optional(|reader| reader.read_der())?;
+                    return Ok(None);
+                }
+
+                reader
+                    .next()
+                    .read_tagged(Tag::context(0), |reader| {
+                        reader.read_sequence(|reader| {
+                            if reader.next().read_oid()?
+                                != ObjectIdentifier::from_slice(OID_OCSP_BASIC)
+                            {
+                                return Err(ASN1Error::new(ASN1ErrorKind::Invalid));
+                            }
+                            reader.next().read_bytes()
+                        })
+                    })
+                    .map(Some)
+            })
+        })
+        .map_err(|_| OcspError::Malformed)?
+        .ok_or(OcspError::Unsuccessful)?;
+
+        yasna::parse_der(&basic, Self::read).map_err(|_| OcspError::Malformed)
+    }
+
+    fn read(reader: BERReader<'_, '_>) -> ASN1Result<Self> {
+        reader.read_sequence(|reader| {
+            let (responses, tbs) = reader
+                .next()
+                .read_with_buffer(|reader| {
+                    reader.read_sequence(|reader| {
+                        reader.read_optional(|reader| {
+                            reader.read_tagged(Tag::context(0), |reader| reader.read_u8())
+                        })?;
+                        // responderID: we find the signer by trying the candidates instead.
+                        reader.next().read_der()?;
+                        // producedAt
+                        reader
+                            .next()
+                            .read_tagged_implicit(TAG_GENERALIZEDTIME, |reader| {
+                                reader.read_bytes()
+                            })?;
+                        let responses = reader
+                            .next()
+                            .collect_sequence_of(SingleResponse::read)?;
+                        reader.read_optional(|reader| {
+                            reader.read_tagged(Tag::context(1), |reader| reader.read_der())
+                        })?;
+                        Ok(responses)
+                    })
+                })?;
+            let signature_algorithm = reader.next().read_der()?;
+            let signature = read_bit_string(reader.next())?;
+            let certs = reader
+                .read_optional(|reader| {
+                    reader.read_tagged(Tag::context(0), |reader| {
+                        reader.collect_sequence_of(|reader| {
+                            reader
+                                .read_der()
+                                .map(CertificateDer::from)
+                        })
+                    })
+                })?
+                .unwrap_or_default();
+
+            Ok(Self {
+                tbs: tbs.to_vec(),
+                signature_algorithm,
+                signature,
+                certs,
+                responses,
+            })
+        })
+    }
+
+    /// Check the response was signed by `issuer`, or by a responder that
+    /// `issuer` delegated to.
+    fn verify_signer(
+        &self,
+        issuer: &Issuer,
+        supported_algs: &[&dyn SignatureVerificationAlgorithm],
+        now: UnixTime,
+    ) -> Result<(), OcspError> {
+        let issuer_key =
+            webpki::RawPublicKeyEntity::try_from(&issuer.spki).map_err(|_| OcspError::Malformed)?;
+        if self.is_signed_by(supported_algs, |alg| {
+            issuer_key.verify_signature(alg, &self.tbs, &self.signature)
+        }) {
+            return Ok(());
+        }
+
+        for der in &self.certs {
+            let responder =
+                webpki::EndEntityCert::try_from(der).map_err(|_| OcspError::Malformed)?;
+            // RFC 6960 section 4.2.2.2: a delegated responder certificate must be issued
+            // directly by the CA, and include `id-kp-OCSPSigning` in its extended key usage.
+            let authorized = responder
+                .verify_for_usage(
+                    supported_algs,
+                    slice::from_ref(&issuer.anchor),
+                    &[],
+                    now,
+                    webpki::KeyUsage::required(EKU_OCSP_SIGNING),
+                    None,
+                    None,
+                )
+                .is_ok();
+            if authorized
+                && self.is_signed_by(supported_algs, |alg| {
+                    responder.verify_signature(alg, &self.tbs, &self.signature)
+                })
+            {
+                return Ok(());
+            }
+        }
+
+        Err(OcspError::BadSignature)
+    }
+
+    /// Does `verify` accept the signature with one of `supported_algs` matching
+    /// the response's signature algorithm?
+    fn is_signed_by(
+        &self,
+        supported_algs: &[&dyn SignatureVerificationAlgorithm],
+        verify: impl Fn(&dyn SignatureVerificationAlgorithm) -> Result<(), webpki::Error>,
+    ) -> bool {
+        supported_algs
+            .iter()
+            .filter(|alg| {
+                wrap_in_sequence(alg.signature_alg_id().as_ref()) == self.signature_algorithm
+            })
+            .any(|alg| verify(*alg).is_ok())
+    }
+}
+
+/// The parts of a `SingleResponse` we need.
+struct SingleResponse {
+    hash_algorithm: ObjectIdentifier,
+    issuer_name_hash: Vec<u8>,
+    issuer_key_hash: Vec<u8>,
+    /// The contents of the `serialNumber`.
+    serial: Vec<u8>,
+    status: OcspStatus,
+    this_update: UnixTime,
+    next_update: Option<UnixTime>,
+    extensions: Vec<Extension>,
+}
+
+impl SingleResponse {
+    fn read(reader: BERReader<'_, '_>) -> ASN1Result<Self> {
+        reader.read_sequence(|reader| {
+            let (hash_algorithm, issuer_name_hash, issuer_key_hash, serial) =
+                reader.next().read_sequence(|reader| {
+                    let hash_algorithm = reader.next().read_sequence(|reader| {
+                        let id = reader.next().read_oid()?;
+                        reader.read_optional(|reader| reader.read_null())?;
+                        Ok(id)
+                    })?;
+                    let issuer_name_hash = reader.next().read_bytes()?;
+                    let issuer_key_hash = reader.next().read_bytes()?;
+                    let serial = reader
+                        .next()
+                        .read_tagged_implicit(TAG_INTEGER, |reader| reader.read_bytes())?;
+                    Ok((hash_algorithm, issuer_name_hash, issuer_key_hash, serial))
+                })?;
+
+            let cert_status = reader.next();
+            let status = match cert_status.lookahead_tag()? {
+                tag if tag == Tag::context(0) => OcspStatus::Good,
+                tag if tag == Tag::context(1) => OcspStatus::Revoked,
+                tag if tag == Tag::context(2) => OcspStatus::Unknown,
+                _ => return Err(ASN1Error::new(ASN1ErrorKind::Invalid)),
+            };
+            cert_status.read_der()?;
+
+            let this_update = read_time(reader.next())?;
+            let next_update =
+                reader.read_optional(|reader| reader.read_tagged(Tag::context(0), read_time))?;
+            let extensions = reader
+                .read_optional(|reader| reader.read_tagged(Tag::context(1), Extension::read_all))?
+                .unwrap_or_default();
+
+            Ok(Self {
+                hash_algorithm,
+                issuer_name_hash,
+                issuer_key_hash,
+                serial,
+                status,
+                this_update,
+                next_update,
+                extensions,
+            })
+        })
+    }
+
+    /// Does this response's `CertID` identify `cert`, issued by `issuer`?
+    fn is_for(&self, cert: &ParsedCertificate<'_>, issuer: &Issuer, hashes: &CertIdHashes) -> bool {
+        if self.serial != cert.0.serial() {
+            return false;
+        }
+
+        let algorithm = [
+            (OID_SHA1, HashAlgorithm::SHA1),
+            (OID_SHA256, HashAlgorithm::SHA256),
+            (OID_SHA384, HashAlgorithm::SHA384),
+            (OID_SHA512, HashAlgorithm::SHA512),
+        ]
+        .iter()
+        .find(|(id, _)| {
+            self.hash_algorithm
+                .components()
+                .as_slice()
+                == *id
+        })
+        .map(|(_, algorithm)| *algorithm);
+        let algorithm = match algorithm {
+            Some(algorithm) => algorithm,
+            None => return false,
+        };
+
+        match hashes.find(algorithm) {
+            Some(hash) => {
+                hash.hash(&wrap_in_sequence(&issuer.anchor.subject))
+                    .as_ref()
+                    == self.issuer_name_hash
+                    && hash.hash(&issuer.key).as_ref() == self.issuer_key_hash
+            }
+            None => true,
+        }
+    }
+}
+
+/// Read a BIT STRING with no unused bits.
+fn read_bit_string(reader: BERReader<'_, '_>) -> ASN1Result<Vec<u8>> {
+    match reader.read_bitvec_bytes()? {
+        (bytes, bits) if bits == bytes.len() * 8 => Ok(bytes),
+        _ => Err(ASN1Error::new(ASN1ErrorKind::Invalid)),
+    }
+}
+
+/// How old a response without a `nextUpdate` may be: RFC 6960 leaves this
+/// to local policy.
+const MAX_RESPONSE_AGE_SECS: u64 = 7 * 24 * 60 * 60;
+
+const OID_OCSP_BASIC: &[u64] = &[1, 3, 6, 1, 5, 5, 7, 48, 1, 1];
+const OID_TLS_FEATURE: &[u64] = &[1, 3, 6, 1, 5, 5, 7, 1, 24];
+const OID_SHA1: &[u64] = &[1, 3, 14, 3, 2, 26];
+const OID_SHA256: &[u64] = &[2, 16, 840, 1, 101, 3, 4, 2, 1];
+const OID_SHA384: &[u64] = &[2, 16, 840, 1, 101, 3, 4, 2, 2];
+const OID_SHA512: &[u64] = &[2, 16, 840, 1, 101, 3, 4, 2, 3];
+/// `id-kp-OCSPSigning` (1.3.6.1.5.5.7.3.9), DER-encoded as webpki requires.
+const EKU_OCSP_SIGNING: &[u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x09];
+
+#[cfg(all(test, any(feature = "ring", feature = "aws_lc_rs")))]
+mod tests {
+    use core::time::Duration;
+
+    use pki_types::{CertificateDer, UnixTime};
+
+    use super::*;
+    use crate::test_provider;
+
+    /// thisUpdate of the responses in test-ca/rsa/end.ocsp.*.der
+    const PRODUCED_AT: u64 = 1_792_359_711;
+
+    const END: &[u8] = include_bytes!("../../../test-ca/rsa/end.cert");
+    const END_MUST_STAPLE: &[u8] = include_bytes!("../../../test-ca/rsa/end-must-staple.cert");
+    const INTER: &[u8] = include_bytes!("../../../test-ca/rsa/inter.cert");
+    const CA: &[u8] = include_bytes!("../../../test-ca/rsa/ca.der");
+    const OCSP_GOOD: &[u8] = include_bytes!("../../../test-ca/rsa/end.ocsp.good.der");
+    const OCSP_GOOD_SHA256: &[u8] = include_bytes!("../../../test-ca/rsa/end.ocsp.good-sha256.der");
+    const OCSP_DELEGATED: &[u8] = include_bytes!("../../../test-ca/rsa/end.ocsp.delegated.der");
+    const OCSP_REVOKED: &[u8] = include_bytes!("../../../test-ca/rsa/end.ocsp.revoked.der");
+    const OCSP_UNKNOWN: &[u8] = include_bytes!("../../../test-ca/rsa/end.ocsp.unknown.der");
+
+    fn load_cert(pem: &[u8]) -> CertificateDer<'static> {
+        rustls_pemfile::certs(&mut &pem[..])
+            .next()
+            .unwrap()
+            .unwrap()
+    }
+
+    /// Check `response` for `end_entity` (issued by the RSA intermediate), `secs`
+    /// after the test responses were produced.
+    fn check(response: &[u8], end_entity: &[u8], secs: i64) -> Result<OcspStatus, OcspError> {
+        let provider = test_provider::default_provider();
+        let hashes = CertIdHashes::new(
+            provider
+                .cipher_suites
+                .iter()
+                .map(|suite| suite.hash_provider()),
+        );
+        let mut roots = RootCertStore::empty();
+        roots
+            .add(CertificateDer::from(CA))
+            .unwrap();
+        let now = (PRODUCED_AT as i64 + secs) as u64;
+
+        check_response(
+            response,
+            &load_cert(end_entity),
+            &[load_cert(INTER)],
+            &roots,
+            provider
+                .signature_verification_algorithms
+                .all,
+            &hashes,
+            UnixTime::since_unix_epoch(Duration::from_secs(now)),
+        )
+    }
+
+    /// `response` with the last digit of its signed producedAt time changed.
+    fn tampered(response: &[u8]) -> Vec<u8> {
+        const GENERALIZED_TIME: u8 = 0x18;
+        let produced_at = response
+            .windows(2)
+            .position(|w| w == [GENERALIZED_TIME, 15])
+            .unwrap();
+        let mut tampered = response.to_vec();
+        tampered[produced_at + 15] ^= 0x01;
+        tampered
+    }
+
+    #[test]
+    fn test_statuses() {
+        assert_eq!(check(OCSP_GOOD, END, 3600), Ok(OcspStatus::Good));
+        assert_eq!(check(OCSP_GOOD_SHA256, END, 3600), Ok(OcspStatus::Good));
+        assert_eq!(check(OCSP_DELEGATED, END, 3600), Ok(OcspStatus::Good));
+        assert_eq!(check(OCSP_REVOKED, END, 3600), Ok(OcspStatus::Revoked));
+        assert_eq!(check(OCSP_UNKNOWN, END, 3600), Ok(OcspStatus::Unknown));
+    }
+
+    #[test]
+    fn test_stale() {
+        assert_eq!(check(OCSP_GOOD, END, -1), Err(OcspError::Stale));
+        assert_eq!(
+            check(OCSP_GOOD, END, 7 * 24 * 3600 + 1),
+            Err(OcspError::Stale)
+        );
+    }
+
+    #[test]
+    fn test_unsuccessful() {
+        // responseStatus malformedRequest
+        assert_eq!(
+            check(&[0x30, 0x03, 0x0a, 0x01, 0x01], END, 3600),
+            Err(OcspError::Unsuccessful)
+        );
+    }
+
+    #[test]
+    fn test_wrong_certificate() {
+        assert_eq!(
+            check(OCSP_GOOD, END_MUST_STAPLE, 3600),
+            Err(OcspError::WrongCertificate)
+        );
+    }
+
+    #[test]
+    fn test_bad_signature() {
+        assert_eq!(
+            check(&tampered(OCSP_GOOD), END, 3600),
+            Err(OcspError::BadSignature)
+        );
+        assert_eq!(
+            check(&tampered(OCSP_GOOD_SHA256), END, 3600),
+            Err(OcspError::BadSignature)
+        );
+        assert_eq!(
+            check(&tampered(OCSP_DELEGATED), END, 3600),
+            Err(OcspError::BadSignature)
+        );
+    }
+
+    #[test]
+    fn test_malformed() {
+        assert_eq!(
+            check(&OCSP_GOOD[..OCSP_GOOD.len() - 1], END, 3600),
+            Err(OcspError::Malformed)
+        );
+        assert_eq!(check(&[], END, 3600), Err(OcspError::Malformed));
+
+        // The same response, with the length of its responseStatus in the long form.
+        assert_eq!(OCSP_GOOD[1], 0x82);
+        assert_eq!(OCSP_GOOD[4..7], [0x0a, 0x01, 0x00]);
+        let length = u16::from_be_bytes([OCSP_GOOD[2], OCSP_GOOD[3]]) + 1;
+        let mut non_der = vec![0x30, 0x82];
+        non_der.extend_from_slice(&length.to_be_bytes());
+        non_der.extend_from_slice(&[0x0a, 0x81, 0x01, 0x00]);
+        non_der.extend_from_slice(&OCSP_GOOD[7..]);
+        assert_eq!(check(&non_der, END, 3600), Err(OcspError::Malformed));
+    }
+
+    #[test]
+    fn test_requires_stapled_response() {
+        assert!(requires_stapled_response(&load_cert(END_MUST_STAPLE)));
+        assert!(!requires_stapled_response(&load_cert(END)));
+    }
+
+    #[test]
+    fn test_good_single_extension() {
+        let provider = test_provider::default_provider();
+        let hashes = CertIdHashes::new(
+            provider
+                .cipher_suites
+                .iter()
+                .map(|suite| suite.hash_provider()),
+        );
+        let has_extension = |response: &[u8]| {
+            good_single_extension(
+                response,
+                &load_cert(END),
+                &[load_cert(INTER)],
+                provider
+                    .signature_verification_algorithms
+                    .all,
+                &hashes,
+                OID_TLS_FEATURE,
+                UnixTime::since_unix_epoch(Duration::from_secs(PRODUCED_AT + 3600)),
+            )
+            .is_some()
+        };
+
+        // The test responses carry no single extensions.
+        assert!(!has_extension(OCSP_GOOD));
+        assert!(!has_extension(&tampered(OCSP_GOOD)));
+        assert!(!has_extension(OCSP_REVOKED));
+        assert!(!has_extension(&OCSP_GOOD[..10]));
+    }
+}
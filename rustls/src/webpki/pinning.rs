@@ -0,0 +1,532 @@
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+
+use pki_types::{CertificateDer, ServerName, UnixTime};
+
+use crate::crypto::hash::{Hash, HashAlgorithm};
+use crate::crypto::{CryptoProvider, WebPkiSupportedAlgorithms};
+use crate::enums::SignatureScheme;
+#[cfg(feature = "logging")]
+use crate::log::warn;
+use crate::verify::{
+    DigitallySignedStruct, HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
+};
+use crate::webpki::verify::ParsedCertificate;
+use crate::webpki::VerificationReport;
+use crate::x509::wrap_in_sequence;
+use crate::{CertificateError, Error, RootCertStore};
+
+/// A [`ServerCertVerifier`] that pins server public keys, on top of another
+/// verifier.
+///
+/// Pins are SHA-256 hashes of DER-encoded `SubjectPublicKeyInfo`s, as
+/// produced by:
+///
+/// ```text
+/// openssl x509 -in cert.pem -pubkey -noout | openssl pkey -pubin -outform der | openssl dgst -sha256
+/// ```
+///
+/// The wrapped verifier is called first, and must accept the certificate.
+/// Then, if any pins are configured for the server name, webpki must be able
+/// to build a path from the end-entity certificate to one of the configured
+/// trust anchors in which some certificate has a pinned public key.  That may
+/// be the end-entity certificate, an intermediate on the path, or the trust
+/// anchor itself, whether or not the server sends it.  Intermediates that the
+/// server sent but which are not on such a path are not considered.
+///
+/// Server names without pins are not restricted.  A mismatch fails with
+/// [`CertificateError::ApplicationVerificationFailure`], unless
+/// [`SpkiPinningVerifier::report_only`] is used.
+pub struct SpkiPinningVerifier {
+    inner: Arc<dyn ServerCertVerifier>,
+    roots: Arc<RootCertStore>,
+    pins: Vec<(ServerName<'static>, Vec<[u8; 32]>)>,
+    report_only: bool,
+    supported: WebPkiSupportedAlgorithms,
+    sha256: Option<&'static dyn Hash>,
+}
+
+impl SpkiPinningVerifier {
+    /// Make a verifier that accepts what `inner` accepts, with no pins yet.
+    ///
+    /// Pins are matched against paths to the trust anchors in `roots`, which
+    /// should be those `inner` uses.
+    ///
+    /// The cryptography used comes from the process-default [`CryptoProvider`]
+    /// for the `ring` feature.
+    #[cfg(feature = "ring")]
+    pub fn new(inner: Arc<dyn ServerCertVerifier>, roots: Arc<RootCertStore>) -> Self {
+        Self::new_with_provider(inner, roots, crate::crypto::ring::default_provider().into())
+    }
+
+    /// Make a verifier that accepts what `inner` accepts, with no pins yet.
+    ///
+    /// Pins are matched against paths to the trust anchors in `roots`, which
+    /// should be those `inner` uses.
+    ///
+    /// The cryptography used comes from the specified [`CryptoProvider`].  Its
+    /// cipher suites must include one using SHA-256, or no certificate will
+    /// match any pin.
+    pub fn new_with_provider(
+        inner: Arc<dyn ServerCertVerifier>,
+        roots: Arc<RootCertStore>,
+        provider: Arc<CryptoProvider>,
+    ) -> Self {
+        let sha256 = provider
+            .cipher_suites
+            .iter()
+            .map(|suite| suite.hash_provider())
+            .find(|hash| hash.algorithm() == HashAlgorithm::SHA256);
+        Self {
+            inner,
+            roots,
+            pins: Vec::new(),
+            report_only: false,
+            supported: provider.signature_verification_algorithms,
+            sha256,
+        }
+    }
+
+    /// Add `pins` for `server_name`.
+    ///
+    /// Calling this more than once for the same server name adds to its pins.
+    pub fn with_pins(
+        mut self,
+        server_name: ServerName<'static>,
+        pins: impl IntoIterator<Item = [u8; 32]>,
+    ) -> Self {
+        match self
+            .pins
+            .iter_mut()
+            .find(|(name, _)| *name == server_name)
+        {
+            Some((_, existing)) => existing.extend(pins),
+            None => self
+                .pins
+                .push((server_name, pins.into_iter().collect())),
+        }
+        self
+    }
+
+    /// Log pin mismatches, instead of rejecting the certificate.
+    ///
+    /// This is useful to check a new set of pins before enforcing it.
+    pub fn report_only(mut self) -> Self {
+        self.report_only = true;
+        self
+    }
+
+    fn check_pins(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        now: UnixTime,
+    ) -> Result<(), Error> {
+        let pins = match self
+            .pins
+            .iter()
+            .find(|(name, _)| name == server_name)
+        {
+            Some((_, pins)) => pins,
+            None => return Ok(()),
+        };
+
+        if self.chain_matches(end_entity, intermediates, pins, now) {
+            return Ok(());
+        }
+
+        match self.report_only {
+            true => {
+                warn!("Certificate chain for {:?} matches no pin", server_name);
+                Ok(())
+            }
+            false => Err(CertificateError::ApplicationVerificationFailure.into()),
+        }
+    }
+
+    /// Can webpki build a path from `end_entity` to one of our trust anchors in
+    /// which some certificate has a public key in `pins`?
+    fn chain_matches(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        pins: &[[u8; 32]],
+        now: UnixTime,
+    ) -> bool {
+        let sha256 = match self.sha256 {
+            Some(sha256) => sha256,
+            None => return false,
+        };
+        let cert = match ParsedCertificate::try_from(end_entity) {
+            Ok(cert) => cert,
+            Err(_) => return false,
+        };
+
+        let pinned = |spki: &[u8]| {
+            let hash = sha256.hash(spki);
+            pins.iter()
+                .any(|pin| pin[..] == *hash.as_ref())
+        };
+        let path_is_pinned = |path: &webpki::VerifiedPath<'_>| {
+            let pinned = pinned(
+                &path
+                    .end_entity()
+                    .subject_public_key_info(),
+            ) || path
+                .intermediate_certificates()
+                .any(|cert| pinned(&cert.subject_public_key_info()))
+                || pinned(&wrap_in_sequence(&path.anchor().subject_public_key_info));
+            match pinned {
+                true => Ok(()),
+                false => Err(webpki::Error::UnknownIssuer),
+            }
+        };
+
+        cert.0
+            .verify_for_usage(
+                self.supported.all,
+                &self.roots.roots,
+                intermediates,
+                now,
+                webpki::KeyUsage::server_auth(),
+                None,
+                Some(&path_is_pinned),
+            )
+            .is_ok()
+    }
+}
+
+impl ServerCertVerifier for SpkiPinningVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        scts: &[&[u8]],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, Error> {
+        let verified = self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            scts,
+            now,
+        )?;
+        self.check_pins(end_entity, intermediates, server_name, now)?;
+        Ok(verified)
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        self.inner
+            .verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        self.inner
+            .verify_tls13_signature(message, cert, dss)
+    }
+
+    fn verify_server_cert_with_report(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        scts: &[&[u8]],
+        now: UnixTime,
+    ) -> (
+        Result<ServerCertVerified, Error>,
+        Option<VerificationReport>,
+    ) {
+        let (result, mut report) = self
+            .inner
+            .verify_server_cert_with_report(
+                end_entity,
+                intermediates,
+                server_name,
+                ocsp_response,
+                scts,
+                now,
+            );
+        let result = result.and_then(|verified| {
+            self.check_pins(end_entity, intermediates, server_name, now)?;
+            Ok(verified)
+        });
+        if let Some(report) = &mut report {
+            report.result = result
+                .as_ref()
+                .map(|_| ())
+                .map_err(Error::clone);
+        }
+        (result, report)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+impl fmt::Debug for SpkiPinningVerifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SpkiPinningVerifier")
+            .field("inner", &self.inner)
+            .field("pins", &self.pins)
+            .field("report_only", &self.report_only)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::prelude::v1::*;
+    use std::sync::Arc;
+
+    use pki_types::{CertificateDer, ServerName, UnixTime};
+
+    use super::SpkiPinningVerifier;
+    use crate::crypto::hash::HashAlgorithm;
+    use crate::verify::ServerCertVerifier;
+    use crate::webpki::WebPkiServerVerifier;
+    use crate::{test_provider, CertificateError, Error, RootCertStore};
+
+    fn load_certs(pem: &[u8]) -> Vec<CertificateDer<'static>> {
+        rustls_pemfile::certs(&mut &pem[..])
+            .map(|cert| cert.unwrap())
+            .collect()
+    }
+
+    fn pin(cert: &CertificateDer<'_>) -> [u8; 32] {
+        let hash = test_provider::default_provider()
+            .cipher_suites
+            .iter()
+            .map(|suite| suite.hash_provider())
+            .find(|hash| hash.algorithm() == HashAlgorithm::SHA256)
+            .unwrap()
+            .hash(
+                &webpki::EndEntityCert::try_from(cert)
+                    .unwrap()
+                    .subject_public_key_info(),
+            );
+        let mut pin = [0u8; 32];
+        pin.copy_from_slice(hash.as_ref());
+        pin
+    }
+
+    struct Chain {
+        end_entity: CertificateDer<'static>,
+        intermediate: CertificateDer<'static>,
+        ca: CertificateDer<'static>,
+    }
+
+    fn rsa_chain() -> Chain {
+        let mut chain =
+            load_certs(include_bytes!("../../../test-ca/rsa/end.fullchain")).into_iter();
+        Chain {
+            end_entity: chain.next().unwrap(),
+            intermediate: chain.next().unwrap(),
+            ca: chain.next().unwrap(),
+        }
+    }
+
+    fn ecdsa_chain() -> Chain {
+        let mut chain =
+            load_certs(include_bytes!("../../../test-ca/ecdsa/end.fullchain")).into_iter();
+        Chain {
+            end_entity: chain.next().unwrap(),
+            intermediate: chain.next().unwrap(),
+            ca: chain.next().unwrap(),
+        }
+    }
+
+    fn pinning_verifier() -> SpkiPinningVerifier {
+        let mut roots = RootCertStore::empty();
+        roots.add(rsa_chain().ca).unwrap();
+        let roots = Arc::new(roots);
+        let inner = WebPkiServerVerifier::builder_with_provider(
+            Arc::clone(&roots),
+            test_provider::default_provider().into(),
+        )
+        .build()
+        .unwrap();
+        SpkiPinningVerifier::new_with_provider(
+            inner,
+            roots,
+            test_provider::default_provider().into(),
+        )
+    }
+
+    fn verify(
+        verifier: &SpkiPinningVerifier,
+        intermediates: &[CertificateDer<'static>],
+        server_name: &str,
+    ) -> Result<(), Error> {
+        verifier
+            .verify_server_cert(
+                &rsa_chain().end_entity,
+                intermediates,
+                &ServerName::try_from(server_name).unwrap(),
+                &[],
+                &[],
+                UnixTime::now(),
+            )
+            .map(|_| ())
+    }
+
+    fn mismatch() -> Result<(), Error> {
+        Err(CertificateError::ApplicationVerificationFailure.into())
+    }
+
+    fn testserver() -> ServerName<'static> {
+        ServerName::try_from("testserver.com").unwrap()
+    }
+
+    #[test]
+    fn unpinned_names_are_not_restricted() {
+        let verifier = pinning_verifier().with_pins(
+            ServerName::try_from("other.com").unwrap(),
+            [pin(&ecdsa_chain().end_entity)],
+        );
+        assert_eq!(
+            verify(&verifier, &[rsa_chain().intermediate], "testserver.com"),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn end_entity_or_issuer_pins_match() {
+        let rsa = rsa_chain();
+        for cert in [&rsa.end_entity, &rsa.intermediate] {
+            let verifier = pinning_verifier().with_pins(testserver(), [pin(cert)]);
+            assert_eq!(
+                verify(
+                    &verifier,
+                    core::slice::from_ref(&rsa.intermediate),
+                    "testserver.com"
+                ),
+                Ok(())
+            );
+        }
+
+        // the trust anchor matches whether or not the server sends it
+        let verifier = pinning_verifier().with_pins(testserver(), [pin(&rsa.ca)]);
+        assert_eq!(
+            verify(
+                &verifier,
+                core::slice::from_ref(&rsa.intermediate),
+                "testserver.com"
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            verify(
+                &verifier,
+                &[rsa.intermediate.clone(), rsa.ca.clone()],
+                "testserver.com"
+            ),
+            Ok(())
+        );
+
+        // a certificate for another trust anchor does not match
+        let verifier = pinning_verifier().with_pins(testserver(), [pin(&ecdsa_chain().ca)]);
+        assert_eq!(
+            verify(
+                &verifier,
+                &[rsa.intermediate.clone(), ecdsa_chain().ca],
+                "testserver.com"
+            ),
+            mismatch()
+        );
+    }
+
+    #[test]
+    fn reports_record_pin_mismatches() {
+        let rsa = rsa_chain();
+        let mut roots = RootCertStore::empty();
+        roots.add(rsa.ca).unwrap();
+        let roots = Arc::new(roots);
+        let inner = WebPkiServerVerifier::builder_with_provider(
+            Arc::clone(&roots),
+            test_provider::default_provider().into(),
+        )
+        .with_verification_reports()
+        .build()
+        .unwrap();
+        let verifier = SpkiPinningVerifier::new_with_provider(
+            inner,
+            roots,
+            test_provider::default_provider().into(),
+        )
+        .with_pins(testserver(), [pin(&ecdsa_chain().end_entity)]);
+
+        let (result, report) = verifier.verify_server_cert_with_report(
+            &rsa.end_entity,
+            &[rsa.intermediate],
+            &testserver(),
+            &[],
+            &[],
+            UnixTime::now(),
+        );
+        assert_eq!(result.map(|_| ()), mismatch());
+        let report = report.unwrap();
+        assert_eq!(report.result, mismatch());
+        // the wrapped verifier's path is still reported
+        assert_eq!(report.chain.len(), 2);
+    }
+
+    #[test]
+    fn unrelated_intermediates_do_not_match() {
+        let ecdsa = ecdsa_chain();
+        let verifier = pinning_verifier()
+            .with_pins(testserver(), [pin(&ecdsa.end_entity)])
+            .with_pins(testserver(), [pin(&ecdsa.intermediate)]);
+        assert_eq!(
+            verify(
+                &verifier,
+                &[
+                    rsa_chain().intermediate,
+                    ecdsa.intermediate,
+                    ecdsa.end_entity
+                ],
+                "testserver.com"
+            ),
+            mismatch()
+        );
+    }
+
+    #[test]
+    fn report_only_accepts_mismatches() {
+        let verifier = pinning_verifier()
+            .with_pins(testserver(), [pin(&ecdsa_chain().end_entity)])
+            .report_only();
+        assert_eq!(
+            verify(&verifier, &[rsa_chain().intermediate], "testserver.com"),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn inner_verifier_errors_are_returned() {
+        let rsa = rsa_chain();
+        let verifier = pinning_verifier()
+            .with_pins(testserver(), [pin(&rsa.end_entity)])
+            .report_only();
+        assert_eq!(
+            verify(&verifier, &[], "testserver.com"),
+            Err(CertificateError::UnknownIssuer.into())
+        );
+    }
+}
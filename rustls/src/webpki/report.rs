@@ -0,0 +1,303 @@
+use alloc::vec::Vec;
+use core::iter;
+use core::slice;
+
+use pki_types::{CertificateDer, SignatureVerificationAlgorithm, TrustAnchor, UnixTime};
+use webpki::{
+    CertRevocationList, EndEntityCert, KeyUsage, RevocationCheckDepth, RevocationOptions,
+};
+
+use super::anchors::RootCertStore;
+use super::pki_error;
+use super::verify::ParsedCertificate;
+use crate::error::Error;
+
+/// The most candidate paths examined when explaining a verification failure.
+const MAX_CANDIDATE_PATHS: usize = 16;
+
+/// The most intermediates in a candidate path examined when explaining a
+/// verification failure.
+const MAX_CANDIDATE_INTERMEDIATES: usize = 6;
+
+/// A record of how a certificate chain was verified, for diagnosing
+/// verification failures and auditing successes.
+///
+/// Reports are produced by [`WebPkiServerVerifier`] and [`WebPkiClientVerifier`],
+/// and are available from [`CommonState::peer_verification_report`] once the
+/// peer's certificate has been verified, if the verifier was built with
+/// `with_verification_reports()`.
+///
+/// [`WebPkiServerVerifier`]: crate::client::WebPkiServerVerifier
+/// [`WebPkiClientVerifier`]: crate::server::WebPkiClientVerifier
+/// [`CommonState::peer_verification_report`]: crate::CommonState::peer_verification_report
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct VerificationReport {
+    /// The path built from the end-entity certificate to a trust anchor: the
+    /// end-entity certificate first, then each intermediate in issuing order.
+    ///
+    /// Empty if no path could be built.
+    pub chain: Vec<CertificateDer<'static>>,
+    /// The trust anchor at the end of `chain`, if a path could be built.
+    pub trust_anchor: Option<TrustAnchor<'static>>,
+    /// The revocation checks performed on the certificates in `chain`.
+    pub revocation_checks: Vec<RevocationCheck>,
+    /// If no path could be built, each candidate path that was tried and the
+    /// reason it was rejected.
+    pub failed_paths: Vec<FailedPath>,
+    /// The outcome of verification.
+    ///
+    /// This may be an error even though a path was built: for example, if the
+    /// end-entity certificate is not valid for the server name.
+    pub result: Result<(), Error>,
+}
+
+impl VerificationReport {
+    pub(crate) fn new() -> Self {
+        Self {
+            chain: Vec::new(),
+            trust_anchor: None,
+            revocation_checks: Vec::new(),
+            failed_paths: Vec::new(),
+            result: Ok(()),
+        }
+    }
+}
+
+/// A candidate path from an end-entity certificate to a trust anchor that was
+/// rejected.  See [`VerificationReport::failed_paths`].
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct FailedPath {
+    /// The end-entity certificate, followed by each intermediate in the path.
+    pub chain: Vec<CertificateDer<'static>>,
+    /// The trust anchor the path ends at.
+    ///
+    /// This is `None` if the presented certificates did not lead to any trust anchor.
+    pub trust_anchor: Option<TrustAnchor<'static>>,
+    /// Why the path was rejected.
+    pub error: Error,
+}
+
+/// A revocation check of one certificate.  See [`VerificationReport::revocation_checks`].
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct RevocationCheck {
+    /// The certificate that was checked.
+    pub certificate: CertificateDer<'static>,
+    /// Where its revocation status came from.
+    pub method: RevocationMethod,
+    /// Its revocation status.
+    pub status: RevocationStatus,
+}
+
+/// A source of revocation information.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RevocationMethod {
+    /// A certificate revocation list (CRL) configured in the verifier.
+    Crl,
+    /// An OCSP response stapled by the server.
+    StapledOcsp,
+}
+
+/// The revocation status of a certificate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RevocationStatus {
+    /// The certificate has not been revoked.
+    Good,
+    /// The certificate has been revoked.
+    Revoked,
+    /// The revocation status of the certificate could not be determined, and
+    /// this was allowed by the verifier's configuration.
+    Unknown,
+}
+
+/// Options for building a path from an end-entity certificate to a trust anchor.
+pub(crate) struct ChainVerification<'a> {
+    pub(crate) roots: &'a RootCertStore,
+    pub(crate) crls: &'a [&'a CertRevocationList<'a>],
+    pub(crate) revocation: Option<RevocationOptions<'a>>,
+    pub(crate) revocation_check_depth: RevocationCheckDepth,
+    pub(crate) supported_algs: &'a [&'a dyn SignatureVerificationAlgorithm],
+    pub(crate) usage: KeyUsage,
+}
+
+impl<'a> ChainVerification<'a> {
+    /// Verify that `cert` chains to a trust anchor, recording how in `report`
+    /// if it is given.
+    pub(crate) fn verify(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        cert: &ParsedCertificate<'_>,
+        intermediates: &[CertificateDer<'_>],
+        now: UnixTime,
+        report: Option<&mut VerificationReport>,
+    ) -> Result<(), Error> {
+        let path = cert.0.verify_for_usage(
+            self.supported_algs,
+            &self.roots.roots,
+            intermediates,
+            now,
+            self.usage,
+            self.revocation,
+            None,
+        );
+
+        let report = match report {
+            Some(report) => report,
+            None => return path.map(|_| ()).map_err(pki_error),
+        };
+
+        let path = match path {
+            Ok(path) => path,
+            Err(err) => {
+                let err = pki_error(err);
+                report.failed_paths =
+                    self.explain_failure(end_entity, cert, intermediates, now, &err);
+                return Err(err);
+            }
+        };
+
+        report.chain = iter::once(end_entity.clone().into_owned())
+            .chain(
+                path.intermediate_certificates()
+                    .map(|cert| cert.der().into_owned()),
+            )
+            .collect();
+        report.trust_anchor = Some(path.anchor().to_owned());
+
+        if !self.crls.is_empty() {
+            let checked = match self.revocation_check_depth {
+                RevocationCheckDepth::EndEntity => 1,
+                RevocationCheckDepth::Chain => report.chain.len(),
+            };
+            report.revocation_checks = report.chain[..checked]
+                .iter()
+                .map(|cert| RevocationCheck {
+                    certificate: cert.clone(),
+                    method: RevocationMethod::Crl,
+                    status: match self.has_crl_for(cert) {
+                        true => RevocationStatus::Good,
+                        false => RevocationStatus::Unknown,
+                    },
+                })
+                .collect();
+        }
+
+        Ok(())
+    }
+
+    /// Find each candidate path from `end_entity` to a trust anchor, and the
+    /// reason each was rejected.
+    fn explain_failure(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        cert: &ParsedCertificate<'_>,
+        intermediates: &[CertificateDer<'_>],
+        now: UnixTime,
+        error: &Error,
+    ) -> Vec<FailedPath> {
+        let parsed = intermediates
+            .iter()
+            .map(|der| EndEntityCert::try_from(der).ok())
+            .collect::<Vec<_>>();
+
+        let mut candidates = Vec::new();
+        find_candidate_paths(
+            cert.0.issuer(),
+            &mut Vec::new(),
+            &parsed,
+            self.roots,
+            &mut candidates,
+        );
+
+        let mut failed = Vec::new();
+        for (path, anchor) in candidates {
+            let anchor = &self.roots.roots[anchor];
+            let path_intermediates = path
+                .iter()
+                .map(|&i| intermediates[i].clone())
+                .collect::<Vec<_>>();
+
+            let result = cert.0.verify_for_usage(
+                self.supported_algs,
+                slice::from_ref(anchor),
+                &path_intermediates,
+                now,
+                self.usage,
+                self.revocation,
+                None,
+            );
+
+            if let Err(err) = result {
+                failed.push(FailedPath {
+                    chain: iter::once(end_entity)
+                        .chain(path.iter().map(|&i| &intermediates[i]))
+                        .map(|cert| cert.clone().into_owned())
+                        .collect(),
+                    trust_anchor: Some(anchor.to_owned()),
+                    error: pki_error(err),
+                });
+            }
+        }
+
+        if failed.is_empty() {
+            failed.push(FailedPath {
+                chain: Vec::from([end_entity.clone().into_owned()]),
+                trust_anchor: None,
+                error: error.clone(),
+            });
+        }
+
+        failed
+    }
+
+    /// Whether any configured CRL was issued by the issuer of `cert`.
+    fn has_crl_for(&self, cert: &CertificateDer<'_>) -> bool {
+        match EndEntityCert::try_from(cert) {
+            Ok(cert) => self
+                .crls
+                .iter()
+                .any(|crl| crl.issuer() == cert.issuer()),
+            Err(_) => false,
+        }
+    }
+}
+
+/// Collect into `out` each path, as indices into `intermediates` and
+/// `roots.roots`, that extends `path` to a trust anchor by subject names.
+///
+/// `issuer` is the contents of the issuer Name of the last certificate in `path`.
+fn find_candidate_paths(
+    issuer: &[u8],
+    path: &mut Vec<usize>,
+    intermediates: &[Option<EndEntityCert<'_>>],
+    roots: &RootCertStore,
+    out: &mut Vec<(Vec<usize>, usize)>,
+) {
+    for (i, anchor) in roots.roots.iter().enumerate() {
+        if out.len() >= MAX_CANDIDATE_PATHS {
+            return;
+        }
+        if issuer == anchor.subject.as_ref() {
+            out.push((path.clone(), i));
+        }
+    }
+
+    if path.len() >= MAX_CANDIDATE_INTERMEDIATES {
+        return;
+    }
+
+    for (i, cert) in intermediates.iter().enumerate() {
+        let cert = match cert {
+            Some(cert) if cert.subject() == issuer && !path.contains(&i) => cert,
+            _ => continue,
+        };
+
+        path.push(i);
+        find_candidate_paths(cert.issuer(), path, intermediates, roots, out);
+        path.pop();
+    }
+}
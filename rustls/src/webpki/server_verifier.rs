@@ -10,12 +10,14 @@ use crate::crypto::{CryptoProvider, WebPkiSupportedAlgorithms};
 use crate::verify::{
     DigitallySignedStruct, HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
 };
-use crate::webpki::verify::{
-    verify_server_cert_signed_by_trust_anchor_impl, verify_tls12_signature, verify_tls13_signature,
-    ParsedCertificate,
+use crate::webpki::crl_store::CrlStore;
+use crate::webpki::ocsp::{self, CertIdHashes, OcspError, OcspStatus};
+use crate::webpki::report::{
+    ChainVerification, RevocationCheck, RevocationMethod, RevocationStatus, VerificationReport,
 };
+use crate::webpki::verify::{verify_tls12_signature, verify_tls13_signature, ParsedCertificate};
 use crate::webpki::{parse_crls, verify_server_name, VerifierBuilderError};
-use crate::{Error, RootCertStore, SignatureScheme};
+use crate::{CertificateError, Error, RootCertStore, SignatureScheme};
 
 #[cfg(doc)]
 use crate::{crypto, ConfigBuilder, ServerConfig};
@@ -27,8 +29,12 @@ use crate::{crypto, ConfigBuilder, ServerConfig};
 pub struct ServerCertVerifierBuilder {
     roots: Arc<RootCertStore>,
     crls: Vec<CertificateRevocationListDer<'static>>,
+    crl_store: Option<CrlStore>,
     revocation_check_depth: RevocationCheckDepth,
     unknown_revocation_policy: UnknownStatusPolicy,
+    stapled_ocsp: StapledOcspPolicy,
+    ocsp_hashes: CertIdHashes,
+    reports: bool,
     supported_algs: WebPkiSupportedAlgorithms,
 }
 
@@ -36,12 +42,17 @@ impl ServerCertVerifierBuilder {
     pub(crate) fn new(
         roots: Arc<RootCertStore>,
         supported_algs: WebPkiSupportedAlgorithms,
+        ocsp_hashes: CertIdHashes,
     ) -> Self {
         Self {
             roots,
             crls: Vec::new(),
+            crl_store: None,
             revocation_check_depth: RevocationCheckDepth::Chain,
             unknown_revocation_policy: UnknownStatusPolicy::Deny,
+            stapled_ocsp: StapledOcspPolicy::Ignore,
+            ocsp_hashes,
+            reports: false,
             supported_algs,
         }
     }
@@ -57,6 +68,18 @@ impl ServerCertVerifierBuilder {
         self
     }
 
+    /// Verify the revocation state of presented certificates against the CRLs in `store`, in
+    /// addition to any provided with [`with_crls`][Self::with_crls].
+    ///
+    /// The CRLs in `store` can be replaced after the verifier is built; each verification uses
+    /// the CRLs current at that time. While `store` holds no current CRLs, the revocation status
+    /// of every certificate is unknown: see
+    /// [`allow_unknown_revocation_status`][Self::allow_unknown_revocation_status].
+    pub fn with_crl_store(mut self, store: CrlStore) -> Self {
+        self.crl_store = Some(store);
+        self
+    }
+
     /// Only check the end entity certificate revocation status when using CRLs.
     ///
     /// If CRLs are provided using [`with_crls`][Self::with_crls] only check the end entity
@@ -84,6 +107,63 @@ impl ServerCertVerifierBuilder {
         self
     }
 
+    /// Validate OCSP responses stapled by the server.
+    ///
+    /// The response must be signed by the issuer of the end entity certificate, or by an
+    /// OCSP responder certificate included in the response that the issuer has authorised
+    /// for OCSP signing. It must be current, and it must identify the end entity certificate.
+    ///
+    /// A response that gives the certificate's status as revoked causes verification to fail with
+    /// [`CertificateError::Revoked`]. A genuine response that gives no usable status (because
+    /// the status is unknown, the responder reported an error, or the response is out of date)
+    /// is treated as [`CertificateError::UnknownRevocationStatus`]; this error is only returned
+    /// if [`allow_unknown_revocation_status`][Self::allow_unknown_revocation_status] was not
+    /// called.
+    ///
+    /// A response that is not correctly signed fails with [`CertificateError::BadSignature`],
+    /// and one that is malformed or does not identify the end entity certificate fails with
+    /// [`CertificateError::InvalidOcspResponse`], whatever the policy for unknown status.
+    ///
+    /// Servers that do not staple a response are not affected, unless
+    /// [`with_ocsp_must_staple`][Self::with_ocsp_must_staple] is used.
+    ///
+    /// The issuer name and key hashes in a response are checked when the response uses a hash
+    /// function offered by the [`CryptoProvider`]'s cipher suites. Responses using other hash
+    /// functions (typically SHA-1) are matched by serial number only: this is sufficient because
+    /// the response must be signed on behalf of the issuer.
+    pub fn with_stapled_ocsp_validation(mut self) -> Self {
+        if self.stapled_ocsp == StapledOcspPolicy::Ignore {
+            self.stapled_ocsp = StapledOcspPolicy::Validate;
+        }
+        self
+    }
+
+    /// Enforce the TLS Feature "must-staple" extension ([RFC 7633]).
+    ///
+    /// End entity certificates carrying a TLS Feature extension that includes `status_request`
+    /// must be presented with a valid, stapled OCSP response giving a good status. Otherwise
+    /// verification fails with [`CertificateError::UnknownRevocationStatus`], regardless of
+    /// [`allow_unknown_revocation_status`][Self::allow_unknown_revocation_status].
+    ///
+    /// This implies [`with_stapled_ocsp_validation`][Self::with_stapled_ocsp_validation].
+    ///
+    /// [RFC 7633]: https://datatracker.ietf.org/doc/html/rfc7633
+    pub fn with_ocsp_must_staple(mut self) -> Self {
+        self.stapled_ocsp = StapledOcspPolicy::MustStaple;
+        self
+    }
+
+    /// Make a [`VerificationReport`] for each server certificate chain verified during a
+    /// handshake, available from
+    /// [`CommonState::peer_verification_report`](crate::CommonState::peer_verification_report).
+    ///
+    /// Making a report copies the verified chain, and examines candidate paths when verification
+    /// fails, so is slower.  [`WebPkiServerVerifier::verify_with_report`] makes a report regardless of this setting.
+    pub fn with_verification_reports(mut self) -> Self {
+        self.reports = true;
+        self
+    }
+
     /// Build a server certificate verifier, allowing control over the root certificates to use as
     /// trust anchors, and to control how server certificate revocation checking is performed.
     ///
@@ -110,18 +190,36 @@ impl ServerCertVerifierBuilder {
             self.unknown_revocation_policy,
             self.supported_algs,
         )
+        .with_stapled_ocsp(self.stapled_ocsp, self.ocsp_hashes)
+        .with_crl_store(self.crl_store)
+        .with_reports(self.reports)
         .into())
     }
 }
 
+/// How a [`WebPkiServerVerifier`] treats stapled OCSP responses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StapledOcspPolicy {
+    /// Stapled responses are not examined.
+    Ignore,
+    /// Stapled responses are validated, if present.
+    Validate,
+    /// As `Validate`, and additionally require a response for must-staple certificates.
+    MustStaple,
+}
+
 /// Default `ServerCertVerifier`, see the trait impl for more information.
 #[allow(unreachable_pub)]
 #[derive(Debug)]
 pub struct WebPkiServerVerifier {
     roots: Arc<RootCertStore>,
     crls: Vec<CertRevocationList<'static>>,
+    crl_store: Option<CrlStore>,
     revocation_check_depth: RevocationCheckDepth,
     unknown_revocation_policy: UnknownStatusPolicy,
+    stapled_ocsp: StapledOcspPolicy,
+    ocsp_hashes: CertIdHashes,
+    reports: bool,
     supported: WebPkiSupportedAlgorithms,
 }
 
@@ -153,7 +251,17 @@ impl WebPkiServerVerifier {
         roots: Arc<RootCertStore>,
         provider: Arc<CryptoProvider>,
     ) -> ServerCertVerifierBuilder {
-        ServerCertVerifierBuilder::new(roots, provider.signature_verification_algorithms)
+        let ocsp_hashes = CertIdHashes::new(
+            provider
+                .cipher_suites
+                .iter()
+                .map(|suite| suite.hash_provider()),
+        );
+        ServerCertVerifierBuilder::new(
+            roots,
+            provider.signature_verification_algorithms,
+            ocsp_hashes,
+        )
     }
 
     /// Short-cut for creating a `WebPkiServerVerifier` that does not perform certificate revocation
@@ -192,37 +300,83 @@ impl WebPkiServerVerifier {
         Self {
             roots: roots.into(),
             crls,
+            crl_store: None,
             revocation_check_depth,
             unknown_revocation_policy,
+            stapled_ocsp: StapledOcspPolicy::Ignore,
+            ocsp_hashes: CertIdHashes::default(),
+            reports: false,
             supported,
         }
     }
-}
 
-impl ServerCertVerifier for WebPkiServerVerifier {
-    /// Will verify the certificate is valid in the following ways:
-    /// - Signed by a trusted `RootCertStore` CA
-    /// - Not Expired
-    /// - Valid for DNS entry
-    /// - Valid revocation status (if applicable).
+    fn with_stapled_ocsp(mut self, policy: StapledOcspPolicy, hashes: CertIdHashes) -> Self {
+        self.stapled_ocsp = policy;
+        self.ocsp_hashes = hashes;
+        self
+    }
+
+    fn with_crl_store(mut self, store: Option<CrlStore>) -> Self {
+        self.crl_store = store;
+        self
+    }
+
+    fn with_reports(mut self, reports: bool) -> Self {
+        self.reports = reports;
+        self
+    }
+
+    /// Verify a server certificate chain as [`ServerCertVerifier::verify_server_cert`] does,
+    /// and report how it was verified.
     ///
-    /// Depending on the verifier's configuration revocation status checking may be performed for
-    /// each certificate in the chain to a root CA (excluding the root itself), or only the
-    /// end entity certificate. Similarly, unknown revocation status may be treated as an error
-    /// or allowed based on configuration.
-    fn verify_server_cert(
+    /// The outcome of verification is in [`VerificationReport::result`].
+    pub fn verify_with_report(
         &self,
         end_entity: &CertificateDer<'_>,
         intermediates: &[CertificateDer<'_>],
         server_name: &ServerName<'_>,
         ocsp_response: &[u8],
         now: UnixTime,
-    ) -> Result<ServerCertVerified, Error> {
+    ) -> VerificationReport {
+        let mut report = VerificationReport::new();
+        report.result = self.verify(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            now,
+            Some(&mut report),
+        );
+        report
+    }
+
+    fn verify(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+        mut report: Option<&mut VerificationReport>,
+    ) -> Result<(), Error> {
         let cert = ParsedCertificate::try_from(end_entity)?;
 
-        let crl_refs = self.crls.iter().collect::<Vec<_>>();
+        let crl_set = self
+            .crl_store
+            .as_ref()
+            .map(CrlStore::snapshot);
+        let crl_refs = self
+            .crls
+            .iter()
+            .chain(
+                crl_set
+                    .iter()
+                    .flat_map(|set| set.current(now)),
+            )
+            .collect::<Vec<_>>();
+        let no_current_crls = self.crl_store.is_some() && crl_refs.is_empty();
 
-        let revocation = if self.crls.is_empty() {
+        let revocation = if crl_refs.is_empty() {
             None
         } else {
             // Note: unwrap here is safe because RevocationOptionsBuilder only errors when given
@@ -238,23 +392,164 @@ impl ServerCertVerifier for WebPkiServerVerifier {
             )
         };
 
-        // Note: we use the crate-internal `_impl` fn here in order to provide revocation
-        // checking information, if applicable.
-        verify_server_cert_signed_by_trust_anchor_impl(
-            &cert,
+        ChainVerification {
+            roots: &self.roots,
+            crls: &crl_refs,
+            revocation,
+            revocation_check_depth: self.revocation_check_depth,
+            supported_algs: self.supported.all,
+            usage: webpki::KeyUsage::server_auth(),
+        }
+        .verify(end_entity, &cert, intermediates, now, report.as_deref_mut())?;
+
+        if no_current_crls && self.unknown_revocation_policy == UnknownStatusPolicy::Deny {
+            return Err(CertificateError::UnknownRevocationStatus.into());
+        }
+
+        match self.stapled_ocsp {
+            StapledOcspPolicy::Ignore => {
+                if !ocsp_response.is_empty() {
+                    trace!("Unvalidated OCSP response: {:?}", ocsp_response.to_vec());
+                }
+            }
+            StapledOcspPolicy::Validate | StapledOcspPolicy::MustStaple => {
+                self.check_stapled_ocsp(end_entity, intermediates, ocsp_response, now, report)?;
+            }
+        }
+
+        verify_server_name(&cert, server_name)
+    }
+
+    /// Apply the configured stapled OCSP policy to an already verified chain.
+    fn check_stapled_ocsp(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        ocsp_response: &[u8],
+        now: UnixTime,
+        report: Option<&mut VerificationReport>,
+    ) -> Result<(), Error> {
+        let must_staple = self.stapled_ocsp == StapledOcspPolicy::MustStaple
+            && ocsp::requires_stapled_response(end_entity);
+
+        if ocsp_response.is_empty() {
+            return match must_staple {
+                true => Err(CertificateError::UnknownRevocationStatus.into()),
+                false => Ok(()),
+            };
+        }
+
+        let status = ocsp::check_response(
+            ocsp_response,
+            end_entity,
+            intermediates,
             &self.roots,
+            self.supported.all,
+            &self.ocsp_hashes,
+            now,
+        );
+
+        let (status, result) = match status {
+            Ok(OcspStatus::Good) => (RevocationStatus::Good, Ok(())),
+            Ok(OcspStatus::Revoked) => (
+                RevocationStatus::Revoked,
+                Err(CertificateError::Revoked.into()),
+            ),
+            // The response is genuine, but gives no usable status.
+            Ok(OcspStatus::Unknown)
+            | Err(OcspError::Unsuccessful | OcspError::Stale | OcspError::UnknownIssuer) => {
+                trace!("Unusable OCSP response: {:?}", status);
+                let result = match (must_staple, self.unknown_revocation_policy) {
+                    (false, UnknownStatusPolicy::Allow) => Ok(()),
+                    _ => Err(CertificateError::UnknownRevocationStatus.into()),
+                };
+                (RevocationStatus::Unknown, result)
+            }
+            // The response is forged, tampered with, or for another certificate:
+            // reject it whatever the policy for unknown revocation status.
+            Err(OcspError::BadSignature) => return Err(CertificateError::BadSignature.into()),
+            Err(OcspError::Malformed | OcspError::WrongCertificate) => {
+                return Err(CertificateError::InvalidOcspResponse.into())
+            }
+        };
+
+        if let Some(report) = report {
+            report
+                .revocation_checks
+                .push(RevocationCheck {
+                    certificate: end_entity.clone().into_owned(),
+                    method: RevocationMethod::StapledOcsp,
+                    status,
+                });
+        }
+
+        result
+    }
+}
+
+impl ServerCertVerifier for WebPkiServerVerifier {
+    /// Will verify the certificate is valid in the following ways:
+    /// - Signed by a trusted `RootCertStore` CA
+    /// - Not Expired
+    /// - Valid for DNS entry
+    /// - Valid revocation status (if applicable).
+    /// - Valid stapled OCSP response (if configured).
+    ///
+    /// Depending on the verifier's configuration revocation status checking may be performed for
+    /// each certificate in the chain to a root CA (excluding the root itself), or only the
+    /// end entity certificate. Similarly, unknown revocation status may be treated as an error
+    /// or allowed based on configuration.
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        _scts: &[&[u8]],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, Error> {
+        self.verify(
+            end_entity,
             intermediates,
-            revocation,
+            server_name,
+            ocsp_response,
             now,
-            self.supported.all,
+            None,
         )?;
+        Ok(ServerCertVerified::assertion())
+    }
 
-        if !ocsp_response.is_empty() {
-            trace!("Unvalidated OCSP response: {:?}", ocsp_response.to_vec());
+    fn verify_server_cert_with_report(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        scts: &[&[u8]],
+        now: UnixTime,
+    ) -> (
+        Result<ServerCertVerified, Error>,
+        Option<VerificationReport>,
+    ) {
+        if !self.reports {
+            let result = self.verify_server_cert(
+                end_entity,
+                intermediates,
+                server_name,
+                ocsp_response,
+                scts,
+                now,
+            );
+            return (result, None);
         }
 
-        verify_server_name(&cert, server_name)?;
-        Ok(ServerCertVerified::assertion())
+        let report =
+            self.verify_with_report(end_entity, intermediates, server_name, ocsp_response, now);
+        let result = report
+            .result
+            .clone()
+            .map(|()| ServerCertVerified::assertion());
+        (result, Some(report))
     }
 
     fn verify_tls12_signature(
@@ -284,10 +579,13 @@ impl ServerCertVerifier for WebPkiServerVerifier {
 mod tests {
     use std::sync::Arc;
 
-    use pki_types::{CertificateDer, CertificateRevocationListDer};
+    use pki_types::{CertificateDer, CertificateRevocationListDer, ServerName, UnixTime};
 
-    use super::{VerifierBuilderError, WebPkiServerVerifier};
-    use crate::{test_provider, RootCertStore};
+    use super::{ServerCertVerifierBuilder, VerifierBuilderError, WebPkiServerVerifier};
+    use crate::client::CrlStore;
+    use crate::verify::ServerCertVerifier;
+    use crate::webpki::{RevocationMethod, RevocationStatus, VerificationReport};
+    use crate::{test_provider, CertificateError, Error, RootCertStore};
 
     fn load_crls(crls_der: &[&[u8]]) -> Vec<CertificateRevocationListDer<'static>> {
         crls_der
@@ -412,4 +710,379 @@ mod tests {
         println!("{:?}", builder);
         builder.build().unwrap();
     }
+
+    fn load_certs(pem: &[u8]) -> Vec<CertificateDer<'static>> {
+        rustls_pemfile::certs(&mut &pem[..])
+            .map(|cert| cert.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_crl_store_updates_apply() {
+        let store = CrlStore::new();
+        let verifier = WebPkiServerVerifier::builder_with_provider(
+            test_roots(),
+            test_provider::default_provider().into(),
+        )
+        .with_crl_store(store.clone())
+        .only_check_end_entity_revocation()
+        .build()
+        .unwrap();
+
+        let end_entity = load_certs(include_bytes!("../../../test-ca/rsa/end.cert")).remove(0);
+        let intermediates = load_certs(include_bytes!("../../../test-ca/rsa/inter.cert"));
+        // between thisUpdate and nextUpdate of the test CRLs
+        let now = UnixTime::since_unix_epoch(core::time::Duration::from_secs(1_698_100_000));
+        let verify = |now| {
+            verifier
+                .verify_server_cert(
+                    &end_entity,
+                    &intermediates,
+                    &ServerName::try_from("testserver.com").unwrap(),
+                    &[],
+                    &[],
+                    now,
+                )
+                .map(|_| ())
+        };
+
+        assert_eq!(
+            verify(now),
+            Err(CertificateError::UnknownRevocationStatus.into())
+        );
+
+        store.replace(
+            load_crls(&[include_bytes!("../../../test-ca/rsa/end.revoked.crl.pem")]),
+            now,
+        );
+        assert_eq!(verify(now), Err(CertificateError::Revoked.into()));
+
+        store.replace(
+            load_crls(&[include_bytes!(
+                "../../../test-ca/rsa/client.revoked.crl.pem"
+            )]),
+            now,
+        );
+        assert_eq!(verify(now), Ok(()));
+
+        // once the CRL expires, revocation status is unknown again
+        let later = UnixTime::since_unix_epoch(core::time::Duration::from_secs(1_698_700_000));
+        assert_eq!(
+            verify(later),
+            Err(CertificateError::UnknownRevocationStatus.into())
+        );
+    }
+
+    fn ocsp_builder() -> ServerCertVerifierBuilder {
+        WebPkiServerVerifier::builder_with_provider(
+            test_roots(),
+            test_provider::default_provider().into(),
+        )
+    }
+
+    /// Verify `end_entity` (issued by the RSA intermediate) with the stapled `ocsp` response,
+    /// `hours` after the test responses were produced.
+    fn verify_with_ocsp(
+        builder: ServerCertVerifierBuilder,
+        end_entity: &[u8],
+        ocsp: &[u8],
+        hours: u64,
+    ) -> Result<(), Error> {
+        // thisUpdate of the responses in test-ca/rsa/end.ocsp.*.der
+        const PRODUCED_AT: u64 = 1_792_359_711;
+
+        let end_entity = load_certs(end_entity).remove(0);
+        let intermediates = load_certs(include_bytes!("../../../test-ca/rsa/inter.cert"));
+        builder
+            .build()
+            .unwrap()
+            .verify_server_cert(
+                &end_entity,
+                &intermediates,
+                &ServerName::try_from("testserver.com").unwrap(),
+                ocsp,
+                &[],
+                UnixTime::since_unix_epoch(core::time::Duration::from_secs(
+                    PRODUCED_AT + hours * 3600,
+                )),
+            )
+            .map(|_| ())
+    }
+
+    const END: &[u8] = include_bytes!("../../../test-ca/rsa/end.cert");
+    const END_MUST_STAPLE: &[u8] = include_bytes!("../../../test-ca/rsa/end-must-staple.cert");
+    const OCSP_GOOD: &[u8] = include_bytes!("../../../test-ca/rsa/end.ocsp.good.der");
+    const OCSP_GOOD_SHA256: &[u8] = include_bytes!("../../../test-ca/rsa/end.ocsp.good-sha256.der");
+    const OCSP_DELEGATED: &[u8] = include_bytes!("../../../test-ca/rsa/end.ocsp.delegated.der");
+    const OCSP_REVOKED: &[u8] = include_bytes!("../../../test-ca/rsa/end.ocsp.revoked.der");
+    const OCSP_UNKNOWN: &[u8] = include_bytes!("../../../test-ca/rsa/end.ocsp.unknown.der");
+
+    fn unknown_status() -> Result<(), Error> {
+        Err(CertificateError::UnknownRevocationStatus.into())
+    }
+
+    #[test]
+    fn test_ocsp_ignored_by_default() {
+        assert_eq!(
+            verify_with_ocsp(ocsp_builder(), END, OCSP_REVOKED, 1),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_ocsp_good() {
+        let builder = ocsp_builder().with_stapled_ocsp_validation();
+        assert_eq!(verify_with_ocsp(builder.clone(), END, OCSP_GOOD, 1), Ok(()));
+        assert_eq!(
+            verify_with_ocsp(builder.clone(), END, OCSP_GOOD_SHA256, 1),
+            Ok(())
+        );
+        assert_eq!(verify_with_ocsp(builder, END, OCSP_DELEGATED, 1), Ok(()));
+    }
+
+    #[test]
+    fn test_ocsp_no_staple() {
+        let builder = ocsp_builder().with_stapled_ocsp_validation();
+        assert_eq!(verify_with_ocsp(builder, END, &[], 1), Ok(()));
+    }
+
+    #[test]
+    fn test_ocsp_revoked() {
+        let builder = ocsp_builder()
+            .with_stapled_ocsp_validation()
+            .allow_unknown_revocation_status();
+        assert_eq!(
+            verify_with_ocsp(builder, END, OCSP_REVOKED, 1),
+            Err(CertificateError::Revoked.into())
+        );
+    }
+
+    #[test]
+    fn test_ocsp_unknown() {
+        let builder = ocsp_builder().with_stapled_ocsp_validation();
+        assert_eq!(
+            verify_with_ocsp(builder.clone(), END, OCSP_UNKNOWN, 1),
+            unknown_status()
+        );
+        assert_eq!(
+            verify_with_ocsp(
+                builder.allow_unknown_revocation_status(),
+                END,
+                OCSP_UNKNOWN,
+                1
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_ocsp_stale() {
+        let builder = ocsp_builder().with_stapled_ocsp_validation();
+        // Responses are valid for a week after production.
+        assert_eq!(
+            verify_with_ocsp(builder.clone(), END, OCSP_GOOD, 7 * 24 + 1),
+            unknown_status()
+        );
+        assert_eq!(
+            verify_with_ocsp(
+                builder.allow_unknown_revocation_status(),
+                END,
+                OCSP_REVOKED,
+                7 * 24 + 1
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_ocsp_wrong_certificate() {
+        // The response is for `END`, which has a different serial number.
+        let builder = ocsp_builder()
+            .with_stapled_ocsp_validation()
+            .allow_unknown_revocation_status();
+        assert_eq!(
+            verify_with_ocsp(builder, END_MUST_STAPLE, OCSP_GOOD, 1),
+            Err(CertificateError::InvalidOcspResponse.into())
+        );
+    }
+
+    #[test]
+    fn test_ocsp_tampered_or_malformed() {
+        // Rejected even though unknown revocation status is allowed.
+        let builder = ocsp_builder()
+            .with_stapled_ocsp_validation()
+            .allow_unknown_revocation_status();
+
+        // Changing the signed producedAt time (a GeneralizedTime) must invalidate the signature.
+        const GENERALIZED_TIME: u8 = 0x18;
+        let mut tampered = OCSP_GOOD.to_vec();
+        let produced_at = OCSP_GOOD
+            .windows(2)
+            .position(|w| w == [GENERALIZED_TIME, 15])
+            .unwrap();
+        // the last digit of the seconds
+        tampered[produced_at + 15] ^= 0x01;
+        assert_eq!(
+            verify_with_ocsp(builder.clone(), END, &tampered, 1),
+            Err(CertificateError::BadSignature.into())
+        );
+        assert_eq!(
+            verify_with_ocsp(builder.clone(), END, &OCSP_GOOD[..OCSP_GOOD.len() - 1], 1),
+            Err(CertificateError::InvalidOcspResponse.into())
+        );
+
+        // A responder error is not malformed: it just gives no status.
+        assert_eq!(
+            verify_with_ocsp(builder.clone(), END, &[0x30, 0x03, 0x0a, 0x01, 0x01], 1),
+            Ok(())
+        );
+        assert_eq!(
+            verify_with_ocsp(
+                ocsp_builder().with_stapled_ocsp_validation(),
+                END,
+                &[0x30, 0x03, 0x0a, 0x01, 0x01],
+                1
+            ),
+            unknown_status()
+        );
+    }
+
+    #[test]
+    fn test_ocsp_must_staple() {
+        let builder = ocsp_builder()
+            .with_ocsp_must_staple()
+            .allow_unknown_revocation_status();
+        assert_eq!(
+            verify_with_ocsp(builder.clone(), END_MUST_STAPLE, &[], 1),
+            unknown_status()
+        );
+        assert_eq!(
+            verify_with_ocsp(builder.clone(), END_MUST_STAPLE, OCSP_GOOD, 1),
+            Err(CertificateError::InvalidOcspResponse.into())
+        );
+        // Certificates without the TLS feature extension are unaffected.
+        assert_eq!(verify_with_ocsp(builder.clone(), END, &[], 1), Ok(()));
+        assert_eq!(verify_with_ocsp(builder, END, OCSP_UNKNOWN, 1), Ok(()));
+    }
+
+    #[test]
+    fn test_ocsp_must_staple_not_enforced_by_default() {
+        let builder = ocsp_builder().with_stapled_ocsp_validation();
+        assert_eq!(verify_with_ocsp(builder, END_MUST_STAPLE, &[], 1), Ok(()));
+    }
+
+    fn verify_with_report(
+        builder: ServerCertVerifierBuilder,
+        intermediates: &[CertificateDer<'_>],
+        now: u64,
+    ) -> VerificationReport {
+        builder
+            .build()
+            .unwrap()
+            .verify_with_report(
+                &load_certs(END).remove(0),
+                intermediates,
+                &ServerName::try_from("testserver.com").unwrap(),
+                &[],
+                UnixTime::since_unix_epoch(core::time::Duration::from_secs(now)),
+            )
+    }
+
+    #[test]
+    fn test_verification_report() {
+        let end_entity = load_certs(END).remove(0);
+        let intermediates = load_certs(include_bytes!("../../../test-ca/rsa/inter.cert"));
+        let builder = ocsp_builder()
+            .with_crls(load_crls(&[include_bytes!(
+                "../../../test-ca/rsa/client.revoked.crl.pem"
+            )]))
+            .allow_unknown_revocation_status();
+
+        let report = verify_with_report(builder, &intermediates, 1_698_100_000);
+        assert_eq!(report.result, Ok(()));
+        assert_eq!(
+            report.chain,
+            vec![end_entity.clone(), intermediates[0].clone()]
+        );
+        assert_eq!(
+            report.trust_anchor.unwrap().subject,
+            test_roots().roots[1].subject
+        );
+        assert!(report.failed_paths.is_empty());
+
+        let checks = report
+            .revocation_checks
+            .iter()
+            .map(|check| (&check.certificate, check.method, check.status))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            checks,
+            vec![
+                (&end_entity, RevocationMethod::Crl, RevocationStatus::Good),
+                (
+                    &intermediates[0],
+                    RevocationMethod::Crl,
+                    RevocationStatus::Unknown
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_verification_report_failed_paths() {
+        let intermediates = load_certs(include_bytes!("../../../test-ca/rsa/inter.cert"));
+
+        // no path to a trust anchor
+        let report = verify_with_report(ocsp_builder(), &[], 1_698_100_000);
+        assert_eq!(report.result, Err(CertificateError::UnknownIssuer.into()));
+        assert!(report.chain.is_empty());
+        assert_eq!(report.failed_paths.len(), 1);
+        assert_eq!(report.failed_paths[0].chain.len(), 1);
+        assert!(report.failed_paths[0]
+            .trust_anchor
+            .is_none());
+
+        // a path to a trust anchor, rejected because the certificates have expired
+        let report = verify_with_report(ocsp_builder(), &intermediates, 4_000_000_000);
+        assert_eq!(report.result, Err(CertificateError::Expired.into()));
+        assert_eq!(report.failed_paths.len(), 1);
+        let failed = &report.failed_paths[0];
+        assert_eq!(failed.chain[1], intermediates[0]);
+        assert_eq!(
+            failed
+                .trust_anchor
+                .as_ref()
+                .unwrap()
+                .subject,
+            test_roots().roots[1].subject
+        );
+        assert_eq!(failed.error, CertificateError::Expired.into());
+    }
+
+    #[test]
+    fn test_verification_reports_are_opt_in() {
+        let end_entity = load_certs(END).remove(0);
+        let intermediates = load_certs(include_bytes!("../../../test-ca/rsa/inter.cert"));
+        let server_name = ServerName::try_from("testserver.com").unwrap();
+        let report = |builder: ServerCertVerifierBuilder| {
+            let (result, report) = builder
+                .build()
+                .unwrap()
+                .verify_server_cert_with_report(
+                    &end_entity,
+                    &intermediates,
+                    &server_name,
+                    &[],
+                    &[],
+                    UnixTime::now(),
+                );
+            if let Some(report) = &report {
+                assert_eq!(report.result, result.map(|_| ()));
+            }
+            report
+        };
+
+        assert!(report(ocsp_builder()).is_none());
+        assert!(report(ocsp_builder().with_verification_reports()).is_some());
+    }
 }
@@ -0,0 +1,279 @@
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+
+use pki_types::{CertificateDer, ServerName, UnixTime};
+
+use super::verify::ParsedCertificate;
+use super::{verify_tls12_signature, verify_tls13_signature};
+use crate::client::StoresKnownHosts;
+use crate::crypto::{CryptoProvider, WebPkiSupportedAlgorithms};
+use crate::enums::SignatureScheme;
+use crate::verify::{
+    DigitallySignedStruct, HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
+};
+use crate::{CertificateError, Error};
+
+/// A [`ServerCertVerifier`] that trusts each server's public key on first use.
+///
+/// Like SSH, the first public key seen for a server name is accepted and
+/// recorded in a [`StoresKnownHosts`].  Later connections to that name must
+/// present a certificate with the same public key, or are rejected with
+/// [`CertificateError::PublicKeyChanged`].  To accept a new key, remove
+/// the recorded one with [`StoresKnownHosts::remove`].
+///
+/// Only the end-entity certificate's public key is considered.  The rest of
+/// the certificate, including its names and validity period, is not checked,
+/// and neither are intermediates.  The handshake signature is verified
+/// against the public key as usual.
+///
+/// Keys are recorded with [`StoresKnownHosts::put`], which only records a
+/// key if none is known yet, so concurrent first connections to a server
+/// cannot each trust a different key.
+pub struct TofuVerifier {
+    store: Arc<dyn StoresKnownHosts>,
+    supported: WebPkiSupportedAlgorithms,
+}
+
+impl TofuVerifier {
+    /// Make a verifier that records keys in `store`.
+    ///
+    /// Handshake signatures are verified using the process-default
+    /// [`CryptoProvider`] for the `ring` feature.
+    #[cfg(feature = "ring")]
+    pub fn new(store: Arc<dyn StoresKnownHosts>) -> Self {
+        Self::new_with_provider(store, crate::crypto::ring::default_provider().into())
+    }
+
+    /// Make a verifier that records keys in `store`.
+    ///
+    /// Handshake signatures are verified using the signature verification
+    /// algorithms of the specified [`CryptoProvider`].
+    pub fn new_with_provider(
+        store: Arc<dyn StoresKnownHosts>,
+        provider: Arc<CryptoProvider>,
+    ) -> Self {
+        Self {
+            store,
+            supported: provider.signature_verification_algorithms,
+        }
+    }
+}
+
+impl ServerCertVerifier for TofuVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _scts: &[&[u8]],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, Error> {
+        let spki = ParsedCertificate::try_from(end_entity)?
+            .0
+            .subject_public_key_info();
+
+        match self
+            .store
+            .put(server_name.to_owned(), spki.to_vec())
+        {
+            Some(known) if known != spki.as_ref() => Err(CertificateError::PublicKeyChanged.into()),
+            _ => Ok(ServerCertVerified::assertion()),
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        verify_tls12_signature(message, cert, dss, &self.supported)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        verify_tls13_signature(message, cert, dss, &self.supported)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.supported.supported_schemes()
+    }
+}
+
+impl fmt::Debug for TofuVerifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TofuVerifier")
+            .field("store", &self.store)
+            .finish()
+    }
+}
+
+#[cfg(all(test, any(feature = "ring", feature = "aws_lc_rs")))]
+mod tests {
+    use std::prelude::v1::*;
+    use std::sync::Arc;
+
+    use pki_types::{CertificateDer, ServerName, UnixTime};
+
+    use super::TofuVerifier;
+    use crate::client::{KnownHostsMemoryCache, StoresKnownHosts};
+    use crate::verify::ServerCertVerifier;
+    use crate::{test_provider, CertificateError, Error};
+
+    fn end_entity(pem: &[u8]) -> CertificateDer<'static> {
+        rustls_pemfile::certs(&mut &pem[..])
+            .next()
+            .unwrap()
+            .unwrap()
+    }
+
+    fn rsa() -> CertificateDer<'static> {
+        end_entity(include_bytes!("../../../test-ca/rsa/end.cert"))
+    }
+
+    fn ecdsa() -> CertificateDer<'static> {
+        end_entity(include_bytes!("../../../test-ca/ecdsa/end.cert"))
+    }
+
+    fn verify(
+        verifier: &TofuVerifier,
+        cert: &CertificateDer<'_>,
+        server_name: &str,
+    ) -> Result<(), Error> {
+        verifier
+            .verify_server_cert(
+                cert,
+                &[],
+                &ServerName::try_from(server_name).unwrap(),
+                &[],
+                &[],
+                UnixTime::now(),
+            )
+            .map(|_| ())
+    }
+
+    #[test]
+    fn first_key_is_trusted() {
+        let store = Arc::new(KnownHostsMemoryCache::new());
+        let verifier = TofuVerifier::new_with_provider(
+            Arc::<KnownHostsMemoryCache>::clone(&store),
+            test_provider::default_provider().into(),
+        );
+
+        assert_eq!(verify(&verifier, &rsa(), "testserver.com"), Ok(()));
+        assert!(store
+            .get(&ServerName::try_from("testserver.com").unwrap())
+            .is_some());
+        assert_eq!(verify(&verifier, &rsa(), "testserver.com"), Ok(()));
+
+        assert_eq!(
+            verify(&verifier, &ecdsa(), "testserver.com"),
+            Err(CertificateError::PublicKeyChanged.into())
+        );
+
+        // other names are independent
+        assert_eq!(verify(&verifier, &ecdsa(), "other.com"), Ok(()));
+        assert_eq!(
+            verify(&verifier, &rsa(), "other.com"),
+            Err(CertificateError::PublicKeyChanged.into())
+        );
+    }
+
+    #[test]
+    fn replaced_key_is_trusted() {
+        let store = Arc::new(KnownHostsMemoryCache::new());
+        let verifier = TofuVerifier::new_with_provider(
+            Arc::<KnownHostsMemoryCache>::clone(&store),
+            test_provider::default_provider().into(),
+        );
+        assert_eq!(verify(&verifier, &rsa(), "testserver.com"), Ok(()));
+
+        let verifier_for_ecdsa = TofuVerifier::new_with_provider(
+            Arc::new(KnownHostsMemoryCache::new()),
+            test_provider::default_provider().into(),
+        );
+        assert_eq!(
+            verify(&verifier_for_ecdsa, &ecdsa(), "testserver.com"),
+            Ok(())
+        );
+
+        let name = ServerName::try_from("testserver.com").unwrap();
+        let ecdsa_spki = verifier_for_ecdsa
+            .store
+            .get(&name)
+            .unwrap();
+        assert!(store
+            .put(name.clone(), ecdsa_spki.clone())
+            .is_some());
+        assert_eq!(
+            verify(&verifier, &ecdsa(), "testserver.com"),
+            Err(CertificateError::PublicKeyChanged.into())
+        );
+
+        assert!(store.remove(&name).is_some());
+        assert_eq!(store.put(name, ecdsa_spki), None);
+
+        assert_eq!(verify(&verifier, &ecdsa(), "testserver.com"), Ok(()));
+        assert_eq!(
+            verify(&verifier, &rsa(), "testserver.com"),
+            Err(CertificateError::PublicKeyChanged.into())
+        );
+    }
+
+    #[test]
+    fn concurrent_first_connections_trust_one_key() {
+        let store = Arc::new(KnownHostsMemoryCache::new());
+        let verifier = Arc::new(TofuVerifier::new_with_provider(
+            Arc::<KnownHostsMemoryCache>::clone(&store),
+            test_provider::default_provider().into(),
+        ));
+
+        let results = (0..8)
+            .map(|i| {
+                let verifier = Arc::clone(&verifier);
+                std::thread::spawn(move || {
+                    let cert = if i % 2 == 0 { rsa() } else { ecdsa() };
+                    verify(&verifier, &cert, "testserver.com").is_ok()
+                })
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|thread| thread.join().unwrap())
+            .collect::<Vec<_>>();
+
+        // whichever key was recorded first, only connections presenting it
+        // succeed
+        let rsa_recorded = verify(&verifier, &rsa(), "testserver.com").is_ok();
+        for (i, ok) in results.into_iter().enumerate() {
+            assert_eq!(ok, (i % 2 == 0) == rsa_recorded);
+        }
+    }
+
+    #[test]
+    fn unparseable_certificate_is_not_recorded() {
+        let store = Arc::new(KnownHostsMemoryCache::new());
+        let verifier = TofuVerifier::new_with_provider(
+            Arc::<KnownHostsMemoryCache>::clone(&store),
+            test_provider::default_provider().into(),
+        );
+
+        assert_eq!(
+            verify(
+                &verifier,
+                &CertificateDer::from(&b"garbage"[..]),
+                "testserver.com"
+            ),
+            Err(CertificateError::BadEncoding.into())
+        );
+        assert_eq!(
+            store.get(&ServerName::try_from("testserver.com").unwrap()),
+            None
+        );
+    }
+}
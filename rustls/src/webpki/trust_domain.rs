@@ -0,0 +1,516 @@
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use pki_types::{CertificateDer, CertificateRevocationListDer, DnsName, UnixTime};
+use webpki::{RevocationCheckDepth, UnknownStatusPolicy};
+
+use super::client_verifier::AnonymousClientPolicy;
+use super::{parse_crls, VerifierBuilderError, WebPkiClientVerifier};
+use crate::crypto::{CryptoProvider, WebPkiSupportedAlgorithms};
+use crate::verify::{
+    ClientCertVerified, ClientCertVerifier, DigitallySignedStruct, HandshakeSignatureValid,
+};
+use crate::webpki::identity::{ClientIdentity, ClientIdentityPolicy};
+use crate::webpki::verify::{verify_tls12_signature, verify_tls13_signature};
+use crate::x509::wrap_in_sequence;
+use crate::{CertificateError, DistinguishedName, Error, RootCertStore, SignatureScheme};
+
+/// How a [`TrustDomainClientVerifier`] decides that a client belongs to a trust domain.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum TrustDomainSelector {
+    /// The client asked for this server name using SNI.
+    ServerName(DnsName<'static>),
+    /// A certificate presented by the client was issued by one of the trust domain's
+    /// trust anchors.
+    Issuer,
+}
+
+/// A builder for configuring a [`TrustDomainClientVerifier`].
+#[derive(Debug, Clone)]
+pub struct TrustDomainVerifierBuilder {
+    domains: Vec<(
+        TrustDomainSelector,
+        Arc<RootCertStore>,
+        Vec<CertificateRevocationListDer<'static>>,
+    )>,
+    revocation_check_depth: RevocationCheckDepth,
+    unknown_revocation_policy: UnknownStatusPolicy,
+    anon_policy: AnonymousClientPolicy,
+    identity_policy: Option<ClientIdentityPolicy>,
+    supported_algs: WebPkiSupportedAlgorithms,
+}
+
+impl TrustDomainVerifierBuilder {
+    fn new(supported_algs: WebPkiSupportedAlgorithms) -> Self {
+        Self {
+            domains: Vec::new(),
+            revocation_check_depth: RevocationCheckDepth::Chain,
+            unknown_revocation_policy: UnknownStatusPolicy::Deny,
+            anon_policy: AnonymousClientPolicy::Deny,
+            identity_policy: None,
+            supported_algs,
+        }
+    }
+
+    /// Add a trust domain, whose clients are selected by `selector` and have their
+    /// certificates verified using the trust anchors in `roots` and the revocation
+    /// lists `crls`.
+    ///
+    /// Domains selected by [`TrustDomainSelector::ServerName`] take priority over
+    /// those selected by [`TrustDomainSelector::Issuer`].  Otherwise, the first
+    /// domain added that matches is used.
+    pub fn with_trust_domain(
+        mut self,
+        selector: TrustDomainSelector,
+        roots: Arc<RootCertStore>,
+        crls: impl IntoIterator<Item = CertificateRevocationListDer<'static>>,
+    ) -> Self {
+        let selector = match selector {
+            TrustDomainSelector::ServerName(name) => {
+                TrustDomainSelector::ServerName(name.to_lowercase_owned())
+            }
+            TrustDomainSelector::Issuer => TrustDomainSelector::Issuer,
+        };
+        self.domains
+            .push((selector, roots, crls.into_iter().collect()));
+        self
+    }
+
+    /// Only check the end entity certificate revocation status when using CRLs.
+    ///
+    /// See [`ClientCertVerifierBuilder::only_check_end_entity_revocation`][crate::server::ClientCertVerifierBuilder::only_check_end_entity_revocation].
+    pub fn only_check_end_entity_revocation(mut self) -> Self {
+        self.revocation_check_depth = RevocationCheckDepth::EndEntity;
+        self
+    }
+
+    /// Allow unknown certificate revocation status when using CRLs.
+    ///
+    /// See [`ClientCertVerifierBuilder::allow_unknown_revocation_status`][crate::server::ClientCertVerifierBuilder::allow_unknown_revocation_status].
+    pub fn allow_unknown_revocation_status(mut self) -> Self {
+        self.unknown_revocation_policy = UnknownStatusPolicy::Allow;
+        self
+    }
+
+    /// Allow unauthenticated clients to connect.
+    ///
+    /// Clients that offer no client certificate will be allowed to connect, in every
+    /// trust domain.
+    pub fn allow_unauthenticated(mut self) -> Self {
+        self.anon_policy = AnonymousClientPolicy::Allow;
+        self
+    }
+
+    /// Only accept clients whose certificates meet `policy`, in every trust domain.
+    ///
+    /// See [`ClientCertVerifierBuilder::with_identity_policy`][crate::server::ClientCertVerifierBuilder::with_identity_policy].
+    pub fn with_identity_policy(mut self, policy: ClientIdentityPolicy) -> Self {
+        self.identity_policy = Some(policy);
+        self
+    }
+
+    /// Build a client certificate verifier.
+    ///
+    /// # Errors
+    /// This function will return a `VerifierBuilderError` if:
+    /// 1. No trust domains have been added, or a trust domain has no trust anchors.
+    /// 2. DER encoded CRLs have been provided that can not be parsed successfully.
+    pub fn build(self) -> Result<Arc<dyn ClientCertVerifier>, VerifierBuilderError> {
+        if self.domains.is_empty() {
+            return Err(VerifierBuilderError::NoRootAnchors);
+        }
+
+        let mut domains = Vec::with_capacity(self.domains.len());
+        let mut issuer_hint_subjects = Vec::new();
+        for (selector, roots, crls) in self.domains {
+            if roots.is_empty() {
+                return Err(VerifierBuilderError::NoRootAnchors);
+            }
+
+            let subjects = roots.subjects();
+            if let TrustDomainSelector::Issuer = selector {
+                issuer_hint_subjects.extend(subjects.iter().cloned());
+            }
+
+            let verifier = WebPkiClientVerifier::new(
+                roots,
+                subjects,
+                parse_crls(crls)?,
+                self.revocation_check_depth,
+                self.unknown_revocation_policy,
+                AnonymousClientPolicy::Deny,
+                self.supported_algs,
+            )
+            .with_identity_policy(self.identity_policy.clone());
+            domains.push((selector, verifier));
+        }
+
+        Ok(Arc::new(TrustDomainClientVerifier {
+            domains,
+            issuer_hint_subjects,
+            anonymous_policy: self.anon_policy,
+            identity_policy: self.identity_policy,
+            supported_algs: self.supported_algs,
+        }))
+    }
+}
+
+/// A client certificate verifier with separate trust anchors and CRLs for each of
+/// several trust domains, such as the tenants of a multi-tenant service or the
+/// members of a SPIFFE federation.
+///
+/// Each client is verified against a single trust domain, selected by a
+/// [`TrustDomainSelector`]:
+///
+/// - If the client asked for a server name using SNI, and a domain was added for
+///   that name, that domain is used.  The client must chain to its trust anchors.
+/// - Otherwise, the first domain selected by [`TrustDomainSelector::Issuer`] with
+///   a trust anchor that issued any of the certificates presented by the client is
+///   used.  Clients need not send their certificates in order.
+///
+/// A client matching no domain is rejected with [`CertificateError::UnknownIssuer`].
+///
+/// The root hint subjects sent to a client are those of the domain for its SNI
+/// server name, or else those of every domain selected by issuer.
+///
+/// ```no_run
+/// # #[cfg(feature = "ring")] {
+/// # use std::sync::Arc;
+/// # use rustls::RootCertStore;
+/// # use rustls::pki_types::DnsName;
+/// # use rustls::server::{TrustDomainClientVerifier, TrustDomainSelector};
+/// # let tenant_roots = Arc::new(RootCertStore::empty());
+/// # let federation_roots = Arc::new(RootCertStore::empty());
+/// let client_verifier = TrustDomainClientVerifier::builder()
+///     .with_trust_domain(
+///         TrustDomainSelector::ServerName(DnsName::try_from("tenant.example.com").unwrap()),
+///         tenant_roots,
+///         Vec::new(),
+///     )
+///     .with_trust_domain(TrustDomainSelector::Issuer, federation_roots, Vec::new())
+///     .build()
+///     .unwrap();
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct TrustDomainClientVerifier {
+    domains: Vec<(TrustDomainSelector, WebPkiClientVerifier)>,
+    issuer_hint_subjects: Vec<DistinguishedName>,
+    anonymous_policy: AnonymousClientPolicy,
+    identity_policy: Option<ClientIdentityPolicy>,
+    supported_algs: WebPkiSupportedAlgorithms,
+}
+
+impl TrustDomainClientVerifier {
+    /// Create a builder for a trust domain client certificate verifier using the
+    /// default [`CryptoProvider`].
+    #[cfg(feature = "ring")]
+    pub fn builder() -> TrustDomainVerifierBuilder {
+        Self::builder_with_provider(crate::crypto::ring::default_provider().into())
+    }
+
+    /// Create a builder for a trust domain client certificate verifier using the
+    /// specified [`CryptoProvider`].
+    pub fn builder_with_provider(provider: Arc<CryptoProvider>) -> TrustDomainVerifierBuilder {
+        TrustDomainVerifierBuilder::new(provider.signature_verification_algorithms)
+    }
+
+    fn for_server_name(&self, server_name: Option<&DnsName<'_>>) -> Option<&WebPkiClientVerifier> {
+        let server_name = server_name?;
+        self.domains
+            .iter()
+            .find_map(|(selector, verifier)| match selector {
+                TrustDomainSelector::ServerName(name) if name.as_ref() == server_name.as_ref() => {
+                    Some(verifier)
+                }
+                _ => None,
+            })
+    }
+
+    fn for_issuers(&self, issuers: &[&[u8]]) -> Option<&WebPkiClientVerifier> {
+        self.domains
+            .iter()
+            .find_map(|(selector, verifier)| match selector {
+                TrustDomainSelector::Issuer
+                    if verifier
+                        .root_hint_subjects()
+                        .iter()
+                        .any(|subject| issuers.contains(&subject.as_ref())) =>
+                {
+                    Some(verifier)
+                }
+                _ => None,
+            })
+    }
+}
+
+impl ClientCertVerifier for TrustDomainClientVerifier {
+    fn offer_client_auth(&self) -> bool {
+        true
+    }
+
+    fn client_auth_mandatory(&self) -> bool {
+        match self.anonymous_policy {
+            AnonymousClientPolicy::Allow => false,
+            AnonymousClientPolicy::Deny => true,
+        }
+    }
+
+    fn root_hint_subjects_for(&self, server_name: Option<&DnsName<'_>>) -> &[DistinguishedName] {
+        match self.for_server_name(server_name) {
+            Some(verifier) => verifier.root_hint_subjects(),
+            None => &self.issuer_hint_subjects,
+        }
+    }
+
+    fn verify_client_cert_for(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: Option<&DnsName<'_>>,
+        now: UnixTime,
+    ) -> Result<ClientCertVerified, Error> {
+        let verifier = match self.for_server_name(server_name) {
+            Some(verifier) => verifier,
+            None => {
+                let issuers = core::iter::once(end_entity)
+                    .chain(intermediates)
+                    .map(|der| {
+                        webpki::EndEntityCert::try_from(der)
+                            .map(|cert| wrap_in_sequence(cert.issuer()))
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| CertificateError::BadEncoding)?;
+                let issuers = issuers
+                    .iter()
+                    .map(Vec::as_slice)
+                    .collect::<Vec<_>>();
+                self.for_issuers(&issuers)
+                    .ok_or(CertificateError::UnknownIssuer)?
+            }
+        };
+
+        verifier.verify_client_cert(end_entity, intermediates, now)
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        verify_tls12_signature(message, cert, dss, &self.supported_algs)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        verify_tls13_signature(message, cert, dss, &self.supported_algs)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.supported_algs.supported_schemes()
+    }
+
+    fn client_identity(
+        &self,
+        end_entity: &CertificateDer<'_>,
+    ) -> Result<Option<ClientIdentity>, Error> {
+        match &self.identity_policy {
+            Some(policy) => policy.verify(end_entity),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(all(test, any(feature = "ring", feature = "aws_lc_rs")))]
+mod tests {
+    use std::prelude::v1::*;
+    use std::sync::Arc;
+
+    use pki_types::{CertificateDer, DnsName, UnixTime};
+
+    use super::{TrustDomainClientVerifier, TrustDomainSelector};
+    use crate::verify::ClientCertVerifier;
+    use crate::{test_provider, CertificateError, Error, RootCertStore};
+
+    fn load_certs(pem: &[u8]) -> Vec<CertificateDer<'static>> {
+        rustls_pemfile::certs(&mut &pem[..])
+            .map(|cert| cert.unwrap())
+            .collect()
+    }
+
+    fn roots(pem: &[u8]) -> Arc<RootCertStore> {
+        let mut roots = RootCertStore::empty();
+        roots.add_parsable_certificates(load_certs(pem));
+        Arc::new(roots)
+    }
+
+    fn rsa_roots() -> Arc<RootCertStore> {
+        roots(include_bytes!("../../../test-ca/rsa/ca.cert"))
+    }
+
+    fn ecdsa_roots() -> Arc<RootCertStore> {
+        roots(include_bytes!("../../../test-ca/ecdsa/ca.cert"))
+    }
+
+    fn rsa_client() -> Vec<CertificateDer<'static>> {
+        load_certs(include_bytes!("../../../test-ca/rsa/client.fullchain"))
+    }
+
+    fn ecdsa_client() -> Vec<CertificateDer<'static>> {
+        load_certs(include_bytes!("../../../test-ca/ecdsa/client.fullchain"))
+    }
+
+    fn verify(
+        verifier: &dyn ClientCertVerifier,
+        chain: &[CertificateDer<'_>],
+        server_name: Option<&str>,
+    ) -> Result<(), Error> {
+        let server_name = server_name.map(|name| DnsName::try_from(name).unwrap());
+        verifier
+            .verify_client_cert_for(
+                &chain[0],
+                &chain[1..],
+                server_name.as_ref(),
+                UnixTime::now(),
+            )
+            .map(|_| ())
+    }
+
+    fn hints(verifier: &dyn ClientCertVerifier, server_name: Option<&str>) -> Vec<Vec<u8>> {
+        let server_name = server_name.map(|name| DnsName::try_from(name).unwrap());
+        verifier
+            .root_hint_subjects_for(server_name.as_ref())
+            .iter()
+            .map(|subject| subject.as_ref().to_vec())
+            .collect()
+    }
+
+    fn subjects(roots: &RootCertStore) -> Vec<Vec<u8>> {
+        roots
+            .subjects()
+            .iter()
+            .map(|subject| subject.as_ref().to_vec())
+            .collect()
+    }
+
+    fn server_name(name: &str) -> TrustDomainSelector {
+        TrustDomainSelector::ServerName(
+            DnsName::try_from(name)
+                .unwrap()
+                .to_owned(),
+        )
+    }
+
+    #[test]
+    fn selects_trust_domain_by_server_name() {
+        let verifier = TrustDomainClientVerifier::builder_with_provider(
+            test_provider::default_provider().into(),
+        )
+        .with_trust_domain(server_name("rsa.example.com"), rsa_roots(), None)
+        .with_trust_domain(server_name("ECDSA.example.com"), ecdsa_roots(), None)
+        .build()
+        .unwrap();
+
+        assert_eq!(
+            verify(&*verifier, &rsa_client(), Some("rsa.example.com")),
+            Ok(())
+        );
+        assert_eq!(
+            verify(&*verifier, &ecdsa_client(), Some("ecdsa.example.com")),
+            Ok(())
+        );
+        assert_eq!(
+            verify(&*verifier, &ecdsa_client(), Some("rsa.example.com")),
+            Err(CertificateError::UnknownIssuer.into())
+        );
+        assert_eq!(
+            verify(&*verifier, &rsa_client(), None),
+            Err(CertificateError::UnknownIssuer.into())
+        );
+
+        assert_eq!(
+            hints(&*verifier, Some("rsa.example.com")),
+            subjects(&rsa_roots())
+        );
+        assert_eq!(
+            hints(&*verifier, Some("ecdsa.example.com")),
+            subjects(&ecdsa_roots())
+        );
+        assert!(hints(&*verifier, None).is_empty());
+    }
+
+    #[test]
+    fn selects_trust_domain_by_issuer() {
+        let verifier = TrustDomainClientVerifier::builder_with_provider(
+            test_provider::default_provider().into(),
+        )
+        .with_trust_domain(server_name("rsa.example.com"), rsa_roots(), None)
+        .with_trust_domain(TrustDomainSelector::Issuer, ecdsa_roots(), None)
+        .build()
+        .unwrap();
+
+        assert_eq!(verify(&*verifier, &ecdsa_client(), None), Ok(()));
+        assert_eq!(
+            verify(&*verifier, &ecdsa_client(), Some("other.example.com")),
+            Ok(())
+        );
+        // a matching server name takes priority over the issuer
+        assert_eq!(
+            verify(&*verifier, &ecdsa_client(), Some("rsa.example.com")),
+            Err(CertificateError::UnknownIssuer.into())
+        );
+        assert_eq!(
+            verify(&*verifier, &rsa_client(), None),
+            Err(CertificateError::UnknownIssuer.into())
+        );
+
+        assert_eq!(hints(&*verifier, None), subjects(&ecdsa_roots()));
+        assert_eq!(
+            hints(&*verifier, Some("other.example.com")),
+            subjects(&ecdsa_roots())
+        );
+    }
+
+    #[test]
+    fn selects_trust_domain_by_any_presented_issuer() {
+        let verifier = TrustDomainClientVerifier::builder_with_provider(
+            test_provider::default_provider().into(),
+        )
+        .with_trust_domain(TrustDomainSelector::Issuer, ecdsa_roots(), None)
+        .build()
+        .unwrap();
+
+        // the intermediates out of order, followed by an unrelated certificate
+        let mut chain = ecdsa_client();
+        chain[1..].reverse();
+        chain.extend(load_certs(include_bytes!("../../../test-ca/rsa/ca.cert")));
+        assert_eq!(verify(&*verifier, &chain, None), Ok(()));
+
+        assert_eq!(
+            verify(&*verifier, &rsa_client(), None),
+            Err(CertificateError::UnknownIssuer.into())
+        );
+    }
+
+    #[test]
+    fn requires_trust_anchors() {
+        let builder = TrustDomainClientVerifier::builder_with_provider(
+            test_provider::default_provider().into(),
+        );
+        assert!(builder.clone().build().is_err());
+        assert!(builder
+            .with_trust_domain(
+                TrustDomainSelector::Issuer,
+                Arc::new(RootCertStore::empty()),
+                None
+            )
+            .build()
+            .is_err());
+    }
+}
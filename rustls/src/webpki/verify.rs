@@ -1,7 +1,9 @@
 use alloc::vec::Vec;
 use core::fmt;
 
-use pki_types::{CertificateDer, ServerName, SignatureVerificationAlgorithm, UnixTime};
+use pki_types::{
+    CertificateDer, ServerName, SignatureVerificationAlgorithm, TrustAnchor, UnixTime,
+};
 
 use super::anchors::RootCertStore;
 use super::pki_error;
@@ -126,6 +128,35 @@ impl<'a> TryFrom<&'a CertificateDer<'a>> for ParsedCertificate<'a> {
     }
 }
 
+/// Find which of `intermediates` and `roots` directly issued the server
+/// certificate `cert`, which must be valid at `now`.
+pub(crate) fn find_issuer(
+    cert: &ParsedCertificate<'_>,
+    intermediates: &[CertificateDer<'_>],
+    roots: &[TrustAnchor<'_>],
+    now: UnixTime,
+    supported_algs: &[&dyn SignatureVerificationAlgorithm],
+) -> Option<TrustAnchor<'static>> {
+    let candidates = intermediates
+        .iter()
+        .filter_map(|der| webpki::anchor_from_trusted_cert(der).ok())
+        .chain(roots.iter().cloned())
+        .collect::<Vec<_>>();
+    let path = cert
+        .0
+        .verify_for_usage(
+            supported_algs,
+            &candidates,
+            &[],
+            now,
+            webpki::KeyUsage::server_auth(),
+            None,
+            None,
+        )
+        .ok()?;
+    Some(path.anchor().to_owned())
+}
+
 /// Verify a message signature using the `cert` public key and any supported scheme.
 ///
 /// This function verifies the `dss` signature over `message` using the subject public key from
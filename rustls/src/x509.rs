@@ -1,6 +1,12 @@
 // Additional x509/asn1 functions to those provided in webpki/ring.
 
 use alloc::vec::Vec;
+use core::time::Duration;
+
+use pki_types::UnixTime;
+use yasna::models::ObjectIdentifier;
+use yasna::tags::{TAG_GENERALIZEDTIME, TAG_UTCTIME};
+use yasna::{ASN1Error, ASN1ErrorKind, ASN1Result, BERReader, Tag};
 
 pub(crate) fn asn1_wrap(tag: u8, bytes: &[u8]) -> Vec<u8> {
     let len = bytes.len();
@@ -40,10 +46,238 @@ pub(crate) fn wrap_in_sequence(bytes: &[u8]) -> Vec<u8> {
 
 const DER_SEQUENCE_TAG: u8 = 0x30;
 
+/// An extension of a certificate or an OCSP response.
+///
+/// webpki parses and verifies certificates, but only exposes some of their
+/// extensions; this is for reading the others.
+#[derive(Debug)]
+pub(crate) struct Extension {
+    pub(crate) id: ObjectIdentifier,
+    /// The contents of the `extnValue`.
+    pub(crate) value: Vec<u8>,
+    /// The entire encoding of the extension.
+    pub(crate) der: Vec<u8>,
+}
+
+impl Extension {
+    /// Read an `Extensions` SEQUENCE.
+    pub(crate) fn read_all(reader: BERReader<'_, '_>) -> ASN1Result<Vec<Self>> {
+        reader.collect_sequence_of(|reader| {
+            let ((id, value), der) = reader.read_with_buffer(|reader| {
+                reader.read_sequence(|reader| {
+                    let id = reader.next().read_oid()?;
+                    reader.read_default(false, |reader| reader.read_bool())?;
+                    let value = reader.next().read_bytes()?;
+                    Ok((id, value))
+                })
+            })?;
+            Ok(Self {
+                id,
+                value,
+                der: der.to_vec(),
+            })
+        })
+    }
+
+    /// Return the `extnValue` contents of the extension in `extensions` with
+    /// object identifier `id`, if present.
+    pub(crate) fn find<'e>(extensions: &'e [Self], id: &[u64]) -> Option<&'e [u8]> {
+        extensions
+            .iter()
+            .find(|extension| extension.id.components().as_slice() == id)
+            .map(|extension| extension.value.as_slice())
+    }
+}
+
+/// The `tbsCertificate` of a certificate, split into the encodings of the
+/// fields before its extensions, and the extensions.
+#[derive(Debug)]
+pub(crate) struct TbsCertificate<'a> {
+    /// The entire encoding of each field before the extensions.
+    pub(crate) fields: Vec<&'a [u8]>,
+    pub(crate) extensions: Vec<Extension>,
+}
+
+impl<'a> TbsCertificate<'a> {
+    /// Split the `tbsCertificate` of the DER-encoded certificate `cert`.
+    ///
+    /// Only the certificate's outer structure is checked: parse it with webpki
+    /// to check the rest.
+    pub(crate) fn of(cert: &'a [u8]) -> Option<Self> {
+        yasna::parse_der(cert, |reader| {
+            reader.read_sequence(|reader| {
+                let tbs = reader.next().read_sequence(|reader| {
+                    let mut fields = Vec::new();
+                    let mut extensions = Vec::new();
+                    while let Some(tag) = reader.read_optional(|reader| reader.lookahead_tag())? {
+                        match tag == Tag::context(3) {
+                            true => {
+                                extensions = reader
+                                    .next()
+                                    .read_tagged(tag, Extension::read_all)?
+                            }
+                            false => fields.push(
+                                reader
+                                    .next()
+                                    .read_with_buffer(|reader| reader.read_der())?
+                                    .1,
+                            ),
+                        }
+                    }
+                    Ok(Self { fields, extensions })
+                })?;
+                // signatureAlgorithm and signatureValue
+                reader.next().read_der()?;
+                reader.next().read_der()?;
+                Ok(tbs)
+            })
+        })
+        .ok()
+    }
+
+    /// Return the `extnValue` contents of the extension with object
+    /// identifier `id`, if present.
+    pub(crate) fn extension(&self, id: &[u64]) -> Option<&[u8]> {
+        Extension::find(&self.extensions, id)
+    }
+}
+
+/// Read a UTCTime or GeneralizedTime.
+pub(crate) fn read_time(reader: BERReader<'_, '_>) -> ASN1Result<UnixTime> {
+    let tag = reader.lookahead_tag()?;
+    if tag != TAG_UTCTIME && tag != TAG_GENERALIZEDTIME {
+        return Err(ASN1Error::new(ASN1ErrorKind::Invalid));
+    }
+    let text = reader.read_tagged_implicit(tag, |reader| reader.read_bytes())?;
+    parse_time(&text, tag == TAG_GENERALIZEDTIME).ok_or(ASN1Error::new(ASN1ErrorKind::Invalid))
+}
+
+/// Convert the text of a UTCTime, or of a GeneralizedTime if `generalized`.
+fn parse_time(text: &[u8], generalized: bool) -> Option<UnixTime> {
+    let (year, rest) = match generalized {
+        false => {
+            let yy = u64::from(two_digits(text)?);
+            (if yy < 50 { 2000 + yy } else { 1900 + yy }, &text[2..])
+        }
+        true => {
+            let yyyy = u64::from(two_digits(text)?) * 100 + u64::from(two_digits(text.get(2..)?)?);
+            (yyyy, &text[4..])
+        }
+    };
+
+    // MMDDHHMMSS followed by "Z", optionally with fractional seconds
+    // (which are ignored) in a GeneralizedTime.
+    let month = two_digits(rest)?;
+    let day = two_digits(rest.get(2..)?)?;
+    let hours = two_digits(rest.get(4..)?)?;
+    let minutes = two_digits(rest.get(6..)?)?;
+    let seconds = two_digits(rest.get(8..)?)?;
+    match rest.get(10..)? {
+        b"Z" => {}
+        // DER forbids an empty fraction, and trailing zeros in it.
+        [b'.', fraction @ .., b'1'..=b'9', b'Z']
+            if generalized && fraction.iter().all(u8::is_ascii_digit) => {}
+        _ => return None,
+    }
+
+    if !(1..=12).contains(&month)
+        || !(1..=days_in_month(year, month)).contains(&day)
+        || hours > 23
+        || minutes > 59
+        || seconds > 59
+    {
+        return None;
+    }
+
+    let days = days_since_unix_epoch(year, month, day)?;
+    let secs =
+        days * 86_400 + u64::from(hours) * 3_600 + u64::from(minutes) * 60 + u64::from(seconds);
+    Some(UnixTime::since_unix_epoch(Duration::from_secs(secs)))
+}
+
+fn two_digits(bytes: &[u8]) -> Option<u8> {
+    match bytes {
+        [hi @ b'0'..=b'9', lo @ b'0'..=b'9', ..] => Some((hi - b'0') * 10 + (lo - b'0')),
+        _ => None,
+    }
+}
+
+fn days_in_month(year: u64, month: u8) -> u8 {
+    match month {
+        4 | 6 | 9 | 11 => 30,
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        _ => 31,
+    }
+}
+
+/// Days from 1970-01-01 to the given (proleptic Gregorian) date.
+fn days_since_unix_epoch(year: u64, month: u8, day: u8) -> Option<u64> {
+    if year < 1970 {
+        return None;
+    }
+
+    // Count from 1 March so that the leap day comes last.
+    let (year, month) = match month {
+        1 | 2 => (year - 1, u64::from(month) + 9),
+        _ => (year, u64::from(month) - 3),
+    };
+    let era_days = 365 * year + year / 4 - year / 100 + year / 400;
+    let day_of_year = (153 * month + 2) / 5 + u64::from(day) - 1;
+
+    // 719_468 is the number of days from 0000-03-01 to 1970-01-01.
+    Some(era_days + day_of_year - 719_468)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_read_time() {
+        fn time(tag: u8, text: &[u8]) -> Option<u64> {
+            yasna::parse_der(&asn1_wrap(tag, text), read_time)
+                .ok()
+                .map(|t| t.as_secs())
+        }
+
+        const UTC_TIME: u8 = 0x17;
+        const GENERALIZED_TIME: u8 = 0x18;
+        assert_eq!(time(UTC_TIME, b"700101000000Z"), Some(0));
+        assert_eq!(time(UTC_TIME, b"231023164004Z"), Some(1_698_079_204));
+        assert_eq!(
+            time(GENERALIZED_TIME, b"20240229235959Z"),
+            Some(1_709_251_199)
+        );
+        assert_eq!(
+            time(GENERALIZED_TIME, b"20240229235959.123Z"),
+            Some(1_709_251_199)
+        );
+        assert_eq!(time(GENERALIZED_TIME, b"20240229235959.Z"), None);
+        assert_eq!(time(GENERALIZED_TIME, b"20240229235959.120Z"), None);
+        assert_eq!(time(GENERALIZED_TIME, b"20230229000000Z"), None);
+        assert_eq!(time(GENERALIZED_TIME, b"20231301000000Z"), None);
+        assert_eq!(time(UTC_TIME, b"231023164004"), None);
+        // an OCTET STRING
+        assert_eq!(time(0x04, b"231023164004Z"), None);
+    }
+
+    #[test]
+    fn test_certificate_extensions() {
+        let cert = rustls_pemfile::certs(&mut &include_bytes!("../../test-ca/rsa/end.cert")[..])
+            .next()
+            .unwrap()
+            .unwrap();
+        let tbs = TbsCertificate::of(&cert).unwrap();
+        // version, serialNumber, signature, issuer, validity, subject, subjectPublicKeyInfo
+        assert_eq!(tbs.fields.len(), 7);
+        // subjectAltName
+        assert!(tbs.extension(&[2, 5, 29, 17]).is_some());
+        assert!(tbs.extension(&[1, 2, 3]).is_none());
+
+        assert!(TbsCertificate::of(&cert[..cert.len() - 1]).is_none());
+    }
+
     #[test]
     fn test_empty() {
         assert_eq!(vec![0x30, 0x00], wrap_in_sequence(&[]));
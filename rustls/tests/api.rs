@@ -5,31 +5,36 @@
 use std::cell::RefCell;
 use std::fmt;
 use std::fmt::Debug;
-use std::io::{self, IoSlice, Read, Write};
+use std::io::{self, BufRead, IoSlice, Read, Write};
 use std::mem;
 use std::ops::{Deref, DerefMut};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::time::Duration;
 
-use pki_types::{CertificateDer, IpAddr, ServerName, UnixTime};
+use pki_types::{CertificateDer, DnsName, IpAddr, ServerName, UnixTime};
 use provider::cipher_suite;
 use provider::sign::RsaSigningKey;
 use rustls::client::{verify_server_cert_signed_by_trust_anchor, ResolvesClientCert, Resumption};
+use rustls::client::{ClientConnectionOptions, ServerNameIndication};
 use rustls::crypto::CryptoProvider;
 use rustls::internal::msgs::base::Payload;
 use rustls::internal::msgs::codec::Codec;
 use rustls::internal::msgs::enums::AlertLevel;
 use rustls::internal::msgs::handshake::{ClientExtension, HandshakePayload};
 use rustls::internal::msgs::message::{Message, MessagePayload, PlainMessage};
-use rustls::server::{ClientHello, ParsedCertificate, ResolvesServerCert};
+use rustls::server::{
+    AlpnSelection, ClientHello, ClientHelloMemoryRegister, ClientIdentity, ClientIdentityPolicy,
+    ParsedCertificate, ResolvesServerCert, TrustDomainClientVerifier, TrustDomainSelector,
+};
 use rustls::SupportedCipherSuite;
 use rustls::{
-    sign, AlertDescription, CertificateError, ConnectionCommon, ContentType, Error, KeyLog,
-    PeerIncompatible, PeerMisbehaved, SideData,
+    sign, AlertDescription, CertificateError, ConnectionCommon, ContentType, Error, InvalidMessage,
+    KeyLog, PeerIncompatible, PeerMisbehaved, SideData,
 };
 use rustls::{CipherSuite, ProtocolVersion, SignatureScheme};
-use rustls::{ClientConfig, ClientConnection};
+use rustls::{ClientConfig, ClientConnection, Connection};
 use rustls::{ConnectionTrafficSecrets, DistinguishedName};
 use rustls::{ServerConfig, ServerConnection};
 use rustls::{Stream, StreamOwned};
@@ -103,6 +108,225 @@ fn alpn() {
     );
 }
 
+#[derive(Debug)]
+struct AlpnBySni;
+
+impl rustls::server::SelectsAlpnProtocol for AlpnBySni {
+    fn select(&self, server_name: Option<&str>, offered: &[&[u8]]) -> AlpnSelection {
+        match server_name {
+            Some("localhost") if offered.contains(&&b"h2"[..]) => {
+                AlpnSelection::Protocol(b"h2".to_vec())
+            }
+            Some("localhost") => AlpnSelection::NoProtocol,
+            Some(_) => AlpnSelection::Protocol(b"not-offered".to_vec()),
+            None => AlpnSelection::Refuse,
+        }
+    }
+}
+
+fn alpn_selector_test(
+    client_protos: Vec<Vec<u8>>,
+    enable_sni: bool,
+    agreed: Option<&[u8]>,
+    expected_error: Option<ErrorFromPeer>,
+) {
+    let mut server_config = make_server_config(KeyType::Rsa);
+    // ignored in favour of the selector
+    server_config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    server_config.alpn_selector = Some(Arc::new(AlpnBySni));
+    let server_config = Arc::new(server_config);
+
+    for version in rustls::ALL_VERSIONS {
+        let mut client_config = make_client_config_with_versions(KeyType::Rsa, &[version]);
+        client_config.alpn_protocols = client_protos.clone();
+        client_config.enable_sni = enable_sni;
+
+        let (mut client, mut server) =
+            make_pair_for_arc_configs(&Arc::new(client_config), &server_config);
+
+        let error = do_handshake_until_error(&mut client, &mut server);
+        assert_eq!(client.alpn_protocol(), agreed);
+        assert_eq!(server.alpn_protocol(), agreed);
+        assert_eq!(error.err(), expected_error);
+    }
+}
+
+#[test]
+fn alpn_selector() {
+    // selected per server name, overriding the configured protocols
+    alpn_selector_test(
+        vec![b"http/1.1".to_vec(), b"h2".to_vec()],
+        true,
+        Some(b"h2"),
+        None,
+    );
+
+    // selector declines to choose, handshake proceeds
+    alpn_selector_test(vec![b"http/1.1".to_vec()], true, None, None);
+
+    // explicit refusal
+    alpn_selector_test(
+        vec![b"h2".to_vec()],
+        false,
+        None,
+        Some(ErrorFromPeer::Server(Error::NoApplicationProtocol)),
+    );
+
+    // not consulted if the client doesn't offer ALPN
+    alpn_selector_test(vec![], false, None, None);
+}
+
+#[test]
+fn alpn_selector_must_choose_offered_protocol() {
+    let mut server_config = make_server_config(KeyType::Rsa);
+    server_config.alpn_selector = Some(Arc::new(AlpnBySni));
+    let server_config = Arc::new(server_config);
+
+    let mut client_config = make_client_config(KeyType::Rsa);
+    client_config.alpn_protocols = vec![b"h2".to_vec()];
+    let mut client =
+        ClientConnection::new(Arc::new(client_config), server_name("example.com")).unwrap();
+    let mut server = ServerConnection::new(server_config).unwrap();
+
+    let error = do_handshake_until_error(&mut client, &mut server);
+    assert_eq!(
+        error.err(),
+        Some(ErrorFromPeer::Server(Error::General(
+            "ALPN selector chose a protocol the client did not offer".into()
+        )))
+    );
+}
+
+#[test]
+fn client_connection_options_override_alpn() {
+    let mut server_config = make_server_config(KeyType::Rsa);
+    server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    let server_config = Arc::new(server_config);
+
+    let mut client_config = make_client_config(KeyType::Rsa);
+    client_config.alpn_protocols = vec![b"h2".to_vec()];
+    let client_config = Arc::new(client_config);
+
+    let options = ClientConnectionOptions {
+        alpn_protocols: Some(vec![b"http/1.1".to_vec()]),
+        ..Default::default()
+    };
+    let mut client =
+        ClientConnection::new_with(client_config.clone(), server_name("localhost"), options)
+            .unwrap();
+    let mut server = ServerConnection::new(server_config.clone()).unwrap();
+    do_handshake(&mut client, &mut server);
+    assert_eq!(client.alpn_protocol(), Some(&b"http/1.1"[..]));
+    assert_eq!(server.alpn_protocol(), Some(&b"http/1.1"[..]));
+
+    // the config itself is unaffected
+    let (mut client, mut server) = make_pair_for_arc_configs(&client_config, &server_config);
+    do_handshake(&mut client, &mut server);
+    assert_eq!(client.alpn_protocol(), Some(&b"h2"[..]));
+    assert_eq!(server.alpn_protocol(), Some(&b"h2"[..]));
+}
+
+#[test]
+fn client_connection_options_override_sni() {
+    let client_config = Arc::new(make_client_config(KeyType::Rsa));
+    let server_config = Arc::new(make_server_config(KeyType::Rsa));
+
+    for (sni, expected) in [
+        (ServerNameIndication::FromServerName, Some("localhost")),
+        (ServerNameIndication::Disabled, None),
+        (
+            ServerNameIndication::Name(DnsName::try_from("tenant.example.com").unwrap()),
+            Some("tenant.example.com"),
+        ),
+    ] {
+        let options = ClientConnectionOptions {
+            server_name_indication: sni,
+            ..Default::default()
+        };
+        let mut client =
+            ClientConnection::new_with(client_config.clone(), server_name("localhost"), options)
+                .unwrap();
+        let mut server = ServerConnection::new(server_config.clone()).unwrap();
+        do_handshake(&mut client, &mut server);
+        assert_eq!(server.server_name(), expected);
+    }
+}
+
+#[test]
+fn client_connection_options_send_extra_extensions() {
+    let options = ClientConnectionOptions {
+        extra_extensions: vec![(0xfafa, b"hello".to_vec())],
+        ..Default::default()
+    };
+    let client = ClientConnection::new_with(
+        Arc::new(make_client_config(KeyType::Rsa)),
+        server_name("localhost"),
+        options,
+    )
+    .unwrap();
+    let server = ServerConnection::new(Arc::new(make_server_config(KeyType::Rsa))).unwrap();
+
+    let seen = RefCell::new(false);
+    let expected = [0xfa, 0xfa, 0x00, 0x05, b'h', b'e', b'l', b'l', b'o'];
+    let (mut client, mut server): (Connection, Connection) = (client.into(), server.into());
+    transfer_altered(
+        &mut client,
+        |msg| {
+            if let MessagePayload::Handshake { parsed, .. } = &msg.payload {
+                if let HandshakePayload::ClientHello(_) = &parsed.payload {
+                    *seen.borrow_mut() = parsed
+                        .get_encoding()
+                        .windows(expected.len())
+                        .any(|w| w == expected);
+                }
+            }
+            Altered::InPlace
+        },
+        &mut server,
+    );
+    assert!(*seen.borrow());
+    server.process_new_packets().unwrap();
+}
+
+#[test]
+fn client_connection_options_reject_known_extensions() {
+    let options = ClientConnectionOptions {
+        extra_extensions: vec![(0x0000, Vec::new())],
+        ..Default::default()
+    };
+    let err = ClientConnection::new_with(
+        Arc::new(make_client_config(KeyType::Rsa)),
+        server_name("localhost"),
+        options,
+    )
+    .err()
+    .unwrap();
+    assert!(matches!(err, Error::General(_)));
+}
+
+#[test]
+fn client_connection_options_share_session_store() {
+    let (client_config, server_config) = early_data_configs();
+
+    let options = |alpn: &[u8]| ClientConnectionOptions {
+        alpn_protocols: Some(vec![alpn.to_vec()]),
+        ..Default::default()
+    };
+
+    let mut client = ClientConnection::new_with(
+        client_config.clone(),
+        server_name("localhost"),
+        options(b"a"),
+    )
+    .unwrap();
+    let mut server = ServerConnection::new(server_config.clone()).unwrap();
+    do_handshake(&mut client, &mut server);
+
+    let mut client =
+        ClientConnection::new_with(client_config, server_name("localhost"), options(b"b")).unwrap();
+    assert!(client.early_data().is_some());
+}
+
 fn version_test(
     client_versions: &[&'static rustls::SupportedProtocolVersion],
     server_versions: &[&'static rustls::SupportedProtocolVersion],
@@ -433,6 +657,48 @@ fn client_can_get_server_cert_after_resumption() {
     }
 }
 
+fn make_server_config_with_ocsp(kt: KeyType, ocsp: &[u8]) -> ServerConfig {
+    server_config_builder()
+        .with_no_client_auth()
+        .with_single_cert_with_ocsp(kt.get_chain(), kt.get_key(), ocsp.to_vec())
+        .unwrap()
+}
+
+#[test]
+fn client_can_get_stapled_ocsp_response() {
+    let server_config = make_server_config_with_ocsp(KeyType::Rsa, b"ocsp response");
+    for version in rustls::ALL_VERSIONS {
+        let client_config = make_client_config_with_versions(KeyType::Rsa, &[version]);
+        let (mut client, mut server) = make_pair_for_configs(client_config, server_config.clone());
+        assert_eq!(client.ocsp_response(), None);
+        do_handshake(&mut client, &mut server);
+        assert_eq!(client.ocsp_response(), Some(&b"ocsp response"[..]));
+    }
+}
+
+#[test]
+fn client_ocsp_response_absent_without_staple() {
+    for version in rustls::ALL_VERSIONS {
+        let client_config = make_client_config_with_versions(KeyType::Rsa, &[version]);
+        let (mut client, mut server) =
+            make_pair_for_configs(client_config, make_server_config(KeyType::Rsa));
+        do_handshake(&mut client, &mut server);
+        assert_eq!(client.ocsp_response(), None);
+    }
+}
+
+#[test]
+fn client_can_disable_ocsp_stapling() {
+    let server_config = make_server_config_with_ocsp(KeyType::Rsa, b"ocsp response");
+    for version in rustls::ALL_VERSIONS {
+        let mut client_config = make_client_config_with_versions(KeyType::Rsa, &[version]);
+        client_config.enable_ocsp_stapling = false;
+        let (mut client, mut server) = make_pair_for_configs(client_config, server_config.clone());
+        do_handshake(&mut client, &mut server);
+        assert_eq!(client.ocsp_response(), None);
+    }
+}
+
 #[test]
 fn server_can_get_client_cert() {
     for kt in ALL_KEY_TYPES.iter() {
@@ -472,6 +738,204 @@ fn server_can_get_client_cert_after_resumption() {
     }
 }
 
+#[test]
+fn server_can_get_client_identity() {
+    let kt = KeyType::Rsa;
+    let server_config = Arc::new(make_server_config_with_client_verifier(
+        kt,
+        webpki_client_verifier_builder(get_client_root_store(kt)).with_identity_policy(
+            ClientIdentityPolicy::new().allow_spiffe_trust_domain("example.org"),
+        ),
+    ));
+    let expected = ClientIdentity::Spiffe {
+        trust_domain: "example.org".into(),
+        path: "/ns/default/sa/web".into(),
+    };
+
+    for version in rustls::ALL_VERSIONS {
+        let client_config = client_config_builder_with_versions(&[version])
+            .with_root_certificates(get_client_root_store(kt))
+            .with_client_auth_cert(kt.get_spiffe_client_chain(), kt.get_client_key())
+            .unwrap();
+        let client_config = Arc::new(client_config);
+
+        let (mut client, mut server) = make_pair_for_arc_configs(&client_config, &server_config);
+        assert_eq!(server.client_identity(), None);
+        do_handshake(&mut client, &mut server);
+        assert_eq!(server.client_identity(), Some(&expected));
+
+        // and after resumption
+        let (mut client, mut server) = make_pair_for_arc_configs(&client_config, &server_config);
+        do_handshake(&mut client, &mut server);
+        assert!(server.peer_certificates().is_some());
+        assert_eq!(server.client_identity(), Some(&expected));
+
+        // a client certificate without an allowed identity is rejected
+        let client_config = make_client_config_with_versions_with_auth(kt, &[version]);
+        let (mut client, mut server) =
+            make_pair_for_arc_configs(&Arc::new(client_config), &server_config);
+        assert_eq!(
+            do_handshake_until_both_error(&mut client, &mut server),
+            Err(vec![
+                ErrorFromPeer::Server(Error::InvalidCertificate(CertificateError::NotValidForName)),
+                ErrorFromPeer::Client(Error::AlertReceived(AlertDescription::BadCertificate)),
+            ])
+        );
+    }
+}
+
+#[test]
+fn server_does_not_resume_client_rejected_by_current_identity_policy() {
+    let kt = KeyType::Rsa;
+    let server_config = Arc::new(make_server_config_with_client_verifier(
+        kt,
+        webpki_client_verifier_builder(get_client_root_store(kt)).with_identity_policy(
+            ClientIdentityPolicy::new().allow_spiffe_trust_domain("example.org"),
+        ),
+    ));
+    let mut stricter_server_config = make_server_config_with_client_verifier(
+        kt,
+        webpki_client_verifier_builder(get_client_root_store(kt)).with_identity_policy(
+            ClientIdentityPolicy::new().allow_spiffe_trust_domain("other.org"),
+        ),
+    );
+    stricter_server_config.session_storage = Arc::clone(&server_config.session_storage);
+    stricter_server_config.ticketer = Arc::clone(&server_config.ticketer);
+    let stricter_server_config = Arc::new(stricter_server_config);
+
+    for version in rustls::ALL_VERSIONS {
+        let client_config = client_config_builder_with_versions(&[version])
+            .with_root_certificates(get_client_root_store(kt))
+            .with_client_auth_cert(kt.get_spiffe_client_chain(), kt.get_client_key())
+            .unwrap();
+        let client_config = Arc::new(client_config);
+
+        let (mut client, mut server) = make_pair_for_arc_configs(&client_config, &server_config);
+        do_handshake(&mut client, &mut server);
+        assert!(server.client_identity().is_some());
+
+        // the stored chain is rechecked, so this does a full handshake, in
+        // which the client is rejected
+        let (mut client, mut server) =
+            make_pair_for_arc_configs(&client_config, &stricter_server_config);
+        assert_eq!(
+            do_handshake_until_both_error(&mut client, &mut server),
+            Err(vec![
+                ErrorFromPeer::Server(Error::InvalidCertificate(CertificateError::NotValidForName)),
+                ErrorFromPeer::Client(Error::AlertReceived(AlertDescription::BadCertificate)),
+            ])
+        );
+    }
+}
+
+#[test]
+fn server_selects_client_trust_domain() {
+    let client_verifier =
+        TrustDomainClientVerifier::builder_with_provider(provider::default_provider().into())
+            .with_trust_domain(
+                TrustDomainSelector::ServerName(DnsName::try_from("localhost").unwrap()),
+                get_client_root_store(KeyType::Ecdsa),
+                None,
+            )
+            .with_trust_domain(
+                TrustDomainSelector::Issuer,
+                get_client_root_store(KeyType::Rsa),
+                None,
+            )
+            .build()
+            .unwrap();
+    let server_config = Arc::new(
+        server_config_builder()
+            .with_client_cert_verifier(client_verifier)
+            .with_single_cert(KeyType::Rsa.get_chain(), KeyType::Rsa.get_key())
+            .unwrap(),
+    );
+
+    for version in rustls::ALL_VERSIONS {
+        for client_kt in [KeyType::Rsa, KeyType::Ecdsa] {
+            let client_config = Arc::new(
+                client_config_builder_with_versions(&[version])
+                    .with_root_certificates(get_client_root_store(KeyType::Rsa))
+                    .with_client_auth_cert(client_kt.get_client_chain(), client_kt.get_client_key())
+                    .unwrap(),
+            );
+
+            for name in ["localhost", "testserver.com"] {
+                let mut client =
+                    ClientConnection::new(Arc::clone(&client_config), server_name(name)).unwrap();
+                let mut server = ServerConnection::new(Arc::clone(&server_config)).unwrap();
+
+                let expect_ok = match name {
+                    "localhost" => client_kt == KeyType::Ecdsa,
+                    _ => client_kt == KeyType::Rsa,
+                };
+                if expect_ok {
+                    do_handshake(&mut client, &mut server);
+                    assert!(server.peer_certificates().is_some());
+                } else {
+                    assert_eq!(
+                        do_handshake_until_both_error(&mut client, &mut server),
+                        Err(vec![
+                            ErrorFromPeer::Server(Error::InvalidCertificate(
+                                CertificateError::UnknownIssuer
+                            )),
+                            ErrorFromPeer::Client(Error::AlertReceived(
+                                AlertDescription::UnknownCA
+                            )),
+                        ])
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn server_can_get_client_identity_in_trust_domain() {
+    let kt = KeyType::Rsa;
+    let client_verifier =
+        TrustDomainClientVerifier::builder_with_provider(provider::default_provider().into())
+            .with_trust_domain(TrustDomainSelector::Issuer, get_client_root_store(kt), None)
+            .with_identity_policy(
+                ClientIdentityPolicy::new().allow_spiffe_trust_domain("example.org"),
+            )
+            .build()
+            .unwrap();
+    let server_config = Arc::new(
+        server_config_builder()
+            .with_client_cert_verifier(client_verifier)
+            .with_single_cert(kt.get_chain(), kt.get_key())
+            .unwrap(),
+    );
+    let expected = ClientIdentity::Spiffe {
+        trust_domain: "example.org".into(),
+        path: "/ns/default/sa/web".into(),
+    };
+
+    for version in rustls::ALL_VERSIONS {
+        let client_config = client_config_builder_with_versions(&[version])
+            .with_root_certificates(get_client_root_store(kt))
+            .with_client_auth_cert(kt.get_spiffe_client_chain(), kt.get_client_key())
+            .unwrap();
+        let (mut client, mut server) =
+            make_pair_for_arc_configs(&Arc::new(client_config), &server_config);
+        do_handshake(&mut client, &mut server);
+        assert_eq!(server.client_identity(), Some(&expected));
+
+        // a client certificate without an allowed identity is rejected
+        let client_config = make_client_config_with_versions_with_auth(kt, &[version]);
+        let (mut client, mut server) =
+            make_pair_for_arc_configs(&Arc::new(client_config), &server_config);
+        assert_eq!(
+            do_handshake_until_both_error(&mut client, &mut server),
+            Err(vec![
+                ErrorFromPeer::Server(Error::InvalidCertificate(CertificateError::NotValidForName)),
+                ErrorFromPeer::Client(Error::AlertReceived(AlertDescription::BadCertificate)),
+            ])
+        );
+    }
+}
+
 #[test]
 #[cfg(feature = "ring")]
 fn test_config_builders_debug() {
@@ -1124,20 +1588,88 @@ fn client_check_server_certificate_ee_revoked() {
 }
 
 #[test]
-fn client_check_server_certificate_ee_unknown_revocation() {
+fn peers_get_verification_reports() {
     for kt in ALL_KEY_TYPES.iter() {
-        let server_config = Arc::new(make_server_config(*kt));
-
-        // Setup a server verifier builder that will check the EE certificate's revocation status, but not
-        // allow unknown revocation status (the default). We'll provide CRLs that are not relevant
-        // to the EE cert to ensure its status is unknown.
-        let unrelated_crls = vec![kt.intermediate_crl()];
-        let forbid_unknown_verifier = webpki_server_verifier_builder(get_client_root_store(*kt))
-            .with_crls(unrelated_crls.clone())
-            .only_check_end_entity_revocation();
+        let server_config = Arc::new(make_server_config_with_client_verifier(
+            *kt,
+            webpki_client_verifier_builder(get_client_root_store(*kt)).with_verification_reports(),
+        ));
+        let server_verifier = webpki_server_verifier_builder(get_client_root_store(*kt))
+            .with_verification_reports()
+            .build()
+            .unwrap();
 
-        // Also set up a verifier builder that will allow unknown revocation status.
-        let allow_unknown_verifier = webpki_server_verifier_builder(get_client_root_store(*kt))
+        for version in rustls::ALL_VERSIONS {
+            let client_config = client_config_builder_with_versions(&[version])
+                .with_webpki_verifier(Arc::clone(&server_verifier))
+                .with_client_auth_cert(kt.get_client_chain(), kt.get_client_key())
+                .unwrap();
+            let (mut client, mut server) =
+                make_pair_for_configs(client_config, (*server_config).clone());
+            assert!(client
+                .peer_verification_report()
+                .is_none());
+            assert!(server
+                .peer_verification_report()
+                .is_none());
+            do_handshake(&mut client, &mut server);
+
+            let report = client
+                .peer_verification_report()
+                .unwrap();
+            assert_eq!(report.result, Ok(()));
+            assert_eq!(report.chain[0], kt.get_chain()[0]);
+            assert!(report.trust_anchor.is_some());
+
+            let report = server
+                .peer_verification_report()
+                .unwrap();
+            assert_eq!(report.result, Ok(()));
+            assert_eq!(report.chain[0], kt.get_client_chain()[0]);
+            assert!(report.trust_anchor.is_some());
+        }
+    }
+
+    // the report explains a failure
+    let server_config = make_server_config(KeyType::Ecdsa);
+    let client_config = make_client_config_with_verifier(
+        &[&rustls::version::TLS13],
+        webpki_server_verifier_builder(get_client_root_store(KeyType::Rsa))
+            .with_verification_reports(),
+    );
+    let (mut client, mut server) = make_pair_for_configs(client_config, server_config);
+    assert_eq!(
+        do_handshake_until_error(&mut client, &mut server),
+        Err(ErrorFromPeer::Client(Error::InvalidCertificate(
+            CertificateError::UnknownIssuer
+        )))
+    );
+    let report = client
+        .peer_verification_report()
+        .unwrap();
+    assert_eq!(
+        report.result,
+        Err(Error::InvalidCertificate(CertificateError::UnknownIssuer))
+    );
+    assert!(report.chain.is_empty());
+    assert!(!report.failed_paths.is_empty());
+}
+
+#[test]
+fn client_check_server_certificate_ee_unknown_revocation() {
+    for kt in ALL_KEY_TYPES.iter() {
+        let server_config = Arc::new(make_server_config(*kt));
+
+        // Setup a server verifier builder that will check the EE certificate's revocation status, but not
+        // allow unknown revocation status (the default). We'll provide CRLs that are not relevant
+        // to the EE cert to ensure its status is unknown.
+        let unrelated_crls = vec![kt.intermediate_crl()];
+        let forbid_unknown_verifier = webpki_server_verifier_builder(get_client_root_store(*kt))
+            .with_crls(unrelated_crls.clone())
+            .only_check_end_entity_revocation();
+
+        // Also set up a verifier builder that will allow unknown revocation status.
+        let allow_unknown_verifier = webpki_server_verifier_builder(get_client_root_store(*kt))
             .with_crls(unrelated_crls)
             .only_check_end_entity_revocation()
             .allow_unknown_revocation_status();
@@ -1931,6 +2463,47 @@ fn client_read_returns_wouldblock_when_no_data() {
                      Err(err) if err.kind() == io::ErrorKind::WouldBlock));
 }
 
+#[test]
+fn client_fill_buf_returns_wouldblock_when_no_data() {
+    let (mut client, _) = make_pair(KeyType::Rsa);
+    assert!(matches!(client.reader().fill_buf(),
+                     Err(err) if err.kind() == io::ErrorKind::WouldBlock));
+}
+
+#[test]
+fn server_fill_buf_returns_one_record_at_a_time() {
+    let (mut client, mut server) = make_pair(KeyType::Rsa);
+    do_handshake(&mut client, &mut server);
+
+    client
+        .writer()
+        .write_all(b"hello")
+        .unwrap();
+    client
+        .writer()
+        .write_all(b"world")
+        .unwrap();
+    client.send_close_notify();
+    transfer(&mut client, &mut server);
+    server.process_new_packets().unwrap();
+
+    let mut reader = server.reader();
+    assert_eq!(reader.fill_buf().unwrap(), b"hello");
+    reader.consume(2);
+    assert_eq!(reader.fill_buf().unwrap(), b"llo");
+    reader.consume(3);
+    assert_eq!(reader.fill_buf().unwrap(), b"world");
+
+    let mut buf = [0u8; 3];
+    reader.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"wor");
+    assert_eq!(reader.fill_buf().unwrap(), b"ld");
+    reader.consume(2);
+
+    // cleanly closed and drained
+    assert_eq!(reader.fill_buf().unwrap(), b"");
+}
+
 #[test]
 fn new_server_returns_initial_io_state() {
     let (_, mut server) = make_pair(KeyType::Rsa);
@@ -3738,6 +4311,152 @@ fn early_data_is_available_on_resumption() {
     assert_eq!(&received_early_data[..], b"hello");
 }
 
+#[derive(Debug)]
+struct ResumptionPolicy {
+    decision: Mutex<rustls::server::ResumptionDecision>,
+    resumed: Mutex<Vec<Vec<u8>>>,
+}
+
+impl rustls::server::HandlesResumption for ResumptionPolicy {
+    fn issue(&self, context: &rustls::server::TicketContext<'_>) -> Vec<u8> {
+        assert_eq!(context.server_name(), Some("localhost"));
+        assert_eq!(context.quic_transport_parameters(), None);
+        let mut data = b"policy:".to_vec();
+        data.extend_from_slice(context.resumption_data());
+        data
+    }
+
+    fn resume(
+        &self,
+        client_hello: &rustls::server::ClientHello<'_>,
+        data: &[u8],
+    ) -> rustls::server::ResumptionDecision {
+        assert_eq!(client_hello.server_name(), Some("localhost"));
+        self.resumed
+            .lock()
+            .unwrap()
+            .push(data.to_vec());
+        *self.decision.lock().unwrap()
+    }
+}
+
+#[test]
+fn resumption_handler_controls_tickets_and_early_data() {
+    use rustls::server::ResumptionDecision;
+
+    let kt = KeyType::Rsa;
+    let mut client_config = make_client_config(kt);
+    client_config.enable_early_data = true;
+    client_config.resumption = Resumption::store(Arc::new(ClientStorage::new()));
+    let client_config = Arc::new(client_config);
+
+    let policy = Arc::new(ResumptionPolicy {
+        decision: Mutex::new(ResumptionDecision::Accept),
+        resumed: Mutex::new(Vec::new()),
+    });
+    let mut server_config = make_server_config(kt);
+    server_config.max_early_data_size = 1234;
+    server_config.resumption_handler = Some(policy.clone());
+    let server_config = Arc::new(server_config);
+
+    let (mut client, mut server) = make_pair_for_arc_configs(&client_config, &server_config);
+    server.set_resumption_data(b"first");
+    do_handshake(&mut client, &mut server);
+    assert_eq!(server.received_resumption_data(), None);
+
+    for (decision, resumed, early_data) in [
+        (ResumptionDecision::Accept, true, true),
+        (ResumptionDecision::RejectEarlyData, true, false),
+        (ResumptionDecision::RejectResumption, false, false),
+    ] {
+        *policy.decision.lock().unwrap() = decision;
+
+        let (mut client, mut server) = make_pair_for_arc_configs(&client_config, &server_config);
+        server.set_resumption_data(b"first");
+        client
+            .early_data()
+            .unwrap()
+            .write_all(b"hello")
+            .unwrap();
+        do_handshake(&mut client, &mut server);
+
+        assert_eq!(
+            policy.resumed.lock().unwrap().pop(),
+            Some(b"policy:first".to_vec())
+        );
+        assert_eq!(
+            server.received_resumption_data(),
+            match resumed {
+                true => Some(&b"policy:first"[..]),
+                false => None,
+            }
+        );
+        assert_eq!(client.is_early_data_accepted(), early_data);
+        assert_eq!(server.early_data().is_some(), early_data);
+    }
+}
+
+fn early_data_client_flight(client_config: &Arc<ClientConfig>) -> Vec<u8> {
+    let mut client =
+        ClientConnection::new(client_config.clone(), server_name("localhost")).unwrap();
+    assert_eq!(
+        client
+            .early_data()
+            .expect("no early data available")
+            .write(b"hello")
+            .unwrap(),
+        5
+    );
+
+    let mut flight = Vec::new();
+    while client.wants_write() {
+        client.write_tls(&mut flight).unwrap();
+    }
+    flight
+}
+
+fn server_accepts_early_data(server_config: &Arc<ServerConfig>, flight: &[u8]) -> bool {
+    let mut server = ServerConnection::new(server_config.clone()).unwrap();
+    server
+        .read_tls(&mut &flight[..])
+        .unwrap();
+    server.process_new_packets().unwrap();
+    server.early_data().is_some()
+}
+
+#[test]
+fn early_data_with_tickets_requires_anti_replay() {
+    let (client_config, server_config) = early_data_configs();
+    let mut server_config = ServerConfig::clone(&server_config);
+    server_config.ticketer = provider::Ticketer::new().unwrap();
+    let server_config = Arc::new(server_config);
+
+    let (mut client, mut server) = make_pair_for_arc_configs(&client_config, &server_config);
+    do_handshake(&mut client, &mut server);
+
+    let mut client = ClientConnection::new(client_config, server_name("localhost")).unwrap();
+    assert!(client.early_data().is_none());
+}
+
+#[test]
+fn early_data_replay_is_rejected() {
+    let (client_config, server_config) = early_data_configs();
+    let mut server_config = ServerConfig::clone(&server_config);
+    server_config.ticketer = provider::Ticketer::new().unwrap();
+    server_config.early_data_anti_replay = Some(ClientHelloMemoryRegister::new(
+        Duration::from_secs(10),
+        1024,
+    ));
+    let server_config = Arc::new(server_config);
+
+    let (mut client, mut server) = make_pair_for_arc_configs(&client_config, &server_config);
+    do_handshake(&mut client, &mut server);
+
+    let flight = early_data_client_flight(&client_config);
+    assert!(server_accepts_early_data(&server_config, &flight));
+    assert!(!server_accepts_early_data(&server_config, &flight));
+}
+
 #[test]
 fn early_data_not_available_on_server_before_client_hello() {
     let mut server = ServerConnection::new(Arc::new(make_server_config(KeyType::Rsa))).unwrap();
@@ -4048,22 +4767,503 @@ mod test_quic {
                 client_params.into(),
             )
             .unwrap();
-            let mut server =
-                quic::ServerConnection::new(server_config, quic::Version::V1, server_params.into())
-                    .unwrap();
+            let mut server =
+                quic::ServerConnection::new(server_config, quic::Version::V1, server_params.into())
+                    .unwrap();
+
+            assert_eq!(
+                step(&mut client, &mut server)
+                    .err()
+                    .unwrap(),
+                Error::NoApplicationProtocol
+            );
+
+            assert_eq!(
+                server.alert(),
+                Some(rustls::AlertDescription::NoApplicationProtocol)
+            );
+        }
+    }
+
+    #[test]
+    fn test_quic_compatible_version_negotiation() {
+        fn packet_can_decrypt(x: &dyn quic::PacketKey, y: &dyn quic::PacketKey) -> bool {
+            let mut buf = [0; 32];
+            let (header, payload_tag) = buf.split_at_mut(8);
+            let (payload, tag_buf) = payload_tag.split_at_mut(8);
+            let tag = x
+                .encrypt_in_place(42, header, payload)
+                .unwrap();
+            tag_buf.copy_from_slice(tag.as_ref());
+            y.decrypt_in_place(42, header, payload_tag)
+                .is_ok()
+        }
+
+        fn can_decrypt(x: &quic::KeyChange, y: &quic::KeyChange) -> bool {
+            fn keys(kc: &quic::KeyChange) -> &quic::Keys {
+                match kc {
+                    quic::KeyChange::Handshake { keys } => keys,
+                    quic::KeyChange::OneRtt { keys, .. } => keys,
+                }
+            }
+
+            packet_can_decrypt(
+                keys(x).local.packet.as_ref(),
+                keys(y).remote.packet.as_ref(),
+            )
+        }
+
+        let kt = KeyType::Rsa;
+        let mut client_config = make_client_config_with_versions(kt, &[&rustls::version::TLS13]);
+        client_config.enable_early_data = true;
+        let client_config = Arc::new(client_config);
+        let mut server_config = make_server_config_with_versions(kt, &[&rustls::version::TLS13]);
+        server_config.max_early_data_size = 0xffffffff;
+        let server_config = Arc::new(server_config);
+
+        // a first handshake, so that the client can send 0-RTT data in the others
+        let mut client = quic::ClientConnection::new(
+            Arc::clone(&client_config),
+            quic::Version::V1,
+            server_name("localhost"),
+            b"client params".to_vec(),
+        )
+        .unwrap();
+        let mut server = quic::ServerConnection::new(
+            Arc::clone(&server_config),
+            quic::Version::V1,
+            b"server params".to_vec(),
+        )
+        .unwrap();
+        while client.is_handshaking() || server.is_handshaking() {
+            step(&mut client, &mut server).unwrap();
+            step(&mut server, &mut client).unwrap();
+        }
+
+        for switch_client in [true, false] {
+            let mut client = quic::ClientConnection::new(
+                Arc::clone(&client_config),
+                quic::Version::V1,
+                server_name("localhost"),
+                b"client params".to_vec(),
+            )
+            .unwrap();
+            let mut server = quic::ServerConnection::new(
+                Arc::clone(&server_config),
+                quic::Version::V1,
+                b"server params".to_vec(),
+            )
+            .unwrap();
+
+            assert!(step(&mut client, &mut server)
+                .unwrap()
+                .is_none());
+            let client_early = client.zero_rtt_keys().unwrap();
+
+            // The server has derived its handshake keys, but not yet returned them.
+            assert_eq!(
+                server.set_quic_version(quic::Version::V1Draft),
+                Err(Error::General(
+                    "QUIC version cannot change between draft and final versions".into()
+                ))
+            );
+            server
+                .set_quic_version(quic::Version::V2)
+                .unwrap();
+            assert_eq!(server.quic_version(), quic::Version::V2);
+            if switch_client {
+                client
+                    .set_quic_version(quic::Version::V2)
+                    .unwrap();
+            }
+
+            // 0-RTT packets keep the original version.
+            let server_early = server.zero_rtt_keys().unwrap();
+            assert!(packet_can_decrypt(
+                client_early.packet.as_ref(),
+                server_early.packet.as_ref()
+            ));
+            assert!(packet_can_decrypt(
+                client
+                    .zero_rtt_keys()
+                    .unwrap()
+                    .packet
+                    .as_ref(),
+                server_early.packet.as_ref()
+            ));
+
+            let server_hs = step(&mut server, &mut client)
+                .unwrap()
+                .unwrap();
+            let server_1rtt = step(&mut server, &mut client)
+                .unwrap()
+                .unwrap();
+            let client_hs = step(&mut client, &mut server)
+                .unwrap()
+                .unwrap();
+            let client_1rtt = step(&mut client, &mut server)
+                .unwrap()
+                .unwrap();
+            assert!(!client.is_handshaking());
+            assert!(!server.is_handshaking());
+            assert!(client.is_early_data_accepted());
+
+            assert_eq!(can_decrypt(&server_hs, &client_hs), switch_client);
+            assert_eq!(can_decrypt(&client_hs, &server_hs), switch_client);
+            assert_eq!(can_decrypt(&server_1rtt, &client_1rtt), switch_client);
+            assert_eq!(can_decrypt(&client_1rtt, &server_1rtt), switch_client);
+
+            assert_eq!(
+                server.set_quic_version(quic::Version::V1),
+                Err(Error::General(
+                    "QUIC version cannot change after handshake keys are in use".into()
+                ))
+            );
+        }
+    }
+
+    #[test]
+    fn test_quic_unbuffered_handshake() {
+        use rustls::quic::unbuffered::{ConnectionState, UnbufferedConnectionCommon};
+        use rustls::unbuffered::EncodeError;
+
+        /// Drive `conn` until it blocks, appending the handshake data it writes to
+        /// `outgoing` at each encryption level.  Returns whether the handshake is complete.
+        fn run<Data>(
+            conn: &mut UnbufferedConnectionCommon<Data>,
+            incoming: &mut Vec<u8>,
+            level: &mut usize,
+            outgoing: &mut [Vec<u8>; 3],
+        ) -> bool {
+            loop {
+                let status = conn.process_quic_handshake(incoming);
+                let done = match status.state.unwrap() {
+                    ConnectionState::EncodeHandshakeData(mut data) => {
+                        let required_size = match data.encode(&mut [0; 1]) {
+                            Err(EncodeError::InsufficientSize(err)) => err.required_size,
+                            other => panic!("unexpected {:?}", other),
+                        };
+                        let mut buf = vec![0; required_size];
+                        assert_eq!(data.encode(&mut buf).unwrap(), required_size);
+                        assert!(matches!(
+                            data.encode(&mut buf),
+                            Err(EncodeError::AlreadyEncoded)
+                        ));
+                        outgoing[*level].extend_from_slice(&buf);
+                        None
+                    }
+                    ConnectionState::KeyChange(_) => {
+                        *level += 1;
+                        None
+                    }
+                    ConnectionState::BlockedHandshake => Some(false),
+                    ConnectionState::Connected => Some(true),
+                    state => panic!("unexpected {:?}", state),
+                };
+                incoming.drain(..status.discard);
+                if let Some(done) = done {
+                    return done;
+                }
+            }
+        }
+
+        let kt = KeyType::Rsa;
+        let client_config = make_client_config_with_versions(kt, &[&rustls::version::TLS13]);
+        let server_config = make_server_config_with_versions(kt, &[&rustls::version::TLS13]);
+
+        let mut client = quic::unbuffered::UnbufferedClientConnection::new(
+            Arc::new(client_config),
+            quic::Version::V1,
+            server_name("localhost"),
+            b"client params".to_vec(),
+        )
+        .unwrap();
+        let mut server = quic::unbuffered::UnbufferedServerConnection::new(
+            Arc::new(server_config),
+            quic::Version::V1,
+            b"server params".to_vec(),
+        )
+        .unwrap();
+
+        let (mut client_level, mut client_out) = (0, <[Vec<u8>; 3]>::default());
+        let (mut server_level, mut server_out) = (0, <[Vec<u8>; 3]>::default());
+
+        // ClientHello
+        assert!(!run(
+            &mut client,
+            &mut Vec::new(),
+            &mut client_level,
+            &mut client_out
+        ));
+        assert_eq!(client_level, 0);
+
+        // ServerHello, then the rest of the server's flight at the handshake level
+        let mut incoming = mem::take(&mut client_out[0]);
+        assert!(!run(
+            &mut server,
+            &mut incoming,
+            &mut server_level,
+            &mut server_out
+        ));
+        assert!(incoming.is_empty());
+        assert_eq!(server_level, 2);
+        assert!(!server_out[0].is_empty());
+        assert!(!server_out[1].is_empty());
+        assert_eq!(
+            server.quic_transport_parameters(),
+            Some(&b"client params"[..])
+        );
+
+        let mut incoming = mem::take(&mut server_out[0]);
+        assert!(!run(
+            &mut client,
+            &mut incoming,
+            &mut client_level,
+            &mut client_out
+        ));
+        assert_eq!(client_level, 1);
+
+        // Supply the handshake level data in two pieces.
+        let mut incoming = mem::take(&mut server_out[1]);
+        let rest = incoming.split_off(incoming.len() / 2);
+        assert!(!run(
+            &mut client,
+            &mut incoming,
+            &mut client_level,
+            &mut client_out
+        ));
+        incoming.extend_from_slice(&rest);
+        assert!(run(
+            &mut client,
+            &mut incoming,
+            &mut client_level,
+            &mut client_out
+        ));
+        assert!(incoming.is_empty());
+        assert_eq!(client_level, 2);
+        assert!(!client.is_handshaking());
+        assert_eq!(
+            client.quic_transport_parameters(),
+            Some(&b"server params"[..])
+        );
+
+        // The client's Finished, then the server's tickets at the 1-RTT level
+        let mut incoming = mem::take(&mut client_out[1]);
+        assert!(run(
+            &mut server,
+            &mut incoming,
+            &mut server_level,
+            &mut server_out
+        ));
+        assert!(!server.is_handshaking());
+        assert!(!server_out[2].is_empty());
+
+        let mut incoming = mem::take(&mut server_out[2]);
+        assert!(run(
+            &mut client,
+            &mut incoming,
+            &mut client_level,
+            &mut client_out
+        ));
+        assert!(incoming.is_empty());
+        assert_eq!(client_level, 2);
+        assert!(client_out
+            .iter()
+            .all(|out| out.is_empty()));
+
+        // A message longer than any handshake message is rejected.
+        let status = client.process_quic_handshake(&mut [4, 0xff, 0xff, 0xff]);
+        assert!(matches!(
+            status.state,
+            Err(Error::InvalidMessage(
+                InvalidMessage::HandshakePayloadTooLarge
+            ))
+        ));
+        assert_eq!(client.alert(), Some(AlertDescription::DecodeError));
+    }
+
+    #[derive(Debug)]
+    struct TransportParamsPolicy {
+        server_params: Mutex<Vec<u8>>,
+    }
+
+    impl rustls::server::HandlesResumption for TransportParamsPolicy {
+        fn issue(&self, context: &rustls::server::TicketContext<'_>) -> Vec<u8> {
+            // remember the parameters this connection's server sent
+            context
+                .quic_transport_parameters()
+                .unwrap()
+                .to_vec()
+        }
+
+        fn resume(
+            &self,
+            _client_hello: &rustls::server::ClientHello<'_>,
+            data: &[u8],
+        ) -> rustls::server::ResumptionDecision {
+            match *self.server_params.lock().unwrap() == data {
+                true => rustls::server::ResumptionDecision::Accept,
+                false => rustls::server::ResumptionDecision::RejectEarlyData,
+            }
+        }
+    }
+
+    #[test]
+    fn test_quic_resumption_handler() {
+        let client_params = &b"client params"[..];
+
+        let kt = KeyType::Rsa;
+        let mut client_config = make_client_config_with_versions(kt, &[&rustls::version::TLS13]);
+        client_config.enable_early_data = true;
+        let client_config = Arc::new(client_config);
+
+        let policy = Arc::new(TransportParamsPolicy {
+            server_params: Mutex::new(b"server params".to_vec()),
+        });
+        let mut server_config = make_server_config_with_versions(kt, &[&rustls::version::TLS13]);
+        server_config.max_early_data_size = 0xffffffff;
+        server_config.resumption_handler = Some(policy.clone());
+        let server_config = Arc::new(server_config);
+
+        let handshake = |expect_early_data: bool| {
+            let server_params = policy
+                .server_params
+                .lock()
+                .unwrap()
+                .clone();
+            let mut client = quic::ClientConnection::new(
+                Arc::clone(&client_config),
+                quic::Version::V1,
+                server_name("localhost"),
+                client_params.into(),
+            )
+            .unwrap();
+            let mut server = quic::ServerConnection::new(
+                Arc::clone(&server_config),
+                quic::Version::V1,
+                server_params,
+            )
+            .unwrap();
+
+            step(&mut client, &mut server).unwrap();
+            assert_eq!(server.zero_rtt_keys().is_some(), expect_early_data);
+            while server.is_handshaking() || client.is_handshaking() {
+                step(&mut server, &mut client).unwrap();
+                step(&mut client, &mut server).unwrap();
+            }
+            step(&mut server, &mut client).unwrap();
+            assert_eq!(client.is_early_data_accepted(), expect_early_data);
+            server
+                .received_resumption_data()
+                .map(|data| data.to_vec())
+        };
+
+        assert_eq!(handshake(false), None);
+        assert_eq!(handshake(true), Some(b"server params".to_vec()));
+
+        *policy.server_params.lock().unwrap() = b"new server params".to_vec();
+        assert_eq!(handshake(false), Some(b"server params".to_vec()));
+    }
+
+    #[test]
+    fn test_quic_acceptor() {
+        let client_params = &b"client params"[..];
+        let server_params = &b"server params"[..];
+
+        let kt = KeyType::Rsa;
+        let mut client_config = make_client_config_with_versions(kt, &[&rustls::version::TLS13]);
+        client_config.alpn_protocols = vec!["foo".into()];
+        let mut client = quic::ClientConnection::new(
+            Arc::new(client_config),
+            quic::Version::V1,
+            server_name("localhost"),
+            client_params.into(),
+        )
+        .unwrap();
+
+        let mut initial = Vec::new();
+        assert!(client.write_hs(&mut initial).is_none());
+
+        let mut acceptor = quic::Acceptor::new(quic::Version::V1);
+        let (first, second) = initial.split_at(initial.len() / 2);
+        acceptor.read_hs(first).unwrap();
+        assert!(acceptor.accept().unwrap().is_none());
+        acceptor.read_hs(second).unwrap();
+        let accepted = acceptor.accept().unwrap().unwrap();
+
+        let hello = accepted.client_hello();
+        assert_eq!(hello.server_name(), Some("localhost"));
+        assert_eq!(hello.quic_transport_parameters(), Some(client_params));
+        assert_eq!(
+            hello
+                .alpn()
+                .unwrap()
+                .collect::<Vec<_>>(),
+            vec![&b"foo"[..]]
+        );
+
+        let mut server_config = make_server_config_with_versions(kt, &[&rustls::version::TLS13]);
+        server_config.alpn_protocols = vec!["foo".into()];
+        let mut server = accepted
+            .into_connection(Arc::new(server_config), server_params.into())
+            .unwrap();
+        assert_eq!(server.quic_transport_parameters(), Some(client_params));
+        assert_eq!(server.server_name(), Some("localhost"));
+
+        step(&mut server, &mut client)
+            .unwrap()
+            .unwrap();
+        step(&mut server, &mut client)
+            .unwrap()
+            .unwrap();
+        assert!(!client.is_handshaking());
+        assert_eq!(client.quic_transport_parameters(), Some(server_params));
+        step(&mut client, &mut server)
+            .unwrap()
+            .unwrap();
+        step(&mut client, &mut server)
+            .unwrap()
+            .unwrap();
+        assert!(!server.is_handshaking());
+        assert_eq!(server.alpn_protocol(), Some(&b"foo"[..]));
 
-            assert_eq!(
-                step(&mut client, &mut server)
-                    .err()
-                    .unwrap(),
-                Error::NoApplicationProtocol
-            );
+        assert!(acceptor.accept().is_err());
+        assert!(acceptor.read_hs(&initial).is_err());
+    }
 
-            assert_eq!(
-                server.alert(),
-                Some(rustls::AlertDescription::NoApplicationProtocol)
-            );
-        }
+    #[test]
+    fn test_quic_acceptor_rejects_missing_alpn() {
+        let kt = KeyType::Rsa;
+        let client_config = make_client_config_with_versions(kt, &[&rustls::version::TLS13]);
+        let mut client = quic::ClientConnection::new(
+            Arc::new(client_config),
+            quic::Version::V1,
+            server_name("localhost"),
+            b"client params".to_vec(),
+        )
+        .unwrap();
+
+        let mut initial = Vec::new();
+        client.write_hs(&mut initial);
+
+        let mut acceptor = quic::Acceptor::new(quic::Version::V1);
+        acceptor.read_hs(&initial).unwrap();
+        let accepted = acceptor.accept().unwrap().unwrap();
+        assert_eq!(
+            accepted
+                .client_hello()
+                .alpn()
+                .map(|_| ()),
+            None
+        );
+
+        let mut server_config = make_server_config_with_versions(kt, &[&rustls::version::TLS13]);
+        server_config.alpn_protocols = vec!["foo".into()];
+        let (err, alert) = accepted
+            .into_connection(Arc::new(server_config), b"server params".to_vec())
+            .unwrap_err();
+        assert_eq!(err, Error::NoApplicationProtocol);
+        assert_eq!(alert, Some(rustls::AlertDescription::NoApplicationProtocol));
     }
 
     #[cfg(feature = "tls12")]
@@ -4448,6 +5648,249 @@ mod test_quic {
         assert_eq!(payload.len(), buf.len() - header_len - tag_len);
     }
 
+    #[test]
+    fn batch_packet_protection() {
+        use cipher_suite::TLS13_AES_128_GCM_SHA256;
+        use quic::{BatchHeader, BatchPacket, HeaderProtectionKey, Keys, PacketKey, Version};
+        use rustls::Side;
+
+        const HEADER_LEN: usize = 5;
+        const PACKET_LEN: usize = 64;
+
+        fn plain_packets() -> Vec<u8> {
+            let mut buf = Vec::new();
+            for pn in 0..4u8 {
+                buf.extend_from_slice(&[0x43, 0, 0, 0, pn]);
+                buf.extend(core::iter::repeat(pn).take(PACKET_LEN - HEADER_LEN));
+            }
+            buf
+        }
+
+        fn protect(packet: &dyn PacketKey, header: &dyn HeaderProtectionKey) -> Vec<u8> {
+            let mut buf = plain_packets();
+            let mut packets = buf
+                .chunks_mut(PACKET_LEN)
+                .enumerate()
+                .map(|(pn, chunk)| {
+                    let (header, payload) = chunk.split_at_mut(HEADER_LEN);
+                    BatchPacket {
+                        packet_number: pn as u64,
+                        header,
+                        payload,
+                    }
+                })
+                .collect::<Vec<_>>();
+            packet
+                .encrypt_batch_in_place(&mut packets)
+                .unwrap();
+
+            let mut headers = buf
+                .chunks_mut(PACKET_LEN)
+                .map(|chunk| {
+                    let (header, payload) = chunk.split_at_mut(HEADER_LEN);
+                    let (first, packet_number) = header.split_first_mut().unwrap();
+                    BatchHeader {
+                        sample: &payload[..16],
+                        first,
+                        packet_number,
+                    }
+                })
+                .collect::<Vec<_>>();
+            header
+                .encrypt_batch_in_place(&mut headers)
+                .unwrap();
+            buf
+        }
+
+        fn unprotect(
+            packet: &dyn PacketKey,
+            header: &dyn HeaderProtectionKey,
+            mut buf: Vec<u8>,
+        ) -> (Vec<u8>, Vec<Result<usize, Error>>) {
+            let mut headers = buf
+                .chunks_mut(PACKET_LEN)
+                .map(|chunk| {
+                    let (header, payload) = chunk.split_at_mut(HEADER_LEN);
+                    let (first, packet_number) = header.split_first_mut().unwrap();
+                    BatchHeader {
+                        sample: &payload[..16],
+                        first,
+                        packet_number,
+                    }
+                })
+                .collect::<Vec<_>>();
+            header
+                .decrypt_batch_in_place(&mut headers)
+                .unwrap();
+
+            let mut packets = buf
+                .chunks_mut(PACKET_LEN)
+                .map(|chunk| {
+                    let (header, payload) = chunk.split_at_mut(HEADER_LEN);
+                    BatchPacket {
+                        packet_number: u64::from(header[4]),
+                        header,
+                        payload,
+                    }
+                })
+                .collect::<Vec<_>>();
+            let mut results = vec![Ok(0); packets.len()];
+            packet.decrypt_batch_in_place(&mut packets, &mut results);
+            (buf, results)
+        }
+
+        let suite = TLS13_AES_128_GCM_SHA256
+            .tls13()
+            .unwrap();
+        let keys = |side| Keys::initial(Version::V1, suite, suite.quic.unwrap(), &[1, 2, 3], side);
+        let (client, server) = (keys(Side::Client), keys(Side::Server));
+
+        // Batches are protected exactly as their packets are one at a time.
+        let batched = protect(client.local.packet.as_ref(), client.local.header.as_ref());
+
+        let mut single = plain_packets();
+        for (pn, chunk) in single
+            .chunks_mut(PACKET_LEN)
+            .enumerate()
+        {
+            let (header, payload) = chunk.split_at_mut(HEADER_LEN);
+            let (payload, tag) = payload.split_at_mut(PACKET_LEN - HEADER_LEN - 16);
+            tag.copy_from_slice(
+                client
+                    .local
+                    .packet
+                    .encrypt_in_place(pn as u64, header, payload)
+                    .unwrap()
+                    .as_ref(),
+            );
+            let (first, packet_number) = header.split_first_mut().unwrap();
+            client
+                .local
+                .header
+                .encrypt_in_place(&payload[..16], first, packet_number)
+                .unwrap();
+        }
+        assert_eq!(single, batched);
+
+        // A packet failing to decrypt does not affect the others.
+        let mut corrupted = batched;
+        corrupted[PACKET_LEN * 2 - 1] ^= 1;
+        let (plain, results) = unprotect(
+            server.remote.packet.as_ref(),
+            server.remote.header.as_ref(),
+            corrupted,
+        );
+        let payload_len = PACKET_LEN - HEADER_LEN - 16;
+        assert_eq!(
+            results,
+            vec![
+                Ok(payload_len),
+                Err(Error::DecryptError),
+                Ok(payload_len),
+                Ok(payload_len)
+            ]
+        );
+        let expected = plain_packets();
+        for i in [0, 2, 3] {
+            let start = i * PACKET_LEN;
+            assert_eq!(
+                plain[start..start + HEADER_LEN + payload_len],
+                expected[start..start + HEADER_LEN + payload_len]
+            );
+        }
+
+        // Encryption requires room for the tag.
+        let mut short = [0u8; 8];
+        let mut packets = [BatchPacket {
+            packet_number: 0,
+            header: &[],
+            payload: &mut short,
+        }];
+        assert_eq!(
+            client
+                .local
+                .packet
+                .encrypt_batch_in_place(&mut packets),
+            Err(Error::EncryptError)
+        );
+    }
+
+    #[test]
+    fn retry_integrity_tag() {
+        use cipher_suite::TLS13_AES_128_GCM_SHA256;
+        use rustls::quic::Version;
+
+        // Test vectors: https://www.rfc-editor.org/rfc/rfc9001.html#name-retry
+        const ORIGINAL_DCID: &[u8] = &[0x83, 0x94, 0xc8, 0xf0, 0x3e, 0x51, 0x57, 0x08];
+        const RETRY_V1: &[u8] = &[
+            0xff, 0x00, 0x00, 0x00, 0x01, 0x00, 0x08, 0xf0, 0x67, 0xa5, 0x50, 0x2a, 0x42, 0x62,
+            0xb5, 0x74, 0x6f, 0x6b, 0x65, 0x6e, 0x04, 0xa2, 0x65, 0xba, 0x2e, 0xff, 0x4d, 0x82,
+            0x90, 0x58, 0xfb, 0x3f, 0x0f, 0x24, 0x96, 0xba,
+        ];
+        // https://www.rfc-editor.org/rfc/rfc9369.html#name-retry
+        const RETRY_V2: &[u8] = &[
+            0xcf, 0x6b, 0x33, 0x43, 0xcf, 0x00, 0x08, 0xf0, 0x67, 0xa5, 0x50, 0x2a, 0x42, 0x62,
+            0xb5, 0x74, 0x6f, 0x6b, 0x65, 0x6e, 0xc8, 0x64, 0x6c, 0xe8, 0xbf, 0xe3, 0x39, 0x52,
+            0xd9, 0x55, 0x54, 0x36, 0x65, 0xdc, 0xc7, 0xb6,
+        ];
+        // https://datatracker.ietf.org/doc/html/draft-ietf-quic-tls-29#appendix-A.4
+        const RETRY_V1_DRAFT: &[u8] = &[
+            0xff, 0xff, 0x00, 0x00, 0x1d, 0x00, 0x08, 0xf0, 0x67, 0xa5, 0x50, 0x2a, 0x42, 0x62,
+            0xb5, 0x74, 0x6f, 0x6b, 0x65, 0x6e, 0xd1, 0x69, 0x26, 0xd8, 0x1f, 0x6f, 0x9c, 0xa2,
+            0x95, 0x3a, 0x8a, 0xa4, 0x57, 0x5e, 0x1e, 0x49,
+        ];
+
+        let quic = TLS13_AES_128_GCM_SHA256
+            .tls13()
+            .unwrap()
+            .quic
+            .unwrap();
+
+        for (version, packet) in [
+            (Version::V1, RETRY_V1),
+            (Version::V2, RETRY_V2),
+            (Version::V1Draft, RETRY_V1_DRAFT),
+        ] {
+            let (header, tag) = packet.split_at(packet.len() - 16);
+            assert_eq!(
+                version
+                    .retry_integrity_tag(quic, ORIGINAL_DCID, header)
+                    .unwrap()
+                    .as_ref(),
+                tag
+            );
+            assert_eq!(
+                version.verify_retry_integrity_tag(quic, ORIGINAL_DCID, packet),
+                Ok(())
+            );
+
+            let mut corrupt = packet.to_vec();
+            *corrupt.last_mut().unwrap() ^= 1;
+            assert_eq!(
+                version.verify_retry_integrity_tag(quic, ORIGINAL_DCID, &corrupt),
+                Err(Error::DecryptError)
+            );
+            assert_eq!(
+                version.verify_retry_integrity_tag(quic, &ORIGINAL_DCID[1..], packet),
+                Err(Error::DecryptError)
+            );
+            assert_eq!(
+                version.verify_retry_integrity_tag(quic, ORIGINAL_DCID, &packet[..15]),
+                Err(Error::DecryptError)
+            );
+        }
+
+        // other AEADs are rejected
+        let chacha = cipher_suite::TLS13_CHACHA20_POLY1305_SHA256
+            .tls13()
+            .unwrap()
+            .quic
+            .unwrap();
+        assert!(Version::V1
+            .retry_integrity_tag(chacha, ORIGINAL_DCID, &RETRY_V1[..RETRY_V1.len() - 16])
+            .is_err());
+    }
+
     #[test]
     fn test_quic_exporter() {
         for &kt in ALL_KEY_TYPES.iter() {
@@ -5153,10 +6596,16 @@ fn test_acceptor() {
             .kind(),
         io::ErrorKind::Other,
     );
+    let (err, mut alert) = acceptor.accept().err().unwrap();
     assert_eq!(
-        acceptor.accept().err(),
-        Some(Error::General("Acceptor polled after completion".into()))
+        err,
+        Error::General("Acceptor polled after completion".into())
     );
+    let mut alert_content = Vec::new();
+    alert
+        .write_all(&mut alert_content)
+        .unwrap();
+    assert!(alert_content.is_empty());
 
     let mut acceptor = Acceptor::default();
     assert!(acceptor.accept().unwrap().is_none());
@@ -5167,21 +6616,129 @@ fn test_acceptor() {
     acceptor
         .read_tls(&mut [0x80, 0x00].as_ref())
         .unwrap(); // invalid message (len = 32k bytes)
-    assert!(acceptor.accept().is_err());
+    let (err, mut alert) = acceptor.accept().err().unwrap();
+    assert_eq!(err, Error::InvalidMessage(InvalidMessage::MessageTooLarge));
+    let mut alert_content = Vec::new();
+    alert
+        .write_all(&mut alert_content)
+        .unwrap();
+    let expected = alert_bytes(AlertDescription::DecodeError);
+    assert_eq!(alert_content, expected);
 
     let mut acceptor = Acceptor::default();
     // Minimal valid 1-byte application data message is not a handshake message
     acceptor
         .read_tls(&mut [0x17, 0x03, 0x03, 0x00, 0x01, 0x00].as_ref())
         .unwrap();
-    assert!(acceptor.accept().is_err());
+    let (err, mut alert) = acceptor.accept().err().unwrap();
+    assert!(matches!(err, Error::InappropriateMessage { .. }));
+    let mut alert_content = Vec::new();
+    alert
+        .write_all(&mut alert_content)
+        .unwrap();
+    assert!(alert_content.is_empty());
 
     let mut acceptor = Acceptor::default();
     // Minimal 1-byte ClientHello message is not a legal handshake message
     acceptor
         .read_tls(&mut [0x16, 0x03, 0x03, 0x00, 0x05, 0x01, 0x00, 0x00, 0x01, 0x00].as_ref())
         .unwrap();
-    assert!(acceptor.accept().is_err());
+    let (err, mut alert) = acceptor.accept().err().unwrap();
+    assert!(matches!(
+        err,
+        Error::InvalidMessage(InvalidMessage::MissingData(_))
+    ));
+    let mut alert_content = Vec::new();
+    alert
+        .write_all(&mut alert_content)
+        .unwrap();
+    let expected = alert_bytes(AlertDescription::DecodeError);
+    assert_eq!(alert_content, expected);
+}
+
+#[cfg(feature = "tls12")]
+#[test]
+fn test_acceptor_rejected_handshake() {
+    use rustls::server::Acceptor;
+
+    let client_config = Arc::new(make_client_config_with_versions(
+        KeyType::Ed25519,
+        &[&rustls::version::TLS13],
+    ));
+    let mut client = ClientConnection::new(client_config, server_name("localhost")).unwrap();
+    let mut buf = Vec::new();
+    client.write_tls(&mut buf).unwrap();
+
+    let server_config = Arc::new(make_server_config_with_versions(
+        KeyType::Ed25519,
+        &[&rustls::version::TLS12],
+    ));
+    let mut acceptor = Acceptor::default();
+    acceptor
+        .read_tls(&mut buf.as_slice())
+        .unwrap();
+    let accepted = acceptor.accept().unwrap().unwrap();
+    let (err, mut alert) = accepted
+        .into_connection(server_config)
+        .err()
+        .unwrap();
+    assert_eq!(
+        err,
+        Error::PeerIncompatible(PeerIncompatible::Tls12NotOfferedOrEnabled)
+    );
+
+    let mut alert_content = Vec::new();
+    alert
+        .write_all(&mut alert_content)
+        .unwrap();
+    let expected = alert_bytes(AlertDescription::ProtocolVersion);
+    assert_eq!(alert_content, expected);
+
+    client
+        .read_tls(&mut alert_content.as_slice())
+        .unwrap();
+    assert_eq!(
+        client.process_new_packets(),
+        Err(Error::AlertReceived(AlertDescription::ProtocolVersion))
+    );
+}
+
+#[test]
+fn test_accepted_into_alert() {
+    use rustls::server::Acceptor;
+
+    let client_config = Arc::new(make_client_config(KeyType::Ed25519));
+    let mut client = ClientConnection::new(client_config, server_name("localhost")).unwrap();
+    let mut buf = Vec::new();
+    client.write_tls(&mut buf).unwrap();
+
+    let mut acceptor = Acceptor::default();
+    acceptor
+        .read_tls(&mut buf.as_slice())
+        .unwrap();
+    let accepted = acceptor.accept().unwrap().unwrap();
+    assert_eq!(accepted.client_hello().server_name(), Some("localhost"));
+
+    let mut alert = accepted.into_alert(AlertDescription::UnrecognisedName);
+    let mut alert_content = Vec::new();
+    alert
+        .write_all(&mut alert_content)
+        .unwrap();
+    let expected = alert_bytes(AlertDescription::UnrecognisedName);
+    assert_eq!(alert_content, expected);
+
+    client
+        .read_tls(&mut alert_content.as_slice())
+        .unwrap();
+    assert_eq!(
+        client.process_new_packets(),
+        Err(Error::AlertReceived(AlertDescription::UnrecognisedName))
+    );
+}
+
+/// Encoding of a plaintext fatal alert record carrying `desc`.
+fn alert_bytes(desc: AlertDescription) -> Vec<u8> {
+    vec![0x15, 0x03, 0x03, 0x00, 0x02, 0x02, desc.get_u8()]
 }
 
 #[derive(Default, Debug)]
@@ -5429,6 +6986,176 @@ fn test_secret_extraction_disabled_or_too_early() {
     }
 }
 
+#[test]
+fn test_kernel_connection_key_updates() {
+    fn key_and_iv(secrets: &ConnectionTrafficSecrets) -> (Vec<u8>, Vec<u8>) {
+        match secrets {
+            ConnectionTrafficSecrets::Aes128Gcm { key, iv } => {
+                (key.as_ref().to_vec(), iv.as_ref().to_vec())
+            }
+            _ => panic!("unexpected secret type"),
+        }
+    }
+
+    let kt = KeyType::Rsa;
+    let provider = Arc::new(CryptoProvider {
+        cipher_suites: vec![cipher_suite::TLS13_AES_128_GCM_SHA256],
+        ..provider::default_provider()
+    });
+    let mut server_config = ServerConfig::builder_with_provider(provider)
+        .with_protocol_versions(&[&rustls::version::TLS13])
+        .unwrap()
+        .with_no_client_auth()
+        .with_single_cert(kt.get_chain(), kt.get_key())
+        .unwrap();
+    server_config.enable_secret_extraction = true;
+    let mut client_config = make_client_config(kt);
+    client_config.enable_secret_extraction = true;
+
+    let (mut client, mut server) = make_pair_for_configs(client_config, server_config);
+    do_handshake(&mut client, &mut server);
+
+    let (client_secrets, mut client) = client
+        .dangerous_into_kernel_connection()
+        .unwrap();
+    let (_, mut server) = server
+        .dangerous_into_kernel_connection()
+        .unwrap();
+    assert_eq!(client.protocol_version(), ProtocolVersion::TLSv1_3);
+
+    // The client sends a KeyUpdate, and the server answers with one of its own.
+    let (seq, client_tx) = client.update_tx_secret().unwrap();
+    assert_eq!(seq, 0);
+    assert_ne!(key_and_iv(&client_tx), key_and_iv(&client_secrets.tx.1));
+    let (seq, server_rx) = server.update_rx_secret().unwrap();
+    assert_eq!(seq, 0);
+    assert_eq!(key_and_iv(&client_tx), key_and_iv(&server_rx));
+
+    let (_, server_tx) = server.update_tx_secret().unwrap();
+    let (_, client_rx) = client.update_rx_secret().unwrap();
+    assert_ne!(key_and_iv(&client_rx), key_and_iv(&client_secrets.rx.1));
+    assert_eq!(key_and_iv(&server_tx), key_and_iv(&client_rx));
+
+    // Each update moves on to a new secret.
+    let (_, client_tx_2) = client.update_tx_secret().unwrap();
+    let (_, server_rx_2) = server.update_rx_secret().unwrap();
+    assert_ne!(key_and_iv(&client_tx), key_and_iv(&client_tx_2));
+    assert_eq!(key_and_iv(&client_tx_2), key_and_iv(&server_rx_2));
+}
+
+#[cfg(feature = "tls12")]
+#[test]
+fn test_kernel_connection_tls12_has_no_key_updates() {
+    let mut client_config =
+        make_client_config_with_versions(KeyType::Rsa, &[&rustls::version::TLS12]);
+    client_config.enable_secret_extraction = true;
+    let (mut client, mut server) =
+        make_pair_for_configs(client_config, make_server_config(KeyType::Rsa));
+    do_handshake(&mut client, &mut server);
+
+    let (_, mut client) = client
+        .dangerous_into_kernel_connection()
+        .unwrap();
+    assert_eq!(client.protocol_version(), ProtocolVersion::TLSv1_2);
+    assert!(client.update_tx_secret().is_err());
+    assert!(client.update_rx_secret().is_err());
+}
+
+#[test]
+fn test_kernel_connection_requires_no_pending_data() {
+    let mut client_config = make_client_config(KeyType::Rsa);
+    client_config.enable_secret_extraction = true;
+    let (mut client, mut server) =
+        make_pair_for_configs(client_config, make_server_config(KeyType::Rsa));
+    do_handshake(&mut client, &mut server);
+
+    client
+        .writer()
+        .write_all(b"unsent")
+        .unwrap();
+    match client.dangerous_into_kernel_connection() {
+        Err(err) => assert_eq!(
+            err,
+            Error::General("cannot hand over a connection with data pending".into())
+        ),
+        Ok(_) => panic!("connection with data pending was handed over"),
+    }
+}
+
+#[test]
+fn test_kernel_connection_stores_new_session_tickets() {
+    let kt = KeyType::Rsa;
+    let mut client_config = make_client_config_with_versions(kt, &[&rustls::version::TLS13]);
+    client_config.enable_secret_extraction = true;
+    let client_config = Arc::new(client_config);
+    let mut server_config = make_server_config(kt);
+    let storage = Arc::new(ServerStorage::new());
+    server_config.session_storage = storage.clone();
+    let server_config = Arc::new(server_config);
+
+    // Complete the handshake, leaving the server's tickets unsent.
+    let (mut client, mut server) = make_pair_for_arc_configs(&client_config, &server_config);
+    while client.is_handshaking() {
+        transfer(&mut client, &mut server);
+        server.process_new_packets().unwrap();
+        transfer(&mut server, &mut client);
+        client.process_new_packets().unwrap();
+    }
+    transfer(&mut client, &mut server);
+    server.process_new_packets().unwrap();
+    let mut records = Vec::new();
+    while server.wants_write() {
+        server.write_tls(&mut records).unwrap();
+    }
+
+    let suite = client
+        .negotiated_cipher_suite()
+        .unwrap();
+    let (secrets, mut client) = client
+        .dangerous_into_kernel_connection()
+        .unwrap();
+    let (mut seq, mut decrypter) = match secrets.rx {
+        (seq, ConnectionTrafficSecrets::Aes256Gcm { key, iv })
+        | (seq, ConnectionTrafficSecrets::Aes128Gcm { key, iv })
+        | (seq, ConnectionTrafficSecrets::Chacha20Poly1305 { key, iv }) => (
+            seq,
+            suite
+                .tls13()
+                .unwrap()
+                .aead_alg
+                .decrypter(key, iv),
+        ),
+        _ => panic!("unexpected secret type"),
+    };
+
+    // Decrypt the tickets as the kernel would, and hand them back.
+    let mut tickets = 0;
+    let mut reader = rustls::internal::msgs::codec::Reader::init(&records);
+    while reader.any_left() {
+        let record = rustls::internal::msgs::message::OpaqueMessage::read(&mut reader).unwrap();
+        let message = decrypter.decrypt(record, seq).unwrap();
+        seq += 1;
+        assert_eq!(message.typ, ContentType::Handshake);
+
+        let mut body = &message.payload.0[..];
+        while !body.is_empty() {
+            let len = usize::from(body[1]) << 16 | usize::from(body[2]) << 8 | usize::from(body[3]);
+            assert_eq!(body[0], rustls::HandshakeType::NewSessionTicket.get_u8());
+            client
+                .handle_new_session_ticket(&body[4..4 + len])
+                .unwrap();
+            tickets += 1;
+            body = &body[4 + len..];
+        }
+    }
+    assert_eq!(tickets, storage.puts());
+
+    // The stored ticket resumes the session.
+    let (mut client, mut server) = make_pair_for_arc_configs(&client_config, &server_config);
+    do_handshake(&mut client, &mut server);
+    assert_eq!(storage.takes(), 1);
+}
+
 #[test]
 fn test_received_plaintext_backpressure() {
     let kt = KeyType::Rsa;
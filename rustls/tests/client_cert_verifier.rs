@@ -13,12 +13,13 @@ use crate::common::{
 use rustls::client::danger::HandshakeSignatureValid;
 use rustls::internal::msgs::handshake::DistinguishedName;
 use rustls::server::danger::{ClientCertVerified, ClientCertVerifier};
+use rustls::server::ClientIdentity;
 use rustls::{
     AlertDescription, ClientConnection, DigitallySignedStruct, Error, InvalidMessage, ServerConfig,
     ServerConnection, SignatureScheme,
 };
 
-use pki_types::{CertificateDer, UnixTime};
+use pki_types::{CertificateDer, DnsName, UnixTime};
 
 use std::sync::Arc;
 
@@ -166,14 +167,15 @@ impl ClientCertVerifier for MockClientVerifier {
         self.mandatory
     }
 
-    fn root_hint_subjects(&self) -> &[DistinguishedName] {
+    fn root_hint_subjects_for(&self, _server_name: Option<&DnsName<'_>>) -> &[DistinguishedName] {
         &self.subjects
     }
 
-    fn verify_client_cert(
+    fn verify_client_cert_for(
         &self,
         _end_entity: &CertificateDer<'_>,
         _intermediates: &[CertificateDer<'_>],
+        _server_name: Option<&DnsName<'_>>,
         _now: UnixTime,
     ) -> Result<ClientCertVerified, Error> {
         (self.verified)()
@@ -206,4 +208,11 @@ impl ClientCertVerifier for MockClientVerifier {
             self.parent.supported_verify_schemes()
         }
     }
+
+    fn client_identity(
+        &self,
+        end_entity: &CertificateDer<'_>,
+    ) -> Result<Option<ClientIdentity>, Error> {
+        self.parent.client_identity(end_entity)
+    }
 }
@@ -98,6 +98,7 @@ embed_files! {
     (RSA_CLIENT_KEY, "rsa", "client.key");
     (RSA_CLIENT_REQ, "rsa", "client.req");
     (RSA_CLIENT_RSA, "rsa", "client.rsa");
+    (RSA_CLIENT_SPIFFE_CERT, "rsa", "client-spiffe.cert");
     (RSA_END_CRL_PEM, "rsa", "end.revoked.crl.pem");
     (RSA_CLIENT_CRL_PEM, "rsa", "client.revoked.crl.pem");
     (RSA_INTERMEDIATE_CRL_PEM, "rsa", "inter.revoked.crl.pem");
@@ -243,6 +244,17 @@ impl KeyType {
             .collect()
     }
 
+    /// The chain of a client certificate with a SPIFFE ID.  Only available for
+    /// `KeyType::Rsa`.
+    pub fn get_spiffe_client_chain(&self) -> Vec<CertificateDer<'static>> {
+        let client = self.bytes_for("client-spiffe.cert");
+        let chain = self.bytes_for("client.chain");
+        rustls_pemfile::certs(&mut io::BufReader::new(client))
+            .chain(rustls_pemfile::certs(&mut io::BufReader::new(chain)))
+            .map(|result| result.unwrap())
+            .collect()
+    }
+
     pub fn end_entity_crl(&self) -> CertificateRevocationListDer<'static> {
         self.get_crl("end")
     }
@@ -255,7 +267,7 @@ impl KeyType {
         self.get_crl("inter")
     }
 
-    fn get_client_key(&self) -> PrivateKeyDer<'static> {
+    pub fn get_client_key(&self) -> PrivateKeyDer<'static> {
         PrivateKeyDer::Pkcs8(
             rustls_pemfile::pkcs8_private_keys(&mut io::BufReader::new(
                 self.bytes_for("client.key"),
@@ -5,11 +5,14 @@
 mod common;
 use crate::common::{
     do_handshake, do_handshake_until_both_error, make_client_config_with_versions,
-    make_pair_for_arc_configs, make_server_config, ErrorFromPeer, ALL_KEY_TYPES,
+    make_pair_for_arc_configs, make_server_config, provider, ErrorFromPeer, KeyType, ALL_KEY_TYPES,
 };
 use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::{CtPolicy, CtVerifier, KnownHostsMemoryCache, TofuVerifier};
+use rustls::server::ResolvesServerCertUsingSni;
+use rustls::sign::CertifiedKey;
 use rustls::DigitallySignedStruct;
-use rustls::{AlertDescription, Error, InvalidMessage, SignatureScheme};
+use rustls::{AlertDescription, CertificateError, Error, InvalidMessage, SignatureScheme};
 
 use pki_types::{CertificateDer, ServerName, UnixTime};
 
@@ -154,12 +157,154 @@ fn client_can_override_certificate_verification_and_offer_no_signature_schemes()
     }
 }
 
+fn make_server_config_with_scts(kt: KeyType, sct_list: &[u8]) -> rustls::ServerConfig {
+    let key = provider::default_provider()
+        .key_provider
+        .load_private_key(kt.get_key())
+        .unwrap();
+    let mut certified_key = CertifiedKey::new(kt.get_chain(), key);
+    certified_key.sct_list = Some(sct_list.to_vec());
+
+    let mut resolver = ResolvesServerCertUsingSni::new();
+    resolver
+        .add("localhost", certified_key)
+        .unwrap();
+    let mut server_config = make_server_config(kt);
+    server_config.cert_resolver = Arc::new(resolver);
+    server_config
+}
+
+#[test]
+fn client_enforces_certificate_transparency_policy() {
+    let sct_list = [0x00, 0x0a, 0x00, 0x03, 1, 2, 3, 0x00, 0x03, 4, 5, 6];
+    let server_config = Arc::new(make_server_config_with_scts(KeyType::Rsa, &sct_list));
+    let ct_verifier = |inner: MockServerVerifier, min_scts| {
+        Arc::new(CtVerifier::new_with_provider(
+            Arc::new(inner),
+            [],
+            CtPolicy {
+                min_scts,
+                min_distinct_operators: 0,
+            },
+            provider::default_provider().into(),
+        ))
+    };
+    let scts = || vec![vec![1, 2, 3], vec![4, 5, 6]];
+
+    for version in rustls::ALL_VERSIONS {
+        // The server's SCTs are requested and passed on to the wrapped
+        // verifier, and (being malformed) ignored by the policy.
+        let mut client_config = make_client_config_with_versions(KeyType::Rsa, &[version]);
+        client_config.enable_signed_cert_timestamps = true;
+        client_config
+            .dangerous()
+            .set_certificate_verifier(ct_verifier(MockServerVerifier::expects_scts(scts()), 0));
+        let (mut client, mut server) =
+            make_pair_for_arc_configs(&Arc::new(client_config), &server_config);
+        do_handshake(&mut client, &mut server);
+
+        // The policy applies once the wrapped verifier accepts the certificate.
+        let mut client_config = make_client_config_with_versions(KeyType::Rsa, &[version]);
+        client_config.enable_signed_cert_timestamps = true;
+        client_config
+            .dangerous()
+            .set_certificate_verifier(ct_verifier(MockServerVerifier::expects_scts(scts()), 1));
+        let (mut client, mut server) =
+            make_pair_for_arc_configs(&Arc::new(client_config), &server_config);
+        let errs = do_handshake_until_both_error(&mut client, &mut server);
+        assert_eq!(
+            errs,
+            Err(vec![
+                ErrorFromPeer::Client(Error::InvalidCertificate(
+                    CertificateError::InsufficientCertificateTransparency
+                )),
+                ErrorFromPeer::Server(Error::AlertReceived(AlertDescription::CertificateUnknown)),
+            ]),
+        );
+
+        // The wrapped verifier's decision comes first.
+        let mut client_config = make_client_config_with_versions(KeyType::Rsa, &[version]);
+        client_config.enable_signed_cert_timestamps = true;
+        client_config
+            .dangerous()
+            .set_certificate_verifier(ct_verifier(
+                MockServerVerifier::rejects_certificate(CertificateError::UnknownIssuer.into()),
+                0,
+            ));
+        let (mut client, mut server) =
+            make_pair_for_arc_configs(&Arc::new(client_config), &server_config);
+        let errs = do_handshake_until_both_error(&mut client, &mut server);
+        assert_eq!(
+            errs,
+            Err(vec![
+                ErrorFromPeer::Client(Error::InvalidCertificate(CertificateError::UnknownIssuer)),
+                ErrorFromPeer::Server(Error::AlertReceived(AlertDescription::UnknownCA)),
+            ]),
+        );
+    }
+}
+
+#[test]
+fn server_sends_no_signed_cert_timestamps_unless_requested() {
+    let sct_list = [0x00, 0x05, 0x00, 0x03, 1, 2, 3];
+    let server_config = Arc::new(make_server_config_with_scts(KeyType::Rsa, &sct_list));
+
+    for version in rustls::ALL_VERSIONS {
+        // The client rejects SCTs it did not ask for, so none may be sent.
+        let mut client_config = make_client_config_with_versions(KeyType::Rsa, &[version]);
+        client_config
+            .dangerous()
+            .set_certificate_verifier(Arc::new(MockServerVerifier::expects_scts(vec![])));
+        let (mut client, mut server) =
+            make_pair_for_arc_configs(&Arc::new(client_config), &server_config);
+        do_handshake(&mut client, &mut server);
+    }
+}
+
+#[test]
+fn client_can_trust_server_keys_on_first_use() {
+    let rsa_server_config = Arc::new(make_server_config(KeyType::Rsa));
+    let ecdsa_server_config = Arc::new(make_server_config(KeyType::Ecdsa));
+
+    for version in rustls::ALL_VERSIONS {
+        let verifier = Arc::new(TofuVerifier::new_with_provider(
+            Arc::new(KnownHostsMemoryCache::new()),
+            provider::default_provider().into(),
+        ));
+        let mut client_config = make_client_config_with_versions(KeyType::Rsa, &[version]);
+        client_config
+            .dangerous()
+            .set_certificate_verifier(verifier);
+        let client_config = Arc::new(client_config);
+
+        for _ in 0..2 {
+            let (mut client, mut server) =
+                make_pair_for_arc_configs(&client_config, &rsa_server_config);
+            do_handshake(&mut client, &mut server);
+        }
+
+        let (mut client, mut server) =
+            make_pair_for_arc_configs(&client_config, &ecdsa_server_config);
+        let errs = do_handshake_until_both_error(&mut client, &mut server);
+        assert_eq!(
+            errs,
+            Err(vec![
+                ErrorFromPeer::Client(Error::InvalidCertificate(
+                    CertificateError::PublicKeyChanged
+                )),
+                ErrorFromPeer::Server(Error::AlertReceived(AlertDescription::BadCertificate)),
+            ]),
+        );
+    }
+}
+
 #[derive(Debug)]
 pub struct MockServerVerifier {
     cert_rejection_error: Option<Error>,
     tls12_signature_error: Option<Error>,
     tls13_signature_error: Option<Error>,
     signature_schemes: Vec<SignatureScheme>,
+    expected_scts: Option<Vec<Vec<u8>>>,
 }
 
 impl ServerCertVerifier for MockServerVerifier {
@@ -169,14 +314,17 @@ impl ServerCertVerifier for MockServerVerifier {
         intermediates: &[CertificateDer<'_>],
         server_name: &ServerName<'_>,
         oscp_response: &[u8],
+        scts: &[&[u8]],
         now: UnixTime,
     ) -> Result<ServerCertVerified, Error> {
         println!(
-            "verify_server_cert({:?}, {:?}, {:?}, {:?}, {:?})",
-            end_entity, intermediates, server_name, oscp_response, now
+            "verify_server_cert({:?}, {:?}, {:?}, {:?}, {:?}, {:?})",
+            end_entity, intermediates, server_name, oscp_response, scts, now
         );
         if let Some(error) = &self.cert_rejection_error {
             Err(error.clone())
+        } else if matches!(&self.expected_scts, Some(expected) if expected != scts) {
+            Err(CertificateError::ApplicationVerificationFailure.into())
         } else {
             Ok(ServerCertVerified::assertion())
         }
@@ -250,6 +398,13 @@ impl MockServerVerifier {
         }
     }
 
+    pub fn expects_scts(scts: Vec<Vec<u8>>) -> Self {
+        MockServerVerifier {
+            expected_scts: Some(scts),
+            ..Default::default()
+        }
+    }
+
     pub fn offers_no_signature_schemes() -> Self {
         MockServerVerifier {
             signature_schemes: vec![],
@@ -271,6 +426,7 @@ impl Default for MockServerVerifier {
                 SignatureScheme::ECDSA_NISTP256_SHA256,
                 SignatureScheme::ECDSA_NISTP384_SHA384,
             ],
+            expected_scts: None,
         }
     }
 }
@@ -1,13 +1,17 @@
 #![cfg(any(feature = "ring", feature = "aws_lc_rs"))]
+use std::io::IoSlice;
 use std::sync::Arc;
 
 use rustls::client::{ClientConnectionData, EarlyDataError, UnbufferedClientConnection};
-use rustls::server::{ServerConnectionData, UnbufferedServerConnection};
+use rustls::server::{
+    ServerConnectionData, UnbufferedAcceptStatus, UnbufferedAcceptor, UnbufferedServerConnection,
+};
 use rustls::unbuffered::{
     ConnectionState, EncodeError, EncryptError, InsufficientSizeError, UnbufferedConnectionCommon,
     UnbufferedStatus, WriteTraffic,
 };
 use rustls::version::TLS13;
+use rustls::{AlertDescription, Error};
 
 use crate::common::*;
 
@@ -559,6 +563,104 @@ fn close_notify_server_to_client() {
     }
 }
 
+#[test]
+fn encrypt_vectored_packs_slices_into_full_records() {
+    const MAX_FRAGMENT_SIZE: usize = 64;
+    const MAX_FRAGMENT_LEN: usize = MAX_FRAGMENT_SIZE - 5;
+
+    let app_data = (0..200u8).collect::<Vec<u8>>();
+    let slices = [
+        IoSlice::new(&app_data[..3]),
+        IoSlice::new(&[]),
+        IoSlice::new(&app_data[3..100]),
+        IoSlice::new(&app_data[100..101]),
+        IoSlice::new(&app_data[101..]),
+    ];
+
+    for version in rustls::ALL_VERSIONS {
+        eprintln!("{version:?}");
+
+        let mut client_config = make_client_config_with_versions(KeyType::Rsa, &[version]);
+        client_config.max_fragment_size = Some(MAX_FRAGMENT_SIZE);
+        let mut client =
+            UnbufferedClientConnection::new(Arc::new(client_config), server_name("localhost"))
+                .unwrap();
+        let mut server =
+            UnbufferedServerConnection::new(Arc::new(make_server_config(KeyType::Rsa))).unwrap();
+        let mut buffers = BothBuffers::default();
+
+        let mut count = 0;
+        let mut client_handshake_done = false;
+        let mut server_handshake_done = false;
+        while !client_handshake_done || !server_handshake_done {
+            match advance_client(&mut client, &mut buffers.client, NO_ACTIONS, None) {
+                State::EncodedTlsData => {}
+                State::TransmitTlsData { .. } => buffers.client_send(),
+                State::BlockedHandshake => buffers.server_send(),
+                State::WriteTraffic { .. } => client_handshake_done = true,
+                state => unreachable!("{state:?}"),
+            }
+
+            match advance_server(&mut server, &mut buffers.server, NO_ACTIONS, None) {
+                State::EncodedTlsData => {}
+                State::TransmitTlsData { .. } => buffers.server_send(),
+                State::BlockedHandshake => buffers.client_send(),
+                State::WriteTraffic { .. } => server_handshake_done = true,
+                state => unreachable!("{state:?}"),
+            }
+
+            count += 1;
+
+            assert!(
+                count <= MAX_ITERATIONS,
+                "handshake {version:?} was not completed"
+            );
+        }
+
+        let UnbufferedStatus { state, .. } =
+            client.process_tls_records(buffers.client.incoming.filled());
+        let mut state = match state.unwrap() {
+            ConnectionState::WriteTraffic(state) => state,
+            state => unreachable!("{state:?}"),
+        };
+
+        let required_size = map_encrypt_error(
+            state
+                .encrypt_vectored(&slices, &mut [])
+                .unwrap_err(),
+        )
+        .required_size;
+        let records = state
+            .encrypt_vectored_records(&slices, buffers.client.outgoing.unfilled())
+            .unwrap();
+        assert_eq!(records.as_slice().len(), required_size);
+        let record_lens = records
+            .map(|record| record.len())
+            .collect::<Vec<_>>();
+        assert_eq!(record_lens.len(), 4);
+        assert_eq!(record_lens.iter().sum::<usize>(), required_size);
+        buffers
+            .client
+            .outgoing
+            .advance(required_size);
+        buffers.client_send();
+
+        let mut received_app_data = vec![];
+        while received_app_data.len() < record_lens.len() {
+            match advance_server(&mut server, &mut buffers.server, NO_ACTIONS, None) {
+                State::ReceivedAppData { records } => received_app_data.extend(records),
+                state => unreachable!("{state:?}"),
+            }
+        }
+
+        assert!(received_app_data
+            .iter()
+            .all(|record| record.len() <= MAX_FRAGMENT_LEN));
+        assert_eq!(received_app_data.len(), record_lens.len());
+        assert_eq!(received_app_data.concat(), app_data);
+    }
+}
+
 #[derive(Debug)]
 enum State {
     Closed,
@@ -950,3 +1052,129 @@ fn server_receives_incorrect_first_handshake_message() {
         _ => panic!("unexpected alert sending state"),
     };
 }
+
+#[test]
+fn handshake_via_unbuffered_acceptor() {
+    let client_config = make_client_config_with_versions(KeyType::Rsa, &[&TLS13]);
+    let mut client =
+        UnbufferedClientConnection::new(Arc::new(client_config), server_name("localhost")).unwrap();
+    let mut buffers = BothBuffers::default();
+
+    // client sends its ClientHello
+    assert!(matches!(
+        advance_client(&mut client, &mut buffers.client, NO_ACTIONS, None),
+        State::EncodedTlsData
+    ));
+    assert!(matches!(
+        advance_client(&mut client, &mut buffers.client, NO_ACTIONS, None),
+        State::TransmitTlsData { .. }
+    ));
+    let client_hello = buffers
+        .client
+        .outgoing
+        .filled()
+        .to_vec();
+    buffers.client.outgoing.clear();
+
+    // deliver the ClientHello to the acceptor in two parts
+    let mut acceptor = UnbufferedAcceptor::default();
+    let (first, second) = client_hello.split_at(client_hello.len() / 2);
+    buffers.server.incoming.append(first);
+    let UnbufferedAcceptStatus { discard, state } =
+        acceptor.accept(buffers.server.incoming.filled());
+    assert!(state.unwrap().is_none());
+    buffers.server.incoming.discard(discard);
+
+    buffers.server.incoming.append(second);
+    let UnbufferedAcceptStatus { discard, state } =
+        acceptor.accept(buffers.server.incoming.filled());
+    let accepted = state.unwrap().unwrap();
+    buffers.server.incoming.discard(discard);
+    assert_eq!(buffers.server.incoming.filled().len(), 0);
+    assert_eq!(accepted.client_hello().server_name(), Some("localhost"));
+
+    let mut server = accepted
+        .into_connection(Arc::new(make_server_config(KeyType::Rsa)))
+        .unwrap();
+
+    let mut count = 0;
+    let mut client_handshake_done = false;
+    let mut server_handshake_done = false;
+    while !client_handshake_done || !server_handshake_done {
+        match advance_server(&mut server, &mut buffers.server, NO_ACTIONS, None) {
+            State::EncodedTlsData => {}
+            State::TransmitTlsData { .. } => buffers.server_send(),
+            State::BlockedHandshake => buffers.client_send(),
+            State::WriteTraffic { .. } => server_handshake_done = true,
+            state => unreachable!("{state:?}"),
+        }
+
+        match advance_client(&mut client, &mut buffers.client, NO_ACTIONS, None) {
+            State::EncodedTlsData => {}
+            State::TransmitTlsData { .. } => buffers.client_send(),
+            State::BlockedHandshake => buffers.server_send(),
+            State::WriteTraffic { .. } => client_handshake_done = true,
+            state => unreachable!("{state:?}"),
+        }
+
+        count += 1;
+
+        assert!(count <= MAX_ITERATIONS, "handshake was not completed");
+    }
+
+    // Reusing an acceptor is not allowed
+    let UnbufferedAcceptStatus { discard, state } = acceptor.accept(&mut []);
+    assert_eq!(discard, 0);
+    assert_eq!(
+        state.err().unwrap().0,
+        Error::General("Acceptor polled after completion".into())
+    );
+}
+
+#[test]
+fn unbuffered_acceptor_rejects_invalid_message() {
+    let mut acceptor = UnbufferedAcceptor::default();
+
+    // 1-byte ClientHello message is not a legal handshake message
+    let mut junk_buffer = [0x16, 0x03, 0x03, 0x00, 0x05, 0x01, 0x00, 0x00, 0x01, 0x00];
+    let junk_buffer_len = junk_buffer.len();
+
+    let UnbufferedAcceptStatus { discard, state } = acceptor.accept(&mut junk_buffer[..]);
+    assert_eq!(discard, junk_buffer_len);
+
+    let (err, mut alert) = state.err().unwrap();
+    assert!(matches!(err, Error::InvalidMessage(_)));
+
+    assert!(matches!(
+        alert.encode(&mut []),
+        Err(EncodeError::InsufficientSize(InsufficientSizeError {
+            required_size: 7
+        }))
+    ));
+    let mut alert_buffer = [0u8; 7];
+    assert_eq!(alert.encode(&mut alert_buffer).unwrap(), 7);
+    assert_eq!(alert_buffer, [0x15, 0x3, 0x3, 0x0, 0x2, 0x2, 0x32]);
+}
+
+#[test]
+fn unbuffered_accepted_into_alert() {
+    let (mut client, _) = make_connection_pair(&TLS13);
+    let mut buffers = BothBuffers::default();
+
+    assert!(matches!(
+        advance_client(&mut client, &mut buffers.client, NO_ACTIONS, None),
+        State::EncodedTlsData
+    ));
+
+    let mut acceptor = UnbufferedAcceptor::default();
+    let UnbufferedAcceptStatus { discard, state } =
+        acceptor.accept(buffers.client.outgoing.filled());
+    let accepted = state.unwrap().unwrap();
+    assert_eq!(discard, buffers.client.outgoing.filled().len());
+
+    let mut alert = accepted.into_alert(AlertDescription::NoApplicationProtocol);
+    let mut alert_buffer = [0u8; 7];
+    assert_eq!(alert.encode(&mut alert_buffer).unwrap(), 7);
+    assert_eq!(alert_buffer, [0x15, 0x3, 0x3, 0x0, 0x2, 0x2, 0x78]);
+    assert_eq!(alert.encode(&mut alert_buffer).unwrap(), 0);
+}
@@ -220,6 +220,93 @@ for kt in rsa ecdsa eddsa ; do
   openssl asn1parse -in $kt/ca.cert -out $kt/ca.der > /dev/null
 done
 
+# OCSP responses for the RSA server certificate.
+#
+# `gen_ocsp <status> <signer> <output> [digest]` writes a response giving
+# `status` ("good", "revoked" or "unknown") for rsa/end.cert, signed by
+# `signer`: either the issuer ("inter") or a delegated responder
+# ("ocsp-responder").  `digest` is used for the CertID, and defaults to sha1.
+gen_ocsp() {
+  local status=$1
+  local signer=$2
+  local out=$3
+  local digest=${4:-sha1}
+
+  rm -f index.txt
+  touch index.txt
+  case "$status" in
+    good)
+      echo -e "V\t290414164004Z\t\t01C8\tunknown\t/CN=testserver.com" > index.txt
+      ;;
+    revoked)
+      echo -e "R\t290414164004Z\t231024000000Z\t01C8\tunknown\t/CN=testserver.com" > index.txt
+      ;;
+  esac
+
+  openssl ocsp \
+            -issuer rsa/inter.cert \
+            "-$digest" \
+            -cert rsa/end.cert \
+            -no_nonce \
+            -reqout ocsp.req
+
+  openssl ocsp \
+            -index index.txt \
+            -CA rsa/inter.cert \
+            -rsigner "rsa/$signer.cert" \
+            -rkey "rsa/$signer.key" \
+            -reqin ocsp.req \
+            -ndays 7 \
+            -respout "rsa/$out"
+
+  rm -f ocsp.req
+}
+
+openssl req -nodes \
+          -newkey rsa:2048 \
+          -keyout rsa/ocsp-responder.key \
+          -out rsa/ocsp-responder.req \
+          -sha256 \
+          -batch \
+          -subj "/CN=ponytown RSA OCSP responder"
+
+openssl x509 -req \
+          -in rsa/ocsp-responder.req \
+          -out rsa/ocsp-responder.cert \
+          -CA rsa/inter.cert \
+          -CAkey rsa/inter.key \
+          -sha256 \
+          -days 2000 \
+          -set_serial 1011 \
+          -extensions v3_ocsp_responder -extfile openssl.cnf
+
+openssl x509 -req \
+          -in rsa/end.req \
+          -out rsa/end-must-staple.cert \
+          -CA rsa/inter.cert \
+          -CAkey rsa/inter.key \
+          -sha256 \
+          -days 2000 \
+          -set_serial 457 \
+          -extensions v3_end_must_staple -extfile openssl.cnf
+
+# A client certificate with a SPIFFE ID, DNS name and email address.
+openssl x509 -req \
+          -in rsa/client.req \
+          -out rsa/client-spiffe.cert \
+          -CA rsa/inter.cert \
+          -CAkey rsa/inter.key \
+          -sha256 \
+          -days 2000 \
+          -set_serial 790 \
+          -extensions v3_client_spiffe -extfile openssl.cnf
+
+gen_ocsp good inter end.ocsp.good.der
+gen_ocsp revoked inter end.ocsp.revoked.der
+gen_ocsp unknown inter end.ocsp.unknown.der
+gen_ocsp good ocsp-responder end.ocsp.delegated.der
+gen_ocsp good inter end.ocsp.good-sha256.der sha256
+
 # Tidy up openssl CA state.
 rm index.txt* || true
 rm crlnumber* || true